@@ -0,0 +1,182 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hyperloglog::HyperLogLog;
+use crate::runtime;
+
+const HOUR_MS: f64 = 3_600_000.0;
+const HOURS_PER_DAY: usize = 24;
+const DAYS_PER_WEEK: usize = 7;
+
+/// Counts distinct items per hour of the day, or per hour of each day of the week, answering
+/// "when are my distinct users active" with one HyperLogLog per bucket.
+///
+/// Buckets are indexed `day * 24 + hour`, with days numbered from Sunday = 0 as in
+/// `Date.getDay`; a daily heatmap has a single day. Times are bucketed in UTC shifted by a
+/// fixed offset.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ActivityHeatmap {
+  buckets: Vec<HyperLogLog>,
+  p: u8,
+  // Added to every timestamp before bucketing
+  offset_ms: f64,
+}
+
+#[wasm_bindgen]
+impl ActivityHeatmap {
+  /// Creates a new activity heatmap.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The precision of each bucket (between 4 and 16), defaulting to 10; a
+  ///   weekly heatmap keeps 168 buckets of `2^precision` bytes
+  /// * `weekly` - Whether to bucket by day of week as well as hour of day
+  /// * `utc_offset_minutes` - The time zone to bucket in, e.g. -300 for UTC-5; defaults to 0
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    precision: Option<u8>,
+    weekly: bool,
+    utc_offset_minutes: Option<f64>,
+  ) -> Result<ActivityHeatmap, SketchError> {
+    let p = precision.unwrap_or(10);
+    if !(4..=16).contains(&p) {
+      return Err(SketchError::InvalidParameter("precision must be between 4 and 16".to_string()));
+    }
+    let offset_minutes = utc_offset_minutes.unwrap_or(0.0);
+    if !offset_minutes.is_finite() {
+      return Err(SketchError::InvalidParameter("utc_offset_minutes must be finite".to_string()));
+    }
+    let days = if weekly { DAYS_PER_WEEK } else { 1 };
+    let buckets = (0..days * HOURS_PER_DAY)
+      .map(|_| HyperLogLog::new(Some(p)).expect("precision was validated"))
+      .collect();
+    Ok(ActivityHeatmap { buckets, p, offset_ms: offset_minutes * 60_000.0 })
+  }
+
+  /// Adds an item seen at the given time.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The item to add, e.g. a user id
+  /// * `timestamp_ms` - When the item was seen; defaults to the current time
+  #[wasm_bindgen]
+  pub fn add(&mut self, value: &str, timestamp_ms: Option<f64>) {
+    let bucket = self.bucket_for(timestamp_ms.unwrap_or_else(runtime::now_ms));
+    self.buckets[bucket].add(value);
+  }
+
+  /// Returns the estimated distinct count of every bucket, indexed `day * 24 + hour`.
+  #[wasm_bindgen]
+  pub fn heatmap(&self) -> Vec<f64> {
+    self.buckets.iter().map(HyperLogLog::count).collect()
+  }
+
+  /// Returns the estimated distinct count of one bucket.
+  ///
+  /// # Arguments
+  ///
+  /// * `day` - The day of week, Sunday = 0; must be 0 for a daily heatmap
+  /// * `hour` - The hour of day, 0 to 23
+  #[wasm_bindgen]
+  pub fn count_at(&self, day: usize, hour: usize) -> Result<f64, SketchError> {
+    if hour >= HOURS_PER_DAY || day * HOURS_PER_DAY >= self.buckets.len() {
+      return Err(SketchError::InvalidParameter("day or hour out of range".to_string()));
+    }
+    Ok(self.buckets[day * HOURS_PER_DAY + hour].count())
+  }
+
+  /// Returns the estimated number of distinct items across all buckets.
+  #[wasm_bindgen]
+  pub fn count_total(&self) -> f64 {
+    let mut union = HyperLogLog::new(Some(self.p)).expect("precision was validated");
+    for bucket in &self.buckets {
+      union.merge(bucket).expect("buckets share a precision");
+    }
+    union.count()
+  }
+
+  /// Merges another heatmap into this one, bucket by bucket.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - A heatmap with the same precision, layout and offset
+  #[wasm_bindgen]
+  pub fn merge(&mut self, other: &ActivityHeatmap) -> Result<(), SketchError> {
+    if self.p != other.p || self.buckets.len() != other.buckets.len() {
+      return Err(SketchError::Incompatible(
+        "heatmaps must have the same precision and layout".to_string(),
+      ));
+    }
+    if self.offset_ms != other.offset_ms {
+      return Err(SketchError::Incompatible("heatmaps must use the same UTC offset".to_string()));
+    }
+    for (bucket, theirs) in self.buckets.iter_mut().zip(&other.buckets) {
+      bucket.merge(theirs).expect("precision was checked");
+    }
+    Ok(())
+  }
+
+  /// Returns the number of buckets: 24 for a daily heatmap, 168 for a weekly one.
+  #[wasm_bindgen(getter)]
+  pub fn bucket_count(&self) -> usize {
+    self.buckets.len()
+  }
+
+  /// Removes all items.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    for bucket in &mut self.buckets {
+      bucket.clear();
+    }
+  }
+}
+
+impl ActivityHeatmap {
+  fn bucket_for(&self, timestamp_ms: f64) -> usize {
+    let hours = ((timestamp_ms + self.offset_ms) / HOUR_MS).floor() as i64;
+    // 1970-01-01 was a Thursday
+    let day = (hours.div_euclid(HOURS_PER_DAY as i64) + 4).rem_euclid(DAYS_PER_WEEK as i64);
+    let hour = hours.rem_euclid(HOURS_PER_DAY as i64);
+    (day as usize * HOURS_PER_DAY + hour as usize) % self.buckets.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Monday 2024-01-01 00:00 UTC
+  const MONDAY: f64 = 1_704_067_200_000.0;
+
+  #[test]
+  fn test_weekly_buckets() {
+    let mut heatmap = ActivityHeatmap::new(Some(8), true, None).unwrap();
+    for user in 0..50 {
+      heatmap.add(&format!("user_{}", user), Some(MONDAY + 9.5 * HOUR_MS));
+    }
+    heatmap.add("user_0", Some(MONDAY - HOUR_MS));
+
+    assert_eq!(heatmap.bucket_count(), 168);
+    assert!((heatmap.count_at(1, 9).unwrap() - 50.0).abs() < 5.0);
+    assert_eq!(heatmap.count_at(0, 23).unwrap(), 1.0);
+    assert_eq!(heatmap.heatmap().iter().filter(|&&count| count > 0.0).count(), 2);
+    assert!((heatmap.count_total() - 50.0).abs() < 5.0);
+    assert!(heatmap.count_at(7, 0).is_err());
+  }
+
+  #[test]
+  fn test_offset_and_merge() {
+    let mut daily = ActivityHeatmap::new(Some(8), false, Some(-300.0)).unwrap();
+    daily.add("user", Some(MONDAY));
+    assert_eq!(daily.count_at(0, 19).unwrap(), 1.0);
+
+    let mut other = ActivityHeatmap::new(Some(8), false, Some(-300.0)).unwrap();
+    other.add("other", Some(MONDAY));
+    daily.merge(&other).unwrap();
+    assert_eq!(daily.count_at(0, 19).unwrap(), 2.0);
+
+    assert!(daily.merge(&ActivityHeatmap::new(Some(8), true, Some(-300.0)).unwrap()).is_err());
+    assert!(daily.merge(&ActivityHeatmap::new(Some(8), false, None).unwrap()).is_err());
+  }
+}
@@ -0,0 +1,182 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hyperloglog::HyperLogLog;
+
+/// A HyperLogLog that starts at a low precision and raises it as the cardinality grows, so
+/// metrics that stay small never pay for a full-size register array.
+///
+/// While sparse, the sketch keeps the distinct hashes it has seen alongside its registers,
+/// which lets it rebuild the registers at a higher precision exactly as if that precision had
+/// been used from the start. Once the hashes would outgrow the register array at the maximum
+/// precision they are dropped, and the sketch stays dense at that precision.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct AdaptiveHyperLogLog {
+  hll: HyperLogLog,
+  max_precision: u8,
+  // Distinct hashes seen so far, sorted; present while sparse
+  hashes: Option<Vec<u32>>,
+}
+
+#[wasm_bindgen]
+impl AdaptiveHyperLogLog {
+  /// Creates a new adaptive HyperLogLog.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The starting precision (between 4 and 16), defaulting to 4
+  /// * `max_precision` - The precision the sketch may grow to, defaulting to 14
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    precision: Option<u8>,
+    max_precision: Option<u8>,
+  ) -> Result<AdaptiveHyperLogLog, SketchError> {
+    let p = precision.unwrap_or(4);
+    let max_precision = max_precision.unwrap_or(14);
+    if !(4..=16).contains(&p) || !(p..=16).contains(&max_precision) {
+      return Err(SketchError::InvalidParameter(
+        "precisions must satisfy 4 <= precision <= max_precision <= 16".to_string(),
+      ));
+    }
+    Ok(AdaptiveHyperLogLog {
+      hll: Self::registers_at(p, &[]),
+      max_precision,
+      hashes: Some(Vec::new()),
+    })
+  }
+
+  /// Adds an item, raising the precision if the sparse hashes have outgrown the registers.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The item to add
+  #[wasm_bindgen]
+  pub fn add(&mut self, value: &str) {
+    let hash = HyperLogLog::hash(value.as_bytes());
+    let (index, rank) = HyperLogLog::slot(hash, self.hll.precision());
+    self.hll.raise_register(index, rank);
+
+    let Some(hashes) = &mut self.hashes else {
+      return;
+    };
+    if let Err(position) = hashes.binary_search(&hash) {
+      hashes.insert(position, hash);
+    }
+    // Keep the hashes no larger than the register array they stand in for
+    while let Some(hashes) = &self.hashes {
+      if hashes.len() * 4 <= self.hll.memory_bytes() {
+        break;
+      }
+      if self.hll.precision() < self.max_precision {
+        self.hll = Self::registers_at(self.hll.precision() + 1, hashes);
+      } else {
+        self.hashes = None;
+      }
+    }
+  }
+
+  /// Raises the precision now rather than waiting for the cardinality to grow. Only possible
+  /// while the sketch is still sparse.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The new precision, between the current and the maximum precision
+  #[wasm_bindgen]
+  pub fn upgrade(&mut self, precision: u8) -> Result<(), SketchError> {
+    let Some(hashes) = &self.hashes else {
+      return Err(SketchError::InvalidParameter(
+        "a dense sketch cannot change precision".to_string(),
+      ));
+    };
+    if !(self.hll.precision()..=self.max_precision).contains(&precision) {
+      return Err(SketchError::InvalidParameter(
+        "precision must be between the current and the maximum precision".to_string(),
+      ));
+    }
+    self.hll = Self::registers_at(precision, hashes);
+    Ok(())
+  }
+
+  /// Returns the estimated number of distinct items.
+  #[wasm_bindgen]
+  pub fn count(&self) -> f64 {
+    self.hll.count()
+  }
+
+  /// Returns the current precision.
+  #[wasm_bindgen(getter)]
+  pub fn precision(&self) -> u8 {
+    self.hll.precision()
+  }
+
+  /// Returns true while the sketch can still change precision losslessly.
+  #[wasm_bindgen(getter)]
+  pub fn is_sparse(&self) -> bool {
+    self.hashes.is_some()
+  }
+
+  /// Returns the number of bytes used by the registers and any sparse hashes.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.hll.memory_bytes() + self.hashes.as_ref().map_or(0, |hashes| hashes.len() * 4)
+  }
+
+  /// Removes all items, returning to the sparse representation at the current precision.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.hll.clear();
+    self.hashes = Some(Vec::new());
+  }
+}
+
+impl AdaptiveHyperLogLog {
+  // Builds the registers for precision `p` from a set of hashes
+  fn registers_at(p: u8, hashes: &[u32]) -> HyperLogLog {
+    let mut hll = HyperLogLog::new(Some(p)).expect("precision was validated");
+    for &hash in hashes {
+      let (index, rank) = HyperLogLog::slot(hash, p);
+      hll.raise_register(index, rank);
+    }
+    hll
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_upgrade_matches_direct_insertion() {
+    let mut adaptive = AdaptiveHyperLogLog::new(Some(4), Some(12)).unwrap();
+    let mut direct = HyperLogLog::new(Some(10)).unwrap();
+    for i in 0..50 {
+      adaptive.add(&format!("item_{}", i));
+      direct.add(&format!("item_{}", i));
+    }
+    assert!(adaptive.is_sparse());
+    assert!(adaptive.precision() > 4);
+    adaptive.upgrade(10).unwrap();
+    assert_eq!(adaptive.count(), direct.count());
+    assert!(adaptive.upgrade(9).is_err());
+    assert!(adaptive.upgrade(13).is_err());
+  }
+
+  #[test]
+  fn test_grows_then_stays_dense() {
+    let mut adaptive = AdaptiveHyperLogLog::new(None, Some(8)).unwrap();
+    assert_eq!(adaptive.memory_bytes(), 16);
+    for i in 0..10_000 {
+      adaptive.add(&format!("item_{}", i));
+    }
+    assert!(!adaptive.is_sparse());
+    assert_eq!(adaptive.precision(), 8);
+    assert_eq!(adaptive.memory_bytes(), 256);
+    assert!((adaptive.count() - 10_000.0).abs() < 1_500.0);
+    assert!(adaptive.upgrade(8).is_err());
+
+    adaptive.clear();
+    assert!(adaptive.is_sparse());
+    assert!(AdaptiveHyperLogLog::new(Some(10), Some(8)).is_err());
+  }
+}
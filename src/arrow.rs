@@ -0,0 +1,543 @@
+//! A minimal reader for string columns in Arrow IPC buffers, backing the `"arrow"` format of
+//! `ingest_column`. It walks the FlatBuffers metadata by hand rather than pulling in the
+//! `arrow` crates, and decodes only what a string column needs: `Utf8` and `LargeUtf8`
+//! values, plain or dictionary-encoded, across any number of record batches.
+
+use crate::error::SketchError;
+
+// Prefix of the IPC file format, padded to at least 8 bytes; the stream format follows it
+const FILE_MAGIC: &[u8] = b"ARROW1";
+// Marks the start of a message since format 0.15; older streams begin with the length
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+// Metadata version V4, the oldest whose buffer layout matches the current one
+const MIN_METADATA_VERSION: i16 = 3;
+// Deepest field nesting followed, so cyclic metadata cannot exhaust the stack
+const MAX_NESTING: usize = 64;
+
+// MessageHeader union tags
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_DICTIONARY_BATCH: u8 = 2;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+// Type union tags
+const TYPE_NULL: u8 = 1;
+const TYPE_BINARY: u8 = 4;
+const TYPE_UTF8: u8 = 5;
+const TYPE_LIST: u8 = 12;
+const TYPE_STRUCT: u8 = 13;
+const TYPE_FIXED_SIZE_LIST: u8 = 16;
+const TYPE_MAP: u8 = 17;
+const TYPE_LARGE_BINARY: u8 = 19;
+const TYPE_LARGE_UTF8: u8 = 20;
+const TYPE_LARGE_LIST: u8 = 21;
+
+/// Calls `f` with every non-null value of the string column at zero-based index `column` of an
+/// Arrow IPC stream or file, in row order. Fails if the column is not `Utf8` or `LargeUtf8`
+/// (optionally dictionary-encoded), or if the buffer uses compression.
+pub(crate) fn for_each_string(
+  buffer: &[u8],
+  column: usize,
+  mut f: impl FnMut(&[u8]) -> Result<(), SketchError>,
+) -> Result<(), SketchError> {
+  let mut input = buffer;
+  if buffer.starts_with(FILE_MAGIC) {
+    input = buffer.get(8..).ok_or_else(|| malformed("truncated Arrow file header"))?;
+    // Writers may pad the magic to a wider alignment; no message starts with 8 zero bytes
+    while let Some(rest) = input.strip_prefix(&[0; 8]) {
+      input = rest;
+    }
+  }
+
+  let mut target: Option<Target> = None;
+  let mut dictionary: Vec<Option<&[u8]>> = Vec::new();
+  while let Some((message, body)) = next_message(&mut input)? {
+    let header = message.table(2)?;
+    match (message.u8(1)?, header) {
+      (HEADER_SCHEMA, Some(schema)) if target.is_none() => {
+        target = Some(Target::from_schema(schema, column)?);
+      }
+      (HEADER_DICTIONARY_BATCH, Some(batch)) => {
+        let target = target.as_ref().ok_or_else(|| malformed("Arrow schema is missing"))?;
+        let Some(encoding) = &target.dictionary else {
+          continue;
+        };
+        if batch.i64(0)? != encoding.id {
+          continue;
+        }
+        let data = batch.table(1)?.ok_or_else(|| malformed("Arrow dictionary has no data"))?;
+        if !batch.bool(2)? {
+          dictionary.clear();
+        }
+        let values = Column { node: 0, buffer: 0 };
+        values.strings(&RecordBatch::new(data, body)?, target.large, |value| {
+          dictionary.push(value);
+          Ok(())
+        })?;
+      }
+      (HEADER_RECORD_BATCH, Some(batch)) => {
+        let target = target.as_ref().ok_or_else(|| malformed("Arrow schema is missing"))?;
+        let batch = RecordBatch::new(batch, body)?;
+        match &target.dictionary {
+          Some(encoding) => target.column.indices(&batch, encoding, |index| {
+            let value = dictionary
+              .get(index)
+              .ok_or_else(|| malformed("Arrow dictionary index out of range"))?;
+            value.map_or(Ok(()), &mut f)
+          })?,
+          None => {
+            target.column.strings(&batch, target.large, |value| value.map_or(Ok(()), &mut f))?
+          }
+        }
+      }
+      // Tensors and repeated schemas carry nothing to read
+      _ => {}
+    }
+  }
+  if target.is_none() {
+    return Err(malformed("Arrow buffer has no schema"));
+  }
+  Ok(())
+}
+
+// Reads the next message's metadata and body, or `None` at the end-of-stream marker or the
+// end of the input
+fn next_message<'a>(input: &mut &'a [u8]) -> Result<Option<(Table<'a>, &'a [u8])>, SketchError> {
+  if input.is_empty() {
+    return Ok(None);
+  }
+  let mut len = read_u32(input, 0)?;
+  let mut start = 4;
+  if len == CONTINUATION {
+    len = read_u32(input, 4)?;
+    start = 8;
+  }
+  if len == 0 {
+    return Ok(None);
+  }
+
+  let metadata = slice(input, start, len as usize)?;
+  let message = Table::root(metadata)?;
+  if message.i16(0)? < MIN_METADATA_VERSION {
+    return Err(SketchError::InvalidParameter("Arrow metadata version is too old".to_string()));
+  }
+  let body_len = to_usize(message.i64(3)?)?;
+  let body = slice(input, start + metadata.len(), body_len)?;
+  *input = &input[start + metadata.len() + body_len..];
+  Ok(Some((message, body)))
+}
+
+// The column being read: where its arrays sit in each record batch, and how to decode them
+struct Target {
+  column: Column,
+  // Offsets are 64-bit (`LargeUtf8`) rather than 32-bit (`Utf8`)
+  large: bool,
+  dictionary: Option<DictionaryEncoding>,
+}
+
+struct DictionaryEncoding {
+  id: i64,
+  // Width of each index in bytes
+  index_width: usize,
+  signed: bool,
+}
+
+impl Target {
+  fn from_schema(schema: Table, column: usize) -> Result<Target, SketchError> {
+    if schema.i16(0)? != 0 {
+      return Err(SketchError::InvalidParameter(
+        "big-endian Arrow data is not supported".to_string(),
+      ));
+    }
+    let fields = schema.tables(1)?;
+    let field = fields.get(column).ok_or_else(|| {
+      SketchError::InvalidParameter(format!("Arrow buffer has {} columns", fields.len()))
+    })?;
+
+    // Record batches list the arrays of every field, children included, in schema order
+    let mut position = Column { node: 0, buffer: 0 };
+    for earlier in &fields[..column] {
+      position.skip(earlier, 0)?;
+    }
+
+    let large = match field.u8(2)? {
+      TYPE_UTF8 => false,
+      TYPE_LARGE_UTF8 => true,
+      _ => {
+        return Err(SketchError::InvalidParameter(format!(
+          "Arrow column {} is not a string column",
+          column
+        )))
+      }
+    };
+    let dictionary = match field.table(4)? {
+      Some(encoding) => {
+        let index_type = encoding.table(1)?;
+        let (bit_width, signed) = match index_type {
+          Some(index_type) => (index_type.i32(0)?, index_type.bool(1)?),
+          // The index type defaults to a signed 32-bit integer
+          None => (32, true),
+        };
+        if !matches!(bit_width, 8 | 16 | 32 | 64) {
+          return Err(malformed("Arrow dictionary index width is invalid"));
+        }
+        Some(DictionaryEncoding {
+          id: encoding.i64(0)?,
+          index_width: bit_width as usize / 8,
+          signed,
+        })
+      }
+      None => None,
+    };
+    Ok(Target { column: position, large, dictionary })
+  }
+}
+
+// Indices of a field's first node and buffer within a record batch
+#[derive(Debug, Clone, Copy)]
+struct Column {
+  node: usize,
+  buffer: usize,
+}
+
+impl Column {
+  // Moves past the nodes and buffers of `field` and its children
+  fn skip(&mut self, field: &Table, depth: usize) -> Result<(), SketchError> {
+    self.node += 1;
+    // Each field takes at least 4 bytes of metadata, which bounds shared or cyclic children
+    if depth > MAX_NESTING || self.node > field.buf.len() / 4 {
+      return Err(malformed("Arrow schema nests too deeply"));
+    }
+    if field.table(4)?.is_some() {
+      // Dictionary-encoded arrays hold a validity bitmap and the indices
+      self.buffer += 2;
+      return Ok(());
+    }
+    let type_tag = field.u8(2)?;
+    self.buffer += match type_tag {
+      TYPE_NULL => 0,
+      TYPE_BINARY | TYPE_UTF8 | TYPE_LARGE_BINARY | TYPE_LARGE_UTF8 => 3,
+      TYPE_LIST | TYPE_MAP | TYPE_LARGE_LIST => 2,
+      TYPE_STRUCT | TYPE_FIXED_SIZE_LIST => 1,
+      // Int, FloatingPoint, Bool, Decimal, Date, Time, Timestamp, Interval, FixedSizeBinary and
+      // Duration: a validity bitmap and the values
+      2 | 3 | 6..=11 | 15 | 18 => 2,
+      _ => {
+        return Err(SketchError::InvalidParameter(format!(
+          "Arrow columns of type {} cannot be skipped",
+          type_tag
+        )))
+      }
+    };
+    for child in field.tables(5)? {
+      self.skip(&child, depth + 1)?;
+    }
+    Ok(())
+  }
+
+  // Passes each value of a string array, or `None` for nulls
+  fn strings<'a>(
+    &self,
+    batch: &RecordBatch<'a>,
+    large: bool,
+    mut f: impl FnMut(Option<&'a [u8]>) -> Result<(), SketchError>,
+  ) -> Result<(), SketchError> {
+    let (len, validity) = batch.node(self.node, self.buffer)?;
+    let width = if large { 8 } else { 4 };
+    let offsets = batch.buffer(self.buffer + 1)?;
+    let data = batch.buffer(self.buffer + 2)?;
+    if len > 0 && offsets.len() / width <= len {
+      return Err(malformed("Arrow offsets buffer is too short"));
+    }
+
+    let offset = |i: usize| to_usize(read_int(offsets, i * width, width, true)?);
+    for i in 0..len {
+      if !validity.is_valid(i) {
+        f(None)?;
+        continue;
+      }
+      let (start, end) = (offset(i)?, offset(i + 1)?);
+      let value = data.get(start..end).ok_or_else(|| malformed("Arrow offsets out of range"))?;
+      f(Some(value))?;
+    }
+    Ok(())
+  }
+
+  // Passes each non-null index of a dictionary-encoded array
+  fn indices(
+    &self,
+    batch: &RecordBatch,
+    encoding: &DictionaryEncoding,
+    mut f: impl FnMut(usize) -> Result<(), SketchError>,
+  ) -> Result<(), SketchError> {
+    let (len, validity) = batch.node(self.node, self.buffer)?;
+    let indices = batch.buffer(self.buffer + 1)?;
+    if indices.len() / encoding.index_width < len {
+      return Err(malformed("Arrow indices buffer is too short"));
+    }
+    for i in 0..len {
+      if validity.is_valid(i) {
+        let index =
+          read_int(indices, i * encoding.index_width, encoding.index_width, encoding.signed)?;
+        f(to_usize(index)?)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+// A record batch's array nodes and buffers, resolved against the message body
+struct RecordBatch<'a> {
+  nodes: &'a [u8],
+  buffers: &'a [u8],
+  body: &'a [u8],
+}
+
+impl<'a> RecordBatch<'a> {
+  fn new(batch: Table<'a>, body: &'a [u8]) -> Result<RecordBatch<'a>, SketchError> {
+    if batch.table(3)?.is_some() {
+      return Err(SketchError::InvalidParameter(
+        "compressed Arrow buffers are not supported".to_string(),
+      ));
+    }
+    // FieldNode and Buffer are both structs of two 64-bit integers
+    Ok(RecordBatch { nodes: batch.structs(1, 16)?, buffers: batch.structs(2, 16)?, body })
+  }
+
+  // Returns a node's length and validity bitmap, given the index of its first buffer
+  fn node(&self, index: usize, buffer: usize) -> Result<(usize, Validity<'a>), SketchError> {
+    let pos = index.checked_mul(16).ok_or_else(|| malformed("Arrow node out of range"))?;
+    let len = to_usize(read_int(self.nodes, pos, 8, true)?)?;
+    let null_count = read_int(self.nodes, pos + 8, 8, true)?;
+    let bitmap = self.buffer(buffer)?;
+    if null_count == 0 || bitmap.is_empty() {
+      return Ok((len, Validity(None)));
+    }
+    if bitmap.len() < len.div_ceil(8) {
+      return Err(malformed("Arrow validity bitmap is too short"));
+    }
+    Ok((len, Validity(Some(bitmap))))
+  }
+
+  fn buffer(&self, index: usize) -> Result<&'a [u8], SketchError> {
+    let pos = index.checked_mul(16).ok_or_else(|| malformed("Arrow buffer out of range"))?;
+    let offset = to_usize(read_int(self.buffers, pos, 8, true)?)?;
+    let len = to_usize(read_int(self.buffers, pos + 8, 8, true)?)?;
+    slice(self.body, offset, len)
+  }
+}
+
+// A validity bitmap, least significant bit first; `None` when every value is present
+#[derive(Debug, Clone, Copy)]
+struct Validity<'a>(Option<&'a [u8]>);
+
+impl Validity<'_> {
+  fn is_valid(&self, i: usize) -> bool {
+    self.0.is_none_or(|bitmap| bitmap[i / 8] & (1 << (i % 8)) != 0)
+  }
+}
+
+// A FlatBuffers table: a position in the buffer and the vtable locating its fields
+#[derive(Debug, Clone, Copy)]
+struct Table<'a> {
+  buf: &'a [u8],
+  pos: usize,
+  vtable: usize,
+  vtable_len: usize,
+}
+
+impl<'a> Table<'a> {
+  fn root(buf: &'a [u8]) -> Result<Table<'a>, SketchError> {
+    Table::at(buf, read_u32(buf, 0)? as usize)
+  }
+
+  fn at(buf: &'a [u8], pos: usize) -> Result<Table<'a>, SketchError> {
+    // The vtable sits at a signed offset back from the table
+    let back = i64::from(read_u32(buf, pos)? as i32);
+    let vtable = usize::try_from(pos as i64 - back).map_err(|_| malformed("bad Arrow metadata"))?;
+    let vtable_len = usize::from(read_u16(buf, vtable)?);
+    Ok(Table { buf, pos, vtable, vtable_len })
+  }
+
+  // Returns the position of field `index`, or `None` if it holds the default
+  fn field(&self, index: usize) -> Result<Option<usize>, SketchError> {
+    let slot = 4 + 2 * index;
+    if slot + 2 > self.vtable_len {
+      return Ok(None);
+    }
+    let offset = usize::from(read_u16(self.buf, self.vtable + slot)?);
+    Ok((offset != 0).then_some(self.pos + offset))
+  }
+
+  fn u8(&self, index: usize) -> Result<u8, SketchError> {
+    self.field(index)?.map_or(Ok(0), |pos| Ok(slice(self.buf, pos, 1)?[0]))
+  }
+
+  fn bool(&self, index: usize) -> Result<bool, SketchError> {
+    Ok(self.u8(index)? != 0)
+  }
+
+  fn i16(&self, index: usize) -> Result<i16, SketchError> {
+    self.field(index)?.map_or(Ok(0), |pos| Ok(read_u16(self.buf, pos)? as i16))
+  }
+
+  fn i32(&self, index: usize) -> Result<i32, SketchError> {
+    self.field(index)?.map_or(Ok(0), |pos| Ok(read_u32(self.buf, pos)? as i32))
+  }
+
+  fn i64(&self, index: usize) -> Result<i64, SketchError> {
+    self.field(index)?.map_or(Ok(0), |pos| read_int(self.buf, pos, 8, true))
+  }
+
+  fn table(&self, index: usize) -> Result<Option<Table<'a>>, SketchError> {
+    match self.field(index)? {
+      Some(pos) => Ok(Some(Table::at(self.buf, indirect(self.buf, pos)?)?)),
+      None => Ok(None),
+    }
+  }
+
+  // Returns the elements of a vector of tables, or nothing if the field is absent
+  fn tables(&self, index: usize) -> Result<Vec<Table<'a>>, SketchError> {
+    let Some((start, len)) = self.vector(index)? else {
+      return Ok(Vec::new());
+    };
+    (0..len).map(|i| Table::at(self.buf, indirect(self.buf, start + 4 * i)?)).collect()
+  }
+
+  // Returns the bytes of a vector of `size`-byte structs
+  fn structs(&self, index: usize, size: usize) -> Result<&'a [u8], SketchError> {
+    match self.vector(index)? {
+      Some((start, len)) => slice(
+        self.buf,
+        start,
+        len.checked_mul(size).ok_or_else(|| malformed("bad Arrow metadata"))?,
+      ),
+      None => Ok(&[]),
+    }
+  }
+
+  // Returns the position of a vector's first element and its length
+  fn vector(&self, index: usize) -> Result<Option<(usize, usize)>, SketchError> {
+    let Some(pos) = self.field(index)? else {
+      return Ok(None);
+    };
+    let start = indirect(self.buf, pos)?;
+    let len = read_u32(self.buf, start)? as usize;
+    // Every element takes at least 4 bytes, so this bounds `tables` too
+    if len > self.buf.len() / 4 {
+      return Err(malformed("bad Arrow metadata"));
+    }
+    Ok(Some((start + 4, len)))
+  }
+}
+
+// Follows the unsigned offset stored at `pos`, which is relative to `pos` itself
+fn indirect(buf: &[u8], pos: usize) -> Result<usize, SketchError> {
+  pos.checked_add(read_u32(buf, pos)? as usize).ok_or_else(|| malformed("bad Arrow metadata"))
+}
+
+fn slice(buf: &[u8], pos: usize, len: usize) -> Result<&[u8], SketchError> {
+  pos
+    .checked_add(len)
+    .and_then(|end| buf.get(pos..end))
+    .ok_or_else(|| malformed("truncated Arrow buffer"))
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Result<u16, SketchError> {
+  Ok(read_int(buf, pos, 2, false)? as u16)
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<u32, SketchError> {
+  Ok(read_int(buf, pos, 4, false)? as u32)
+}
+
+// Reads a little-endian integer of `width` bytes, sign-extending it if `signed`
+fn read_int(buf: &[u8], pos: usize, width: usize, signed: bool) -> Result<i64, SketchError> {
+  let bytes = slice(buf, pos, width)?;
+  let mut raw = [0u8; 8];
+  raw[..width].copy_from_slice(bytes);
+  let value = u64::from_le_bytes(raw);
+  let shift = 64 - 8 * width as u32;
+  Ok(if signed { ((value << shift) as i64) >> shift } else { value as i64 })
+}
+
+fn to_usize(value: i64) -> Result<usize, SketchError> {
+  usize::try_from(value).map_err(|_| malformed("negative Arrow length or offset"))
+}
+
+fn malformed(message: &str) -> SketchError {
+  SketchError::Malformed(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use arrow_array::builder::{ListBuilder, StringBuilder};
+  use arrow_array::types::Int8Type;
+  use arrow_array::{
+    ArrayRef, DictionaryArray, Int32Array, LargeStringArray, RecordBatch as Batch,
+  };
+  use arrow_ipc::writer::{FileWriter, StreamWriter};
+
+  use super::*;
+
+  fn batch(offset: i32, names: Vec<Option<&str>>, cities: Vec<Option<&str>>) -> Batch {
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    for _ in 0..names.len() {
+      tags.values().append_value("tag");
+      tags.append(true);
+    }
+    let columns: Vec<(&str, ArrayRef)> = vec![
+      ("id", Arc::new(Int32Array::from_iter_values(offset..offset + names.len() as i32))),
+      ("tags", Arc::new(tags.finish())),
+      ("name", Arc::new(arrow_array::StringArray::from(names))),
+      ("city", Arc::new(cities.into_iter().collect::<DictionaryArray<Int8Type>>())),
+    ];
+    Batch::try_from_iter(columns).unwrap()
+  }
+
+  fn collect(buffer: &[u8], column: usize) -> Result<Vec<String>, SketchError> {
+    let mut values = Vec::new();
+    for_each_string(buffer, column, |value| {
+      values.push(String::from_utf8(value.to_vec()).unwrap());
+      Ok(())
+    })?;
+    Ok(values)
+  }
+
+  #[test]
+  fn test_reads_string_columns_of_a_stream() {
+    let first =
+      batch(0, vec![Some("ann"), None, Some("bo")], vec![Some("oslo"), Some("rome"), None]);
+    let second = batch(3, vec![Some("cy")], vec![Some("oslo")]);
+    let mut writer = StreamWriter::try_new(Vec::new(), &first.schema()).unwrap();
+    writer.write(&first).unwrap();
+    writer.write(&second).unwrap();
+    let stream = writer.into_inner().unwrap();
+
+    assert_eq!(collect(&stream, 2).unwrap(), ["ann", "bo", "cy"]);
+    assert_eq!(collect(&stream, 3).unwrap(), ["oslo", "rome", "oslo"]);
+    assert!(matches!(collect(&stream, 0), Err(SketchError::InvalidParameter(_))));
+    assert!(matches!(collect(&stream, 4), Err(SketchError::InvalidParameter(_))));
+
+    // Truncation anywhere fails cleanly or stops at a message boundary
+    for len in 0..stream.len() {
+      let _ = collect(&stream[..len], 2);
+    }
+    assert!(collect(&stream[..stream.len() / 2], 2).is_err());
+  }
+
+  #[test]
+  fn test_reads_large_strings_of_a_file() {
+    let values: ArrayRef = Arc::new(LargeStringArray::from(vec!["x", "", "y"]));
+    let batch = Batch::try_from_iter(vec![("value", values)]).unwrap();
+    let mut writer = FileWriter::try_new(Vec::new(), &batch.schema()).unwrap();
+    writer.write(&batch).unwrap();
+    writer.finish().unwrap();
+    let file = writer.into_inner().unwrap();
+
+    assert_eq!(collect(&file, 0).unwrap(), ["x", "", "y"]);
+    assert!(collect(b"ARROW1", 0).is_err());
+    assert!(collect(&[0xff; 12], 0).is_err());
+  }
+}
@@ -0,0 +1,89 @@
+/** Options for time-sliced batch processing. */
+export interface BatchOptions {
+  /** Longest time to run before yielding to the event loop, in milliseconds. Defaults to 8. */
+  sliceMs?: number;
+  /** Called after each slice with the number of items processed so far. */
+  onProgress?: (processed: number, total: number) => void;
+  /** Stops processing at the next slice boundary, rejecting with the signal's reason. */
+  signal?: AbortSignal;
+}
+
+// Items between clock checks, so reading the clock stays cheap relative to the work
+const CHECK_INTERVAL = 256;
+
+function yieldToEventLoop(): Promise<void> {
+  return new Promise((resolve) => setTimeout(resolve, 0));
+}
+
+/**
+ * Applies `apply` to every item in time-limited slices, yielding to the event loop between
+ * slices so ingesting a very large input keeps the page responsive. Resolves with the number
+ * of items processed.
+ *
+ * ```typescript
+ * await processInSlices(userIds, (id) => hll.add(id));
+ * ```
+ */
+export async function processInSlices<T>(
+  items: ArrayLike<T>,
+  apply: (item: T) => void,
+  options: BatchOptions = {}
+): Promise<number> {
+  const sliceMs = options.sliceMs ?? 8;
+  let index = 0;
+  while (index < items.length) {
+    options.signal?.throwIfAborted();
+    const deadline = Date.now() + sliceMs;
+    do {
+      const end = Math.min(index + CHECK_INTERVAL, items.length);
+      for (; index < end; index++) {
+        apply(items[index]);
+      }
+    } while (index < items.length && Date.now() < deadline);
+
+    options.onProgress?.(index, items.length);
+    if (index < items.length) {
+      await yieldToEventLoop();
+    }
+  }
+  return index;
+}
+
+/**
+ * Applies `apply` to every non-empty line of a text buffer (e.g. a log export) in time-limited
+ * slices. Lines are split lazily, so the whole buffer is never copied into an array.
+ * Resolves with the number of lines processed.
+ */
+export async function processLinesInSlices(
+  text: string,
+  apply: (line: string) => void,
+  options: BatchOptions = {}
+): Promise<number> {
+  const sliceMs = options.sliceMs ?? 8;
+  let offset = 0;
+  let processed = 0;
+  while (offset < text.length) {
+    options.signal?.throwIfAborted();
+    const deadline = Date.now() + sliceMs;
+    do {
+      for (let n = 0; n < CHECK_INTERVAL && offset < text.length; n++) {
+        let end = text.indexOf('\n', offset);
+        if (end === -1) {
+          end = text.length;
+        }
+        const line = text.slice(offset, end).replace(/\r$/, '');
+        offset = end + 1;
+        if (line.length > 0) {
+          apply(line);
+          processed++;
+        }
+      }
+    } while (offset < text.length && Date.now() < deadline);
+
+    options.onProgress?.(Math.min(offset, text.length), text.length);
+    if (offset < text.length) {
+      await yieldToEventLoop();
+    }
+  }
+  return processed;
+}
@@ -1,14 +1,119 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use js_sys::{SharedArrayBuffer, Uint8Array};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use crate::codec::{decode_base64, encode_base64, read_u64, read_varint, write_varint};
+use crate::column::for_each_value;
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+use crate::memory::try_zeroed;
+use crate::replay::{Journal, ReplayLog, ReplayOp, ReplayTarget};
+use crate::runtime;
+
+const MAGIC: &[u8; 4] = b"SWBF";
+const FORMAT_VERSION: u8 = 1;
+// Format of seeded filters: the version 1 header followed by the seed as a varint
+const SEEDED_FORMAT_VERSION: u8 = 2;
+// Format of unevenly downsampled filters: the version 2 header followed by the probe size
+const FOLDED_FORMAT_VERSION: u8 = 3;
+const JSON_VERSION: u8 = 1;
+// Magic, version byte and up to four varints of at most ten bytes each
+pub(crate) const MAX_HEADER_LEN: usize = 45;
+// Upper bound on decoded bits (256 MiB), guarding against hostile headers
+const MAX_DECODED_BITS: u64 = 1 << 31;
+// Checkpoints kept by `checkpoint`; older ones are dropped first
+const MAX_CHECKPOINTS: usize = 1024;
+
+/// The probes examined by one `contains` call, recorded while probe tracing is enabled.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ProbeTrace {
+  item: String,
+  positions: Vec<u32>,
+  failed_probe: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl ProbeTrace {
+  /// Returns the item that was checked.
+  #[wasm_bindgen(getter)]
+  pub fn item(&self) -> String {
+    self.item.clone()
+  }
+
+  /// Returns the bit positions probed, in probe order. For a negative result the last one is
+  /// the clear bit that ruled the item out; for a positive result all of them were set.
+  #[wasm_bindgen(getter)]
+  pub fn positions(&self) -> Vec<u32> {
+    self.positions.clone()
+  }
+
+  /// Returns the index of the probe that found a clear bit, or `undefined` if all were set.
+  #[wasm_bindgen(getter)]
+  pub fn failed_probe(&self) -> Option<u32> {
+    self.failed_probe
+  }
+
+  /// Returns the result of the check.
+  #[wasm_bindgen(getter)]
+  pub fn found(&self) -> bool {
+    self.failed_probe.is_none()
+  }
+}
+
 /// A space-efficient probabilistic data structure that is used to test whether an element is a member of a set.
 /// False positives are possible, but false negatives are not.
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BloomFilter {
-  bits: Vec<bool>,
+  // Bit array packed into 64-bit words, least significant bit first; bits past `len` stay clear
+  bits: Vec<u64>,
+  // Number of bits in the filter
+  len: usize,
+  // Slots probes are spread over before wrapping onto `len`; larger than `len` only after
+  // downsampling by a factor that does not divide the size
+  probe_len: usize,
   hash_count: usize,
+  // Mixed into every key hash when non-zero, so differently seeded filters probe independently
+  seed: u64,
+  // Recording and write-ahead log, either of which may be active
+  journal: Journal,
+  // (timestamp in ms, set bits) recorded by `checkpoint`, oldest first
+  checkpoints: Vec<(f64, u32)>,
+  // Set bits at the newest dropped checkpoint, the baseline of the oldest kept one
+  dropped_set_bits: u32,
+  // Probes of the latest `contains` call, present while tracing; interior so checks stay `&self`
+  trace: RefCell<Option<Option<ProbeTrace>>>,
+}
+
+// JSON shape of a filter: the bit array is base64 of its bytes, least significant bit first
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct BloomFilterJson {
+  version: u8,
+  bit_count: u64,
+  hash_count: u64,
+  // 16 hex digits, since a 64-bit seed does not fit a JSON number; absent when unseeded
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  seed: Option<String>,
+  // Absent unless the filter was downsampled unevenly
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  probe_bits: Option<u64>,
+  bits: String,
+}
+
+/// Parameters decoded from a serialized filter header.
+#[derive(Debug)]
+pub(crate) struct FilterParams {
+  pub(crate) bit_count: usize,
+  pub(crate) hash_count: usize,
+  pub(crate) seed: u64,
+  pub(crate) probe_len: usize,
 }
 
 #[wasm_bindgen]
@@ -21,47 +126,783 @@ impl BloomFilter {
   /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
   #[wasm_bindgen(constructor)]
   pub fn new(expected_items: usize, false_positive_rate: f64) -> BloomFilter {
-    let size = Self::optimal_size(expected_items, false_positive_rate);
-    let hash_count = Self::optimal_hash_count(size, expected_items);
+    let (size, hash_count) = Self::geometry(expected_items, false_positive_rate);
+    BloomFilter::with_size(size, hash_count)
+  }
+
+  /// Creates a new Bloom filter whose probe positions are derived from `seed`. Filters built
+  /// with the same parameters and seed probe identical positions in any session, on a server
+  /// using this crate, or in another implementation of its hashing: the item's xxHash64 mixed
+  /// with the seed by the SplitMix64 finalizer. The seed is kept when the filter is serialized.
+  /// A seed of 0 gives the same positions as the constructor.
+  ///
+  /// Only string keys are seeded: `insert_hashed` and `from_hashes` take hashes as given.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items to be inserted
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  /// * `seed` - The hash seed (a `BigInt` on the JS side)
+  #[wasm_bindgen]
+  pub fn with_seed(expected_items: usize, false_positive_rate: f64, seed: u64) -> BloomFilter {
+    let mut filter = BloomFilter::new(expected_items, false_positive_rate);
+    filter.seed = seed;
+    filter
+  }
+
+  /// Creates an empty Bloom filter with an exact geometry, e.g. to match a filter built
+  /// elsewhere, instead of deriving it from a capacity and false positive rate.
+  ///
+  /// # Arguments
+  ///
+  /// * `bit_size` - The number of bits in the filter
+  /// * `hash_count` - The number of probes per item (between 1 and 64)
+  #[wasm_bindgen]
+  pub fn with_params(bit_size: usize, hash_count: usize) -> Result<BloomFilter, SketchError> {
+    if bit_size == 0 {
+      return Err(SketchError::InvalidParameter("bit_size must be positive".to_string()));
+    }
+    if !(1..=64).contains(&hash_count) {
+      return Err(SketchError::InvalidParameter("hash_count must be between 1 and 64".to_string()));
+    }
+    Ok(BloomFilter::with_size(bit_size, hash_count))
+  }
+
+  /// Creates the most accurate filter for `expected_items` items whose bit array fits in
+  /// `bytes`, for sizing by a hard memory cap rather than a target false positive rate. The
+  /// bit array is rounded down to whole 64-bit words and the probe count is the integer that
+  /// minimizes the false positive rate at `expected_items`; read it back with
+  /// `expected_false_positive_rate`.
+  ///
+  /// # Arguments
+  ///
+  /// * `bytes` - The memory budget for the bit array (at least 8)
+  /// * `expected_items` - The expected number of items to be inserted
+  #[wasm_bindgen]
+  pub fn with_byte_budget(bytes: usize, expected_items: usize) -> Result<BloomFilter, SketchError> {
+    if bytes < 8 {
+      return Err(SketchError::InvalidParameter("bytes must be at least 8".to_string()));
+    }
+    if expected_items == 0 {
+      return Err(SketchError::InvalidParameter("expected_items must be positive".to_string()));
+    }
+    let size = bytes / 8 * 64;
+    let optimal = size as f64 / expected_items as f64 * std::f64::consts::LN_2;
+    let hash_count = [optimal.floor(), optimal.ceil()]
+      .map(|k| (k as usize).clamp(1, 64))
+      .into_iter()
+      .min_by(|&a, &b| {
+        let fpr = |k| Self::false_positive_rate_for(size, k, expected_items);
+        fpr(a).total_cmp(&fpr(b))
+      })
+      .unwrap_or(1);
+    Ok(BloomFilter::with_size(size, hash_count))
+  }
+
+  /// Creates a new Bloom filter like the constructor, but fails with an out-of-memory error
+  /// instead of aborting if the bit array cannot be allocated. Use it for filters large enough
+  /// that allocation may fail, e.g. after `reserve_memory` was refused.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items to be inserted
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn try_new(
+    expected_items: usize,
+    false_positive_rate: f64,
+  ) -> Result<BloomFilter, SketchError> {
+    let (size, hash_count) = Self::geometry(expected_items, false_positive_rate);
+    let mut filter = BloomFilter::with_size(0, hash_count);
+    filter.bits = try_zeroed(size.div_ceil(64))?;
+    filter.len = size;
+    filter.probe_len = size;
+    Ok(filter)
+  }
+
+  /// Builds a filter sized for exactly the given set of items, e.g. a blocklist shipped to
+  /// clients, and inserts them all. Duplicates are counted once when sizing.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to insert (an array of strings)
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn from_items(items: Vec<String>, false_positive_rate: f64) -> BloomFilter {
+    let distinct: HashSet<&str> = items.iter().map(String::as_str).collect();
+    let mut filter = BloomFilter::new(distinct.len().max(1), false_positive_rate);
+    for item in distinct {
+      filter.insert(item);
+    }
+    filter
+  }
+
+  /// Builds a filter directly from precomputed 64-bit key hashes, e.g. a server-provided list,
+  /// without receiving or hashing the raw keys. The filter is sized for the number of hashes.
+  ///
+  /// Probe `i` of a hash `h` lands on bit `(lo + i * (hi | 1)) mod size`, where `lo` and `hi`
+  /// are the low and high 32 bits of `h`; `insert` uses the same scheme over its own string
+  /// hash. The step is forced odd so that hashes with no high bits, such as 32-bit hashes,
+  /// still spread their probes.
+  ///
+  /// # Arguments
+  ///
+  /// * `hashes` - The key hashes to insert (a `BigUint64Array` on the JS side)
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn from_hashes(hashes: &[u64], false_positive_rate: f64) -> BloomFilter {
+    let mut filter = BloomFilter::new(hashes.len().max(1), false_positive_rate);
+    for &hash in hashes {
+      filter.set_probes(hash, filter.hash_count);
+    }
+    filter
+  }
+
+  /// Inserts an item into the Bloom filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    self.insert_hash(self.key_hash(item));
+  }
+
+  /// Inserts many items in one call, avoiding a JS-to-wasm crossing per item.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to insert (an array of strings)
+  #[wasm_bindgen]
+  pub fn insert_many(&mut self, items: Vec<String>) {
+    for item in &items {
+      self.insert(item);
+    }
+  }
+
+  /// Adds every non-empty value of one column of a CSV, newline-delimited or Arrow IPC buffer,
+  /// parsing it entirely in wasm so no JS string array is built. Nothing is added if any row is
+  /// malformed.
+  /// Returns the number of values inserted.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The column data as UTF-8 text or an Arrow IPC stream or file (a `Uint8Array`)
+  /// * `format` - `"csv"` (with a header row), `"lines"` (one value per line) or `"arrow"` (a
+  ///   `Utf8` column, optionally dictionary-encoded; nulls are skipped)
+  /// * `column` - The zero-based column to read; defaults to 0
+  #[wasm_bindgen]
+  pub fn ingest_column(
+    &mut self,
+    buffer: &[u8],
+    format: &str,
+    column: Option<usize>,
+  ) -> Result<usize, SketchError> {
+    for_each_value(buffer, format, column, |value| self.insert(value))
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    let hash = self.key_hash(item);
+    let mut trace = self.trace.borrow_mut();
+    let Some(last) = trace.as_mut() else {
+      return self.contains_hash(hash);
+    };
+
+    let mut positions = Vec::with_capacity(self.hash_count);
+    let mut failed_probe = None;
+    for (probe, position) in self.probe_positions(hash).into_iter().enumerate() {
+      positions.push(position as u32);
+      if !self.check_bit(position) {
+        failed_probe = Some(probe as u32);
+        break;
+      }
+    }
+    *last = Some(ProbeTrace { item: item.to_string(), positions, failed_probe });
+    failed_probe.is_none()
+  }
+
+  /// Starts recording the probes of each `contains` call, for debugging a specific false
+  /// positive or visualizing how lookups work. Only the latest call is kept.
+  #[wasm_bindgen]
+  pub fn enable_probe_trace(&mut self) {
+    *self.trace.get_mut() = Some(None);
+  }
+
+  /// Stops recording probes and discards the latest trace.
+  #[wasm_bindgen]
+  pub fn disable_probe_trace(&mut self) {
+    *self.trace.get_mut() = None;
+  }
+
+  /// Returns the probes of the latest `contains` call while tracing, or `undefined` if there
+  /// has been none since tracing was enabled.
+  #[wasm_bindgen]
+  pub fn last_probe_trace(&self) -> Option<ProbeTrace> {
+    self.trace.borrow().clone().flatten()
+  }
+
+  /// Inserts an item by a 64-bit hash computed elsewhere, skipping string hashing. Probes are
+  /// derived from the hash as described for `from_hashes`, so a filter should be fed either
+  /// hashes or strings, not both.
+  ///
+  /// # Arguments
+  ///
+  /// * `hash` - The item's hash (a `BigInt` on the JS side)
+  #[wasm_bindgen]
+  pub fn insert_hashed(&mut self, hash: u64) {
+    self.insert_hash(hash);
+  }
+
+  /// Checks if an item inserted with `insert_hashed` might be in the set.
+  ///
+  /// # Arguments
+  ///
+  /// * `hash` - The item's hash (a `BigInt` on the JS side)
+  #[wasm_bindgen]
+  pub fn contains_hashed(&self, hash: u64) -> bool {
+    self.contains_hash(hash)
+  }
+
+  /// Returns the bit positions an item probes, in probe order, for explaining a specific false
+  /// positive or checking another implementation against this one.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to locate
+  #[wasm_bindgen]
+  pub fn bloom_indices(&self, item: &str) -> Vec<u32> {
+    self.probe_positions(self.key_hash(item)).into_iter().map(|position| position as u32).collect()
+  }
+
+  /// Adds every item of another filter to this one by OR-ing the bit arrays, e.g. to combine
+  /// per-worker filters. Both filters must have the same size and probe count.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The filter to merge in
+  #[wasm_bindgen]
+  pub fn union(&mut self, other: &BloomFilter) -> Result<(), SketchError> {
+    self.check_compatible(other)?;
+    self.journal.check_unlogged("union filters")?;
+    for (word, &theirs) in self.bits.iter_mut().zip(&other.bits) {
+      *word |= theirs;
+    }
+    Ok(())
+  }
+
+  /// Returns a new filter holding the items of both filters, leaving both unchanged.
+  /// Both filters must have the same size and probe count.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The filter to combine with
+  #[wasm_bindgen]
+  pub fn union_new(&self, other: &BloomFilter) -> Result<BloomFilter, SketchError> {
+    self.check_compatible(other)?;
+    let mut combined = BloomFilter::with_size(self.len, self.hash_count);
+    combined.seed = self.seed;
+    combined.probe_len = self.probe_len;
+    for ((word, &ours), &theirs) in combined.bits.iter_mut().zip(&self.bits).zip(&other.bits) {
+      *word = ours | theirs;
+    }
+    Ok(combined)
+  }
+
+  /// Keeps only the bits set in both filters by AND-ing the bit arrays, approximating the
+  /// items seen by both. Every item in both sets is still found, but bits set by different
+  /// items in each filter survive too, so the result has a higher false-positive rate than a
+  /// filter built from the true intersection; `estimate_intersection_size` gauges the overlap
+  /// without that bias. Both filters must have the same size and probe count.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The filter to intersect with
+  #[wasm_bindgen]
+  pub fn intersect(&mut self, other: &BloomFilter) -> Result<(), SketchError> {
+    self.check_compatible(other)?;
+    self.journal.check_unlogged("intersect filters")?;
+    for (word, &theirs) in self.bits.iter_mut().zip(&other.bits) {
+      *word &= theirs;
+    }
+    Ok(())
+  }
+
+  /// Estimates the number of items inserted into both filters from the fill of each filter
+  /// and of their union, as `n(A) + n(B) - n(A ∪ B)`. Returns infinity once the union is
+  /// completely full, when the overlap can no longer be estimated.
+  /// Both filters must have the same size and probe count.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The filter to compare with
+  #[wasm_bindgen]
+  pub fn estimate_intersection_size(&self, other: &BloomFilter) -> Result<f64, SketchError> {
+    self.check_compatible(other)?;
+    let union = self.bits.iter().zip(&other.bits).map(|(a, b)| (a | b).count_ones()).sum();
+    if union as usize == self.len {
+      return Ok(f64::INFINITY);
+    }
+    let estimate = self.items_for_set_bits(self.set_bit_count())
+      + self.items_for_set_bits(other.set_bit_count())
+      - self.items_for_set_bits(union);
+    Ok(estimate.max(0.0))
+  }
+
+  /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the two filters' item sets, from
+  /// 0 (disjoint) to 1 (identical), e.g. to spot near-duplicate document sets. The union size
+  /// is estimated from the OR of the bit arrays and the overlap as in
+  /// `estimate_intersection_size`. Once the union is completely full, falls back to the ratio
+  /// of AND to OR population counts, which overstates the similarity. Two empty filters have a
+  /// similarity of 1. Both filters must have the same size, probe count and seed.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The filter to compare with
+  #[wasm_bindgen]
+  pub fn estimate_jaccard(&self, other: &BloomFilter) -> Result<f64, SketchError> {
+    self.check_compatible(other)?;
+    let (and, or) = self
+      .bits
+      .iter()
+      .zip(&other.bits)
+      .fold((0, 0), |(and, or), (a, b)| (and + (a & b).count_ones(), or + (a | b).count_ones()));
+    if or == 0 {
+      return Ok(1.0);
+    }
+    if or as usize == self.len {
+      return Ok(f64::from(and) / f64::from(or));
+    }
+    let union = self.items_for_set_bits(or);
+    let intersection = self.items_for_set_bits(self.set_bit_count())
+      + self.items_for_set_bits(other.set_bit_count())
+      - union;
+    Ok((intersection / union).clamp(0.0, 1.0))
+  }
+
+  /// Estimates how many distinct items have been inserted from the number of set bits `X`, as
+  /// `-m / k * ln(1 - X / m)`, e.g. to monitor whether the filter is nearing its design
+  /// capacity. Returns infinity once every bit is set.
+  #[wasm_bindgen]
+  pub fn estimated_count(&self) -> f64 {
+    self.items_for_set_bits(self.set_bit_count())
+  }
+
+  /// Returns the false-positive rate implied by the current fill, `(X / m)^k` for `X` set bits,
+  /// rather than the target passed to the constructor. It rises as a long-lived filter takes
+  /// in more items than it was sized for.
+  #[wasm_bindgen]
+  pub fn current_false_positive_rate(&self) -> f64 {
+    self.saturation().powi(self.hash_count as i32)
+  }
+
+  /// Returns the false positive rate the filter is expected to have once `items` distinct
+  /// items have been inserted, `(1 - e^(-k * n / m))^k`, e.g. to see what a byte budget buys.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The number of distinct items inserted
+  #[wasm_bindgen]
+  pub fn expected_false_positive_rate(&self, items: usize) -> f64 {
+    Self::false_positive_rate_for(self.len, self.hash_count, items)
+  }
+
+  /// Records the current bit occupancy, so `approx_distinct` can report how many distinct
+  /// items arrived between checkpoints. The latest 1024 checkpoints are kept. Clearing or
+  /// downsampling the filter discards the checkpoints.
+  ///
+  /// # Arguments
+  ///
+  /// * `timestamp_ms` - When the checkpoint is taken; defaults to the current time
+  #[wasm_bindgen]
+  pub fn checkpoint(&mut self, timestamp_ms: Option<f64>) {
+    let timestamp_ms = timestamp_ms.unwrap_or_else(runtime::now_ms);
+    if self.checkpoints.len() == MAX_CHECKPOINTS {
+      self.dropped_set_bits = self.checkpoints.remove(0).1;
+    }
+    self.checkpoints.push((timestamp_ms, self.set_bit_count()));
+  }
+
+  /// Returns a rough distinct-insert trend: for each checkpoint, the estimated number of new
+  /// distinct items since the previous one (for the first, since the checkpoint before it was
+  /// dropped, or since the filter was empty).
+  /// Estimates come from bit occupancy alone and grow noisy as the filter fills up.
+  #[wasm_bindgen]
+  pub fn approx_distinct(&self) -> Vec<f64> {
+    let mut previous = self.items_for_set_bits(self.dropped_set_bits);
+    self
+      .checkpoints
+      .iter()
+      .map(|&(_, set_bits)| {
+        let total = self.items_for_set_bits(set_bits);
+        let delta = total - previous;
+        previous = total;
+        delta
+      })
+      .collect()
+  }
+
+  /// Returns the timestamps of the checkpoints, aligned with `approx_distinct`.
+  #[wasm_bindgen]
+  pub fn checkpoint_times(&self) -> Vec<f64> {
+    self.checkpoints.iter().map(|&(time, _)| time).collect()
+  }
+
+  /// Checks many items in one call. Returns one byte per item, in order: 1 if the item is
+  /// probably in the set, 0 if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to check (an array of strings)
+  #[wasm_bindgen]
+  pub fn contains_many(&self, items: Vec<String>) -> Vec<u8> {
+    items.iter().map(|item| u8::from(self.contains(item))).collect()
+  }
+
+  /// Returns true if every item is probably in the set, stopping at the first definite miss;
+  /// e.g. to skip a search whose tokens cannot all match. True for an empty array.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to check (an array of strings)
+  #[wasm_bindgen]
+  pub fn contains_all(&self, items: Vec<String>) -> bool {
+    items.iter().all(|item| self.contains(item))
+  }
+
+  /// Returns true if at least one item is probably in the set, stopping at the first hit.
+  /// False for an empty array.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to check (an array of strings)
+  #[wasm_bindgen]
+  pub fn contains_any(&self, items: Vec<String>) -> bool {
+    items.iter().any(|item| self.contains(item))
+  }
+
+  /// Measures the false-positive rate empirically by querying `sample_size` deterministic
+  /// pseudo-random key hashes, which are almost surely not inserted keys. Useful as a health
+  /// check for filters loaded from untrusted or aged sources.
+  ///
+  /// # Arguments
+  ///
+  /// * `sample_size` - The number of probe keys to query
+  /// * `seed` - The seed for the probe key generator; the same seed gives the same result
+  #[wasm_bindgen]
+  pub fn probe_fpr(&self, sample_size: u32, seed: u64) -> f64 {
+    if sample_size == 0 {
+      return 0.0;
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let hits = (0..sample_size).filter(|_| self.contains_hash(rng.gen())).count();
+    hits as f64 / sample_size as f64
+  }
+
+  /// Shrinks the bit array to `ceil(bits / factor)` bits for bandwidth-limited sync, OR-ing
+  /// every bit onto the bit it maps to at the new size. When the new size divides the old one,
+  /// the result equals a filter of the new size that saw the same inserts, so a server can
+  /// merge it after downsampling its own copy by the same factor. Otherwise the filter keeps
+  /// probing over its old size and wraps probes onto the new one; the serialized formats carry
+  /// that probe size. The false-positive rate rises accordingly.
+  ///
+  /// # Arguments
+  ///
+  /// * `factor` - The shrink factor; after one uneven downsample, the new size must divide the
+  ///   current one
+  #[wasm_bindgen]
+  pub fn downsample(&mut self, factor: u32) -> Result<(), SketchError> {
+    self.journal.check_unlogged("downsample a filter")?;
+    if factor == 0 {
+      return Err(SketchError::InvalidParameter("factor must be positive".to_string()));
+    }
+    // Probes land on p = x % probe_len % len, which folds onto p % new_len. That equals
+    // x % probe_len % new_len only if new_len divides len or the filter is not yet folded
+    let new_len = self.len.div_ceil(factor as usize);
+    if !self.len.is_multiple_of(new_len) && self.probe_len != self.len {
+      return Err(SketchError::InvalidParameter(
+        "an unevenly downsampled filter can only shrink to a size that divides its own".to_string(),
+      ));
+    }
+    let mut folded = BloomFilter::with_size(new_len, self.hash_count);
+    for (index, &word) in self.bits.iter().enumerate() {
+      let mut rest = word;
+      while rest != 0 {
+        folded.set_bit((index * 64 + rest.trailing_zeros() as usize) % new_len);
+        rest &= rest - 1;
+      }
+    }
+    self.bits = folded.bits;
+    if !self.len.is_multiple_of(new_len) {
+      self.probe_len = self.len;
+    }
+    self.len = new_len;
+    self.checkpoints.clear();
+    self.dropped_set_bits = 0;
+    Ok(())
+  }
+
+  /// Starts recording every insert and clear applied to the filter.
+  /// Any previous recording is discarded.
+  #[wasm_bindgen]
+  pub fn start_recording(&mut self) {
+    self.journal.start_recording();
+  }
+
+  /// Stops recording and returns the recorded operations as a compact buffer of hashed keys.
+  /// Returns an empty buffer if the filter was not recording.
+  #[wasm_bindgen]
+  pub fn stop_recording(&mut self) -> Vec<u8> {
+    self.journal.stop_recording()
+  }
+
+  /// Starts keeping the most recent `capacity` operations in a write-ahead log, so updates made
+  /// since the last persisted snapshot can be recovered with `replay` after a crash. Older
+  /// operations are dropped, which `wal_overflowed` reports. Any previous log is discarded.
+  ///
+  /// While recording or keeping a write-ahead log, mutations that cannot be replayed, such as
+  /// merges and resizes, fail instead of silently diverging from the log.
+  ///
+  /// # Arguments
+  ///
+  /// * `capacity` - The maximum number of operations kept
+  #[wasm_bindgen]
+  pub fn enable_wal(&mut self, capacity: usize) {
+    self.journal.enable_wal(capacity);
+  }
+
+  /// Stops keeping a write-ahead log.
+  #[wasm_bindgen]
+  pub fn disable_wal(&mut self) {
+    self.journal.disable_wal();
+  }
+
+  /// Returns the write-ahead log in the same format as `stop_recording`, ready for `replay`.
+  /// Returns an empty buffer if the filter has no write-ahead log.
+  #[wasm_bindgen]
+  pub fn wal_bytes(&self) -> Vec<u8> {
+    self.journal.wal_bytes()
+  }
+
+  /// Returns true if the write-ahead log has dropped operations since it was last truncated,
+  /// so replaying it onto the last snapshot would lose updates; persist a new snapshot instead.
+  #[wasm_bindgen]
+  pub fn wal_overflowed(&self) -> bool {
+    self.journal.wal_overflowed()
+  }
+
+  /// Empties the write-ahead log; call after persisting a snapshot of the filter.
+  #[wasm_bindgen]
+  pub fn truncate_wal(&mut self) {
+    self.journal.truncate_wal();
+  }
+
+  /// Re-applies a buffer produced by `stop_recording` to this filter.
+  /// The buffer is validated fully before any operation is applied.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The recorded operations
+  #[wasm_bindgen]
+  pub fn replay(&mut self, buffer: &[u8]) -> Result<(), SketchError> {
+    for op in ReplayLog::parse(buffer, ReplayTarget::BloomFilter)? {
+      match op {
+        // Weighted updates only parse for CountMinSketch logs; the weight is moot here anyway
+        ReplayOp::Update(hash) | ReplayOp::UpdateBy(hash, _) => self.insert_hash(hash),
+        ReplayOp::Clear => self.clear(),
+      }
+    }
+    Ok(())
+  }
+
+  /// Serializes the filter for storage (e.g. IndexedDB) or transfer. The format is the magic
+  /// `SWBF`, a version byte, varint probe count, varint bit count, then the bit array as
+  /// little-endian 64-bit words, least significant bit first. Seeded filters (version 2) add
+  /// the seed after the bit count, and unevenly downsampled ones (version 3) the seed and the
+  /// probe size.
+  #[wasm_bindgen]
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    let version = if self.probe_len != self.len {
+      FOLDED_FORMAT_VERSION
+    } else if self.seed != 0 {
+      SEEDED_FORMAT_VERSION
+    } else {
+      FORMAT_VERSION
+    };
+    out.push(version);
+    write_varint(&mut out, self.hash_count as u64);
+    write_varint(&mut out, self.len as u64);
+    if version != FORMAT_VERSION {
+      write_varint(&mut out, self.seed);
+    }
+    if version == FOLDED_FORMAT_VERSION {
+      write_varint(&mut out, self.probe_len as u64);
+    }
+    for word in &self.bits {
+      out.extend_from_slice(&word.to_le_bytes());
+    }
+    out
+  }
+
+  /// Copies the serialized filter (see `to_bytes`) into a new `SharedArrayBuffer`, to post
+  /// to workers that query it through a `BloomFilterView` without copying the bit array
+  /// again. The buffer is a snapshot: later inserts into this filter do not reach it.
+  /// Requires a cross-origin isolated page.
+  #[wasm_bindgen]
+  pub fn to_shared_buffer(&self) -> SharedArrayBuffer {
+    let bytes = self.to_bytes();
+    let buffer = SharedArrayBuffer::new(bytes.len() as u32);
+    Uint8Array::new(&buffer).copy_from(&bytes);
+    buffer
+  }
+
+  /// Restores a filter from a buffer produced by `to_bytes`, with the same size, probe count
+  /// and bits.
+  ///
+  /// # Arguments
+  ///
+  /// * `bytes` - The serialized filter
+  #[wasm_bindgen]
+  pub fn from_bytes(bytes: &[u8]) -> Result<BloomFilter, SketchError> {
+    let mut input = bytes;
+    let params = Self::read_params(&mut input)?;
+    // Checked before allocating, so a short hostile header cannot force a huge bit array
+    if input.len() != params.bit_count.div_ceil(64) * 8 {
+      return Err(SketchError::Malformed("BloomFilter bit array has the wrong length".to_string()));
+    }
+    let mut filter = BloomFilter::from_params(&params);
+    let mut index = 0;
+    while !input.is_empty() {
+      filter.load_word(index, read_u64(&mut input)?)?;
+      index += 1;
+    }
+    Ok(filter)
+  }
 
-    BloomFilter { bits: vec![false; size], hash_count }
+  /// Returns the bare bit array, `ceil(bit_size / 8)` bytes with bit `i` in byte `i / 8` at
+  /// position `i % 8` (least significant bit first), for Bloom filter implementations in other
+  /// languages: probe `i` of an item is `(lo + i * (hi | 1)) mod bit_size`, where `lo` and `hi`
+  /// are the 32-bit halves of its key hash, the xxHash64 of its UTF-8 bytes with seed 0 (mixed
+  /// with the filter's seed if it has one; see `hash_test_vectors`). Pair it with `bit_size`,
+  /// `hash_count` and `seed`. Unevenly downsampled filters probe differently; ship those with
+  /// `to_bytes` instead.
+  #[wasm_bindgen]
+  pub fn raw_bits(&self) -> Vec<u8> {
+    let bytes: Vec<u8> = self.bits.iter().flat_map(|word| word.to_le_bytes()).collect();
+    bytes[..self.len.div_ceil(8)].to_vec()
   }
 
-  /// Inserts an item into the Bloom filter.
+  /// Creates a filter from a bare bit array in the layout of `raw_bits`, e.g. one built by
+  /// another implementation of the same hashing scheme.
   ///
   /// # Arguments
   ///
-  /// * `item` - The item to insert
+  /// * `bits` - The bit array, `ceil(bit_size / 8)` bytes
+  /// * `bit_size` - The number of bits in the filter
+  /// * `hash_count` - The number of probes per item (between 1 and 64)
+  /// * `seed` - The hash seed, if the filter is seeded
   #[wasm_bindgen]
-  pub fn insert(&mut self, item: &str) {
-    for i in 0..self.hash_count {
-      let index = self.get_hash(item, i) % self.bits.len();
-      self.bits[index] = true;
+  pub fn from_raw_bits(
+    bits: &[u8],
+    bit_size: usize,
+    hash_count: usize,
+    seed: Option<u64>,
+  ) -> Result<BloomFilter, SketchError> {
+    // Checked before allocating, so a wrong bit_size cannot force a huge bit array
+    if bits.len() != bit_size.div_ceil(8) {
+      return Err(SketchError::Malformed("BloomFilter bit array has the wrong length".to_string()));
     }
+    let mut filter = Self::with_params(bit_size, hash_count)?;
+    filter.seed = seed.unwrap_or(0);
+    filter.load_bytes(bits)?;
+    Ok(filter)
   }
 
-  /// Checks if an item might be in the set.
-  /// Returns true if the item is probably in the set, false if it is definitely not.
+  /// Returns a plain object for `JSON.stringify`:
+  /// `{ version, bitCount, hashCount, bits }`, where `bits` is the bit array in base64,
+  /// least significant bit of each byte first. Seeded filters add `seed`, and unevenly
+  /// downsampled ones `probeBits`. Suited to small filters in config documents.
+  #[wasm_bindgen(js_name = toJSON)]
+  pub fn to_json(&self) -> Result<JsValue, SketchError> {
+    serde_wasm_bindgen::to_value(&self.to_json_repr())
+      .map_err(|err| SketchError::Malformed(err.to_string()))
+  }
+
+  /// Restores a filter from the object produced by `toJSON`, e.g. after `JSON.parse`.
   ///
   /// # Arguments
   ///
-  /// * `item` - The item to check
+  /// * `value` - The parsed JSON object
+  #[wasm_bindgen(js_name = fromJSON)]
+  pub fn from_json(value: JsValue) -> Result<BloomFilter, SketchError> {
+    let repr = serde_wasm_bindgen::from_value(value)
+      .map_err(|err| SketchError::Malformed(err.to_string()))?;
+    Self::from_json_repr(repr)
+  }
+
+  /// Returns the number of bytes used by the bit array.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.bits.len() * 8
+  }
+
+  /// Returns the number of bits in the filter.
+  #[wasm_bindgen(getter)]
+  pub fn bit_size(&self) -> usize {
+    self.len
+  }
+
+  /// Returns the number of probes per item.
+  #[wasm_bindgen(getter)]
+  pub fn hash_count(&self) -> usize {
+    self.hash_count
+  }
+
+  /// Returns the hash seed, 0 for an unseeded filter.
+  #[wasm_bindgen(getter)]
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// Returns the number of bits currently set.
   #[wasm_bindgen]
-  pub fn contains(&self, item: &str) -> bool {
-    for i in 0..self.hash_count {
-      let index = self.get_hash(item, i) % self.bits.len();
-      if !self.bits[index] {
-        return false;
-      }
-    }
-    true
+  pub fn bits_set(&self) -> u32 {
+    self.set_bit_count()
+  }
+
+  /// Returns the fraction of bits set, from 0 to 1. A well-sized filter stays near 0.5 at
+  /// its expected capacity; higher values mean the false positive rate is above target.
+  #[wasm_bindgen]
+  pub fn saturation(&self) -> f64 {
+    f64::from(self.set_bit_count()) / self.len as f64
+  }
+
+  /// Returns an independent deep copy of the filter, e.g. to snapshot it while the original
+  /// keeps changing and compare the two later.
+  #[wasm_bindgen(js_name = clone)]
+  pub fn deep_clone(&self) -> BloomFilter {
+    self.clone()
+  }
+
+  /// Removes all items from the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.bits.fill(0);
+    self.checkpoints.clear();
+    self.dropped_set_bits = 0;
+    self.journal.record(ReplayOp::Clear);
   }
 
-  fn get_hash(&self, item: &str, seed: usize) -> usize {
-    let mut hasher = DefaultHasher::new();
-    item.hash(&mut hasher);
-    seed.hash(&mut hasher);
-    hasher.finish() as usize
+  // Derives the i-th probe position from a 64-bit key hash by double hashing its two halves
+  fn index(&self, hash: u64, i: usize) -> usize {
+    Self::probe_index(hash, i, self.probe_len) % self.len
   }
 
   fn optimal_size(items: usize, false_positive_rate: f64) -> usize {
@@ -70,6 +911,11 @@ impl BloomFilter {
     size.ceil() as usize
   }
 
+  fn false_positive_rate_for(size: usize, hash_count: usize, items: usize) -> f64 {
+    let k = hash_count as f64;
+    (1.0 - (-k * items as f64 / size as f64).exp()).powf(k)
+  }
+
   fn optimal_hash_count(size: usize, items: usize) -> usize {
     let ln2 = std::f64::consts::LN_2;
     let hash_count = (size as f64 / items as f64) * ln2;
@@ -77,6 +923,275 @@ impl BloomFilter {
   }
 }
 
+impl BloomFilter {
+  /// Returns the bit count and probe count that reach `false_positive_rate` at
+  /// `expected_items` items.
+  pub(crate) fn geometry(expected_items: usize, false_positive_rate: f64) -> (usize, usize) {
+    let size = Self::optimal_size(expected_items, false_positive_rate);
+    (size, Self::optimal_hash_count(size, expected_items))
+  }
+
+  /// Returns probe `i` of a key hash in a filter of `len` slots: `(lo + i * (hi | 1)) mod len`,
+  /// where `lo` and `hi` are the low and high 32 bits of the hash. The odd step keeps probes
+  /// apart even when the high half is zero.
+  pub(crate) fn probe_index(hash: u64, i: usize, len: usize) -> usize {
+    let h1 = hash & 0xffff_ffff;
+    let h2 = (hash >> 32) | 1;
+    (h1.wrapping_add((i as u64).wrapping_mul(h2)) % len as u64) as usize
+  }
+
+  // Creates an empty filter of `len` bits
+  fn with_size(len: usize, hash_count: usize) -> BloomFilter {
+    BloomFilter {
+      bits: vec![0; len.div_ceil(64)],
+      len,
+      probe_len: len,
+      hash_count,
+      seed: 0,
+      journal: Journal::new(ReplayTarget::BloomFilter),
+      checkpoints: Vec::new(),
+      dropped_set_bits: 0,
+      trace: RefCell::new(None),
+    }
+  }
+
+  fn set_bit_count(&self) -> u32 {
+    self.bits.iter().map(|word| word.count_ones()).sum()
+  }
+
+  // Estimates how many items set `set_bits` bits, inverting the expected fill of k probes per item
+  fn items_for_set_bits(&self, set_bits: u32) -> f64 {
+    let len = self.len as f64;
+    -len / self.hash_count as f64 * (1.0 - f64::from(set_bits) / len).ln()
+  }
+
+  fn check_compatible(&self, other: &BloomFilter) -> Result<(), SketchError> {
+    if self.len != other.len
+      || self.probe_len != other.probe_len
+      || self.hash_count != other.hash_count
+      || self.seed != other.seed
+    {
+      return Err(SketchError::Incompatible(
+        "BloomFilters must have the same size, probe count and seed".to_string(),
+      ));
+    }
+    Ok(())
+  }
+
+  /// Returns the hash an item is probed with, mixing in the seed if there is one.
+  pub(crate) fn key_hash(&self, item: &str) -> u64 {
+    Self::seeded_key_hash(item, self.seed)
+  }
+
+  /// Returns the hash an item is probed with in a filter with the given seed.
+  pub(crate) fn seeded_key_hash(item: &str, seed: u64) -> u64 {
+    match seed {
+      0 => hash_str(item),
+      seed => mix64(hash_str(item), seed),
+    }
+  }
+
+  pub(crate) fn insert_hash(&mut self, hash: u64) {
+    self.set_probes(hash, self.hash_count);
+    self.journal.record(ReplayOp::Update(hash));
+  }
+
+  pub(crate) fn contains_hash(&self, hash: u64) -> bool {
+    self.check_probes(hash, self.hash_count)
+  }
+
+  /// Sets the first `probes` probe positions of a key hash, bypassing any recording.
+  pub(crate) fn set_probes(&mut self, hash: u64, probes: usize) {
+    for i in 0..probes {
+      let index = self.index(hash, i);
+      self.set_bit(index);
+    }
+  }
+
+  /// Checks the first `probes` probe positions of a key hash.
+  pub(crate) fn check_probes(&self, hash: u64, probes: usize) -> bool {
+    (0..probes).all(|i| self.check_bit(self.index(hash, i)))
+  }
+
+  /// Returns every probe position of a key hash.
+  pub(crate) fn probe_positions(&self, hash: u64) -> Vec<usize> {
+    (0..self.hash_count).map(|i| self.index(hash, i)).collect()
+  }
+
+  pub(crate) fn check_bit(&self, position: usize) -> bool {
+    self.bits[position / 64] & (1 << (position % 64)) != 0
+  }
+
+  fn set_bit(&mut self, position: usize) {
+    self.bits[position / 64] |= 1 << (position % 64);
+  }
+
+  pub(crate) fn bit_count(&self) -> usize {
+    self.len
+  }
+
+  pub(crate) fn probe_count(&self) -> usize {
+    self.hash_count
+  }
+
+  /// Reads the header written by `to_bytes` from the front of `input`, advancing it, and
+  /// returns an empty filter with the encoded dimensions.
+  pub(crate) fn read_header(input: &mut &[u8]) -> Result<BloomFilter, SketchError> {
+    Ok(BloomFilter::from_params(&Self::read_params(input)?))
+  }
+
+  // Creates an empty filter with decoded parameters
+  fn from_params(params: &FilterParams) -> BloomFilter {
+    let mut filter = BloomFilter::with_size(params.bit_count, params.hash_count);
+    filter.seed = params.seed;
+    filter.probe_len = params.probe_len;
+    filter
+  }
+
+  /// Decodes the filter parameters from a serialized header without allocating the bit array,
+  /// advancing `input` to the first word.
+  pub(crate) fn read_params(input: &mut &[u8]) -> Result<FilterParams, SketchError> {
+    let Some(rest) = input.strip_prefix(MAGIC) else {
+      return Err(SketchError::Malformed("not a serialized BloomFilter".to_string()));
+    };
+    let Some((&version, rest)) = rest.split_first() else {
+      return Err(SketchError::Malformed("truncated BloomFilter header".to_string()));
+    };
+    if !(FORMAT_VERSION..=FOLDED_FORMAT_VERSION).contains(&version) {
+      return Err(SketchError::Malformed(format!("unsupported BloomFilter version {}", version)));
+    }
+    *input = rest;
+
+    let hash_count = read_varint(input)?;
+    let bit_count = read_varint(input)?;
+    let (bit_count, hash_count) = Self::check_decoded_params(bit_count, hash_count)?;
+    let seed = if version != FORMAT_VERSION { read_varint(input)? } else { 0 };
+    let probe_len = if version == FOLDED_FORMAT_VERSION {
+      Self::check_probe_len(read_varint(input)?, bit_count)?
+    } else {
+      bit_count
+    };
+    Ok(FilterParams { bit_count, hash_count, seed, probe_len })
+  }
+
+  // Converts a decoded probe size, which is never smaller than the bit array
+  fn check_probe_len(probe_len: u64, bit_count: usize) -> Result<usize, SketchError> {
+    match usize::try_from(probe_len) {
+      Ok(probe_len) if probe_len >= bit_count => Ok(probe_len),
+      _ => Err(SketchError::Malformed("BloomFilter probe size out of range".to_string())),
+    }
+  }
+
+  // Converts decoded dimensions, rejecting hostile or corrupt values
+  fn check_decoded_params(bit_count: u64, hash_count: u64) -> Result<(usize, usize), SketchError> {
+    if hash_count == 0 || hash_count > 64 || bit_count == 0 || bit_count > MAX_DECODED_BITS {
+      return Err(SketchError::Malformed("BloomFilter dimensions out of range".to_string()));
+    }
+    Ok((bit_count as usize, hash_count as usize))
+  }
+
+  pub(crate) fn to_json_repr(&self) -> BloomFilterJson {
+    let bytes: Vec<u8> = self.bits.iter().flat_map(|word| word.to_le_bytes()).collect();
+    BloomFilterJson {
+      version: JSON_VERSION,
+      bit_count: self.len as u64,
+      hash_count: self.hash_count as u64,
+      seed: (self.seed != 0).then(|| format!("{:016x}", self.seed)),
+      probe_bits: (self.probe_len != self.len).then_some(self.probe_len as u64),
+      bits: encode_base64(&bytes[..self.len.div_ceil(8)]),
+    }
+  }
+
+  pub(crate) fn from_json_repr(repr: BloomFilterJson) -> Result<BloomFilter, SketchError> {
+    if repr.version != JSON_VERSION {
+      return Err(SketchError::Malformed(format!(
+        "unsupported BloomFilter JSON version {}",
+        repr.version
+      )));
+    }
+    let (bit_count, hash_count) = Self::check_decoded_params(repr.bit_count, repr.hash_count)?;
+    let bits = decode_base64(&repr.bits)?;
+    // Checked before allocating, like the binary format
+    if bits.len() != bit_count.div_ceil(8) {
+      return Err(SketchError::Malformed("BloomFilter bit array has the wrong length".to_string()));
+    }
+    let mut filter = BloomFilter::with_size(bit_count, hash_count);
+    if let Some(seed) = &repr.seed {
+      filter.seed = u64::from_str_radix(seed, 16)
+        .map_err(|_| SketchError::Malformed("BloomFilter seed is not hexadecimal".to_string()))?;
+    }
+    if let Some(probe_len) = repr.probe_bits {
+      filter.probe_len = Self::check_probe_len(probe_len, bit_count)?;
+    }
+    filter.load_bytes(&bits)?;
+    Ok(filter)
+  }
+
+  // Loads a bit array of exactly `len` bits packed into bytes, least significant bit first
+  fn load_bytes(&mut self, bytes: &[u8]) -> Result<(), SketchError> {
+    if bytes.len() != self.len.div_ceil(8) {
+      return Err(SketchError::Malformed("BloomFilter bit array has the wrong length".to_string()));
+    }
+    for (index, chunk) in bytes.chunks(8).enumerate() {
+      let mut word = [0; 8];
+      word[..chunk.len()].copy_from_slice(chunk);
+      self.load_word(index, u64::from_le_bytes(word))?;
+    }
+    Ok(())
+  }
+
+  /// Checks that a header prefix could still become a valid header once more bytes arrive,
+  /// so streamed input with the wrong magic or version fails on its first chunk.
+  pub(crate) fn check_header_prefix(prefix: &[u8]) -> Result<(), SketchError> {
+    let magic_len = prefix.len().min(MAGIC.len());
+    if prefix[..magic_len] != MAGIC[..magic_len] {
+      return Err(SketchError::Malformed("not a serialized BloomFilter".to_string()));
+    }
+    match prefix.get(MAGIC.len()) {
+      Some(&version) if !(FORMAT_VERSION..=FOLDED_FORMAT_VERSION).contains(&version) => {
+        Err(SketchError::Malformed(format!("unsupported BloomFilter version {}", version)))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Stores serialized word `index`, rejecting bits set past the end of the filter.
+  pub(crate) fn load_word(&mut self, index: usize, word: u64) -> Result<(), SketchError> {
+    let len = self.len;
+    if index * 64 + 64 > len && (index * 64 >= len || word >> (len - index * 64) != 0) {
+      return Err(SketchError::Malformed("BloomFilter has bits set past its end".to_string()));
+    }
+    self.or_word(index, word);
+    Ok(())
+  }
+
+  /// Returns the bit array packed into 64-bit words, least significant bit first.
+  pub(crate) fn words(&self) -> &[u64] {
+    &self.bits
+  }
+
+  /// Returns the parameters another filter must share for its bits to line up with these:
+  /// size, probe range, probe count and seed, as compared by `check_compatible`.
+  pub(crate) fn shape(&self) -> [u64; 4] {
+    [self.len as u64, self.probe_len as u64, self.hash_count as u64, self.seed]
+  }
+
+  /// Fails if the filter is recording or keeping a write-ahead log, which could not replay the
+  /// mutation described by `operation`.
+  pub(crate) fn check_unlogged(&self, operation: &str) -> Result<(), SketchError> {
+    self.journal.check_unlogged(operation)
+  }
+
+  /// ORs a 64-bit word into the bit array at word `index`; bits past the end are ignored.
+  pub(crate) fn or_word(&mut self, index: usize, word: u64) {
+    let Some(slot) = self.bits.get_mut(index) else {
+      return;
+    };
+    let valid = self.len - index * 64;
+    *slot |= if valid < 64 { word & ((1 << valid) - 1) } else { word };
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -159,4 +1274,450 @@ mod tests {
       "very_long_string_that_might_cause_issues_with_hashing_and_should_be_handled_properly"
     ));
   }
+
+  #[test]
+  fn test_downsample() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    let size = filter.len;
+    let factor = (2..size).find(|&f| size.is_multiple_of(f)).unwrap();
+    let mut coarse = BloomFilter::with_size(size / factor, filter.hash_count);
+    for i in 0..100 {
+      filter.insert(&format!("item_{}", i));
+      coarse.insert(&format!("item_{}", i));
+    }
+
+    filter.downsample(factor as u32).unwrap();
+    assert_eq!(filter.bits, coarse.bits);
+    assert!(filter.contains("item_7"));
+    assert!(filter.downsample(0).is_err());
+  }
+
+  #[test]
+  fn test_uneven_downsample() {
+    let mut filter = BloomFilter::with_seed(100, 0.01, 7);
+    let size = filter.len;
+    let factor = (2..size).find(|&f| !size.is_multiple_of(size.div_ceil(f))).unwrap();
+    for i in 0..100 {
+      filter.insert(&format!("item_{}", i));
+    }
+
+    filter.downsample(factor as u32).unwrap();
+    assert_eq!(filter.len, size.div_ceil(factor));
+    for i in 0..100 {
+      assert!(filter.contains(&format!("item_{}", i)));
+    }
+    // The probe size survives both serialized forms
+    let bytes = filter.to_bytes();
+    assert_eq!(bytes[4], FOLDED_FORMAT_VERSION);
+    for restored in [
+      BloomFilter::from_bytes(&bytes).unwrap(),
+      BloomFilter::from_json_repr(filter.to_json_repr()).unwrap(),
+    ] {
+      assert_eq!(restored.to_bytes(), bytes);
+      assert!(restored.contains("item_42"));
+    }
+
+    // A second uneven fold would no longer match the probes, but an even one still does
+    let len = filter.len;
+    let uneven = (2..len).find(|&f| !len.is_multiple_of(len.div_ceil(f))).unwrap();
+    assert!(filter.downsample(uneven as u32).is_err());
+    if let Some(even) = (2..len).find(|&f| len.is_multiple_of(f)) {
+      filter.downsample(even as u32).unwrap();
+      assert!(filter.contains("item_42"));
+    }
+    // Filters of the same size that probe differently cannot be combined
+    let mut plain = BloomFilter::with_size(filter.len, filter.hash_count);
+    plain.seed = 7;
+    assert!(filter.union(&plain).is_err());
+  }
+
+  #[test]
+  fn test_packed_storage() {
+    let filter = BloomFilter::new(10_000_000, 0.01);
+    assert_eq!(filter.memory_bytes(), filter.len.div_ceil(64) * 8);
+    assert!(filter.memory_bytes() < 12 * 1024 * 1024);
+
+    let mut filter = BloomFilter::new(10, 0.01);
+    filter.or_word(filter.bits.len() - 1, u64::MAX);
+    assert_eq!(filter.bits.last().unwrap().count_ones() as usize, (filter.len - 1) % 64 + 1);
+  }
+
+  #[test]
+  fn test_bytes_roundtrip() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    for i in 0..100 {
+      filter.insert(&format!("item_{}", i));
+    }
+    let bytes = filter.to_bytes();
+    let decoded = BloomFilter::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.bits, filter.bits);
+    assert_eq!(decoded.hash_count, filter.hash_count);
+
+    assert!(BloomFilter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut past_end = bytes.clone();
+    *past_end.last_mut().unwrap() = 0xff;
+    assert!(BloomFilter::from_bytes(&past_end).is_err());
+
+    // A header declaring the largest bit array is rejected before the array is allocated
+    let mut hostile = MAGIC.to_vec();
+    hostile.push(FORMAT_VERSION);
+    write_varint(&mut hostile, 7);
+    write_varint(&mut hostile, MAX_DECODED_BITS);
+    assert!(BloomFilter::from_bytes(&hostile).is_err());
+  }
+
+  #[test]
+  fn test_union() {
+    let mut left = BloomFilter::new(100, 0.01);
+    let mut right = BloomFilter::new(100, 0.01);
+    left.insert("left");
+    right.insert("right");
+
+    let combined = left.union_new(&right).unwrap();
+    assert!(combined.contains("left") && combined.contains("right"));
+    assert!(!left.contains("right"));
+    left.union(&right).unwrap();
+    assert_eq!(left.bits, combined.bits);
+
+    assert!(left.union(&BloomFilter::new(200, 0.01)).is_err());
+    assert!(left.union_new(&BloomFilter::new(100, 0.2)).is_err());
+  }
+
+  #[test]
+  fn test_estimated_count() {
+    let mut filter = BloomFilter::new(1000, 0.01);
+    assert_eq!(filter.estimated_count(), 0.0);
+    for i in 0..500 {
+      filter.insert(&format!("item_{}", i));
+      filter.insert(&format!("item_{}", i));
+    }
+    assert!((filter.estimated_count() - 500.0).abs() < 25.0);
+    for index in 0..filter.bits.len() {
+      filter.or_word(index, u64::MAX);
+    }
+    assert_eq!(filter.estimated_count(), f64::INFINITY);
+  }
+
+  #[test]
+  fn test_current_false_positive_rate() {
+    let mut filter = BloomFilter::new(1000, 0.01);
+    assert_eq!(filter.current_false_positive_rate(), 0.0);
+    for i in 0..1000 {
+      filter.insert(&format!("item_{}", i));
+    }
+    assert!((filter.current_false_positive_rate() - 0.01).abs() < 0.005);
+    for i in 1000..3000 {
+      filter.insert(&format!("item_{}", i));
+    }
+    let rate = filter.current_false_positive_rate();
+    assert!(rate > 0.1);
+    assert!((rate - filter.probe_fpr(20000, 3)).abs() < 0.03);
+  }
+
+  #[test]
+  fn test_approx_distinct_trend() {
+    let mut filter = BloomFilter::new(5000, 0.01);
+    for (hour, distinct) in [(1.0, 1000), (2.0, 200), (3.0, 0)] {
+      for i in 0..distinct {
+        filter.insert(&format!("hour_{}_{}", hour, i));
+        filter.insert(&format!("hour_{}_{}", hour, i));
+      }
+      filter.checkpoint(Some(hour));
+    }
+
+    let trend = filter.approx_distinct();
+    assert_eq!(filter.checkpoint_times(), [1.0, 2.0, 3.0]);
+    assert!((trend[0] - 1000.0).abs() < 50.0);
+    assert!((trend[1] - 200.0).abs() < 30.0);
+    assert_eq!(trend[2], 0.0);
+
+    for i in 0..MAX_CHECKPOINTS {
+      filter.insert(&format!("late_{}", i));
+      filter.checkpoint(Some(4.0 + i as f64));
+    }
+    let times = filter.checkpoint_times();
+    assert_eq!(times.len(), MAX_CHECKPOINTS);
+    assert_eq!(times[0], 4.0);
+    // The oldest kept checkpoint is measured from the dropped one before it, not from empty
+    assert!(filter.approx_distinct()[0] < 5.0);
+
+    filter.clear();
+    assert!(filter.approx_distinct().is_empty());
+  }
+
+  #[test]
+  fn test_batch_insert_and_contains() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    filter.insert_many(vec!["a".to_string(), "b".to_string()]);
+    let mut single = BloomFilter::new(100, 0.01);
+    single.insert("a");
+    single.insert("b");
+    assert_eq!(filter.bits, single.bits);
+
+    let found = filter.contains_many(vec!["a".to_string(), "missing".to_string(), "b".to_string()]);
+    assert_eq!(found, [1, 0, 1]);
+
+    assert!(filter.contains_all(vec!["a".to_string(), "b".to_string()]));
+    assert!(!filter.contains_all(vec!["a".to_string(), "missing".to_string()]));
+    assert!(filter.contains_any(vec!["missing".to_string(), "b".to_string()]));
+    assert!(!filter.contains_any(vec!["missing".to_string()]));
+    assert!(filter.contains_all(Vec::new()) && !filter.contains_any(Vec::new()));
+  }
+
+  #[test]
+  fn test_ingest_column() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    let added = filter.ingest_column(b"id,email\n1,a@x.io\n2,b@x.io\n", "csv", Some(1)).unwrap();
+    assert_eq!(added, 2);
+    assert!(filter.contains("a@x.io") && filter.contains("b@x.io"));
+    assert!(!filter.contains("email"));
+    assert!(filter.ingest_column(b"a", "arrow", None).is_err());
+    assert!(filter.ingest_column(b"id\nc@x.io\n\"open", "csv", None).is_err());
+    assert!(!filter.contains("c@x.io"));
+  }
+
+  #[test]
+  fn test_intersect() {
+    let mut left = BloomFilter::new(1000, 0.01);
+    let mut right = BloomFilter::new(1000, 0.01);
+    for i in 0..600 {
+      left.insert(&format!("item_{}", i));
+      right.insert(&format!("item_{}", i + 300));
+    }
+
+    let overlap = left.estimate_intersection_size(&right).unwrap();
+    assert!((overlap - 300.0).abs() < 45.0, "overlap {}", overlap);
+    let jaccard = left.estimate_jaccard(&right).unwrap();
+    assert!((jaccard - 1.0 / 3.0).abs() < 0.05, "jaccard {}", jaccard);
+    assert_eq!(left.estimate_jaccard(&left).unwrap(), 1.0);
+    let empty = BloomFilter::new(1000, 0.01);
+    assert_eq!(empty.estimate_jaccard(&BloomFilter::new(1000, 0.01)).unwrap(), 1.0);
+    assert!(left.estimate_jaccard(&BloomFilter::new(10, 0.01)).is_err());
+    left.intersect(&right).unwrap();
+    for i in 300..600 {
+      assert!(left.contains(&format!("item_{}", i)));
+    }
+    assert!(left.intersect(&BloomFilter::new(10, 0.01)).is_err());
+  }
+
+  #[test]
+  fn test_json_roundtrip() {
+    let mut filter = BloomFilter::new(20, 0.01);
+    for i in 0..20 {
+      filter.insert(&format!("item_{}", i));
+    }
+    let repr = filter.to_json_repr();
+    assert_eq!(repr.version, 1);
+    assert_eq!(repr.bit_count as usize, filter.len);
+
+    let decoded = BloomFilter::from_json_repr(repr).unwrap();
+    assert_eq!(decoded.bits, filter.bits);
+    assert_eq!(decoded.hash_count, filter.hash_count);
+
+    let wrong_version = BloomFilterJson { version: 2, ..filter.to_json_repr() };
+    assert!(BloomFilter::from_json_repr(wrong_version).is_err());
+    let truncated = BloomFilterJson { bits: "AAAA".to_string(), ..filter.to_json_repr() };
+    assert!(BloomFilter::from_json_repr(truncated).is_err());
+    let oversized = BloomFilterJson { bit_count: MAX_DECODED_BITS, ..filter.to_json_repr() };
+    assert!(BloomFilter::from_json_repr(oversized).is_err());
+  }
+
+  #[test]
+  fn test_raw_bits_roundtrip() {
+    let mut filter = BloomFilter::with_seed(20, 0.01, 7);
+    filter.insert("alpha");
+    let bits = filter.raw_bits();
+    assert_eq!(bits.len(), filter.bit_size().div_ceil(8));
+    for position in filter.bloom_indices("alpha") {
+      assert_ne!(bits[position as usize / 8] & (1 << (position % 8)), 0);
+    }
+
+    let decoded =
+      BloomFilter::from_raw_bits(&bits, filter.bit_size(), filter.hash_count(), Some(7)).unwrap();
+    assert!(decoded.contains("alpha"));
+    assert_eq!(decoded.bits, filter.bits);
+    assert!(BloomFilter::from_raw_bits(&bits[1..], filter.bit_size(), 7, None).is_err());
+    // A bit size that disagrees with the array is rejected before anything is allocated
+    assert!(BloomFilter::from_raw_bits(&bits, usize::MAX / 2, 7, None).is_err());
+    assert!(BloomFilter::from_raw_bits(&bits, filter.bit_size(), 0, None).is_err());
+  }
+
+  #[test]
+  fn test_record_and_replay() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    filter.insert("before");
+    filter.start_recording();
+    filter.insert("a");
+    filter.clear();
+    filter.insert("b");
+    let log = filter.stop_recording();
+
+    let mut replayed = BloomFilter::new(100, 0.01);
+    replayed.insert("stale");
+    replayed.replay(&log).unwrap();
+    assert_eq!(replayed.bits, filter.bits);
+    assert!(replayed.contains("b"));
+    assert!(!replayed.contains("a"));
+    assert!(!replayed.contains("stale"));
+
+    assert!(replayed.replay(&[1, 2, 3]).is_err());
+  }
+
+  #[test]
+  fn test_from_hashes() {
+    let keys = ["alpha", "beta", "gamma"];
+    let mut hashes: Vec<u64> = keys.iter().map(|key| hash_str(key)).collect();
+    hashes.sort_unstable();
+
+    let filter = BloomFilter::from_hashes(&hashes, 0.01);
+    let mut expected = BloomFilter::new(keys.len(), 0.01);
+    for key in keys {
+      assert!(filter.contains(key));
+      expected.insert(key);
+    }
+    assert_eq!(filter.bits, expected.bits);
+    assert!(!BloomFilter::from_hashes(&[], 0.01).contains("alpha"));
+  }
+
+  #[test]
+  fn test_try_new() {
+    let filter = BloomFilter::try_new(1000, 0.01).unwrap();
+    assert_eq!(filter.bits, BloomFilter::new(1000, 0.01).bits);
+    assert!(matches!(BloomFilter::try_new(usize::MAX, 1e-9), Err(SketchError::OutOfMemory(_))));
+  }
+
+  #[test]
+  fn test_seeded_filters() {
+    let mut a = BloomFilter::with_seed(1000, 0.01, 42);
+    let b = BloomFilter::with_seed(1000, 0.01, 42);
+    assert_eq!(a.bloom_indices("alpha"), b.bloom_indices("alpha"));
+    assert_ne!(a.bloom_indices("alpha"), BloomFilter::new(1000, 0.01).bloom_indices("alpha"));
+    assert_eq!(
+      BloomFilter::with_seed(1000, 0.01, 0).bloom_indices("alpha"),
+      BloomFilter::new(1000, 0.01).bloom_indices("alpha")
+    );
+
+    a.insert("alpha");
+    let restored = BloomFilter::from_bytes(&a.to_bytes()).unwrap();
+    assert_eq!(restored.seed(), 42);
+    assert!(restored.contains("alpha"));
+    let json = BloomFilter::from_json_repr(a.to_json_repr()).unwrap();
+    assert_eq!(json.seed(), 42);
+    assert!(json.contains("alpha"));
+
+    assert!(a.union(&BloomFilter::new(1000, 0.01)).is_err());
+    assert_eq!(BloomFilter::new(10, 0.01).to_bytes()[4], FORMAT_VERSION);
+    // Pinned so other implementations can check their seeded hash: SplitMix64 over xxHash64
+    assert_eq!(BloomFilter::seeded_key_hash("hello", 42), 0x6de0_2f87_ff05_4408);
+  }
+
+  #[test]
+  fn test_introspection() {
+    let mut filter = BloomFilter::with_params(1000, 3).unwrap();
+    assert_eq!((filter.bit_size(), filter.hash_count()), (1000, 3));
+    assert_eq!(filter.saturation(), 0.0);
+
+    filter.insert("alpha");
+    let set = filter.bits_set();
+    assert!((1..=3).contains(&set));
+    assert_eq!(filter.saturation(), f64::from(set) / 1000.0);
+  }
+
+  #[test]
+  fn test_from_items() {
+    let items: Vec<String> = ["alpha", "beta", "alpha"].iter().map(|s| s.to_string()).collect();
+    let filter = BloomFilter::from_items(items, 0.01);
+    assert!(filter.contains("alpha") && filter.contains("beta"));
+    assert_eq!(filter.bit_count(), BloomFilter::new(2, 0.01).bit_count());
+    assert!(!BloomFilter::from_items(Vec::new(), 0.01).contains("alpha"));
+  }
+
+  #[test]
+  fn test_with_params() {
+    let reference = BloomFilter::new(1000, 0.01);
+    let mut filter =
+      BloomFilter::with_params(reference.bit_count(), reference.probe_count()).unwrap();
+    filter.insert("alpha");
+    assert!(filter.contains("alpha"));
+    assert!(filter.check_compatible(&reference).is_ok());
+
+    assert!(BloomFilter::with_params(0, 3).is_err());
+    assert!(BloomFilter::with_params(100, 0).is_err());
+    assert!(BloomFilter::with_params(100, 65).is_err());
+  }
+
+  #[test]
+  fn test_with_byte_budget() {
+    let filter = BloomFilter::with_byte_budget(1200, 1000).unwrap();
+    assert!(filter.memory_bytes() <= 1200);
+    assert_eq!(filter.bit_size(), 9600);
+    assert_eq!(filter.hash_count(), 7);
+    let expected = filter.expected_false_positive_rate(1000);
+    assert!((expected - 0.00997).abs() < 0.0001, "expected fpr {}", expected);
+
+    let tiny = BloomFilter::with_byte_budget(15, 1000).unwrap();
+    assert_eq!((tiny.bit_size(), tiny.hash_count()), (64, 1));
+    assert!(BloomFilter::with_byte_budget(7, 10).is_err());
+    assert!(BloomFilter::with_byte_budget(64, 0).is_err());
+  }
+
+  #[test]
+  fn test_probe_trace() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    filter.insert("alpha");
+    filter.contains("alpha");
+    assert!(filter.last_probe_trace().is_none());
+
+    filter.enable_probe_trace();
+    assert!(filter.contains("alpha"));
+    let trace = filter.last_probe_trace().unwrap();
+    assert!(trace.found());
+    assert_eq!(trace.positions(), filter.bloom_indices("alpha"));
+
+    assert!(!filter.contains("beta"));
+    let trace = filter.last_probe_trace().unwrap();
+    assert_eq!(trace.item(), "beta");
+    let failed = trace.failed_probe().unwrap() as usize;
+    assert_eq!(trace.positions().len(), failed + 1);
+    assert!(!filter.check_bit(trace.positions()[failed] as usize));
+
+    filter.disable_probe_trace();
+    assert!(filter.last_probe_trace().is_none());
+  }
+
+  #[test]
+  fn test_insert_hashed() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    filter.insert_hashed(0x1234_5678_9abc_def0);
+    assert!(filter.contains_hashed(0x1234_5678_9abc_def0));
+    assert!(!filter.contains_hashed(42));
+
+    // Hashes without high bits still probe distinct positions
+    let positions = filter.probe_positions(7);
+    assert_eq!(positions, (0..filter.hash_count).map(|i| 7 + i).collect::<Vec<_>>());
+
+    let hash = hash_str("alpha");
+    filter.insert_hashed(hash);
+    assert!(filter.contains("alpha"));
+    assert!(BloomFilter::from_hashes(&[hash], 0.01).contains_hashed(hash));
+  }
+
+  #[test]
+  fn test_probe_fpr() {
+    let mut filter = BloomFilter::new(1000, 0.01);
+    assert_eq!(filter.probe_fpr(1000, 7), 0.0);
+
+    for i in 0..1000 {
+      filter.insert(&format!("item_{}", i));
+    }
+    let observed = filter.probe_fpr(20000, 7);
+    assert!(observed > 0.0 && observed <= 0.02);
+    assert_eq!(filter.probe_fpr(20000, 7), observed);
+
+    // An overfilled filter reports a degraded rate
+    for i in 1000..10000 {
+      filter.insert(&format!("item_{}", i));
+    }
+    assert!(filter.probe_fpr(20000, 7) > 0.1);
+  }
 }
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::codec::{read_varint, write_varint};
+use crate::error::SketchError;
+
+const MAGIC: &[u8; 4] = b"SWBC";
+const FORMAT_VERSION: u8 = 1;
+// False positive rate of every layer after the first
+const DEEP_LAYER_RATE: f64 = 0.5;
+// Each layer shrinks the set it corrects by its false positive rate, so this is never reached
+const MAX_LAYERS: usize = 64;
+
+/// A Bloom filter cascade (as in CRLite) that answers membership with no errors for every
+/// key it was built from, e.g. a certificate blocklist distributed to browsers along with the
+/// list of valid certificates it must not block.
+///
+/// The first layer holds the included keys; each later layer holds the keys of the opposite
+/// set that the layer before wrongly matched, until no known key is misclassified. Keys outside
+/// both sets get an ordinary false positive rate, that of the first layer. Each layer is seeded
+/// differently so their false positives are independent.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct BloomCascade {
+  layers: Vec<BloomFilter>,
+}
+
+#[wasm_bindgen]
+impl BloomCascade {
+  /// Builds a cascade from the complete included and excluded sets, which must be disjoint.
+  ///
+  /// # Arguments
+  ///
+  /// * `includes` - The keys `contains` must report (an array of strings)
+  /// * `excludes` - The keys `contains` must not report (an array of strings)
+  /// * `false_positive_rate` - The first layer's false positive rate, which applies to keys
+  ///   outside both sets; defaults to 0.01. Later layers use 0.5
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    false_positive_rate: Option<f64>,
+  ) -> Result<BloomCascade, SketchError> {
+    let false_positive_rate = false_positive_rate.unwrap_or(0.01);
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+      return Err(SketchError::InvalidParameter(
+        "false_positive_rate must be between 0 and 1".to_string(),
+      ));
+    }
+    let mut inside: HashSet<&str> = includes.iter().map(String::as_str).collect();
+    let mut outside: HashSet<&str> = excludes.iter().map(String::as_str).collect();
+    if let Some(key) = inside.intersection(&outside).next() {
+      return Err(SketchError::InvalidParameter(format!(
+        "key {:?} is both included and excluded",
+        key
+      )));
+    }
+
+    let mut layers = Vec::new();
+    while !inside.is_empty() {
+      if layers.len() == MAX_LAYERS {
+        return Err(SketchError::InvalidParameter(format!(
+          "cascade did not converge in {} layers",
+          MAX_LAYERS
+        )));
+      }
+      let rate = if layers.is_empty() { false_positive_rate } else { DEEP_LAYER_RATE };
+      let mut layer = BloomFilter::with_seed(inside.len(), rate, layers.len() as u64 + 1);
+      for key in &inside {
+        layer.insert(key);
+      }
+      // The keys of the other set this layer wrongly matches are what the next layer corrects
+      let wrong = outside.into_iter().filter(|key| layer.contains(key)).collect();
+      layers.push(layer);
+      outside = inside;
+      inside = wrong;
+    }
+    Ok(BloomCascade { layers })
+  }
+
+  /// Checks if a key is in the included set. Exact for every key the cascade was built from;
+  /// other keys are reported with the first layer's false positive rate.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The key to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    match self.layers.iter().position(|layer| !layer.contains(item)) {
+      // Missing from an even layer means the key is not among that layer's included keys
+      Some(depth) => depth % 2 == 1,
+      None => self.layers.len() % 2 == 1,
+    }
+  }
+
+  /// Returns the number of layers.
+  #[wasm_bindgen(getter)]
+  pub fn layer_count(&self) -> usize {
+    self.layers.len()
+  }
+
+  /// Returns the number of bytes used by the bit arrays of all layers.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.layers.iter().map(BloomFilter::memory_bytes).sum()
+  }
+
+  /// Serializes the cascade for distribution. The format is the magic `SWBC`, a version
+  /// byte, a varint layer count, then each layer as a varint length and its `to_bytes`.
+  #[wasm_bindgen]
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(FORMAT_VERSION);
+    write_varint(&mut out, self.layers.len() as u64);
+    for layer in &self.layers {
+      let bytes = layer.to_bytes();
+      write_varint(&mut out, bytes.len() as u64);
+      out.extend_from_slice(&bytes);
+    }
+    out
+  }
+
+  /// Restores a cascade from a buffer produced by `to_bytes`.
+  ///
+  /// # Arguments
+  ///
+  /// * `bytes` - The serialized cascade
+  #[wasm_bindgen]
+  pub fn from_bytes(bytes: &[u8]) -> Result<BloomCascade, SketchError> {
+    let Some(mut input) = bytes.strip_prefix(MAGIC) else {
+      return Err(SketchError::Malformed("not a serialized BloomCascade".to_string()));
+    };
+    let Some((&version, rest)) = input.split_first() else {
+      return Err(SketchError::Malformed("truncated BloomCascade header".to_string()));
+    };
+    if version != FORMAT_VERSION {
+      return Err(SketchError::Malformed(format!("unsupported BloomCascade version {}", version)));
+    }
+    input = rest;
+
+    let layer_count = read_varint(&mut input)?;
+    if layer_count > MAX_LAYERS as u64 {
+      return Err(SketchError::Malformed(format!("{} layers is too many", layer_count)));
+    }
+    let mut layers = Vec::with_capacity(layer_count as usize);
+    for _ in 0..layer_count {
+      let len = read_varint(&mut input)?;
+      if len > input.len() as u64 {
+        return Err(SketchError::Malformed("truncated BloomCascade layer".to_string()));
+      }
+      let (layer, rest) = input.split_at(len as usize);
+      layers.push(BloomFilter::from_bytes(layer)?);
+      input = rest;
+    }
+    if !input.is_empty() {
+      return Err(SketchError::Malformed("trailing bytes after BloomCascade".to_string()));
+    }
+    Ok(BloomCascade { layers })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_exact_for_known_keys() {
+    let revoked: Vec<String> = (0..1000).map(|i| format!("revoked_{}", i)).collect();
+    let valid: Vec<String> = (0..20_000).map(|i| format!("valid_{}", i)).collect();
+    let cascade = BloomCascade::new(revoked.clone(), valid.clone(), None).unwrap();
+    assert!(cascade.layer_count() > 1);
+
+    let restored = BloomCascade::from_bytes(&cascade.to_bytes()).unwrap();
+    for filter in [&cascade, &restored] {
+      assert!(revoked.iter().all(|key| filter.contains(key)));
+      assert!(!valid.iter().any(|key| filter.contains(key)));
+    }
+    let unknown = (0..10_000).filter(|i| cascade.contains(&format!("unknown_{}", i))).count();
+    assert!(unknown < 200, "{} unknown keys reported", unknown);
+  }
+
+  #[test]
+  fn test_rejects_bad_input() {
+    let empty = BloomCascade::new(Vec::new(), vec!["a".to_string()], None).unwrap();
+    assert_eq!(empty.layer_count(), 0);
+    assert!(!empty.contains("a"));
+
+    let overlap = BloomCascade::new(vec!["a".to_string()], vec!["a".to_string()], None);
+    assert!(overlap.is_err());
+    assert!(BloomCascade::new(Vec::new(), Vec::new(), Some(1.0)).is_err());
+
+    let bytes = BloomCascade::new(vec!["a".to_string()], Vec::new(), None).unwrap().to_bytes();
+    assert!(BloomCascade::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(BloomCascade::from_bytes(b"SWBF").is_err());
+  }
+}
@@ -0,0 +1,118 @@
+use js_sys::{SharedArrayBuffer, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::{BloomFilter, MAX_HEADER_LEN};
+use crate::error::SketchError;
+
+/// A read-only Bloom filter that queries a `SharedArrayBuffer` published by
+/// `BloomFilter.to_shared_buffer` in place, so any number of workers can check membership
+/// against one copy of a large filter. Each probe reads a single byte of the shared buffer.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct BloomFilterView {
+  buffer: Uint8Array,
+  layout: Layout,
+}
+
+// Where the bit array sits in a serialized filter, and how to probe it
+#[derive(Debug)]
+pub(crate) struct Layout {
+  // Byte offset of the bit array
+  offset: usize,
+  len: usize,
+  probe_len: usize,
+  hash_count: usize,
+  seed: u64,
+}
+
+#[wasm_bindgen]
+impl BloomFilterView {
+  /// Creates a view over a published filter, validating its header and length.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The buffer returned by `BloomFilter.to_shared_buffer`
+  #[wasm_bindgen(constructor)]
+  pub fn new(buffer: &SharedArrayBuffer) -> Result<BloomFilterView, SketchError> {
+    let buffer = Uint8Array::new(buffer);
+    let total_len = buffer.length() as usize;
+    let prefix = buffer.subarray(0, total_len.min(MAX_HEADER_LEN) as u32).to_vec();
+    let layout = Layout::parse(&prefix, total_len)?;
+    Ok(BloomFilterView { buffer, layout })
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.layout.contains(item, |index| self.buffer.get_index(index as u32))
+  }
+
+  /// Returns the number of bits in the filter.
+  #[wasm_bindgen(getter)]
+  pub fn bit_size(&self) -> usize {
+    self.layout.len
+  }
+
+  /// Returns the number of probes per item.
+  #[wasm_bindgen(getter)]
+  pub fn hash_count(&self) -> usize {
+    self.layout.hash_count
+  }
+}
+
+impl Layout {
+  // Decodes the header at the start of a serialized filter of `total_len` bytes
+  pub(crate) fn parse(prefix: &[u8], total_len: usize) -> Result<Layout, SketchError> {
+    let mut input = prefix;
+    let params = BloomFilter::read_params(&mut input)?;
+    let offset = prefix.len() - input.len();
+    if total_len != offset + params.bit_count.div_ceil(64) * 8 {
+      return Err(SketchError::Malformed("BloomFilter bit array has the wrong length".to_string()));
+    }
+    Ok(Layout {
+      offset,
+      len: params.bit_count,
+      probe_len: params.probe_len,
+      hash_count: params.hash_count,
+      seed: params.seed,
+    })
+  }
+
+  // Probes the bit array through `byte_at`, which reads one byte of the serialized filter
+  pub(crate) fn contains(&self, item: &str, byte_at: impl Fn(usize) -> u8) -> bool {
+    let hash = BloomFilter::seeded_key_hash(item, self.seed);
+    (0..self.hash_count).all(|i| {
+      let position = BloomFilter::probe_index(hash, i, self.probe_len) % self.len;
+      byte_at(self.offset + position / 8) & (1 << (position % 8)) != 0
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_layout_matches_filter() {
+    for (seed, factor) in [(0, 1), (42, 1), (42, 3)] {
+      let mut filter = BloomFilter::with_seed(1000, 0.01, seed);
+      for i in 0..1000 {
+        filter.insert(&format!("item_{}", i));
+      }
+      filter.downsample(factor).unwrap();
+      let bytes = filter.to_bytes();
+      let layout = Layout::parse(&bytes[..MAX_HEADER_LEN], bytes.len()).unwrap();
+      assert_eq!(layout.len, filter.bit_size());
+      for i in 0..2000 {
+        let item = format!("item_{}", i);
+        assert_eq!(layout.contains(&item, |index| bytes[index]), filter.contains(&item));
+      }
+      assert!(Layout::parse(&bytes[..MAX_HEADER_LEN], bytes.len() - 1).is_err());
+    }
+  }
+}
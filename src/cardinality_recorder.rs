@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hyperloglog::HyperLogLog;
+use crate::runtime;
+
+/// Records a HyperLogLog's distinct-count estimate at a fixed interval into a bounded ring
+/// buffer, so live charts can read the whole series in one call instead of polling.
+///
+/// Snapshots are taken lazily: each `add` or `tick` first records every interval boundary that
+/// has passed since the last snapshot. Call `tick` from a timer if adds may pause.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct CardinalityRecorder {
+  hll: HyperLogLog,
+  interval_ms: f64,
+  capacity: usize,
+  // Whether the counter starts over after each snapshot
+  per_interval: bool,
+  // Boundary of the next snapshot, unset until the first add or tick
+  next_snapshot_ms: Option<f64>,
+  // Snapshots as (boundary time, estimate), oldest first
+  series: VecDeque<(f64, f64)>,
+}
+
+#[wasm_bindgen]
+impl CardinalityRecorder {
+  /// Creates a new recorder.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The precision of the underlying HyperLogLog (between 4 and 16)
+  /// * `interval_ms` - The time between snapshots
+  /// * `capacity` - The number of snapshots kept; older ones are dropped
+  /// * `per_interval` - True to count distinct items per interval, false to count them cumulatively
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    precision: Option<u8>,
+    interval_ms: f64,
+    capacity: usize,
+    per_interval: bool,
+  ) -> Result<CardinalityRecorder, SketchError> {
+    let p = precision.unwrap_or(14);
+    if !(4..=16).contains(&p) {
+      return Err(SketchError::InvalidParameter("precision must be between 4 and 16".to_string()));
+    }
+    if interval_ms.is_nan() || interval_ms <= 0.0 || capacity == 0 {
+      return Err(SketchError::InvalidParameter(
+        "interval_ms and capacity must be positive".to_string(),
+      ));
+    }
+    Ok(CardinalityRecorder {
+      hll: HyperLogLog::new(Some(p)).expect("precision was validated"),
+      interval_ms,
+      capacity,
+      per_interval,
+      next_snapshot_ms: None,
+      series: VecDeque::new(),
+    })
+  }
+
+  /// Adds an item, first recording any snapshots that are due.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The item to add
+  /// * `timestamp_ms` - When the item was seen; defaults to the current time
+  #[wasm_bindgen]
+  pub fn add(&mut self, value: &str, timestamp_ms: Option<f64>) {
+    self.tick(timestamp_ms);
+    self.hll.add(value);
+  }
+
+  /// Records any snapshots that are due without adding an item.
+  ///
+  /// # Arguments
+  ///
+  /// * `now_ms` - The current time; defaults to the runtime clock
+  #[wasm_bindgen]
+  pub fn tick(&mut self, now_ms: Option<f64>) {
+    let now_ms = now_ms.unwrap_or_else(runtime::now_ms);
+    let Some(mut next) = self.next_snapshot_ms else {
+      self.next_snapshot_ms = Some(now_ms + self.interval_ms);
+      return;
+    };
+
+    // After a long pause only the most recent `capacity` boundaries can be kept
+    let missed = ((now_ms - next) / self.interval_ms).floor();
+    if missed >= self.capacity as f64 {
+      next += (missed - self.capacity as f64 + 1.0) * self.interval_ms;
+    }
+    while now_ms >= next {
+      self.record(next);
+      next += self.interval_ms;
+    }
+    self.next_snapshot_ms = Some(next);
+  }
+
+  /// Returns the recorded estimates, oldest first, as a `Float64Array`.
+  #[wasm_bindgen]
+  pub fn series(&self) -> Vec<f64> {
+    self.series.iter().map(|&(_, estimate)| estimate).collect()
+  }
+
+  /// Returns the time of each recorded estimate, aligned with `series`.
+  #[wasm_bindgen]
+  pub fn timestamps(&self) -> Vec<f64> {
+    self.series.iter().map(|&(time, _)| time).collect()
+  }
+
+  /// Returns the live estimate for the current interval, or since creation if cumulative.
+  #[wasm_bindgen]
+  pub fn count(&self) -> f64 {
+    self.hll.count()
+  }
+}
+
+impl CardinalityRecorder {
+  fn record(&mut self, time_ms: f64) {
+    if self.series.len() == self.capacity {
+      self.series.pop_front();
+    }
+    self.series.push_back((time_ms, self.hll.count()));
+    if self.per_interval {
+      self.hll.clear();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_records_cumulative_series() {
+    let mut recorder = CardinalityRecorder::new(Some(12), 1_000.0, 3, false).unwrap();
+    for second in 0..5 {
+      for i in 0..100 {
+        recorder.add(&format!("user_{}_{}", second, i), Some(second as f64 * 1_000.0 + 500.0));
+      }
+    }
+    recorder.tick(Some(5_600.0));
+
+    // Boundaries at 1.5s..5.5s were due; only the last three are kept
+    assert_eq!(recorder.timestamps(), vec![3_500.0, 4_500.0, 5_500.0]);
+    let series = recorder.series();
+    assert!(series.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((450.0..=550.0).contains(&series[2]));
+  }
+
+  #[test]
+  fn test_per_interval_and_idle_gaps() {
+    let mut recorder = CardinalityRecorder::new(Some(12), 1_000.0, 10, true).unwrap();
+    recorder.add("a", Some(0.0));
+    recorder.add("b", Some(500.0));
+    recorder.add("c", Some(1_200.0));
+    recorder.tick(Some(3_000.0));
+
+    assert_eq!(recorder.timestamps(), vec![1_000.0, 2_000.0, 3_000.0]);
+    assert_eq!(recorder.series(), vec![2.0, 1.0, 0.0]);
+
+    // A pause longer than the buffer keeps only the latest boundaries
+    recorder.tick(Some(1_000_000.0));
+    assert_eq!(recorder.timestamps().len(), 10);
+    assert_eq!(recorder.timestamps()[9], 1_000_000.0);
+  }
+
+  #[test]
+  fn test_rejects_bad_parameters() {
+    assert!(CardinalityRecorder::new(Some(2), 1_000.0, 10, false).is_err());
+    assert!(CardinalityRecorder::new(None, 0.0, 10, false).is_err());
+    assert!(CardinalityRecorder::new(None, 1_000.0, 0, false).is_err());
+  }
+}
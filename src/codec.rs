@@ -0,0 +1,121 @@
+use crate::error::SketchError;
+
+/// Appends `value` as an unsigned LEB128 varint.
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+  while value >= 0x80 {
+    out.push((value as u8) | 0x80);
+    value >>= 7;
+  }
+  out.push(value as u8);
+}
+
+/// Reads an unsigned LEB128 varint from the front of `input`, advancing it.
+pub(crate) fn read_varint(input: &mut &[u8]) -> Result<u64, SketchError> {
+  let mut value = 0u64;
+  for shift in (0..64).step_by(7) {
+    let Some((&byte, rest)) = input.split_first() else {
+      return Err(SketchError::Malformed("truncated varint".to_string()));
+    };
+    *input = rest;
+    if shift == 63 && byte > 1 {
+      return Err(SketchError::Malformed("varint overflows 64 bits".to_string()));
+    }
+    value |= u64::from(byte & 0x7f) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+  Err(SketchError::Malformed("varint overflows 64 bits".to_string()))
+}
+
+/// Reads a little-endian u64 from the front of `input`, advancing it.
+pub(crate) fn read_u64(input: &mut &[u8]) -> Result<u64, SketchError> {
+  let Some((bytes, rest)) = input.split_first_chunk::<8>() else {
+    return Err(SketchError::Malformed("truncated u64".to_string()));
+  };
+  *input = rest;
+  Ok(u64::from_le_bytes(*bytes))
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as standard padded base64.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+  let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let group = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |group, (i, &byte)| group | u32::from(byte) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+/// Decodes standard padded base64, rejecting any other alphabet or padding.
+pub(crate) fn decode_base64(text: &str) -> Result<Vec<u8>, SketchError> {
+  let malformed = || SketchError::Malformed("invalid base64".to_string());
+  if !text.len().is_multiple_of(4) {
+    return Err(malformed());
+  }
+  let mut out = Vec::with_capacity(text.len() / 4 * 3);
+  let chunks = text.as_bytes().chunks(4);
+  let last = chunks.len().saturating_sub(1);
+  for (index, chunk) in chunks.enumerate() {
+    let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+    if padding > 2 || (padding > 0 && index != last) {
+      return Err(malformed());
+    }
+    let mut group = 0u32;
+    for &c in &chunk[..4 - padding] {
+      let value = BASE64_ALPHABET.iter().position(|&a| a == c).ok_or_else(malformed)?;
+      group = group << 6 | value as u32;
+    }
+    group <<= 6 * padding;
+    let bytes = group.to_be_bytes();
+    out.extend_from_slice(&bytes[1..4 - padding]);
+  }
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_varint_roundtrip() {
+    let values = [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX];
+    let mut out = Vec::new();
+    for value in values {
+      write_varint(&mut out, value);
+    }
+
+    let mut input = out.as_slice();
+    for value in values {
+      assert_eq!(read_varint(&mut input).unwrap(), value);
+    }
+    assert!(input.is_empty());
+    assert!(read_varint(&mut [0x80].as_slice()).is_err());
+    assert!(read_varint(&mut [0xff; 10].as_slice()).is_err());
+  }
+
+  #[test]
+  fn test_base64_roundtrip() {
+    assert_eq!(encode_base64(b"sketch"), "c2tldGNo");
+    assert_eq!(encode_base64(b"sk"), "c2s=");
+    for len in 0..8 {
+      let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+      assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+    }
+    assert!(decode_base64("c2s").is_err());
+    assert!(decode_base64("c2s=c2s=").is_err());
+    assert!(decode_base64("c2$=").is_err());
+  }
+}
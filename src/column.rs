@@ -0,0 +1,157 @@
+use std::borrow::Cow;
+
+use crate::arrow::for_each_string;
+use crate::error::SketchError;
+
+/// Calls `f` with every non-empty value of one column of a buffer, without building a JS
+/// array of strings, and returns the number of values passed to `f`. Backs the
+/// `ingest_column` method of every sketch. The whole buffer is validated before `f` is first
+/// called, so a malformed row anywhere leaves the sketch untouched.
+///
+/// Formats:
+/// * `"csv"` - RFC 4180 CSV with a header row, which is skipped; quoted fields may contain
+///   commas, newlines and doubled quotes
+/// * `"lines"` - one raw value per line, with no header or quoting (`column` must be 0)
+/// * `"arrow"` - an Arrow IPC stream or file whose column is `Utf8` or `LargeUtf8`, optionally
+///   dictionary-encoded; nulls are skipped
+///
+/// `column` is zero-based and defaults to 0.
+pub(crate) fn for_each_value(
+  buffer: &[u8],
+  format: &str,
+  column: Option<usize>,
+  f: impl FnMut(&str),
+) -> Result<usize, SketchError> {
+  let column = column.unwrap_or(0);
+  scan(buffer, format, column, |_| {})?;
+  scan(buffer, format, column, f)
+}
+
+// Parses the buffer, passing values to `f` as it goes; an error may follow earlier values
+fn scan(
+  buffer: &[u8],
+  format: &str,
+  column: usize,
+  mut f: impl FnMut(&str),
+) -> Result<usize, SketchError> {
+  let mut count = 0;
+  let mut emit = |value: &[u8]| -> Result<(), SketchError> {
+    if !value.is_empty() {
+      let value = std::str::from_utf8(value)
+        .map_err(|_| SketchError::Malformed("column value is not valid UTF-8".to_string()))?;
+      f(value);
+      count += 1;
+    }
+    Ok(())
+  };
+
+  match format {
+    "csv" => {
+      let mut pos = 0;
+      let mut header = true;
+      while pos < buffer.len() {
+        let mut index = 0;
+        loop {
+          let (field, end) = read_csv_field(buffer, pos)?;
+          if index == column && !header {
+            emit(&field)?;
+          }
+          pos = end + 1;
+          match buffer.get(end) {
+            Some(b',') => index += 1,
+            Some(b'\r') if buffer.get(pos) == Some(&b'\n') => {
+              pos += 1;
+              break;
+            }
+            _ => break,
+          }
+        }
+        header = false;
+      }
+    }
+    "lines" => {
+      if column != 0 {
+        return Err(SketchError::InvalidParameter("\"lines\" buffers have one column".to_string()));
+      }
+      for line in buffer.split(|&byte| byte == b'\n') {
+        emit(line.strip_suffix(b"\r").unwrap_or(line))?;
+      }
+    }
+    "arrow" => for_each_string(buffer, column, &mut emit)?,
+    _ => {
+      return Err(SketchError::InvalidParameter(format!(
+        "unsupported column format \"{}\"; expected \"csv\", \"lines\" or \"arrow\"",
+        format
+      )))
+    }
+  }
+  Ok(count)
+}
+
+// Reads the CSV field starting at `pos`, returning it with the index of the byte that ends it
+// (a comma, a line break or the end of the buffer)
+fn read_csv_field(buffer: &[u8], pos: usize) -> Result<(Cow<'_, [u8]>, usize), SketchError> {
+  let is_end = |byte: Option<&u8>| matches!(byte, None | Some(b',' | b'\r' | b'\n'));
+  if buffer.get(pos) != Some(&b'"') {
+    let len = buffer[pos..].iter().position(|&byte| is_end(Some(&byte)));
+    let end = len.map_or(buffer.len(), |len| pos + len);
+    return Ok((Cow::Borrowed(&buffer[pos..end]), end));
+  }
+
+  let mut field = Vec::new();
+  let mut cursor = pos + 1;
+  loop {
+    let Some(offset) = buffer[cursor..].iter().position(|&byte| byte == b'"') else {
+      return Err(SketchError::Malformed("unterminated quoted CSV field".to_string()));
+    };
+    field.extend_from_slice(&buffer[cursor..cursor + offset]);
+    cursor += offset + 1;
+    if buffer.get(cursor) == Some(&b'"') {
+      field.push(b'"');
+      cursor += 1;
+    } else if is_end(buffer.get(cursor)) {
+      return Ok((Cow::Owned(field), cursor));
+    } else {
+      return Err(SketchError::Malformed(
+        "unexpected character after quoted CSV field".to_string(),
+      ));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn collect(buffer: &[u8], format: &str, column: usize) -> Result<Vec<String>, SketchError> {
+    let mut values = Vec::new();
+    let count =
+      for_each_value(buffer, format, Some(column), |value| values.push(value.to_string()))?;
+    assert_eq!(count, values.len());
+    Ok(values)
+  }
+
+  #[test]
+  fn test_csv_columns() {
+    let csv = b"id,name\r\n1,\"Smith, \"\"J\"\"\"\r\n2,\n3,\"multi\nline\"\n4";
+    assert_eq!(collect(csv, "csv", 0).unwrap(), ["1", "2", "3", "4"]);
+    assert_eq!(collect(csv, "csv", 1).unwrap(), ["Smith, \"J\"", "multi\nline"]);
+    assert!(collect(csv, "csv", 5).unwrap().is_empty());
+
+    assert!(collect(b"h\n\"open", "csv", 0).is_err());
+    assert!(collect(b"h\n\"a\"b", "csv", 0).is_err());
+    assert!(collect(b"h\n\xff", "csv", 0).is_err());
+
+    // A malformed row fails the whole buffer before any value is passed on
+    let mut seen = 0;
+    assert!(for_each_value(b"h\na\nb\n\"open", "csv", None, |_| seen += 1).is_err());
+    assert_eq!(seen, 0);
+  }
+
+  #[test]
+  fn test_lines_and_formats() {
+    assert_eq!(collect(b"a\r\nb\n\nc\n", "lines", 0).unwrap(), ["a", "b", "c"]);
+    assert!(collect(b"a", "lines", 1).is_err());
+    assert!(collect(b"a", "parquet", 0).is_err());
+  }
+}
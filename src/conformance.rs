@@ -0,0 +1,155 @@
+//! Hash conformance vectors for verifying other implementations (e.g. a Java or Go backend)
+//! against this crate before relying on cross-language merges.
+//!
+//! Key hashes are xxHash64 of the key's UTF-8 bytes with seed 0. Bloom probe `i` is
+//! `(lo + i * (hi | 1)) % bits` over the hash's 32-bit halves. CountMinSketch and
+//! HeavyKeeper columns are `splitmix64(hash ^ (row + 1) * 0x9e3779b97f4a7c15) % width`, and
+//! HyperLogLog uses its own 32-bit hash, reported alongside its slot.
+
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::count_min_sketch::CountMinSketch;
+use crate::hash::hash_str;
+use crate::heavy_keeper::HeavyKeeper;
+use crate::hyperloglog::HyperLogLog;
+
+// Fixed inputs, covering empty, ASCII, multi-byte UTF-8 and multi-block keys
+const INPUTS: [&str; 6] = [
+  "",
+  "a",
+  "hello",
+  "sketch-wasm",
+  "ünïcødé ✓",
+  "a fairly long key that spans several blocks of every hash function used here",
+];
+
+const BLOOM_ITEMS: usize = 1000;
+const BLOOM_FPR: f64 = 0.01;
+const CMS_WIDTH: usize = 1000;
+const CMS_DEPTH: usize = 4;
+const HLL_PRECISION: u8 = 14;
+
+/// The expected hash output of one structure for one input.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct HashTestVector {
+  structure: String,
+  parameters: String,
+  input: String,
+  hash: String,
+  positions: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl HashTestVector {
+  /// Returns the structure the vector applies to, e.g. `"BloomFilter"`.
+  #[wasm_bindgen(getter)]
+  pub fn structure(&self) -> String {
+    self.structure.clone()
+  }
+
+  /// Returns the structure parameters the positions were computed with, e.g. `"width=1000 depth=4"`.
+  #[wasm_bindgen(getter)]
+  pub fn parameters(&self) -> String {
+    self.parameters.clone()
+  }
+
+  /// Returns the input key.
+  #[wasm_bindgen(getter)]
+  pub fn input(&self) -> String {
+    self.input.clone()
+  }
+
+  /// Returns the key hash as lowercase hex, zero-padded to its width (16 or 8 digits).
+  /// Empty for HeavyKeeper, which hashes the key separately for each row.
+  #[wasm_bindgen(getter)]
+  pub fn hash(&self) -> String {
+    self.hash.clone()
+  }
+
+  /// Returns the derived positions: Bloom probe bits, CountMinSketch or HeavyKeeper columns per
+  /// row, or the HyperLogLog register index followed by the rank.
+  #[wasm_bindgen(getter)]
+  pub fn positions(&self) -> Vec<u32> {
+    self.positions.clone()
+  }
+}
+
+/// Returns the crate's hash outputs for a fixed set of inputs and parameters, one vector per
+/// structure and input. The set only grows; existing vectors change only with a format break.
+#[wasm_bindgen]
+pub fn hash_test_vectors() -> Vec<HashTestVector> {
+  let filter = BloomFilter::new(BLOOM_ITEMS, BLOOM_FPR);
+  let sketch = CountMinSketch::new(CMS_WIDTH, CMS_DEPTH);
+  let keeper = HeavyKeeper::new(CMS_WIDTH, CMS_DEPTH, 1, 0.9);
+  let bloom_parameters = format!("bits={} probes={}", filter.bit_count(), filter.probe_count());
+  let grid_parameters = format!("width={} depth={}", CMS_WIDTH, CMS_DEPTH);
+
+  let mut vectors = Vec::new();
+  for input in INPUTS {
+    let key_hash = format!("{:016x}", hash_str(input));
+    let mut push = |structure: &str, parameters: &str, hash: String, positions: Vec<usize>| {
+      vectors.push(HashTestVector {
+        structure: structure.to_string(),
+        parameters: parameters.to_string(),
+        input: input.to_string(),
+        hash,
+        positions: positions.into_iter().map(|position| position as u32).collect(),
+      });
+    };
+
+    push(
+      "BloomFilter",
+      &bloom_parameters,
+      key_hash.clone(),
+      filter.probe_positions(hash_str(input)),
+    );
+    push("CountMinSketch", &grid_parameters, key_hash, sketch.columns(hash_str(input)));
+    push("HeavyKeeper", &grid_parameters, String::new(), keeper.positions(input));
+
+    let hll_hash = HyperLogLog::hash(input.as_bytes());
+    let (index, rank) = HyperLogLog::slot(hll_hash, HLL_PRECISION);
+    push(
+      "HyperLogLog",
+      &format!("precision={}", HLL_PRECISION),
+      format!("{:08x}", hll_hash),
+      vec![index, usize::from(rank)],
+    );
+  }
+  vectors
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_vectors_are_stable() {
+    let vectors = hash_test_vectors();
+    assert_eq!(vectors.len(), INPUTS.len() * 4);
+
+    // Pinned outputs; a change here breaks compatibility with other implementations
+    let hello: Vec<_> = vectors.iter().filter(|v| v.input == "hello").collect();
+    assert_eq!(hello[0].hash, "26c7827d889f6da3");
+    assert_eq!(hello[0].positions, vec![2863, 734, 8191, 6062, 3933, 1804, 9261]);
+    assert_eq!(hello[1].positions, vec![924, 241, 989, 341]);
+    assert_eq!(hello[2].positions, vec![924, 241, 989, 341]);
+    assert_eq!(
+      (hello[3].hash.as_str(), hello[3].positions.as_slice()),
+      ("1e4e4df5", &[3573, 4][..])
+    );
+  }
+
+  #[test]
+  fn test_debug_positions_match_vectors() {
+    let filter = BloomFilter::new(BLOOM_ITEMS, BLOOM_FPR);
+    let sketch = CountMinSketch::new(CMS_WIDTH, CMS_DEPTH);
+    let vectors = hash_test_vectors();
+    for input in INPUTS {
+      let mut matching = vectors.iter().filter(|v| v.input == input);
+      assert_eq!(filter.bloom_indices(input), matching.next().unwrap().positions);
+      assert_eq!(sketch.cms_positions(input), matching.next().unwrap().positions);
+    }
+  }
+}
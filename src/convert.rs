@@ -0,0 +1,107 @@
+//! Hand-offs between summary types that need no access to the original stream.
+//!
+//! Each converter takes the `Entries` export of any structure that retains identifiable items
+//! (e.g. `HeavyKeeper::entries`), so a pipeline can, say, ship the current leaders as a Bloom
+//! filter or seed a Count-Min Sketch with their counts without re-ingesting events.
+
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::count_min_sketch::CountMinSketch;
+use crate::entries::Entries;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+use crate::hyperloglog::HyperLogLog;
+
+/// Builds a Bloom filter containing every item of `entries`, sized for their number.
+///
+/// # Arguments
+///
+/// * `entries` - The items to insert; weights are ignored
+/// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+#[wasm_bindgen]
+pub fn bloom_from_entries(entries: &Entries, false_positive_rate: f64) -> BloomFilter {
+  let mut filter = BloomFilter::new(entries.len().max(1), false_positive_rate);
+  for (item, _) in entries.iter() {
+    filter.insert(item);
+  }
+  filter
+}
+
+/// Builds a Count-Min Sketch whose estimates start at the weight of each item of `entries`,
+/// rounded to the nearest count. Counting can then continue on the new sketch.
+///
+/// # Arguments
+///
+/// * `entries` - The items and their counts
+/// * `width` - The number of counters in each row
+/// * `depth` - The number of hash functions (rows)
+#[wasm_bindgen]
+pub fn count_min_from_entries(
+  entries: &Entries,
+  width: usize,
+  depth: usize,
+) -> Result<CountMinSketch, SketchError> {
+  if width == 0 || depth == 0 {
+    return Err(SketchError::InvalidParameter("width and depth must be positive".to_string()));
+  }
+  let mut sketch = CountMinSketch::new(width, depth);
+  for (item, weight) in entries.iter() {
+    sketch.increment_hash_by(hash_str(item), weight.round().clamp(0.0, f64::from(u32::MAX)) as u32);
+  }
+  Ok(sketch)
+}
+
+/// Builds a HyperLogLog over the distinct items of `entries`, e.g. to merge a leaderboard's
+/// items into a wider cardinality estimate.
+///
+/// # Arguments
+///
+/// * `entries` - The items to add; weights are ignored
+/// * `precision` - The precision parameter (between 4 and 16)
+#[wasm_bindgen]
+pub fn hyperloglog_from_entries(
+  entries: &Entries,
+  precision: Option<u8>,
+) -> Result<HyperLogLog, SketchError> {
+  let p = precision.unwrap_or(14);
+  if !(4..=16).contains(&p) {
+    return Err(SketchError::InvalidParameter("precision must be between 4 and 16".to_string()));
+  }
+  let mut hll = HyperLogLog::new(Some(p)).expect("precision was validated");
+  for (item, _) in entries.iter() {
+    hll.add(item);
+  }
+  Ok(hll)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heavy_keeper::HeavyKeeper;
+
+  #[test]
+  fn test_heavy_keeper_hand_offs() {
+    let mut hk = HeavyKeeper::new(100, 3, 5, 0.9);
+    for (item, count) in [("alpha", 7), ("beta", 3), ("gamma", 1)] {
+      for _ in 0..count {
+        hk.add(item);
+      }
+    }
+    let entries = hk.entries();
+
+    let filter = bloom_from_entries(&entries, 0.01);
+    assert!(filter.contains("alpha") && filter.contains("gamma"));
+
+    let sketch = count_min_from_entries(&entries, 64, 4).unwrap();
+    for (item, weight) in entries.iter() {
+      assert!(f64::from(sketch.estimate(item)) >= weight);
+    }
+    assert_eq!(sketch.total_count(), entries.weights().iter().sum::<f64>());
+    assert!(count_min_from_entries(&entries, 0, 4).is_err());
+
+    let hll = hyperloglog_from_entries(&entries, Some(10)).unwrap();
+    assert_eq!(hll.count().round(), 3.0);
+    assert!(hyperloglog_from_entries(&entries, Some(20)).is_err());
+  }
+}
@@ -1,7 +1,110 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use crate::codec::{read_varint, write_varint};
+use crate::column::for_each_value;
+use crate::entries::Entries;
+use crate::error::SketchError;
+use crate::frequency_histogram::FrequencyHistogram;
+use crate::hash::{hash_str, mix64};
+use crate::memory::try_zeroed;
+use crate::replay::{Journal, ReplayLog, ReplayOp, ReplayTarget};
+
+// Counter matrix and total shared between a sketch and any local sketches mirroring into it
+type SharedCounters = Rc<RefCell<Vec<Vec<u32>>>>;
+type SharedTotal = Rc<Cell<u64>>;
+
+const MAGIC: &[u8; 4] = b"SWCM";
+const FORMAT_VERSION: u8 = 1;
+// Format of conservative-update sketches, otherwise identical to version 1
+const CONSERVATIVE_FORMAT_VERSION: u8 = 2;
+const ENCODING_RAW: u8 = 0;
+const ENCODING_COMPRESSED: u8 = 1;
+const JSON_VERSION: u8 = 1;
+// Upper bound on decoded counters (256 MiB of u32), guarding against hostile headers; it also
+// caps sketches sized by from_error
+const MAX_DECODED_CELLS: usize = 1 << 26;
+
+// JSON shape of a sketch: one array of counters per row, and each row's seed as 16 hex digits
+// since a 64-bit seed does not fit a JSON number
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CountMinSketchJson {
+  version: u8,
+  width: u64,
+  depth: u64,
+  total: u64,
+  // Absent for sketches using the standard update
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  conservative: bool,
+  seeds: Vec<String>,
+  counters: Vec<Vec<u32>>,
+}
+
+/// An estimate crossing a watched threshold, queued by `CountMinSketch::watch`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ThresholdAlert {
+  key: String,
+  threshold: u32,
+  estimate: u32,
+}
+
+#[wasm_bindgen]
+impl ThresholdAlert {
+  /// Returns the watched key.
+  #[wasm_bindgen(getter)]
+  pub fn key(&self) -> String {
+    self.key.clone()
+  }
+
+  /// Returns the threshold that was crossed.
+  #[wasm_bindgen(getter)]
+  pub fn threshold(&self) -> u32 {
+    self.threshold
+  }
+
+  /// Returns the key's estimate right after the crossing increment.
+  #[wasm_bindgen(getter)]
+  pub fn estimate(&self) -> u32 {
+    self.estimate
+  }
+}
+
+// A registered threshold; fires once until the sketch is cleared
+#[derive(Debug, Clone)]
+struct Watch {
+  key: String,
+  threshold: u32,
+  fired: bool,
+}
+
+// Keys whose estimate reached the heavy-hitter cutoff when they were last incremented
+#[derive(Debug, Clone)]
+struct HeavyHitters {
+  // An absolute count when at least 1, otherwise a fraction of the total count
+  threshold: f64,
+  keys: HashMap<String, u32>,
+  // Candidate count at which keys that fell below the cutoff are dropped
+  prune_at: usize,
+}
+
+impl HeavyHitters {
+  const MIN_PRUNE_AT: usize = 64;
+
+  fn cutoff(&self, total: u64) -> f64 {
+    if self.threshold >= 1.0 {
+      self.threshold
+    } else {
+      self.threshold * total as f64
+    }
+  }
+}
+
 /// A probabilistic data structure for counting the frequency of events in a data stream.
 /// It uses a small amount of memory while providing approximate frequency estimates.
 #[wasm_bindgen]
@@ -9,8 +112,27 @@ use wasm_bindgen::prelude::*;
 pub struct CountMinSketch {
   width: usize,
   depth: usize,
-  counters: Vec<Vec<u32>>,
+  counters: SharedCounters,
   hash_seeds: Vec<u64>,
+  // Counter matrices and totals of other sketches that receive every increment applied to
+  // this one
+  mirrors: Vec<(SharedCounters, SharedTotal)>,
+  // Total number of increments applied since the last clear, including mirrored ones
+  total: SharedTotal,
+  // Expected per-row collision error above which the width is doubled, if enabled
+  auto_resize_error: Option<f64>,
+  max_width: usize,
+  // Recording and write-ahead log, either of which may be active
+  journal: Journal,
+  // Watched thresholds by key hash, and the crossings not yet drained
+  watches: HashMap<u64, Watch>,
+  alerts: Vec<ThresholdAlert>,
+  // Distribution of per-key estimates, present while quantile tracking is enabled
+  frequencies: Option<FrequencyHistogram>,
+  // Raise only the cells at the item's current minimum on increment
+  conservative: bool,
+  // Candidate heavy hitters, present while tracking is enabled
+  heavy_hitters: Option<HeavyHitters>,
 }
 
 #[wasm_bindgen]
@@ -23,36 +145,148 @@ impl CountMinSketch {
   /// * `depth` - The number of hash functions (rows)
   #[wasm_bindgen(constructor)]
   pub fn new(width: usize, depth: usize) -> CountMinSketch {
-    let mut hash_seeds = Vec::with_capacity(depth);
-    for i in 0..depth {
-      hash_seeds.push(i as u64);
+    CountMinSketch::with_counters(width, vec![vec![0; width]; depth])
+  }
+
+  /// Creates a new sketch like the constructor, but fails with an out-of-memory error instead
+  /// of aborting if the counters cannot be allocated.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  #[wasm_bindgen]
+  pub fn try_new(width: usize, depth: usize) -> Result<CountMinSketch, SketchError> {
+    let mut counters = try_zeroed::<Vec<u32>>(depth)?;
+    for row in &mut counters {
+      *row = try_zeroed(width)?;
     }
+    Ok(CountMinSketch::with_counters(width, counters))
+  }
+
+  /// Creates a sketch that uses conservative update: an increment raises only the item's cells
+  /// that are at its current minimum, which greatly reduces overestimation on skewed streams.
+  /// Estimates still never undercount, also after merging. The mode is kept when the sketch
+  /// is serialized.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  #[wasm_bindgen]
+  pub fn with_conservative_update(width: usize, depth: usize) -> CountMinSketch {
+    let mut sketch = CountMinSketch::new(width, depth);
+    sketch.conservative = true;
+    sketch
+  }
 
-    let counters = vec![vec![0; width]; depth];
+  /// Creates a sketch sized for an accuracy target: with probability at least `1 - delta`,
+  /// an estimate exceeds the true count by at most `epsilon` times the total count. The width
+  /// is `ceil(e / epsilon)` and the depth `ceil(ln(1 / delta))`. Fails if that would take more
+  /// than 2^26 counters (256 MiB).
+  ///
+  /// # Arguments
+  ///
+  /// * `epsilon` - The error bound as a fraction of the total count (between 0 and 1)
+  /// * `delta` - The probability of exceeding the bound (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn from_error(epsilon: f64, delta: f64) -> Result<CountMinSketch, SketchError> {
+    if !(epsilon > 0.0 && epsilon < 1.0) {
+      return Err(SketchError::InvalidParameter("epsilon must be between 0 and 1".to_string()));
+    }
+    if !(delta > 0.0 && delta < 1.0) {
+      return Err(SketchError::InvalidParameter("delta must be between 0 and 1".to_string()));
+    }
+    let width = (std::f64::consts::E / epsilon).ceil();
+    let depth = (1.0 / delta).ln().ceil().max(1.0);
+    // Checked in f64, since a tiny epsilon overflows the cast to usize
+    if width * depth > MAX_DECODED_CELLS as f64 {
+      return Err(SketchError::InvalidParameter(format!(
+        "epsilon {} and delta {} need {} counters, more than the limit of {}",
+        epsilon,
+        delta,
+        width * depth,
+        MAX_DECODED_CELLS
+      )));
+    }
+    Self::try_new(width as usize, depth as usize)
+  }
 
-    CountMinSketch { width, depth, counters, hash_seeds }
+  // Creates an empty sketch around a zeroed counter matrix of `width` columns
+  fn with_counters(width: usize, counters: Vec<Vec<u32>>) -> CountMinSketch {
+    let depth = counters.len();
+    CountMinSketch {
+      width,
+      depth,
+      counters: Rc::new(RefCell::new(counters)),
+      hash_seeds: (0..depth as u64).collect(),
+      mirrors: Vec::new(),
+      total: Rc::new(Cell::new(0)),
+      auto_resize_error: None,
+      max_width: width,
+      journal: Journal::new(ReplayTarget::CountMinSketch),
+      watches: HashMap::new(),
+      alerts: Vec::new(),
+      frequencies: None,
+      conservative: false,
+      heavy_hitters: None,
+    }
   }
 
-  fn hash(&self, item: &str, seed: u64) -> usize {
-    let mut hasher = DefaultHasher::new();
-    item.hash(&mut hasher);
-    seed.hash(&mut hasher);
-    (hasher.finish() as usize) % self.width
+  // Maps a 64-bit key hash to its column in the row with the given seed
+  fn position(&self, hash: u64, seed: u64) -> usize {
+    column(hash, seed, self.width)
   }
 
   /// Increments the count for an item.
+  /// The increment is also applied to every sketch this one mirrors into.
   ///
   /// # Arguments
   ///
   /// * `item` - The item to increment
   #[wasm_bindgen]
   pub fn increment(&mut self, item: &str) {
-    for i in 0..self.depth {
-      let pos = self.hash(item, self.hash_seeds[i]);
-      self.counters[i][pos] = self.counters[i][pos].saturating_add(1);
+    let hash = hash_str(item);
+    self.increment_hash(hash);
+    if self.heavy_hitters.is_some() {
+      self.track_heavy_hitter(item, hash);
+    }
+  }
+
+  /// Increments many items in one call, avoiding a JS-to-wasm crossing per item. Repeated
+  /// items are counted once per occurrence.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to increment (an array of strings)
+  #[wasm_bindgen]
+  pub fn increment_many(&mut self, items: Vec<String>) {
+    for item in &items {
+      self.increment(item);
     }
   }
 
+  /// Adds every non-empty value of one column of a CSV, newline-delimited or Arrow IPC buffer,
+  /// parsing it entirely in wasm so no JS string array is built. Nothing is added if any row is
+  /// malformed.
+  /// Returns the number of values counted.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The column data as UTF-8 text or an Arrow IPC stream or file (a `Uint8Array`)
+  /// * `format` - `"csv"` (with a header row), `"lines"` (one value per line) or `"arrow"` (a
+  ///   `Utf8` column, optionally dictionary-encoded; nulls are skipped)
+  /// * `column` - The zero-based column to read; defaults to 0
+  #[wasm_bindgen]
+  pub fn ingest_column(
+    &mut self,
+    buffer: &[u8],
+    format: &str,
+    column: Option<usize>,
+  ) -> Result<usize, SketchError> {
+    for_each_value(buffer, format, column, |value| self.increment(value))
+  }
+
   /// Returns the estimated frequency of an item.
   ///
   /// # Arguments
@@ -60,44 +294,1770 @@ impl CountMinSketch {
   /// * `item` - The item to query
   #[wasm_bindgen]
   pub fn estimate(&self, item: &str) -> u32 {
-    let mut min_count = u32::MAX;
-    for i in 0..self.depth {
-      let pos = self.hash(item, self.hash_seeds[i]);
-      min_count = min_count.min(self.counters[i][pos]);
+    self.estimate_hash(hash_str(item))
+  }
+
+  /// Estimates many items in one call. Returns one estimate per item, in order, as a
+  /// `Uint32Array`.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The items to query (an array of strings)
+  #[wasm_bindgen]
+  pub fn estimate_many(&self, items: Vec<String>) -> Vec<u32> {
+    items.iter().map(|item| self.estimate(item)).collect()
+  }
+
+  /// Returns the Count-Mean-Min estimate of an item's frequency: in each row, the item's
+  /// counter minus the mean of the row's other counters (the expected collision noise), then
+  /// the median across rows, capped by `estimate` and floored at 0. Far more accurate than
+  /// `estimate` when the sketch is crowded with many small keys, but it can undercount, and
+  /// each call reads every counter. The noise model assumes every increment reached every
+  /// row, so conservative-update sketches are rejected.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn estimate_corrected(&self, item: &str) -> Result<f64, SketchError> {
+    self.check_standard_update("correct estimates")?;
+    let hash = hash_str(item);
+    let upper = f64::from(self.estimate_hash(hash));
+    if self.width < 2 || self.depth == 0 {
+      return Ok(upper);
     }
-    min_count
+    let counters = self.counters.borrow();
+    let mut corrected: Vec<f64> = (0..self.depth)
+      .map(|i| {
+        let row = &counters[i];
+        let count = f64::from(row[self.position(hash, self.hash_seeds[i])]);
+        let row_sum: f64 = row.iter().map(|&cell| f64::from(cell)).sum();
+        count - (row_sum - count) / (self.width - 1) as f64
+      })
+      .collect();
+    corrected.sort_by(f64::total_cmp);
+    let mid = corrected.len() / 2;
+    let median = if corrected.len().is_multiple_of(2) {
+      (corrected[mid - 1] + corrected[mid]) / 2.0
+    } else {
+      corrected[mid]
+    };
+    Ok(median.clamp(0.0, upper))
+  }
+
+  /// Returns the column an item maps to in each row, for explaining a specific overestimate
+  /// or checking another implementation against this one.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to locate
+  #[wasm_bindgen]
+  pub fn cms_positions(&self, item: &str) -> Vec<u32> {
+    self.columns(hash_str(item)).into_iter().map(|column| column as u32).collect()
+  }
+
+  /// Returns an independent deep copy of the sketch, e.g. to snapshot it while the original
+  /// keeps changing and compare the two later.
+  /// The copy has its own counters and does not mirror into the sketches this one does.
+  #[wasm_bindgen(js_name = clone)]
+  pub fn deep_clone(&self) -> CountMinSketch {
+    self.clone()
   }
 
   /// Clears all counters in the sketch.
+  /// Sketches this one mirrors into are left untouched.
   #[wasm_bindgen]
   pub fn clear(&mut self) {
-    for row in &mut self.counters {
+    for row in self.counters.borrow_mut().iter_mut() {
       for count in row {
         *count = 0;
       }
     }
+    self.total.set(0);
+    for watch in self.watches.values_mut() {
+      watch.fired = false;
+    }
+    if let Some(frequencies) = &mut self.frequencies {
+      frequencies.clear();
+    }
+    if let Some(heavy_hitters) = &mut self.heavy_hitters {
+      heavy_hitters.keys.clear();
+    }
+    self.journal.record(ReplayOp::Clear);
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+  /// Starts tracking the distribution of per-key estimated frequencies, so that
+  /// `frequency_quantile` can answer e.g. "what is the p90 per-key frequency". Each increment
+  /// moves its key between the buckets of a log-scale histogram, costing one extra estimate.
+  ///
+  /// Only increments made while tracking is enabled are reflected; merges, resizes and
+  /// replays are not. A new key whose cells all hold collisions is mistaken for a tracked one.
+  ///
+  /// # Arguments
+  ///
+  /// * `relative_accuracy` - The relative error of reported quantiles (between 0 and 1),
+  ///   defaulting to 0.01
+  #[wasm_bindgen]
+  pub fn enable_frequency_quantiles(
+    &mut self,
+    relative_accuracy: Option<f64>,
+  ) -> Result<(), SketchError> {
+    let relative_accuracy = relative_accuracy.unwrap_or(0.01);
+    if !(relative_accuracy > 0.0 && relative_accuracy < 1.0) {
+      return Err(SketchError::InvalidParameter(
+        "relative_accuracy must be between 0 and 1".to_string(),
+      ));
+    }
+    self.frequencies = Some(FrequencyHistogram::new(relative_accuracy));
+    Ok(())
+  }
 
-  #[test]
-  fn test_count_min_sketch() {
-    let mut cms = CountMinSketch::new(1000, 5);
+  /// Stops tracking per-key frequencies and discards the histogram.
+  #[wasm_bindgen]
+  pub fn disable_frequency_quantiles(&mut self) {
+    self.frequencies = None;
+  }
 
-    // Test increment and estimate
-    cms.increment("test");
-    cms.increment("test");
-    assert_eq!(cms.estimate("test"), 2);
+  /// Returns the approximate per-key frequency at quantile `q`, over the keys incremented
+  /// since tracking was enabled; 0.5 gives the median key's frequency. Returns 0 if no key
+  /// has been tracked.
+  ///
+  /// # Arguments
+  ///
+  /// * `q` - The quantile to query (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn frequency_quantile(&self, q: f64) -> Result<f64, SketchError> {
+    let Some(frequencies) = &self.frequencies else {
+      return Err(SketchError::InvalidParameter("frequency quantiles are not enabled".to_string()));
+    };
+    if !(0.0..=1.0).contains(&q) {
+      return Err(SketchError::InvalidParameter("q must be between 0 and 1".to_string()));
+    }
+    Ok(frequencies.quantile(q))
+  }
 
-    // Test different items
-    cms.increment("other");
-    assert_eq!(cms.estimate("other"), 1);
+  /// Returns the number of distinct keys in the frequency histogram, or 0 when tracking is off.
+  #[wasm_bindgen]
+  pub fn tracked_key_count(&self) -> f64 {
+    self.frequencies.as_ref().map_or(0.0, |frequencies| frequencies.key_count() as f64)
+  }
 
-    // Test clear
-    cms.clear();
-    assert_eq!(cms.estimate("test"), 0);
+  /// Starts tracking heavy hitters: the keys whose estimate is at least `threshold`, kept by
+  /// name so `heavy_hitters` can list them. A threshold below 1 is a fraction φ of the total
+  /// count, so the keys making up at least that share of the stream are reported.
+  ///
+  /// Only keys incremented while tracking is enabled are known; merges, resizes and replays
+  /// update estimates but add no keys. Enabling again replaces the threshold and forgets the
+  /// keys seen so far.
+  ///
+  /// # Arguments
+  ///
+  /// * `threshold` - The minimum estimate as an absolute count (at least 1), or as a fraction
+  ///   of the total count (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn enable_heavy_hitters(&mut self, threshold: f64) -> Result<(), SketchError> {
+    if !(threshold > 0.0 && threshold.is_finite()) {
+      return Err(SketchError::InvalidParameter("threshold must be positive".to_string()));
+    }
+    self.heavy_hitters =
+      Some(HeavyHitters { threshold, keys: HashMap::new(), prune_at: HeavyHitters::MIN_PRUNE_AT });
+    Ok(())
+  }
+
+  /// Stops tracking heavy hitters and forgets the keys seen so far.
+  #[wasm_bindgen]
+  pub fn disable_heavy_hitters(&mut self) {
+    self.heavy_hitters = None;
+  }
+
+  /// Returns the tracked keys whose current estimate is at least the heavy-hitter threshold,
+  /// largest first. Like `estimate`, counts may be overestimated, so a key just below the
+  /// threshold can be reported but a key above it is never missed.
+  #[wasm_bindgen]
+  pub fn heavy_hitters(&self) -> Result<Entries, SketchError> {
+    let Some(heavy_hitters) = &self.heavy_hitters else {
+      return Err(SketchError::InvalidParameter("heavy hitters are not enabled".to_string()));
+    };
+    let cutoff = heavy_hitters.cutoff(self.total.get());
+    Ok(
+      heavy_hitters
+        .keys
+        .keys()
+        .map(|key| (key.clone(), f64::from(self.estimate(key))))
+        .filter(|&(_, estimate)| estimate >= cutoff)
+        .collect(),
+    )
+  }
+
+  /// Watches a key: the first time an increment of that key brings its estimate to at least
+  /// `threshold`, an alert is queued for `drain_alerts`. The watch re-arms when the sketch is
+  /// cleared. Watching a key again replaces its threshold and re-arms it.
+  ///
+  /// Thresholds are only checked when the watched key itself is incremented, not when merges
+  /// or colliding keys raise its estimate.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - The key to watch
+  /// * `threshold` - The estimate at which to raise an alert
+  #[wasm_bindgen]
+  pub fn watch(&mut self, key: &str, threshold: u32) {
+    let fired = self.estimate(key) >= threshold;
+    self.watches.insert(hash_str(key), Watch { key: key.to_string(), threshold, fired });
+  }
+
+  /// Stops watching a key.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - The key to stop watching
+  #[wasm_bindgen]
+  pub fn unwatch(&mut self, key: &str) {
+    self.watches.remove(&hash_str(key));
+  }
+
+  /// Returns the alerts queued since the last call, oldest first, and empties the queue.
+  #[wasm_bindgen]
+  pub fn drain_alerts(&mut self) -> Vec<ThresholdAlert> {
+    std::mem::take(&mut self.alerts)
+  }
+
+  /// Starts recording every increment and clear applied to the sketch.
+  /// Any previous recording is discarded.
+  /// Fails if another sketch mirrors into this one, since its increments would not be recorded.
+  #[wasm_bindgen]
+  pub fn start_recording(&mut self) -> Result<(), SketchError> {
+    self.check_not_mirrored_into("record")?;
+    self.journal.start_recording();
+    Ok(())
+  }
+
+  /// Stops recording and returns the recorded operations as a compact buffer of hashed keys.
+  /// Returns an empty buffer if the sketch was not recording.
+  #[wasm_bindgen]
+  pub fn stop_recording(&mut self) -> Vec<u8> {
+    self.journal.stop_recording()
+  }
+
+  /// Starts keeping the most recent `capacity` operations in a write-ahead log, so updates made
+  /// since the last persisted snapshot can be recovered with `replay` after a crash. Older
+  /// operations are dropped, which `wal_overflowed` reports. Any previous log is discarded.
+  ///
+  /// While recording or keeping a write-ahead log, mutations that cannot be replayed, such as
+  /// merges and resizes, fail instead of silently diverging from the log. Automatic width
+  /// growth is paused for the same reason. Fails if another sketch mirrors into this one.
+  ///
+  /// # Arguments
+  ///
+  /// * `capacity` - The maximum number of operations kept
+  #[wasm_bindgen]
+  pub fn enable_wal(&mut self, capacity: usize) -> Result<(), SketchError> {
+    self.check_not_mirrored_into("keep a write-ahead log")?;
+    self.journal.enable_wal(capacity);
+    Ok(())
+  }
+
+  /// Stops keeping a write-ahead log.
+  #[wasm_bindgen]
+  pub fn disable_wal(&mut self) {
+    self.journal.disable_wal();
+  }
+
+  /// Returns the write-ahead log in the same format as `stop_recording`, ready for `replay`.
+  /// Returns an empty buffer if the sketch has no write-ahead log.
+  #[wasm_bindgen]
+  pub fn wal_bytes(&self) -> Vec<u8> {
+    self.journal.wal_bytes()
+  }
+
+  /// Returns true if the write-ahead log has dropped operations since it was last truncated,
+  /// so replaying it onto the last snapshot would lose updates; persist a new snapshot instead.
+  #[wasm_bindgen]
+  pub fn wal_overflowed(&self) -> bool {
+    self.journal.wal_overflowed()
+  }
+
+  /// Empties the write-ahead log; call after persisting a snapshot of the sketch.
+  #[wasm_bindgen]
+  pub fn truncate_wal(&mut self) {
+    self.journal.truncate_wal();
+  }
+
+  /// Re-applies a buffer produced by `stop_recording` to this sketch.
+  /// The buffer is validated fully before any operation is applied.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The recorded operations
+  #[wasm_bindgen]
+  pub fn replay(&mut self, buffer: &[u8]) -> Result<(), SketchError> {
+    for op in ReplayLog::parse(buffer, ReplayTarget::CountMinSketch)? {
+      match op {
+        ReplayOp::Update(hash) => self.increment_hash(hash),
+        ReplayOp::UpdateBy(hash, count) => self.increment_hash_by(hash, count),
+        ReplayOp::Clear => self.clear(),
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns true if the sketch uses conservative update.
+  #[wasm_bindgen(getter)]
+  pub fn conservative(&self) -> bool {
+    self.conservative
+  }
+
+  /// Returns the number of counters in each row.
+  #[wasm_bindgen]
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// Returns the number of hash functions (rows).
+  #[wasm_bindgen]
+  pub fn depth(&self) -> usize {
+    self.depth
+  }
+
+  /// Returns the error bound the width guarantees, `e / width`, as a fraction of the total
+  /// count.
+  #[wasm_bindgen]
+  pub fn epsilon(&self) -> f64 {
+    std::f64::consts::E / self.width as f64
+  }
+
+  /// Returns the probability that an estimate exceeds the `epsilon` bound, `e^-depth`.
+  #[wasm_bindgen]
+  pub fn delta(&self) -> f64 {
+    (-(self.depth as f64)).exp()
+  }
+
+  /// Returns the total number of increments applied since the last clear.
+  #[wasm_bindgen]
+  pub fn total_count(&self) -> f64 {
+    self.total.get() as f64
+  }
+
+  /// Returns the expected overestimate caused by hash collisions in a single row
+  /// (total count divided by width).
+  #[wasm_bindgen]
+  pub fn expected_error(&self) -> f64 {
+    self.total.get() as f64 / self.width as f64
+  }
+
+  /// Enables automatic width growth.
+  /// Whenever the expected collision error exceeds `max_expected_error`, the sketch is rebuilt
+  /// with double the width, up to `max_width`.
+  ///
+  /// # Arguments
+  ///
+  /// * `max_expected_error` - The expected per-row overestimate that triggers a resize
+  /// * `max_width` - The largest width the sketch may grow to
+  #[wasm_bindgen]
+  pub fn enable_auto_resize(&mut self, max_expected_error: f64, max_width: usize) {
+    self.auto_resize_error = Some(max_expected_error);
+    self.max_width = max_width;
+  }
+
+  /// Disables automatic width growth.
+  #[wasm_bindgen]
+  pub fn disable_auto_resize(&mut self) {
+    self.auto_resize_error = None;
+  }
+
+  /// Rebuilds the sketch with double the width.
+  /// Existing counters are re-spread into both columns an item could now hash to, so estimates
+  /// never drop below true counts; new increments then benefit from the lower collision rate.
+  /// Fails if this sketch mirrors into, or is mirrored into by, another sketch, or while it is
+  /// recording or keeping a write-ahead log.
+  #[wasm_bindgen]
+  pub fn double_width(&mut self) -> Result<(), SketchError> {
+    self.journal.check_unlogged("resize a sketch")?;
+    if !self.mirrors.is_empty() || Rc::strong_count(&self.counters) > 1 {
+      return Err(SketchError::Incompatible(
+        "cannot resize a CountMinSketch that is linked to mirrors".to_string(),
+      ));
+    }
+
+    let new_width = self.width * 2;
+    let mut counters = self.counters.borrow_mut();
+    for row in counters.iter_mut() {
+      // A column j in the new row maps back to j % width, since h % (2w) % w == h % w
+      let mut grown = Vec::with_capacity(new_width);
+      grown.extend_from_slice(row);
+      grown.extend_from_slice(row);
+      *row = grown;
+    }
+    drop(counters);
+    self.width = new_width;
+    Ok(())
+  }
+
+  /// Rebuilds the sketch with half the width by adding each column in the upper half onto the
+  /// matching column in the lower half. Requires an even width.
+  /// Fails if this sketch mirrors into, or is mirrored into by, another sketch.
+  #[wasm_bindgen]
+  pub fn halve_width(&mut self) -> Result<(), SketchError> {
+    if self.width < 2 || !self.width.is_multiple_of(2) {
+      return Err(SketchError::InvalidParameter("width must be even to be halved".to_string()));
+    }
+    if !self.mirrors.is_empty() || Rc::strong_count(&self.counters) > 1 {
+      return Err(SketchError::Incompatible(
+        "cannot resize a CountMinSketch that is linked to mirrors".to_string(),
+      ));
+    }
+
+    self.downsample(2)
+  }
+
+  /// Shrinks the width by `factor` for bandwidth-limited sync, adding every column onto the
+  /// column it maps to at the new width. The result equals a sketch of the new width that saw
+  /// the same increments, so a server can merge it after downsampling its own copy by the same
+  /// factor. Fails if this sketch mirrors into, or is mirrored into by, another sketch.
+  ///
+  /// # Arguments
+  ///
+  /// * `factor` - The shrink factor; must divide the width
+  #[wasm_bindgen]
+  pub fn downsample(&mut self, factor: u32) -> Result<(), SketchError> {
+    self.journal.check_unlogged("downsample a sketch")?;
+    let factor = factor as usize;
+    if factor == 0 || !self.width.is_multiple_of(factor) {
+      return Err(SketchError::InvalidParameter("factor must divide the width".to_string()));
+    }
+    if !self.mirrors.is_empty() || Rc::strong_count(&self.counters) > 1 {
+      return Err(SketchError::Incompatible(
+        "cannot resize a CountMinSketch that is linked to mirrors".to_string(),
+      ));
+    }
+
+    // Column j maps to j % new_width, since h % w % (w / f) == h % (w / f)
+    let new_width = self.width / factor;
+    for row in self.counters.borrow_mut().iter_mut() {
+      let (low, high) = row.split_at_mut(new_width);
+      for (offset, folded) in high.iter().enumerate() {
+        let count = &mut low[offset % new_width];
+        *count = count.saturating_add(*folded);
+      }
+      row.truncate(new_width);
+      row.shrink_to_fit();
+    }
+    self.width = new_width;
+    Ok(())
+  }
+
+  /// Divides every counter and the total count by `factor`, rounding down, so old counts fade.
+  /// Calling it periodically keeps estimates weighted towards recent activity and stops counters
+  /// from growing without bound. Keys with fewer than `factor` increments in a cell drop to 0.
+  ///
+  /// Frequency quantiles, watches and heavy-hitter candidates are not rescaled. Fails while
+  /// recording or keeping a write-ahead log, which cannot replay it.
+  ///
+  /// # Arguments
+  ///
+  /// * `factor` - The divisor, at least 1
+  #[wasm_bindgen]
+  pub fn scale_down(&mut self, factor: u32) -> Result<(), SketchError> {
+    self.journal.check_unlogged("scale down a sketch")?;
+    if factor == 0 {
+      return Err(SketchError::InvalidParameter("factor must be at least 1".to_string()));
+    }
+    for row in self.counters.borrow_mut().iter_mut() {
+      for count in row {
+        *count /= factor;
+      }
+    }
+    self.total.set(self.total.get() / u64::from(factor));
+    Ok(())
+  }
+
+  /// Halves every counter and the total count, the classic periodic aging step. Equivalent to
+  /// `scale_down(2)`.
+  #[wasm_bindgen]
+  pub fn halve(&mut self) -> Result<(), SketchError> {
+    self.scale_down(2)
+  }
+
+  /// Merges another Count-Min Sketch into this one by adding its counters.
+  /// Both sketches must have the same width, depth and hash seeds.
+  /// Merged counts are not forwarded to mirrors.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch to merge with
+  #[wasm_bindgen]
+  pub fn merge(&mut self, other: &CountMinSketch) -> Result<(), SketchError> {
+    self.merge_weighted(other, 1.0)
+  }
+
+  /// Merges another Count-Min Sketch into this one, scaling its counters by `weight` before
+  /// adding them (rounded to the nearest integer). This blends decayed historical sketches
+  /// into the current window at reduced weight.
+  /// Both sketches must have the same width, depth and hash seeds.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch to merge with
+  /// * `weight` - The non-negative factor applied to the other sketch's counters
+  #[wasm_bindgen]
+  pub fn merge_weighted(&mut self, other: &CountMinSketch, weight: f64) -> Result<(), SketchError> {
+    self.check_compatible(other)?;
+    self.journal.check_unlogged("merge sketches")?;
+    if !(weight >= 0.0 && weight.is_finite()) {
+      return Err(SketchError::InvalidParameter("weight must be non-negative".to_string()));
+    }
+
+    let other_counters = other.counters.borrow();
+    let mut counters = self.counters.borrow_mut();
+    for (row, other_row) in counters.iter_mut().zip(other_counters.iter()) {
+      for (count, &other_count) in row.iter_mut().zip(other_row) {
+        *count = count.saturating_add(Self::scale(other_count, weight));
+      }
+    }
+    self
+      .total
+      .set(self.total.get().saturating_add((other.total.get() as f64 * weight).round() as u64));
+    Ok(())
+  }
+
+  /// Subtracts another sketch's counters from this one, stopping at zero, e.g. to turn this
+  /// window's sketch into "this window minus last window" and surface rising keys.
+  /// Estimates of keys that fell are clamped to zero; use `estimate_change` for signed deltas.
+  /// Both sketches must have the same width, depth and hash seeds.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch to subtract
+  #[wasm_bindgen]
+  pub fn subtract(&mut self, other: &CountMinSketch) -> Result<(), SketchError> {
+    self.check_compatible(other)?;
+    self.journal.check_unlogged("subtract sketches")?;
+
+    let other_counters = other.counters.borrow();
+    let mut counters = self.counters.borrow_mut();
+    for (row, other_row) in counters.iter_mut().zip(other_counters.iter()) {
+      for (count, &other_count) in row.iter_mut().zip(other_row) {
+        *count = count.saturating_sub(other_count);
+      }
+    }
+    self.total.set(self.total.get().saturating_sub(other.total.get()));
+    Ok(())
+  }
+
+  /// Returns the signed change in an item's count from `previous` to this sketch.
+  /// Each row's cell difference is an unbiased but noisy estimate, so the median across rows
+  /// is returned; collisions can push it either way. Conservative-update sketches are rejected,
+  /// since their cells do not hold additive counts.
+  ///
+  /// # Arguments
+  ///
+  /// * `previous` - The earlier sketch, e.g. last window's
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn estimate_change(&self, previous: &CountMinSketch, item: &str) -> Result<f64, SketchError> {
+    self.check_compatible(previous)?;
+    self.check_standard_update("estimate changes")?;
+    previous.check_standard_update("estimate changes")?;
+
+    let hash = hash_str(item);
+    let counters = self.counters.borrow();
+    let previous_counters = previous.counters.borrow();
+    let mut deltas: Vec<i64> = (0..self.depth)
+      .map(|i| {
+        let pos = self.position(hash, self.hash_seeds[i]);
+        i64::from(counters[i][pos]) - i64::from(previous_counters[i][pos])
+      })
+      .collect();
+    deltas.sort_unstable();
+
+    let mid = deltas.len() / 2;
+    let median = match deltas.len() {
+      0 => 0.0,
+      len if len.is_multiple_of(2) => (deltas[mid - 1] + deltas[mid]) as f64 / 2.0,
+      _ => deltas[mid] as f64,
+    };
+    Ok(median)
+  }
+
+  /// Estimates how often an item occurred in both streams: the smaller of its estimates here
+  /// and in `other`. Like `estimate`, it never underestimates.
+  /// Both sketches must have the same width, depth and hash seeds.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch of the other stream
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn co_count(&self, other: &CountMinSketch, item: &str) -> Result<u32, SketchError> {
+    self.check_compatible(other)?;
+    let hash = hash_str(item);
+    Ok(self.estimate_hash(hash).min(other.estimate_hash(hash)))
+  }
+
+  /// Estimates the aggregate overlap of two streams, the sum over all keys of each key's
+  /// `co_count`. When each stream counts a key at most once (e.g. deduplicated user IDs for
+  /// "did A" and "did B"), this is the number of keys present in both.
+  /// Collisions only inflate the result, and the least inflated row is used.
+  /// Both sketches must have the same width, depth and hash seeds, and use the standard update.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch of the other stream
+  #[wasm_bindgen]
+  pub fn overlap(&self, other: &CountMinSketch) -> Result<f64, SketchError> {
+    self.check_compatible(other)?;
+    self.check_standard_update("estimate overlap")?;
+    other.check_standard_update("estimate overlap")?;
+    let counters = self.counters.borrow();
+    let other_counters = other.counters.borrow();
+    let overlap = counters
+      .iter()
+      .zip(other_counters.iter())
+      .map(|(row, other_row)| {
+        row.iter().zip(other_row).map(|(&a, &b)| u64::from(a.min(b))).sum::<u64>()
+      })
+      .min()
+      .unwrap_or(0);
+    Ok(overlap as f64)
+  }
+
+  /// Estimates the inner product of the two streams' frequency vectors, `Σ f_a(x) · f_b(x)`
+  /// over all keys, e.g. the size of an equi-join between two datasets keyed by the counted
+  /// value. Each row gives an overestimate from the products of matching counters, and the
+  /// least inflated row is used. Conservative update leaves rows that undercount the product,
+  /// so both sketches must use the standard update and share width, depth and hash seeds.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch of the other stream
+  #[wasm_bindgen]
+  pub fn inner_product(&self, other: &CountMinSketch) -> Result<f64, SketchError> {
+    self.check_compatible(other)?;
+    self.check_standard_update("estimate inner products")?;
+    other.check_standard_update("estimate inner products")?;
+    let counters = self.counters.borrow();
+    let other_counters = other.counters.borrow();
+    let product = counters
+      .iter()
+      .zip(other_counters.iter())
+      .map(|(row, other_row)| {
+        row.iter().zip(other_row).map(|(&a, &b)| u128::from(a) * u128::from(b)).sum::<u128>()
+      })
+      .min()
+      .unwrap_or(0);
+    Ok(product as f64)
+  }
+
+  /// Mirrors every future increment of this sketch into `global`.
+  /// This keeps a shared sketch's counters and total up to date cell by cell, avoiding periodic
+  /// full-sketch merges.
+  /// Only increments made after this call are mirrored, and increments made directly on
+  /// `global` are not forwarded to the sketches `global` itself mirrors into.
+  /// Fails while `global` is recording or keeping a write-ahead log, which would miss the
+  /// mirrored increments.
+  ///
+  /// # Arguments
+  ///
+  /// * `global` - The sketch to mirror into; must have the same width and depth
+  #[wasm_bindgen]
+  pub fn mirror_into(&mut self, global: &CountMinSketch) -> Result<(), SketchError> {
+    global.journal.check_unlogged("mirror into a sketch")?;
+    if self.width != global.width || self.depth != global.depth {
+      return Err(SketchError::Incompatible(
+        "cannot mirror into a CountMinSketch with different dimensions".to_string(),
+      ));
+    }
+    if Rc::ptr_eq(&self.counters, &global.counters)
+      || self.mirrors.iter().any(|(mirror, _)| Rc::ptr_eq(mirror, &global.counters))
+    {
+      return Err(SketchError::Incompatible(
+        "sketch is already mirrored into this CountMinSketch".to_string(),
+      ));
+    }
+
+    self.mirrors.push((Rc::clone(&global.counters), Rc::clone(&global.total)));
+    Ok(())
+  }
+
+  /// Stops mirroring increments into any other sketch.
+  #[wasm_bindgen]
+  pub fn clear_mirrors(&mut self) {
+    self.mirrors.clear();
+  }
+
+  /// Serializes the sketch for storage or transport, e.g. to persist it from a worker and ship
+  /// it to a backend for aggregation. The format is the magic `SWCM`, a version byte (2 for
+  /// conservative-update sketches, otherwise 1), an encoding byte (0), varint width, depth and
+  /// total count, one varint hash seed per row, then every counter row by row as a
+  /// little-endian u32.
+  #[wasm_bindgen]
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = self.header(ENCODING_RAW);
+    for &count in self.counters.borrow().iter().flatten() {
+      out.extend_from_slice(&count.to_le_bytes());
+    }
+    out
+  }
+
+  /// Restores a sketch from a buffer produced by `to_bytes` or `to_bytes_compressed`, with
+  /// the same dimensions, seeds, total and counters.
+  ///
+  /// # Arguments
+  ///
+  /// * `bytes` - The serialized sketch
+  #[wasm_bindgen]
+  pub fn from_bytes(bytes: &[u8]) -> Result<CountMinSketch, SketchError> {
+    let (mut sketch, mut input, encoding) = Self::read_header(bytes)?;
+    let cells = sketch.width * sketch.depth;
+    let values = match encoding {
+      ENCODING_RAW => {
+        if input.len() != cells * 4 {
+          return Err(SketchError::Malformed(
+            "CountMinSketch counters have the wrong length".to_string(),
+          ));
+        }
+        input.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect()
+      }
+      ENCODING_COMPRESSED => Self::read_compressed(&mut input, cells)?,
+      _ => {
+        return Err(SketchError::Malformed(format!(
+          "unsupported CountMinSketch encoding {}",
+          encoding
+        )))
+      }
+    };
+    sketch.set_cells(values);
+    Ok(sketch)
+  }
+
+  /// Returns a plain object for `JSON.stringify`:
+  /// `{ version, width, depth, total, seeds, counters }`, where `seeds` holds each row's hash
+  /// seed as 16 hex digits and `counters` one array per row. Conservative-update sketches also
+  /// carry `conservative: true`. Suited to debugging and to small sketches stored with
+  /// application state; it also survives `structuredClone`.
+  #[wasm_bindgen(js_name = toJSON)]
+  pub fn to_json(&self) -> Result<JsValue, SketchError> {
+    serde_wasm_bindgen::to_value(&self.to_json_repr())
+      .map_err(|err| SketchError::Malformed(err.to_string()))
+  }
+
+  /// Restores a sketch from the object produced by `toJSON`, e.g. after `JSON.parse`.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The parsed JSON object
+  #[wasm_bindgen(js_name = fromJSON)]
+  pub fn from_json(value: JsValue) -> Result<CountMinSketch, SketchError> {
+    let repr = serde_wasm_bindgen::from_value(value)
+      .map_err(|err| SketchError::Malformed(err.to_string()))?;
+    Self::from_json_repr(repr)
+  }
+
+  /// Serializes the sketch into a compact buffer for transport.
+  /// Counters are varint-encoded and runs of zero counters are run-length encoded, so a mostly
+  /// empty sketch (e.g. a freshly rotated window) costs a few bytes instead of width×depth×4.
+  #[wasm_bindgen]
+  pub fn to_bytes_compressed(&self) -> Vec<u8> {
+    let mut out = self.header(ENCODING_COMPRESSED);
+    let mut zeros = 0u64;
+    for &count in self.counters.borrow().iter().flatten() {
+      if count == 0 {
+        zeros += 1;
+        continue;
+      }
+      if zeros > 0 {
+        // A zero marker is followed by the run length
+        write_varint(&mut out, 0);
+        write_varint(&mut out, zeros);
+        zeros = 0;
+      }
+      write_varint(&mut out, u64::from(count));
+    }
+    if zeros > 0 {
+      write_varint(&mut out, 0);
+      write_varint(&mut out, zeros);
+    }
+    out
+  }
+
+  /// Restores a sketch from a buffer produced by `to_bytes_compressed`.
+  ///
+  /// Runs of zero counters cost a few bytes however long they are, so a short buffer may
+  /// decode to a sketch of up to 2^26 counters (256 MiB). Check the dimensions of untrusted
+  /// buffers against what the application expects before decoding them.
+  ///
+  /// # Arguments
+  ///
+  /// * `bytes` - The serialized sketch
+  #[wasm_bindgen]
+  pub fn from_bytes_compressed(bytes: &[u8]) -> Result<CountMinSketch, SketchError> {
+    let (mut sketch, mut input, encoding) = Self::read_header(bytes)?;
+    if encoding != ENCODING_COMPRESSED {
+      return Err(SketchError::Malformed("buffer is not a compressed CountMinSketch".to_string()));
+    }
+
+    let values = Self::read_compressed(&mut input, sketch.width * sketch.depth)?;
+    sketch.set_cells(values);
+    Ok(sketch)
+  }
+}
+
+impl CountMinSketch {
+  // Records an incremented key if it reached the heavy-hitter cutoff. With a fractional
+  // threshold the cutoff rises with the total, so stale candidates are pruned whenever their
+  // number doubles
+  fn track_heavy_hitter(&mut self, item: &str, hash: u64) {
+    let estimate = self.estimate_hash(hash);
+    let total = self.total.get();
+    let heavy_hitters = self.heavy_hitters.as_mut().expect("tracking was checked");
+    let cutoff = heavy_hitters.cutoff(total);
+    if f64::from(estimate) < cutoff {
+      return;
+    }
+    heavy_hitters.keys.insert(item.to_string(), estimate);
+    if heavy_hitters.keys.len() >= heavy_hitters.prune_at {
+      heavy_hitters.keys.retain(|_, estimate| f64::from(*estimate) >= cutoff);
+      heavy_hitters.prune_at = (heavy_hitters.keys.len() * 2).max(HeavyHitters::MIN_PRUNE_AT);
+    }
+  }
+
+  pub(crate) fn increment_hash(&mut self, hash: u64) {
+    self.increment_hash_by(hash, 1);
+  }
+
+  /// Adds `count` to a key hash, as if it had been incremented `count` times.
+  pub(crate) fn increment_hash_by(&mut self, hash: u64, count: u32) {
+    if count == 0 {
+      return;
+    }
+    let previous = self.frequencies.is_some().then(|| self.estimate_hash(hash));
+    {
+      let mut counters = self.counters.borrow_mut();
+      let mut mirrors: Vec<_> =
+        self.mirrors.iter().map(|(mirror, _)| mirror.borrow_mut()).collect();
+      let positions: Vec<usize> =
+        (0..self.depth).map(|i| self.position(hash, self.hash_seeds[i])).collect();
+      // Conservative update raises every cell to the new minimum; cells above it already
+      // account for this item
+      let target = self.conservative.then(|| {
+        let min = positions.iter().enumerate().map(|(i, &pos)| counters[i][pos]).min();
+        min.unwrap_or(0).saturating_add(count)
+      });
+      for (i, &pos) in positions.iter().enumerate() {
+        counters[i][pos] = match target {
+          Some(target) => counters[i][pos].max(target),
+          None => counters[i][pos].saturating_add(count),
+        };
+        for mirror in &mut mirrors {
+          mirror[i][pos] = mirror[i][pos].saturating_add(count);
+        }
+      }
+    }
+    self.total.set(self.total.get() + u64::from(count));
+    for (_, total) in &self.mirrors {
+      total.set(total.get() + u64::from(count));
+    }
+    if let Some(previous) = previous {
+      let current = self.estimate_hash(hash);
+      let frequencies = self.frequencies.as_mut().expect("tracking was checked");
+      frequencies.record_change(previous, current);
+    }
+    self.journal.record(match count {
+      1 => ReplayOp::Update(hash),
+      count => ReplayOp::UpdateBy(hash, count),
+    });
+
+    if self.watches.get(&hash).is_some_and(|watch| !watch.fired) {
+      let estimate = self.estimate_hash(hash);
+      let watch = self.watches.get_mut(&hash).expect("watch was just found");
+      if estimate >= watch.threshold {
+        watch.fired = true;
+        self.alerts.push(ThresholdAlert {
+          key: watch.key.clone(),
+          threshold: watch.threshold,
+          estimate,
+        });
+      }
+    }
+
+    if let Some(max_error) = self.auto_resize_error {
+      if self.expected_error() > max_error && self.width * 2 <= self.max_width {
+        // Growth is refused while mirrors are attached or the sketch is recording or keeping a
+        // write-ahead log; estimates stay correct, just noisier
+        let _ = self.double_width();
+      }
+    }
+  }
+
+  /// Returns the column of a key hash in each row.
+  pub(crate) fn columns(&self, hash: u64) -> Vec<usize> {
+    self.hash_seeds.iter().map(|&seed| self.position(hash, seed)).collect()
+  }
+
+  pub(crate) fn estimate_hash(&self, hash: u64) -> u32 {
+    let counters = self.counters.borrow();
+    let mut min_count = u32::MAX;
+    for i in 0..self.depth {
+      let pos = self.position(hash, self.hash_seeds[i]);
+      min_count = min_count.min(counters[i][pos]);
+    }
+    min_count
+  }
+
+  /// Returns all counters flattened row by row.
+  pub(crate) fn cells(&self) -> Vec<u32> {
+    self.counters.borrow().concat()
+  }
+
+  pub(crate) fn hash_seeds(&self) -> &[u64] {
+    &self.hash_seeds
+  }
+
+  pub(crate) fn total(&self) -> u64 {
+    self.total.get()
+  }
+
+  /// Fails if the sketch is recording or keeping a write-ahead log, which could not replay the
+  /// mutation described by `operation`.
+  pub(crate) fn check_unlogged(&self, operation: &str) -> Result<(), SketchError> {
+    self.journal.check_unlogged(operation)
+  }
+
+  /// Adds `delta` to the counter at flattened `index`, leaving the total count untouched.
+  pub(crate) fn add_to_cell(&mut self, index: usize, delta: u32) {
+    let (row, col) = (index / self.width, index % self.width);
+    let mut counters = self.counters.borrow_mut();
+    counters[row][col] = counters[row][col].saturating_add(delta);
+  }
+
+  /// Adds `delta` to the total count without touching any counter.
+  pub(crate) fn add_to_total(&mut self, delta: u64) {
+    self.total.set(self.total.get().saturating_add(delta));
+  }
+
+  pub(crate) fn memory_bytes(&self) -> usize {
+    self.width * self.depth * std::mem::size_of::<u32>()
+  }
+
+  // Refuses operations whose log would miss the increments another sketch mirrors into this one
+  fn check_not_mirrored_into(&self, operation: &str) -> Result<(), SketchError> {
+    if Rc::strong_count(&self.counters) > 1 {
+      return Err(SketchError::Incompatible(format!(
+        "cannot {} while another sketch mirrors into this one",
+        operation
+      )));
+    }
+    Ok(())
+  }
+
+  // Refuses operations that need each row to hold the full count of every key, which
+  // conservative update does not keep
+  fn check_standard_update(&self, operation: &str) -> Result<(), SketchError> {
+    if self.conservative {
+      return Err(SketchError::Incompatible(format!(
+        "cannot {} on a conservative-update sketch",
+        operation
+      )));
+    }
+    Ok(())
+  }
+
+  pub(crate) fn check_compatible(&self, other: &CountMinSketch) -> Result<(), SketchError> {
+    if self.width != other.width || self.depth != other.depth || self.hash_seeds != other.hash_seeds
+    {
+      return Err(SketchError::Incompatible(
+        "CountMinSketch instances must share width, depth and hash seeds".to_string(),
+      ));
+    }
+    Ok(())
+  }
+
+  fn scale(count: u32, weight: f64) -> u32 {
+    // Float-to-int casts saturate, so oversized products clamp to u32::MAX
+    (count as f64 * weight).round() as u32
+  }
+
+  // Layout: magic, version, encoding, then varint width, depth, total and one varint seed per row
+  fn header(&self, encoding: u8) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(if self.conservative { CONSERVATIVE_FORMAT_VERSION } else { FORMAT_VERSION });
+    out.push(encoding);
+    write_varint(&mut out, self.width as u64);
+    write_varint(&mut out, self.depth as u64);
+    write_varint(&mut out, self.total.get());
+    for &seed in &self.hash_seeds {
+      write_varint(&mut out, seed);
+    }
+    out
+  }
+
+  fn to_json_repr(&self) -> CountMinSketchJson {
+    CountMinSketchJson {
+      version: JSON_VERSION,
+      width: self.width as u64,
+      depth: self.depth as u64,
+      total: self.total.get(),
+      conservative: self.conservative,
+      seeds: self.hash_seeds.iter().map(|seed| format!("{:016x}", seed)).collect(),
+      counters: self.counters.borrow().clone(),
+    }
+  }
+
+  fn from_json_repr(repr: CountMinSketchJson) -> Result<CountMinSketch, SketchError> {
+    if repr.version != JSON_VERSION {
+      return Err(SketchError::Malformed(format!(
+        "unsupported CountMinSketch JSON version {}",
+        repr.version
+      )));
+    }
+    let (width, depth) = Self::check_decoded_dimensions(repr.width, repr.depth)?;
+    if repr.seeds.len() != depth
+      || repr.counters.len() != depth
+      || repr.counters.iter().any(|row| row.len() != width)
+    {
+      return Err(SketchError::Malformed(
+        "CountMinSketch seeds or counters do not match its dimensions".to_string(),
+      ));
+    }
+    let mut sketch = CountMinSketch::new(width, depth);
+    sketch.hash_seeds = repr
+      .seeds
+      .iter()
+      .map(|seed| u64::from_str_radix(seed, 16))
+      .collect::<Result<_, _>>()
+      .map_err(|_| SketchError::Malformed("CountMinSketch seed is not hexadecimal".to_string()))?;
+    sketch.total.set(repr.total);
+    sketch.conservative = repr.conservative;
+    sketch.counters = Rc::new(RefCell::new(repr.counters));
+    Ok(sketch)
+  }
+
+  // Converts decoded dimensions, rejecting empty, hostile or corrupt values
+  fn check_decoded_dimensions(width: u64, depth: u64) -> Result<(usize, usize), SketchError> {
+    match (usize::try_from(width), usize::try_from(depth)) {
+      (Ok(width), Ok(depth))
+        if width > 0
+          && depth > 0
+          && width.checked_mul(depth).is_some_and(|cells| cells <= MAX_DECODED_CELLS) =>
+      {
+        Ok((width, depth))
+      }
+      _ => Err(SketchError::Malformed("invalid CountMinSketch dimensions".to_string())),
+    }
+  }
+
+  // Decodes `cells` varint counters with zero runs, rejecting any bytes left over. A zero run
+  // is bounded only by the cells left, so a few bytes can expand to MAX_DECODED_CELLS zeros
+  fn read_compressed(input: &mut &[u8], cells: usize) -> Result<Vec<u32>, SketchError> {
+    let mut values = Vec::new();
+    while values.len() < cells {
+      match read_varint(input)? {
+        0 => {
+          let run = read_varint(input)?;
+          if run == 0 || run > (cells - values.len()) as u64 {
+            return Err(SketchError::Malformed("invalid zero run length".to_string()));
+          }
+          values.resize(values.len() + run as usize, 0);
+        }
+        count => values.push(
+          u32::try_from(count)
+            .map_err(|_| SketchError::Malformed("counter exceeds 32 bits".to_string()))?,
+        ),
+      }
+    }
+    if !input.is_empty() {
+      return Err(SketchError::Malformed("trailing bytes after counters".to_string()));
+    }
+    Ok(values)
+  }
+
+  // Replaces the counter matrix with decoded row-major counters
+  fn set_cells(&mut self, values: Vec<u32>) {
+    self.counters =
+      Rc::new(RefCell::new(values.chunks(self.width.max(1)).map(<[u32]>::to_vec).collect()));
+  }
+
+  // Returns an empty sketch with the serialized geometry, the remaining input and the encoding
+  fn read_header(bytes: &[u8]) -> Result<(CountMinSketch, &[u8], u8), SketchError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+      return Err(SketchError::Malformed("not a serialized CountMinSketch".to_string()));
+    };
+    let [version, encoding, rest @ ..] = rest else {
+      return Err(SketchError::Malformed("truncated CountMinSketch header".to_string()));
+    };
+    if *version != FORMAT_VERSION && *version != CONSERVATIVE_FORMAT_VERSION {
+      return Err(SketchError::Malformed(format!(
+        "unsupported CountMinSketch version {}",
+        version
+      )));
+    }
+
+    let mut input = rest;
+    let width = read_varint(&mut input)?;
+    let depth = read_varint(&mut input)?;
+    let (width, depth) = Self::check_decoded_dimensions(width, depth)?;
+    let total = read_varint(&mut input)?;
+    let hash_seeds = (0..depth).map(|_| read_varint(&mut input)).collect::<Result<Vec<_>, _>>()?;
+
+    // Counters are left empty for the caller to fill once they have been decoded
+    let sketch = CountMinSketch {
+      width,
+      depth,
+      counters: Rc::new(RefCell::new(Vec::new())),
+      hash_seeds,
+      mirrors: Vec::new(),
+      total: Rc::new(Cell::new(total)),
+      auto_resize_error: None,
+      max_width: width,
+      journal: Journal::new(ReplayTarget::CountMinSketch),
+      watches: HashMap::new(),
+      alerts: Vec::new(),
+      frequencies: None,
+      conservative: *version == CONSERVATIVE_FORMAT_VERSION,
+      heavy_hitters: None,
+    };
+    Ok((sketch, input, *encoding))
+  }
+}
+
+impl Clone for CountMinSketch {
+  // Copies the counter matrix rather than sharing it, and leaves mirroring behind
+  fn clone(&self) -> CountMinSketch {
+    CountMinSketch {
+      width: self.width,
+      depth: self.depth,
+      counters: Rc::new(RefCell::new(self.counters.borrow().clone())),
+      hash_seeds: self.hash_seeds.clone(),
+      mirrors: Vec::new(),
+      total: Rc::new(Cell::new(self.total.get())),
+      auto_resize_error: self.auto_resize_error,
+      max_width: self.max_width,
+      journal: self.journal.clone(),
+      watches: self.watches.clone(),
+      alerts: self.alerts.clone(),
+      frequencies: self.frequencies.clone(),
+      conservative: self.conservative,
+      heavy_hitters: self.heavy_hitters.clone(),
+    }
+  }
+}
+
+/// Maps a 64-bit key hash to its column in a row of `width` counters with the given seed.
+pub(crate) fn column(hash: u64, seed: u64, width: usize) -> usize {
+  (mix64(hash, seed) % width as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_count_min_sketch() {
+    let mut cms = CountMinSketch::new(1000, 5);
+
+    // Test increment and estimate
+    cms.increment("test");
+    cms.increment("test");
+    assert_eq!(cms.estimate("test"), 2);
+
+    // Test different items
+    cms.increment("other");
+    assert_eq!(cms.estimate("other"), 1);
+
+    // Test clear
+    cms.clear();
+    assert_eq!(cms.estimate("test"), 0);
+  }
+
+  #[test]
+  fn test_try_new() {
+    let cms = CountMinSketch::try_new(1000, 5).unwrap();
+    assert_eq!(cms.cells(), CountMinSketch::new(1000, 5).cells());
+    assert!(matches!(CountMinSketch::try_new(usize::MAX, 2), Err(SketchError::OutOfMemory(_))));
+  }
+
+  #[test]
+  fn test_batch_increment_and_estimate() {
+    let mut cms = CountMinSketch::new(1000, 5);
+    cms.increment_many(vec!["a".to_string(), "b".to_string(), "a".to_string()]);
+    assert_eq!(cms.total_count(), 3.0);
+    let estimates =
+      cms.estimate_many(vec!["a".to_string(), "missing".to_string(), "b".to_string()]);
+    assert_eq!(estimates, [2, 0, 1]);
+  }
+
+  #[test]
+  fn test_conservative_update() {
+    let mut standard = CountMinSketch::new(200, 4);
+    let mut conservative = CountMinSketch::with_conservative_update(200, 4);
+    assert!(conservative.conservative() && !standard.conservative());
+    // Zipf-like stream: key i appears 1000 / (i + 1) times
+    for i in 0..2000 {
+      for _ in 0..1000 / (i + 1) {
+        standard.increment(&format!("key{}", i));
+        conservative.increment(&format!("key{}", i));
+      }
+    }
+
+    let (mut standard_error, mut conservative_error) = (0, 0);
+    for i in 0..2000 {
+      let key = format!("key{}", i);
+      let truth = 1000 / (i + 1);
+      assert!(conservative.estimate(&key) >= truth);
+      assert!(conservative.estimate(&key) <= standard.estimate(&key));
+      standard_error += standard.estimate(&key) - truth;
+      conservative_error += conservative.estimate(&key) - truth;
+    }
+    assert!(
+      conservative_error * 4 < standard_error * 3,
+      "{} vs {}",
+      conservative_error,
+      standard_error
+    );
+    assert_eq!(conservative.total_count(), standard.total_count());
+
+    // The mode survives every serialized form, so restored sketches keep updating the same way
+    let restored = [
+      CountMinSketch::from_bytes(&conservative.to_bytes()).unwrap(),
+      CountMinSketch::from_bytes_compressed(&conservative.to_bytes_compressed()).unwrap(),
+      CountMinSketch::from_json_repr(conservative.to_json_repr()).unwrap(),
+    ];
+    for sketch in restored {
+      assert!(sketch.conservative());
+      assert_eq!(sketch.cells(), conservative.cells());
+    }
+    assert!(!CountMinSketch::from_bytes(&standard.to_bytes()).unwrap().conservative());
+    assert_eq!(standard.to_bytes()[4], FORMAT_VERSION);
+  }
+
+  #[test]
+  fn test_from_error() {
+    let cms = CountMinSketch::from_error(0.001, 0.01).unwrap();
+    assert_eq!((cms.width(), cms.depth()), (2719, 5));
+    assert!(cms.epsilon() <= 0.001 && cms.delta() <= 0.01);
+
+    assert_eq!(CountMinSketch::from_error(0.5, 0.9).unwrap().depth(), 1);
+    assert!(CountMinSketch::from_error(0.0, 0.01).is_err());
+    assert!(CountMinSketch::from_error(0.01, 1.0).is_err());
+    assert!(matches!(
+      CountMinSketch::from_error(1e-12, 0.01),
+      Err(SketchError::InvalidParameter(_))
+    ));
+    assert!(CountMinSketch::from_error(1e-7, 1e-300).is_err());
+  }
+
+  #[test]
+  fn test_estimate_corrected() {
+    let mut cms = CountMinSketch::new(100, 5);
+    for i in 0..5000 {
+      cms.increment(&format!("noise{}", i));
+    }
+    for _ in 0..200 {
+      cms.increment("heavy");
+    }
+
+    let corrected = cms.estimate_corrected("heavy").unwrap();
+    assert!((corrected - 200.0).abs() < 30.0, "corrected {}", corrected);
+    assert!(f64::from(cms.estimate("heavy")) - 200.0 > corrected - 200.0);
+    assert!(cms.estimate_corrected("absent").unwrap() < 20.0);
+    assert_eq!(CountMinSketch::new(100, 5).estimate_corrected("heavy").unwrap(), 0.0);
+    assert!(CountMinSketch::with_conservative_update(100, 5).estimate_corrected("heavy").is_err());
+  }
+
+  #[test]
+  fn test_frequency_quantiles() {
+    let mut cms = CountMinSketch::new(10_000, 5);
+    assert!(cms.frequency_quantile(0.5).is_err());
+    cms.enable_frequency_quantiles(None).unwrap();
+    assert_eq!(cms.frequency_quantile(0.5).unwrap(), 0.0);
+
+    // Key i is incremented i times, for i in 1..=100
+    for i in 1..=100 {
+      for _ in 0..i {
+        cms.increment(&format!("key_{}", i));
+      }
+    }
+    assert_eq!(cms.tracked_key_count(), 100.0);
+    let median = cms.frequency_quantile(0.5).unwrap();
+    assert!((median - 50.0).abs() <= 50.0 * 0.02 + 1.0, "median {}", median);
+    let p90 = cms.frequency_quantile(0.9).unwrap();
+    assert!((p90 - 90.0).abs() <= 90.0 * 0.02 + 1.0, "p90 {}", p90);
+    assert!((cms.frequency_quantile(1.0).unwrap() - 100.0).abs() <= 1.0);
+    assert!(cms.frequency_quantile(1.5).is_err());
+
+    cms.clear();
+    assert_eq!(cms.tracked_key_count(), 0.0);
+    assert!(cms.enable_frequency_quantiles(Some(1.0)).is_err());
+  }
+
+  #[test]
+  fn test_heavy_hitters() {
+    let mut cms = CountMinSketch::new(2000, 5);
+    assert!(cms.heavy_hitters().is_err());
+    assert!(cms.enable_heavy_hitters(0.0).is_err());
+
+    // Two keys each make up about 10% of the stream; the rest appear once
+    cms.enable_heavy_hitters(0.05).unwrap();
+    for i in 0..9000 {
+      cms.increment(&format!("noise_{}", i));
+      if i % 9 == 0 {
+        cms.increment("hot");
+        cms.increment("warm");
+      }
+    }
+    let entries = cms.heavy_hitters().unwrap();
+    let mut items = entries.items();
+    items.sort();
+    assert_eq!(items, ["hot", "warm"]);
+    assert!(entries.weights().iter().all(|&weight| weight >= 1000.0));
+
+    cms.enable_heavy_hitters(3.0).unwrap();
+    for _ in 0..3 {
+      cms.increment("fresh");
+    }
+    assert_eq!(cms.heavy_hitters().unwrap().items(), ["fresh"]);
+
+    cms.clear();
+    assert!(cms.heavy_hitters().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_mirror_into() {
+    let global = CountMinSketch::new(1000, 5);
+    let mut local_a = CountMinSketch::new(1000, 5);
+    let mut local_b = CountMinSketch::new(1000, 5);
+    local_a.mirror_into(&global).unwrap();
+    local_b.mirror_into(&global).unwrap();
+
+    local_a.increment("shared");
+    local_b.increment("shared");
+    local_b.increment("only_b");
+
+    assert_eq!(local_a.estimate("shared"), 1);
+    assert_eq!(global.estimate("shared"), 2);
+    assert_eq!(global.estimate("only_b"), 1);
+    assert_eq!(global.total_count(), 3.0);
+
+    // Clearing a local sketch leaves the global one intact
+    local_b.clear();
+    assert_eq!(global.estimate("only_b"), 1);
+    assert_eq!(global.total_count(), 3.0);
+
+    local_a.clear_mirrors();
+    local_a.increment("shared");
+    assert_eq!(global.estimate("shared"), 2);
+  }
+
+  #[test]
+  fn test_clone_is_independent() {
+    let global = CountMinSketch::new(1000, 5);
+    let mut local = CountMinSketch::new(1000, 5);
+    local.mirror_into(&global).unwrap();
+    local.increment("a");
+
+    let mut snapshot = local.deep_clone();
+    let global_snapshot = global.deep_clone();
+    local.increment("a");
+    snapshot.increment("b");
+    assert_eq!((local.estimate("a"), local.estimate("b")), (2, 0));
+    assert_eq!((snapshot.estimate("a"), snapshot.estimate("b")), (1, 1));
+    // The snapshot neither mirrors into the global sketch nor sees its later updates
+    assert_eq!((global.estimate("a"), global.estimate("b")), (2, 0));
+    assert_eq!(global_snapshot.estimate("a"), 1);
+  }
+
+  #[test]
+  fn test_mirror_into_rejects_incompatible() {
+    let mut local = CountMinSketch::new(1000, 5);
+    assert!(local.mirror_into(&CountMinSketch::new(500, 5)).is_err());
+
+    let global = CountMinSketch::new(1000, 5);
+    local.mirror_into(&global).unwrap();
+    assert!(local.mirror_into(&global).is_err());
+  }
+
+  #[test]
+  fn test_double_width_preserves_estimates() {
+    let mut cms = CountMinSketch::new(16, 4);
+    for i in 0..200 {
+      cms.increment(&format!("item{}", i % 20));
+    }
+    let before: Vec<u32> = (0..20).map(|i| cms.estimate(&format!("item{}", i))).collect();
+
+    cms.double_width().unwrap();
+    assert_eq!(cms.width(), 32);
+    for (i, &estimate) in before.iter().enumerate() {
+      assert_eq!(cms.estimate(&format!("item{}", i)), estimate);
+    }
+  }
+
+  #[test]
+  fn test_auto_resize() {
+    let mut cms = CountMinSketch::new(8, 4);
+    cms.enable_auto_resize(2.0, 64);
+    for i in 0..1000 {
+      cms.increment(&format!("item{}", i));
+    }
+    assert_eq!(cms.width(), 64);
+    assert_eq!(cms.total_count(), 1000.0);
+    assert!(cms.estimate("item0") >= 1);
+
+    let global = CountMinSketch::new(8, 4);
+    let mut local = CountMinSketch::new(8, 4);
+    local.mirror_into(&global).unwrap();
+    assert!(local.double_width().is_err());
+  }
+
+  #[test]
+  fn test_scale_down() {
+    let mut cms = CountMinSketch::new(1000, 5);
+    for _ in 0..100 {
+      cms.increment("old");
+    }
+    cms.increment("rare");
+    cms.halve().unwrap();
+    assert_eq!(cms.estimate("old"), 50);
+    assert_eq!(cms.estimate("rare"), 0);
+    assert_eq!(cms.total_count(), 50.0);
+
+    cms.scale_down(10).unwrap();
+    assert_eq!(cms.estimate("old"), 5);
+    assert!(cms.scale_down(0).is_err());
+
+    cms.start_recording().unwrap();
+    assert!(matches!(cms.halve(), Err(SketchError::Incompatible(_))));
+    assert_eq!(cms.estimate("old"), 5);
+  }
+
+  #[test]
+  fn test_halve_width_never_underestimates() {
+    let mut cms = CountMinSketch::new(64, 4);
+    for i in 0..50 {
+      for _ in 0..=i {
+        cms.increment(&format!("item{}", i));
+      }
+    }
+
+    cms.halve_width().unwrap();
+    assert_eq!(cms.width(), 32);
+    for i in 0..50 {
+      assert!(cms.estimate(&format!("item{}", i)) > i);
+    }
+    assert!(CountMinSketch::new(5, 2).halve_width().is_err());
+  }
+
+  #[test]
+  fn test_record_and_replay() {
+    let mut cms = CountMinSketch::new(100, 4);
+    cms.start_recording().unwrap();
+    for i in 0..50 {
+      cms.increment(&format!("item{}", i % 7));
+    }
+    let log = cms.stop_recording();
+    cms.increment("after");
+
+    let mut replayed = CountMinSketch::new(100, 4);
+    replayed.replay(&log).unwrap();
+    for i in 0..7 {
+      let item = format!("item{}", i);
+      assert_eq!(replayed.estimate(&item), cms.estimate(&item));
+    }
+    assert_eq!(replayed.total_count(), 50.0);
+  }
+
+  #[test]
+  fn test_weighted_increment_is_logged_once() {
+    let mut cms = CountMinSketch::new(100, 4);
+    cms.start_recording().unwrap();
+    cms.increment_hash_by(hash_str("bulk"), u32::MAX);
+    let log = cms.stop_recording();
+    assert!(log.len() < 32);
+
+    let mut replayed = CountMinSketch::new(100, 4);
+    replayed.replay(&log).unwrap();
+    assert_eq!(replayed.estimate("bulk"), u32::MAX);
+  }
+
+  #[test]
+  fn test_wal_recovers_updates_since_snapshot() {
+    let mut cms = CountMinSketch::new(100, 4);
+    cms.enable_wal(16).unwrap();
+    cms.increment("persisted");
+    let snapshot = cms.to_bytes_compressed();
+    cms.truncate_wal();
+
+    cms.increment("lost");
+    cms.increment("lost");
+
+    let mut recovered = CountMinSketch::from_bytes_compressed(&snapshot).unwrap();
+    recovered.replay(&cms.wal_bytes()).unwrap();
+    assert_eq!(recovered.cells(), cms.cells());
+    assert_eq!(recovered.estimate("lost"), 2);
+
+    cms.disable_wal();
+    assert!(cms.wal_bytes().is_empty());
+  }
+
+  #[test]
+  fn test_wal_flags_overflow_and_unlogged_mutations() {
+    let mut cms = CountMinSketch::new(100, 4);
+    cms.enable_wal(2).unwrap();
+    for _ in 0..3 {
+      cms.increment("key");
+    }
+    assert!(cms.wal_overflowed());
+    cms.truncate_wal();
+    assert!(!cms.wal_overflowed());
+
+    let other = CountMinSketch::new(100, 4);
+    assert!(cms.merge(&other).is_err());
+    assert!(cms.subtract(&other).is_err());
+    assert!(cms.downsample(2).is_err());
+    assert_eq!(cms.width(), 100);
+
+    cms.disable_wal();
+    cms.merge(&other).unwrap();
+    cms.start_recording().unwrap();
+    assert!(cms.halve_width().is_err());
+    assert!(matches!(cms.double_width(), Err(SketchError::Incompatible(_))));
+    assert_eq!(cms.width(), 100);
+
+    // Auto-resize pauses while the log is kept, so replaying it rebuilds the same sketch
+    let mut growing = CountMinSketch::new(8, 4);
+    growing.enable_auto_resize(2.0, 64);
+    growing.enable_wal(1000).unwrap();
+    for i in 0..100 {
+      growing.increment(&format!("item{}", i));
+    }
+    assert_eq!(growing.width(), 8);
+    let mut replayed = CountMinSketch::new(8, 4);
+    replayed.replay(&growing.wal_bytes()).unwrap();
+    assert_eq!(replayed.cells(), growing.cells());
+  }
+
+  #[test]
+  fn test_mirrors_refuse_logged_sketches() {
+    let mut global = CountMinSketch::new(100, 4);
+    let mut local = CountMinSketch::new(100, 4);
+    global.enable_wal(16).unwrap();
+    assert!(matches!(local.mirror_into(&global), Err(SketchError::Incompatible(_))));
+    global.disable_wal();
+
+    local.mirror_into(&global).unwrap();
+    assert!(matches!(global.enable_wal(16), Err(SketchError::Incompatible(_))));
+    assert!(matches!(global.start_recording(), Err(SketchError::Incompatible(_))));
+    assert!(global.wal_bytes().is_empty());
+  }
+
+  #[test]
+  fn test_threshold_alerts() {
+    let mut cms = CountMinSketch::new(100, 4);
+    cms.watch("client-1", 3);
+    cms.watch("client-2", 10);
+
+    for _ in 0..5 {
+      cms.increment("client-1");
+      cms.increment("client-2");
+    }
+    let alerts = cms.drain_alerts();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!((alerts[0].key().as_str(), alerts[0].estimate()), ("client-1", 3));
+    assert!(cms.drain_alerts().is_empty());
+
+    // Clearing re-arms the watch
+    cms.clear();
+    cms.unwatch("client-2");
+    for _ in 0..10 {
+      cms.increment("client-1");
+      cms.increment("client-2");
+    }
+    let keys: Vec<String> = cms.drain_alerts().iter().map(ThresholdAlert::key).collect();
+    assert_eq!(keys, vec!["client-1"]);
+  }
+
+  #[test]
+  fn test_downsample_matches_smaller_sketch() {
+    let mut full = CountMinSketch::new(120, 4);
+    let mut coarse = CountMinSketch::new(40, 4);
+    for i in 0..500 {
+      let key = format!("key{}", i % 37);
+      full.increment(&key);
+      coarse.increment(&key);
+    }
+    full.downsample(3).unwrap();
+    assert_eq!(full.width(), 40);
+    assert_eq!(full.cells(), coarse.cells());
+
+    assert!(full.downsample(3).is_err());
+    assert!(full.downsample(0).is_err());
+  }
+
+  #[test]
+  fn test_subtract_and_estimate_change() {
+    let (mut current, mut previous) = (CountMinSketch::new(1000, 5), CountMinSketch::new(1000, 5));
+    for _ in 0..10 {
+      previous.increment("falling");
+      current.increment("rising");
+    }
+    for _ in 0..3 {
+      previous.increment("rising");
+      current.increment("falling");
+    }
+
+    assert_eq!(current.estimate_change(&previous, "rising").unwrap(), 7.0);
+    assert_eq!(current.estimate_change(&previous, "falling").unwrap(), -7.0);
+
+    current.subtract(&previous).unwrap();
+    assert_eq!(current.estimate("rising"), 7);
+    assert_eq!(current.estimate("falling"), 0);
+    assert_eq!(current.total_count(), 0.0);
+
+    assert!(current.subtract(&CountMinSketch::new(10, 5)).is_err());
+  }
+
+  #[test]
+  fn test_co_count_and_overlap() {
+    let (mut did_a, mut did_b) = (CountMinSketch::new(2000, 5), CountMinSketch::new(2000, 5));
+    for user in 0..300 {
+      did_a.increment(&format!("user_{}", user));
+    }
+    for user in 200..400 {
+      did_b.increment(&format!("user_{}", user));
+    }
+    did_b.increment("user_250");
+
+    assert_eq!(did_a.co_count(&did_b, "user_250").unwrap(), 1);
+    assert_eq!(did_a.co_count(&did_b, "user_10").unwrap(), 0);
+
+    let overlap = did_a.overlap(&did_b).unwrap();
+    assert!((100.0..=110.0).contains(&overlap), "overlap {}", overlap);
+    assert!(did_a.overlap(&CountMinSketch::new(10, 5)).is_err());
+  }
+
+  #[test]
+  fn test_inner_product() {
+    let (mut orders, mut visits) = (CountMinSketch::new(2000, 5), CountMinSketch::new(2000, 5));
+    // Customers 0..100 place 2 orders each; customers 50..150 visit 3 times each
+    for customer in 0..100 {
+      orders.increment_hash_by(hash_str(&format!("customer_{}", customer)), 2);
+    }
+    for customer in 50..150 {
+      visits.increment_hash_by(hash_str(&format!("customer_{}", customer)), 3);
+    }
+
+    let join_size = orders.inner_product(&visits).unwrap();
+    assert!((300.0..=330.0).contains(&join_size), "join size {}", join_size);
+    assert_eq!(orders.inner_product(&CountMinSketch::new(2000, 5)).unwrap(), 0.0);
+    assert!(orders.inner_product(&CountMinSketch::new(10, 5)).is_err());
+
+    let conservative = CountMinSketch::with_conservative_update(2000, 5);
+    assert!(orders.inner_product(&conservative).is_err());
+    assert!(conservative.inner_product(&visits).is_err());
+    assert!(orders.overlap(&conservative).is_err());
+    assert!(orders.estimate_change(&conservative, "key").is_err());
+  }
+
+  #[test]
+  fn test_compressed_roundtrip() {
+    let mut cms = CountMinSketch::new(1000, 5);
+    let empty = cms.to_bytes_compressed();
+    assert!(empty.len() < 32);
+
+    for i in 0..100 {
+      cms.increment(&format!("item{}", i % 10));
+    }
+    let bytes = cms.to_bytes_compressed();
+    assert!(bytes.len() < 1000 * 5 * 4 / 10);
+
+    let restored = CountMinSketch::from_bytes_compressed(&bytes).unwrap();
+    assert_eq!(restored.cells(), cms.cells());
+    assert_eq!(restored.total_count(), 100.0);
+    assert_eq!(restored.estimate("item3"), cms.estimate("item3"));
+
+    assert!(CountMinSketch::from_bytes_compressed(&bytes[..bytes.len() - 1]).is_err());
+    assert!(CountMinSketch::from_bytes_compressed(b"SWCM").is_err());
+
+    // Width 5, depth 0 and total 0 would decode to a sketch with no rows
+    let mut zero_depth = MAGIC.to_vec();
+    zero_depth.extend([FORMAT_VERSION, ENCODING_COMPRESSED, 5, 0, 0]);
+    assert!(CountMinSketch::from_bytes_compressed(&zero_depth).is_err());
+  }
+
+  #[test]
+  fn test_json_roundtrip() {
+    let mut cms = CountMinSketch::new(50, 3);
+    for i in 0..20 {
+      cms.increment(&format!("item{}", i % 4));
+    }
+    let repr = cms.to_json_repr();
+    assert_eq!(repr.seeds[2], "0000000000000002");
+    assert_eq!(repr.counters.len(), 3);
+
+    let decoded = CountMinSketch::from_json_repr(repr).unwrap();
+    assert_eq!(decoded.cells(), cms.cells());
+    assert_eq!(decoded.total_count(), 20.0);
+    assert!(decoded.check_compatible(&cms).is_ok());
+
+    let wrong_version = CountMinSketchJson { version: 2, ..cms.to_json_repr() };
+    assert!(CountMinSketch::from_json_repr(wrong_version).is_err());
+    let ragged = CountMinSketchJson {
+      counters: vec![vec![0; 50], vec![0; 49], vec![0; 50]],
+      ..cms.to_json_repr()
+    };
+    assert!(CountMinSketch::from_json_repr(ragged).is_err());
+    let bad_seed = CountMinSketchJson { seeds: vec!["x".to_string(); 3], ..cms.to_json_repr() };
+    assert!(CountMinSketch::from_json_repr(bad_seed).is_err());
+    let no_rows = CountMinSketchJson {
+      depth: 0,
+      seeds: Vec::new(),
+      counters: Vec::new(),
+      ..cms.to_json_repr()
+    };
+    assert!(CountMinSketch::from_json_repr(no_rows).is_err());
+  }
+
+  #[test]
+  fn test_bytes_roundtrip() {
+    let mut cms = CountMinSketch::new(100, 4);
+    for i in 0..100 {
+      cms.increment(&format!("item{}", i % 10));
+    }
+    let bytes = cms.to_bytes();
+    let restored = CountMinSketch::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.cells(), cms.cells());
+    assert_eq!(restored.total_count(), 100.0);
+    assert_eq!(restored.estimate("item3"), cms.estimate("item3"));
+
+    // Compressed buffers decode too, but not the other way around
+    let compressed = CountMinSketch::from_bytes(&cms.to_bytes_compressed()).unwrap();
+    assert_eq!(compressed.cells(), cms.cells());
+    assert!(CountMinSketch::from_bytes_compressed(&bytes).is_err());
+
+    assert!(CountMinSketch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut unknown = bytes.clone();
+    unknown[5] = 9;
+    assert!(CountMinSketch::from_bytes(&unknown).is_err());
+  }
+
+  #[test]
+  fn test_merge_and_merge_weighted() {
+    let mut current = CountMinSketch::new(1000, 5);
+    let mut history = CountMinSketch::new(1000, 5);
+    for _ in 0..10 {
+      current.increment("key");
+      history.increment("key");
+      history.increment("old");
+    }
+
+    current.merge_weighted(&history, 0.5).unwrap();
+    assert_eq!(current.estimate("key"), 15);
+    assert_eq!(current.estimate("old"), 5);
+    assert_eq!(current.total_count(), 20.0);
+
+    current.merge(&history).unwrap();
+    assert_eq!(current.estimate("key"), 25);
+
+    assert!(current.merge(&CountMinSketch::new(100, 5)).is_err());
+    assert!(current.merge_weighted(&history, -1.0).is_err());
   }
 }
@@ -0,0 +1,213 @@
+use wasm_bindgen::prelude::*;
+
+use crate::count_min_sketch::CountMinSketch;
+use crate::counter_matrix::CounterMatrix;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+
+/// A Count-Min Sketch with 64-bit counters, for long-running streams whose counts would
+/// overflow the 32-bit cells of `CountMinSketch`. Counts cross the JS boundary as `BigInt`.
+/// It hashes keys exactly as `CountMinSketch` does, so an existing sketch can be widened
+/// with `from_sketch` when its counts approach the 32-bit limit.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct CountMinSketch64 {
+  counters: CounterMatrix<u64>,
+  // Total of all increments applied since the last clear
+  total: u64,
+  // Raise only the cells at the item's current minimum on increment, as CountMinSketch does
+  conservative: bool,
+}
+
+#[wasm_bindgen]
+impl CountMinSketch64 {
+  /// Creates a new sketch with the specified width and depth.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  #[wasm_bindgen(constructor)]
+  pub fn new(width: usize, depth: usize) -> Result<CountMinSketch64, SketchError> {
+    Ok(CountMinSketch64 {
+      counters: CounterMatrix::new(width, depth)?,
+      total: 0,
+      conservative: false,
+    })
+  }
+
+  /// Creates a 64-bit copy of a 32-bit sketch, keeping its counts and update mode, so counting
+  /// can continue past 4,294,967,295 without starting over.
+  ///
+  /// # Arguments
+  ///
+  /// * `sketch` - The sketch to widen
+  #[wasm_bindgen]
+  pub fn from_sketch(sketch: &CountMinSketch) -> CountMinSketch64 {
+    CountMinSketch64 {
+      counters: CounterMatrix::from_parts(
+        sketch.width(),
+        sketch.cells().into_iter().map(u64::from).collect(),
+        sketch.hash_seeds().to_vec(),
+      ),
+      total: sketch.total(),
+      conservative: sketch.conservative(),
+    }
+  }
+
+  /// Increments the count for an item by one.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to increment
+  #[wasm_bindgen]
+  pub fn increment(&mut self, item: &str) {
+    self.increment_by(item, 1);
+  }
+
+  /// Adds `count` to an item's count, as if it had been incremented `count` times.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to increment
+  /// * `count` - The amount to add
+  #[wasm_bindgen]
+  pub fn increment_by(&mut self, item: &str, count: u64) {
+    let hash = hash_str(item);
+    let indices: Vec<usize> = self.counters.indices(hash).collect();
+    let target = self.conservative.then(|| {
+      let min = indices.iter().map(|&index| self.counters[index]).min();
+      min.unwrap_or(0).saturating_add(count)
+    });
+    for index in indices {
+      self.counters[index] = match target {
+        Some(target) => self.counters[index].max(target),
+        None => self.counters[index].saturating_add(count),
+      };
+    }
+    self.total = self.total.saturating_add(count);
+  }
+
+  /// Returns the estimated frequency of an item.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn estimate(&self, item: &str) -> u64 {
+    let hash = hash_str(item);
+    self.counters.indices(hash).map(|index| self.counters[index]).min().unwrap_or(0)
+  }
+
+  /// Merges another sketch into this one. Both must share width, depth and hash seeds.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch to merge
+  #[wasm_bindgen]
+  pub fn merge(&mut self, other: &CountMinSketch64) -> Result<(), SketchError> {
+    self.counters.check_compatible(&other.counters, "CountMinSketch64")?;
+    for (count, other) in self.counters.zip_mut(&other.counters) {
+      *count = count.saturating_add(*other);
+    }
+    self.total = self.total.saturating_add(other.total);
+    Ok(())
+  }
+
+  /// Returns an independent copy of this sketch.
+  #[wasm_bindgen(js_name = clone)]
+  pub fn deep_clone(&self) -> CountMinSketch64 {
+    self.clone()
+  }
+
+  /// Clears all counters in the sketch.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.counters.clear();
+    self.total = 0;
+  }
+
+  /// Returns true if the sketch uses conservative update.
+  #[wasm_bindgen(getter)]
+  pub fn conservative(&self) -> bool {
+    self.conservative
+  }
+
+  /// Returns the total of all increments since the last clear.
+  #[wasm_bindgen]
+  pub fn total_count(&self) -> u64 {
+    self.total
+  }
+
+  /// Returns the number of counters in each row.
+  #[wasm_bindgen]
+  pub fn width(&self) -> usize {
+    self.counters.width()
+  }
+
+  /// Returns the number of rows.
+  #[wasm_bindgen]
+  pub fn depth(&self) -> usize {
+    self.counters.depth()
+  }
+
+  /// Returns the size of the counter matrix in bytes.
+  #[wasm_bindgen]
+  pub fn memory_bytes(&self) -> usize {
+    self.counters.memory_bytes()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_counts_past_u32() {
+    let mut cms = CountMinSketch64::new(1000, 5).unwrap();
+    cms.increment_by("big", u64::from(u32::MAX));
+    cms.increment_by("big", 10);
+    cms.increment("small");
+    assert_eq!(cms.estimate("big"), u64::from(u32::MAX) + 10);
+    assert_eq!(cms.estimate("small"), 1);
+    assert_eq!(cms.total_count(), u64::from(u32::MAX) + 11);
+
+    let mut other = CountMinSketch64::new(1000, 5).unwrap();
+    other.increment_by("big", u64::from(u32::MAX));
+    cms.merge(&other).unwrap();
+    assert_eq!(cms.estimate("big"), 2 * u64::from(u32::MAX) + 10);
+    assert!(cms.merge(&CountMinSketch64::new(500, 5).unwrap()).is_err());
+    assert!(cms.merge(&CountMinSketch64::new(1000, 4).unwrap()).is_err());
+    assert!(CountMinSketch64::new(0, 5).is_err());
+
+    cms.clear();
+    assert_eq!(cms.estimate("big"), 0);
+    assert_eq!(cms.memory_bytes(), 1000 * 5 * 8);
+  }
+
+  #[test]
+  fn test_from_sketch_keeps_counts() {
+    let mut narrow = CountMinSketch::new(1000, 5);
+    for i in 0..500 {
+      for _ in 0..i % 7 {
+        narrow.increment(&format!("key_{}", i));
+      }
+    }
+    let mut wide = CountMinSketch64::from_sketch(&narrow);
+    assert_eq!(wide.total_count(), narrow.total_count() as u64);
+    for i in 0..500 {
+      let key = format!("key_{}", i);
+      assert_eq!(wide.estimate(&key), u64::from(narrow.estimate(&key)));
+    }
+    wide.increment("key_1");
+    assert_eq!(wide.estimate("key_1"), u64::from(narrow.estimate("key_1")) + 1);
+    assert!(!wide.conservative());
+
+    let mut narrow = CountMinSketch::with_conservative_update(1000, 5);
+    narrow.increment("key");
+    let mut wide = CountMinSketch64::from_sketch(&narrow);
+    assert!(wide.conservative());
+    wide.increment_by("key", 2);
+    assert_eq!(wide.estimate("key"), 3);
+  }
+}
@@ -0,0 +1,145 @@
+use wasm_bindgen::prelude::*;
+
+use crate::counter_matrix::CounterMatrix;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+
+/// A Count-Min Sketch that accumulates floating-point weights instead of counts, for metrics
+/// such as total latency or bytes per key. Weights must be non-negative, so an estimate never
+/// falls below a key's true sum.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct CountMinSketchF64 {
+  sums: CounterMatrix<f64>,
+  // Sum of all weights added since the last clear
+  total: f64,
+}
+
+#[wasm_bindgen]
+impl CountMinSketchF64 {
+  /// Creates a new sketch with the specified width and depth.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  #[wasm_bindgen(constructor)]
+  pub fn new(width: usize, depth: usize) -> Result<CountMinSketchF64, SketchError> {
+    Ok(CountMinSketchF64 { sums: CounterMatrix::new(width, depth)?, total: 0.0 })
+  }
+
+  /// Adds a weight to an item's sum.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to update
+  /// * `weight` - The amount to add, finite and non-negative
+  #[wasm_bindgen]
+  pub fn add(&mut self, item: &str, weight: f64) -> Result<(), SketchError> {
+    if !(weight >= 0.0 && weight.is_finite()) {
+      return Err(SketchError::InvalidParameter(
+        "weight must be finite and non-negative".to_string(),
+      ));
+    }
+    let hash = hash_str(item);
+    for index in self.sums.indices(hash).collect::<Vec<_>>() {
+      self.sums[index] += weight;
+    }
+    self.total += weight;
+    Ok(())
+  }
+
+  /// Returns the estimated sum of an item's weights.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn estimate(&self, item: &str) -> f64 {
+    let hash = hash_str(item);
+    self.sums.indices(hash).map(|index| self.sums[index]).fold(f64::INFINITY, f64::min)
+  }
+
+  /// Merges another sketch into this one. Both must share width, depth and hash seeds.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The sketch to merge
+  #[wasm_bindgen]
+  pub fn merge(&mut self, other: &CountMinSketchF64) -> Result<(), SketchError> {
+    self.sums.check_compatible(&other.sums, "CountMinSketchF64")?;
+    for (sum, other) in self.sums.zip_mut(&other.sums) {
+      *sum += other;
+    }
+    self.total += other.total;
+    Ok(())
+  }
+
+  /// Returns an independent copy of this sketch.
+  #[wasm_bindgen(js_name = clone)]
+  pub fn deep_clone(&self) -> CountMinSketchF64 {
+    self.clone()
+  }
+
+  /// Clears all sums in the sketch.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.sums.clear();
+    self.total = 0.0;
+  }
+
+  /// Returns the sum of all weights added since the last clear.
+  #[wasm_bindgen]
+  pub fn total_weight(&self) -> f64 {
+    self.total
+  }
+
+  /// Returns the number of counters in each row.
+  #[wasm_bindgen]
+  pub fn width(&self) -> usize {
+    self.sums.width()
+  }
+
+  /// Returns the number of rows.
+  #[wasm_bindgen]
+  pub fn depth(&self) -> usize {
+    self.sums.depth()
+  }
+
+  /// Returns the size of the counter matrix in bytes.
+  #[wasm_bindgen]
+  pub fn memory_bytes(&self) -> usize {
+    self.sums.memory_bytes()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_weighted_sums() {
+    let mut cms = CountMinSketchF64::new(1000, 5).unwrap();
+    assert_eq!(cms.estimate("checkout"), 0.0);
+    for latency in [12.5, 40.25, 7.0] {
+      cms.add("checkout", latency).unwrap();
+    }
+    cms.add("search", 3.5).unwrap();
+    assert_eq!(cms.estimate("checkout"), 59.75);
+    assert_eq!(cms.estimate("search"), 3.5);
+    assert_eq!(cms.total_weight(), 63.25);
+    assert!(cms.add("search", -1.0).is_err());
+    assert!(cms.add("search", f64::NAN).is_err());
+
+    let mut other = CountMinSketchF64::new(1000, 5).unwrap();
+    other.add("search", 0.5).unwrap();
+    cms.merge(&other).unwrap();
+    assert_eq!(cms.estimate("search"), 4.0);
+    assert!(cms.merge(&CountMinSketchF64::new(1000, 4).unwrap()).is_err());
+    assert!(CountMinSketchF64::new(0, 5).is_err());
+
+    cms.clear();
+    assert_eq!(cms.estimate("checkout"), 0.0);
+    assert_eq!(cms.total_weight(), 0.0);
+  }
+}
@@ -0,0 +1,116 @@
+//! The counter matrix behind the Count-Min Sketch variants with wider counters.
+
+use std::ops::{Index, IndexMut};
+
+use crate::count_min_sketch::column;
+use crate::error::SketchError;
+use crate::memory::try_zeroed;
+
+/// `depth` rows of `width` counters, flattened row by row, with one hash seed per row. Keys map
+/// to columns exactly as in `CountMinSketch`, so the variants agree on where a key lands.
+#[derive(Debug, Clone)]
+pub(crate) struct CounterMatrix<T> {
+  width: usize,
+  counters: Vec<T>,
+  hash_seeds: Vec<u64>,
+}
+
+impl<T: Copy + Default> CounterMatrix<T> {
+  /// Allocates a zeroed matrix with the default seeds, rejecting empty dimensions.
+  pub(crate) fn new(width: usize, depth: usize) -> Result<CounterMatrix<T>, SketchError> {
+    if width == 0 || depth == 0 {
+      return Err(SketchError::InvalidParameter("width and depth must be positive".to_string()));
+    }
+    let cells = width.checked_mul(depth).ok_or_else(|| {
+      SketchError::OutOfMemory(format!("{} by {} counters do not fit in memory", width, depth))
+    })?;
+    Ok(CounterMatrix {
+      width,
+      counters: try_zeroed(cells)?,
+      hash_seeds: (0..depth as u64).collect(),
+    })
+  }
+
+  /// Wraps existing row-major counters, e.g. those of a `CountMinSketch`.
+  pub(crate) fn from_parts(width: usize, counters: Vec<T>, hash_seeds: Vec<u64>) -> Self {
+    CounterMatrix { width, counters, hash_seeds }
+  }
+
+  /// Returns the flattened cell of a key hash in each row.
+  pub(crate) fn indices(&self, hash: u64) -> impl Iterator<Item = usize> + '_ {
+    let width = self.width;
+    self
+      .hash_seeds
+      .iter()
+      .enumerate()
+      .map(move |(row, &seed)| row * width + column(hash, seed, width))
+  }
+
+  /// Fails unless `other` has the same dimensions and seeds, so cells line up one to one.
+  pub(crate) fn check_compatible(&self, other: &Self, name: &str) -> Result<(), SketchError> {
+    if self.width != other.width || self.hash_seeds != other.hash_seeds {
+      return Err(SketchError::Incompatible(format!(
+        "{} instances must share width, depth and hash seeds",
+        name
+      )));
+    }
+    Ok(())
+  }
+
+  /// Pairs each counter with the one in the same cell of `other`.
+  pub(crate) fn zip_mut<'a>(
+    &'a mut self,
+    other: &'a Self,
+  ) -> impl Iterator<Item = (&'a mut T, &'a T)> {
+    self.counters.iter_mut().zip(&other.counters)
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.counters.fill(T::default());
+  }
+
+  pub(crate) fn width(&self) -> usize {
+    self.width
+  }
+
+  pub(crate) fn depth(&self) -> usize {
+    self.hash_seeds.len()
+  }
+
+  pub(crate) fn memory_bytes(&self) -> usize {
+    self.counters.len() * std::mem::size_of::<T>()
+  }
+}
+
+impl<T> Index<usize> for CounterMatrix<T> {
+  type Output = T;
+
+  fn index(&self, index: usize) -> &T {
+    &self.counters[index]
+  }
+}
+
+impl<T> IndexMut<usize> for CounterMatrix<T> {
+  fn index_mut(&mut self, index: usize) -> &mut T {
+    &mut self.counters[index]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rejects_empty_dimensions() {
+    assert!(matches!(CounterMatrix::<u64>::new(0, 4), Err(SketchError::InvalidParameter(_))));
+    assert!(CounterMatrix::<f64>::new(4, 0).is_err());
+    assert!(CounterMatrix::<u64>::new(usize::MAX, 2).is_err());
+
+    let matrix = CounterMatrix::<u64>::new(10, 3).unwrap();
+    let indices: Vec<usize> = matrix.indices(42).collect();
+    assert_eq!(indices.len(), 3);
+    for (row, index) in indices.into_iter().enumerate() {
+      assert_eq!(index / 10, row);
+    }
+  }
+}
@@ -0,0 +1,164 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::hash::hash_str;
+
+// Counters are 4 bits wide, two per byte
+const MAX_COUNT: u8 = 15;
+
+/// A Bloom filter that supports deletion, using a small counter per slot instead of a bit.
+///
+/// Counters are 4 bits wide and saturate at 15. A saturated counter is never decremented,
+/// since its true count is unknown; this keeps `remove` from ever causing false negatives at
+/// the cost of slots that stay set. Slot layout and probes match `BloomFilter` of the same
+/// capacity and false positive rate, with four times the memory.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct CountingBloomFilter {
+  // Packed 4-bit counters, slot `i` in the low nibble of byte `i / 2` when `i` is even
+  counters: Vec<u8>,
+  len: usize,
+  hash_count: usize,
+}
+
+#[wasm_bindgen]
+impl CountingBloomFilter {
+  /// Creates a new counting Bloom filter with the specified expected number of items and
+  /// false positive rate.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items present at once
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  #[wasm_bindgen(constructor)]
+  pub fn new(expected_items: usize, false_positive_rate: f64) -> CountingBloomFilter {
+    let (len, hash_count) = BloomFilter::geometry(expected_items, false_positive_rate);
+    CountingBloomFilter { counters: vec![0; len.div_ceil(2)], len, hash_count }
+  }
+
+  /// Inserts an item. Inserting an item twice requires removing it twice.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    for slot in self.slots(hash_str(item)) {
+      let count = self.get(slot);
+      if count < MAX_COUNT {
+        self.set(slot, count + 1);
+      }
+    }
+  }
+
+  /// Removes one insertion of an item. Returns false, changing nothing, if the item is
+  /// definitely not in the set.
+  ///
+  /// Removing an item that was never inserted but tests positive corrupts the filter,
+  /// possibly causing false negatives for other items.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to remove
+  #[wasm_bindgen]
+  pub fn remove(&mut self, item: &str) -> bool {
+    let slots = self.slots(hash_str(item));
+    if slots.iter().any(|&slot| self.get(slot) == 0) {
+      return false;
+    }
+    for slot in slots {
+      let count = self.get(slot);
+      if count < MAX_COUNT {
+        self.set(slot, count - 1);
+      }
+    }
+    true
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.slots(hash_str(item)).into_iter().all(|slot| self.get(slot) > 0)
+  }
+
+  /// Returns the number of counters stuck at their maximum, which `remove` can no longer
+  /// clear. A growing value means the filter holds far more items than it was sized for.
+  #[wasm_bindgen]
+  pub fn saturated_count(&self) -> usize {
+    (0..self.len).filter(|&slot| self.get(slot) == MAX_COUNT).count()
+  }
+
+  /// Returns the memory used by the counters, in bytes.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.counters.len()
+  }
+
+  /// Clears all counters in the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.counters.fill(0);
+  }
+}
+
+impl CountingBloomFilter {
+  fn slots(&self, hash: u64) -> Vec<usize> {
+    (0..self.hash_count).map(|i| BloomFilter::probe_index(hash, i, self.len)).collect()
+  }
+
+  fn get(&self, slot: usize) -> u8 {
+    (self.counters[slot / 2] >> ((slot % 2) * 4)) & 0x0f
+  }
+
+  fn set(&mut self, slot: usize, count: u8) {
+    let shift = (slot % 2) * 4;
+    let byte = &mut self.counters[slot / 2];
+    *byte = (*byte & !(0x0f << shift)) | (count << shift);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_insert_remove_contains() {
+    let mut filter = CountingBloomFilter::new(1000, 0.01);
+    filter.insert("session_1");
+    filter.insert("session_2");
+    filter.insert("session_2");
+    assert!(filter.contains("session_1") && filter.contains("session_2"));
+
+    assert!(filter.remove("session_1"));
+    assert!(!filter.contains("session_1"));
+    assert!(!filter.remove("session_1"));
+
+    assert!(filter.remove("session_2"));
+    assert!(filter.contains("session_2"));
+    assert!(filter.remove("session_2"));
+    assert!(!filter.contains("session_2"));
+    assert_eq!(filter.counters.iter().filter(|&&byte| byte != 0).count(), 0);
+  }
+
+  #[test]
+  fn test_saturated_counters_stay_set() {
+    let mut filter = CountingBloomFilter::new(100, 0.01);
+    for _ in 0..20 {
+      filter.insert("hot");
+    }
+    assert_eq!(filter.saturated_count(), filter.hash_count);
+    for _ in 0..20 {
+      assert!(filter.remove("hot"));
+    }
+    assert!(filter.contains("hot"));
+
+    filter.clear();
+    assert!(!filter.contains("hot"));
+    assert_eq!(filter.memory_bytes(), filter.len.div_ceil(2));
+  }
+}
@@ -0,0 +1,131 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hyperloglog::HyperLogLog;
+
+/// A HyperLogLog variant that supports removals, for distinct counts of sets that shrink as
+/// well as grow (e.g. currently active sessions).
+///
+/// Each register keeps a counter per rank instead of only the maximum rank; an add increments
+/// the counter of its rank and a remove decrements it, and the register's value is the highest
+/// rank with a non-zero counter. Estimates match a plain HyperLogLog holding the items that were
+/// added more often than removed, at the cost of one counter per rank per register.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct CountingHyperLogLog {
+  p: u8,
+  // Counters indexed by register * ranks + (rank - 1)
+  counters: Vec<u32>,
+  ranks: usize,
+}
+
+#[wasm_bindgen]
+impl CountingHyperLogLog {
+  /// Creates a new counting HyperLogLog.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The precision parameter (between 4 and 16); uses about
+  ///   `2^precision * (33 - precision) * 4` bytes, so it defaults to 12
+  #[wasm_bindgen(constructor)]
+  pub fn new(precision: Option<u8>) -> Result<CountingHyperLogLog, SketchError> {
+    let p = precision.unwrap_or(12);
+    if !(4..=16).contains(&p) {
+      return Err(SketchError::InvalidParameter("precision must be between 4 and 16".to_string()));
+    }
+    let ranks = usize::from(32 - p + 1);
+    Ok(CountingHyperLogLog { p, counters: vec![0; (1 << p) * ranks], ranks })
+  }
+
+  /// Adds an item.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The item to add
+  #[wasm_bindgen]
+  pub fn add(&mut self, value: &str) {
+    let cell = self.cell(value);
+    self.counters[cell] = self.counters[cell].saturating_add(1);
+  }
+
+  /// Removes one earlier add of an item. Returns false, leaving the sketch unchanged, if the
+  /// item cannot have been added. Removing an item that was never added but shares its counter
+  /// with one that was corrupts the estimate, so only remove items known to have been added.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The item to remove
+  #[wasm_bindgen]
+  pub fn remove(&mut self, value: &str) -> bool {
+    let cell = self.cell(value);
+    match self.counters[cell].checked_sub(1) {
+      Some(count) => {
+        self.counters[cell] = count;
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns the estimated number of distinct items currently in the set.
+  #[wasm_bindgen]
+  pub fn count(&self) -> f64 {
+    let mut snapshot = HyperLogLog::new(Some(self.p)).expect("precision was validated");
+    for (index, ranks) in self.counters.chunks(self.ranks).enumerate() {
+      if let Some(top) = ranks.iter().rposition(|&count| count > 0) {
+        snapshot.raise_register(index, top as u8 + 1);
+      }
+    }
+    snapshot.count()
+  }
+
+  /// Removes all items.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.counters.fill(0);
+  }
+}
+
+impl CountingHyperLogLog {
+  fn cell(&self, value: &str) -> usize {
+    let (index, rank) = HyperLogLog::slot(HyperLogLog::hash(value.as_bytes()), self.p);
+    index * self.ranks + usize::from(rank) - 1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_add_and_remove() {
+    let mut sessions = CountingHyperLogLog::new(Some(12)).unwrap();
+    let mut reference = HyperLogLog::new(Some(12)).unwrap();
+    for i in 0..2000 {
+      sessions.add(&format!("session_{}", i));
+    }
+    for i in 0..1500 {
+      assert!(sessions.remove(&format!("session_{}", i)));
+    }
+    for i in 1500..2000 {
+      reference.add(&format!("session_{}", i));
+    }
+
+    // Identical to a plain HyperLogLog of the remaining items
+    assert_eq!(sessions.count(), reference.count());
+    assert!((450.0..=550.0).contains(&sessions.count()));
+  }
+
+  #[test]
+  fn test_remove_unknown_item() {
+    let mut sessions = CountingHyperLogLog::new(None).unwrap();
+    assert!(!sessions.remove("never_added"));
+    sessions.add("a");
+    sessions.add("a");
+    assert!(sessions.remove("a"));
+    assert_eq!(sessions.count(), 1.0);
+    assert!(sessions.remove("a"));
+    assert_eq!(sessions.count(), 0.0);
+    assert!(CountingHyperLogLog::new(Some(17)).is_err());
+  }
+}
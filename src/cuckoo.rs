@@ -0,0 +1,271 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+use crate::runtime;
+
+const SLOTS_PER_BUCKET: usize = 4;
+// Load factor the table is sized for; four-way buckets fill to about 95% before inserts fail
+const TARGET_LOAD: f64 = 0.95;
+const MAX_KICKS: usize = 500;
+// Seed for hashing a fingerprint to the offset between an item's two buckets
+const ALT_SEED: u64 = 0x4355_434b;
+// Marks an empty slot; fingerprints are never zero
+const EMPTY: u16 = 0;
+
+/// A cuckoo filter (Fan et al., 2014): approximate set membership with deletion, storing a
+/// 16-bit fingerprint per item in one of two candidate buckets of four slots.
+///
+/// At its target load this uses about 17 bits per item for a false positive rate around
+/// 0.01%, several times less than a counting Bloom filter. Inserts can fail once the table is
+/// nearly full; size it for the largest set it will hold. Deleting an item that was never
+/// inserted may remove a colliding item instead.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct CuckooFilter {
+  // Bucket `b` occupies slots `b * 4 .. b * 4 + 4`
+  slots: Vec<u16>,
+  bucket_mask: usize,
+  items: usize,
+  // A fingerprint evicted by a failed insert, kept so that no inserted item is lost
+  victim: Option<(usize, u16)>,
+  rng: ChaCha8Rng,
+}
+
+#[wasm_bindgen]
+impl CuckooFilter {
+  /// Creates a new cuckoo filter able to hold at least `capacity` items.
+  ///
+  /// # Arguments
+  ///
+  /// * `capacity` - The largest number of items the filter will hold at once
+  #[wasm_bindgen(constructor)]
+  pub fn new(capacity: usize) -> Result<CuckooFilter, SketchError> {
+    if capacity == 0 {
+      return Err(SketchError::InvalidParameter("capacity must be positive".to_string()));
+    }
+    let buckets = ((capacity as f64 / TARGET_LOAD) / SLOTS_PER_BUCKET as f64).ceil() as usize;
+    // A power of two lets the alternate bucket be found by XOR from either side
+    let buckets = buckets.max(1).next_power_of_two();
+    Ok(CuckooFilter {
+      slots: vec![EMPTY; buckets * SLOTS_PER_BUCKET],
+      bucket_mask: buckets - 1,
+      items: 0,
+      victim: None,
+      rng: runtime::new_rng(),
+    })
+  }
+
+  /// Inserts an item. Returns false if the filter is too full to take it, in which case
+  /// further inserts fail until items are deleted. Inserting an item twice stores it twice.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) -> bool {
+    if self.victim.is_some() {
+      return false;
+    }
+    let (fingerprint, first) = self.locate(item);
+    let second = self.alternate(first, fingerprint);
+    if self.place(first, fingerprint) || self.place(second, fingerprint) {
+      self.items += 1;
+      return true;
+    }
+
+    // Evict random residents along a path until one finds a free slot
+    let mut bucket = if self.rng.gen() { first } else { second };
+    let mut fingerprint = fingerprint;
+    for _ in 0..MAX_KICKS {
+      let slot = bucket * SLOTS_PER_BUCKET + self.rng.gen_range(0..SLOTS_PER_BUCKET);
+      std::mem::swap(&mut fingerprint, &mut self.slots[slot]);
+      bucket = self.alternate(bucket, fingerprint);
+      if self.place(bucket, fingerprint) {
+        self.items += 1;
+        return true;
+      }
+    }
+    // The new item is stored; the last evicted fingerprint waits aside
+    self.items += 1;
+    self.victim = Some((bucket, fingerprint));
+    true
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    let (fingerprint, first) = self.locate(item);
+    let second = self.alternate(first, fingerprint);
+    self.bucket(first).contains(&fingerprint)
+      || self.bucket(second).contains(&fingerprint)
+      || self.victim.is_some_and(|(bucket, victim)| {
+        victim == fingerprint && (bucket == first || bucket == second)
+      })
+  }
+
+  /// Deletes one copy of an item. Returns false if the item was not found.
+  /// Only delete items that were inserted, or a colliding item may be removed instead.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to delete
+  #[wasm_bindgen]
+  pub fn delete(&mut self, item: &str) -> bool {
+    let (fingerprint, first) = self.locate(item);
+    let second = self.alternate(first, fingerprint);
+    let removed = if self.victim.is_some_and(|(bucket, victim)| {
+      victim == fingerprint && (bucket == first || bucket == second)
+    }) {
+      self.victim = None;
+      true
+    } else {
+      self.remove(first, fingerprint) || self.remove(second, fingerprint)
+    };
+    if !removed {
+      return false;
+    }
+    self.items -= 1;
+
+    // A freed slot may make room for the fingerprint waiting aside
+    if let Some((bucket, victim)) = self.victim {
+      let alternate = self.alternate(bucket, victim);
+      if self.place(bucket, victim) || self.place(alternate, victim) {
+        self.victim = None;
+      }
+    }
+    true
+  }
+
+  /// Returns the fraction of slots in use, from 0 to 1. Inserts start failing near 0.95.
+  #[wasm_bindgen]
+  pub fn load_factor(&self) -> f64 {
+    self.items as f64 / self.slots.len() as f64
+  }
+
+  /// Returns the number of items stored.
+  #[wasm_bindgen(getter)]
+  pub fn len(&self) -> usize {
+    self.items
+  }
+
+  /// Returns true if no items are stored.
+  #[wasm_bindgen]
+  pub fn is_empty(&self) -> bool {
+    self.items == 0
+  }
+
+  /// Returns the number of fingerprint slots.
+  #[wasm_bindgen(getter)]
+  pub fn slot_count(&self) -> usize {
+    self.slots.len()
+  }
+
+  /// Returns the number of bytes used by the fingerprint table.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.slots.len() * std::mem::size_of::<u16>()
+  }
+
+  /// Removes all items.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.slots.fill(EMPTY);
+    self.items = 0;
+    self.victim = None;
+  }
+}
+
+impl CuckooFilter {
+  // Returns an item's fingerprint and first bucket, taken from independent halves of its hash
+  fn locate(&self, item: &str) -> (u16, usize) {
+    let hash = hash_str(item);
+    let fingerprint = ((hash >> 48) as u16).max(1);
+    (fingerprint, hash as usize & self.bucket_mask)
+  }
+
+  fn alternate(&self, bucket: usize, fingerprint: u16) -> usize {
+    (bucket ^ mix64(u64::from(fingerprint), ALT_SEED) as usize) & self.bucket_mask
+  }
+
+  fn bucket(&self, bucket: usize) -> &[u16] {
+    &self.slots[bucket * SLOTS_PER_BUCKET..(bucket + 1) * SLOTS_PER_BUCKET]
+  }
+
+  // Stores a fingerprint in a free slot of the bucket, returning whether there was one
+  fn place(&mut self, bucket: usize, fingerprint: u16) -> bool {
+    let start = bucket * SLOTS_PER_BUCKET;
+    match self.slots[start..start + SLOTS_PER_BUCKET].iter().position(|&slot| slot == EMPTY) {
+      Some(offset) => {
+        self.slots[start + offset] = fingerprint;
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn remove(&mut self, bucket: usize, fingerprint: u16) -> bool {
+    let start = bucket * SLOTS_PER_BUCKET;
+    match self.slots[start..start + SLOTS_PER_BUCKET].iter().position(|&slot| slot == fingerprint) {
+      Some(offset) => {
+        self.slots[start + offset] = EMPTY;
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_insert_contains_delete() {
+    let mut filter = CuckooFilter::new(1000).unwrap();
+    for i in 0..1000 {
+      assert!(filter.insert(&format!("item_{}", i)));
+    }
+    for i in 0..1000 {
+      assert!(filter.contains(&format!("item_{}", i)));
+    }
+    assert_eq!(filter.len(), 1000);
+
+    for i in 0..500 {
+      assert!(filter.delete(&format!("item_{}", i)));
+    }
+    for i in 500..1000 {
+      assert!(filter.contains(&format!("item_{}", i)));
+    }
+    let remaining = (0..500).filter(|i| filter.contains(&format!("item_{}", i))).count();
+    assert!(remaining < 5);
+    assert!(!filter.delete("never"));
+    assert_eq!(filter.len(), 500);
+  }
+
+  #[test]
+  fn test_fills_without_losing_items() {
+    let mut filter = CuckooFilter::new(1000).unwrap();
+    let mut inserted = 0;
+    while filter.insert(&format!("item_{}", inserted)) {
+      inserted += 1;
+    }
+    assert!(filter.load_factor() > 0.9, "load {}", filter.load_factor());
+    for i in 0..inserted {
+      assert!(filter.contains(&format!("item_{}", i)));
+    }
+
+    assert!(filter.delete("item_0"));
+    assert!(filter.contains(&format!("item_{}", inserted - 1)));
+    filter.clear();
+    assert!(filter.is_empty());
+    assert!(CuckooFilter::new(0).is_err());
+  }
+}
@@ -0,0 +1,188 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+
+/// A deletable Bloom filter (Rothenberg et al., 2010): a Bloom filter that can remove items
+/// with one extra bit per region instead of a counter per slot.
+///
+/// The bit array is split into regions, and a region is marked as collided as soon as an
+/// insert sets a bit that was already set. `remove` clears an item's bits only in collision-free
+/// regions, so it never causes false negatives, and it succeeds when at least one of the item's
+/// bits could be cleared. Removal gets less likely as the filter fills up; `deletable_fraction`
+/// tracks how many regions still allow it.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct DeletableBloomFilter {
+  // Bit array packed into 64-bit words, least significant bit first
+  bits: Vec<u64>,
+  len: usize,
+  hash_count: usize,
+  // One bit per region, set once two inserts have shared a bit in it
+  collisions: Vec<u64>,
+  regions: usize,
+  region_len: usize,
+}
+
+#[wasm_bindgen]
+impl DeletableBloomFilter {
+  /// Creates a new deletable Bloom filter with the specified expected number of items and
+  /// false positive rate.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items present at once
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  /// * `regions` - The number of regions; more regions make removal succeed more often at the
+  ///   cost of one bit each. Defaults to one region per 32 bits
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    expected_items: usize,
+    false_positive_rate: f64,
+    regions: Option<usize>,
+  ) -> Result<DeletableBloomFilter, SketchError> {
+    let (len, hash_count) = BloomFilter::geometry(expected_items, false_positive_rate);
+    let regions = regions.unwrap_or_else(|| len.div_ceil(32));
+    if regions == 0 || regions > len {
+      return Err(SketchError::InvalidParameter(format!(
+        "regions must be between 1 and the filter's {} bits",
+        len
+      )));
+    }
+    Ok(DeletableBloomFilter {
+      bits: vec![0; len.div_ceil(64)],
+      len,
+      hash_count,
+      collisions: vec![0; regions.div_ceil(64)],
+      regions,
+      region_len: len.div_ceil(regions),
+    })
+  }
+
+  /// Inserts an item, marking the region of every bit it shares with an earlier insert.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    for position in self.positions(hash_str(item)) {
+      if get(&self.bits, position) {
+        set(&mut self.collisions, position / self.region_len, true);
+      } else {
+        set(&mut self.bits, position, true);
+      }
+    }
+  }
+
+  /// Removes an item by clearing its bits in collision-free regions. Returns true if the
+  /// item is no longer found; false, changing nothing, if it was not found or all of its bits
+  /// lie in collided regions.
+  ///
+  /// Removing an item that was never inserted but tests positive can cause false negatives
+  /// for other items.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to remove
+  #[wasm_bindgen]
+  pub fn remove(&mut self, item: &str) -> bool {
+    let positions = self.positions(hash_str(item));
+    if !positions.iter().all(|&position| get(&self.bits, position)) {
+      return false;
+    }
+    let deletable: Vec<usize> = positions
+      .into_iter()
+      .filter(|&position| !get(&self.collisions, position / self.region_len))
+      .collect();
+    for &position in &deletable {
+      set(&mut self.bits, position, false);
+    }
+    !deletable.is_empty()
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.positions(hash_str(item)).into_iter().all(|position| get(&self.bits, position))
+  }
+
+  /// Returns the fraction of regions with no collision yet, from 0 to 1. An item can be
+  /// removed if any of its bits falls in one of them.
+  #[wasm_bindgen]
+  pub fn deletable_fraction(&self) -> f64 {
+    let collided: u32 = self.collisions.iter().map(|word| word.count_ones()).sum();
+    1.0 - f64::from(collided) / self.regions as f64
+  }
+
+  /// Returns the number of regions.
+  #[wasm_bindgen(getter)]
+  pub fn regions(&self) -> usize {
+    self.regions
+  }
+
+  /// Returns the memory used by the bit array and collision bitmap, in bytes.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    (self.bits.len() + self.collisions.len()) * 8
+  }
+
+  /// Removes all items and collision marks from the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.bits.fill(0);
+    self.collisions.fill(0);
+  }
+}
+
+impl DeletableBloomFilter {
+  fn positions(&self, hash: u64) -> Vec<usize> {
+    (0..self.hash_count).map(|i| BloomFilter::probe_index(hash, i, self.len)).collect()
+  }
+}
+
+fn get(words: &[u64], index: usize) -> bool {
+  words[index / 64] & (1 << (index % 64)) != 0
+}
+
+fn set(words: &mut [u64], index: usize, value: bool) {
+  if value {
+    words[index / 64] |= 1 << (index % 64);
+  } else {
+    words[index / 64] &= !(1 << (index % 64));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_remove_never_causes_false_negatives() {
+    let mut filter = DeletableBloomFilter::new(1000, 0.01, None).unwrap();
+    for i in 0..500 {
+      filter.insert(&format!("key_{}", i));
+    }
+    assert!(filter.deletable_fraction() > 0.0 && filter.deletable_fraction() < 1.0);
+
+    let removed: Vec<usize> = (0..250).filter(|i| filter.remove(&format!("key_{}", i))).collect();
+    assert!(removed.len() > 125, "{} removed", removed.len());
+    for i in &removed {
+      assert!(!filter.contains(&format!("key_{}", i)));
+    }
+    for i in 250..500 {
+      assert!(filter.contains(&format!("key_{}", i)));
+    }
+    assert!(!filter.remove("missing"));
+
+    filter.clear();
+    assert_eq!(filter.deletable_fraction(), 1.0);
+    assert!(DeletableBloomFilter::new(100, 0.01, Some(0)).is_err());
+  }
+}
@@ -0,0 +1,128 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+
+/// The items a summary currently retains with their counts or weights, as two parallel
+/// arrays ordered by descending weight. Every structure that keeps identifiable items exports
+/// this same shape, so one table or CSV renderer works for all of them.
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct Entries {
+  items: Vec<String>,
+  weights: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl Entries {
+  /// Returns the retained items, heaviest first.
+  #[wasm_bindgen(getter)]
+  pub fn items(&self) -> Vec<String> {
+    self.items.clone()
+  }
+
+  /// Returns the weight of each item, aligned with `items`.
+  #[wasm_bindgen(getter)]
+  pub fn weights(&self) -> Vec<f64> {
+    self.weights.clone()
+  }
+
+  /// Returns the number of entries.
+  #[wasm_bindgen(getter)]
+  pub fn len(&self) -> usize {
+    self.items.len()
+  }
+
+  /// Returns true if no items are retained.
+  #[wasm_bindgen]
+  pub fn is_empty(&self) -> bool {
+    self.items.is_empty()
+  }
+
+  /// Formats the entries as CSV with an `item,weight` header row, heaviest first.
+  ///
+  /// # Arguments
+  ///
+  /// * `delimiter` - The field separator, e.g. `"\t"` for TSV; defaults to a comma
+  #[wasm_bindgen]
+  pub fn to_csv(&self, delimiter: Option<char>) -> Result<String, SketchError> {
+    write_csv(["item", "weight"], self.iter(), delimiter)
+  }
+}
+
+impl Entries {
+  /// Iterates over the entries, heaviest first.
+  pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+    self.items.iter().map(String::as_str).zip(self.weights.iter().copied())
+  }
+}
+
+/// Formats two-column rows as RFC 4180 CSV under the given header. Items containing the
+/// delimiter, a quote or a line break are quoted, with quotes doubled.
+pub(crate) fn write_csv<'a>(
+  header: [&str; 2],
+  rows: impl IntoIterator<Item = (&'a str, f64)>,
+  delimiter: Option<char>,
+) -> Result<String, SketchError> {
+  let delimiter = delimiter.unwrap_or(',');
+  if matches!(delimiter, '"' | '\r' | '\n') {
+    return Err(SketchError::InvalidParameter(
+      "delimiter cannot be a quote or line break".to_string(),
+    ));
+  }
+
+  let mut csv = format!("{}{}{}\r\n", header[0], delimiter, header[1]);
+  for (item, weight) in rows {
+    if item.contains([delimiter, '"', '\r', '\n']) {
+      csv.push('"');
+      csv.push_str(&item.replace('"', "\"\""));
+      csv.push('"');
+    } else {
+      csv.push_str(item);
+    }
+    csv.push(delimiter);
+    csv.push_str(&weight.to_string());
+    csv.push_str("\r\n");
+  }
+  Ok(csv)
+}
+
+impl FromIterator<(String, f64)> for Entries {
+  fn from_iter<I: IntoIterator<Item = (String, f64)>>(iter: I) -> Self {
+    let mut pairs: Vec<(String, f64)> = iter.into_iter().collect();
+    // Ties break by item so exports are deterministic
+    pairs.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let (items, weights) = pairs.into_iter().unzip();
+    Entries { items, weights }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_sorted_by_weight_then_item() {
+    let entries: Entries = [("b".to_string(), 2.0), ("c".to_string(), 5.0), ("a".to_string(), 2.0)]
+      .into_iter()
+      .collect();
+    assert_eq!(entries.items(), ["c", "a", "b"]);
+    assert_eq!(entries.weights(), [5.0, 2.0, 2.0]);
+    assert_eq!(entries.len(), 3);
+    assert!(Entries::default().is_empty());
+  }
+
+  #[test]
+  fn test_to_csv() {
+    let entries: Entries =
+      [("plain".to_string(), 3.0), ("a, \"quoted\"".to_string(), 0.5)].into_iter().collect();
+    assert_eq!(
+      entries.to_csv(None).unwrap(),
+      "item,weight\r\nplain,3\r\n\"a, \"\"quoted\"\"\",0.5\r\n"
+    );
+    assert_eq!(
+      entries.to_csv(Some('\t')).unwrap(),
+      "item\tweight\r\nplain\t3\r\n\"a, \"\"quoted\"\"\"\t0.5\r\n"
+    );
+    assert!(entries.to_csv(Some('"')).is_err());
+  }
+}
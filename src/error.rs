@@ -0,0 +1,25 @@
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+/// Errors returned by sketch operations.
+#[derive(Debug, Error)]
+pub enum SketchError {
+  /// Two sketches cannot be combined because their parameters differ.
+  #[error("Incompatible sketches: {0}")]
+  Incompatible(String),
+  /// A parameter is outside its valid range.
+  #[error("Invalid parameter: {0}")]
+  InvalidParameter(String),
+  /// A serialized buffer could not be decoded.
+  #[error("Malformed input: {0}")]
+  Malformed(String),
+  /// Memory for a sketch could not be allocated.
+  #[error("Out of memory: {0}")]
+  OutOfMemory(String),
+}
+
+impl From<SketchError> for JsValue {
+  fn from(err: SketchError) -> JsValue {
+    JsValue::from_str(&err.to_string())
+  }
+}
@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+use crate::runtime;
+
+/// A Bloom filter whose memberships lapse after a time-to-live, e.g. "seen within the last
+/// hour" for client-side deduplication.
+///
+/// Time is split into generations of `ttl / (generations - 1)`, each with its own filter.
+/// Inserts go into the newest generation, lookups check every live one, and the oldest is
+/// dropped as a new one starts. An item is therefore found for at least `ttl` after its last
+/// insert and forgotten at most one generation later.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ExpiringBloomFilter {
+  // (start time in ms, filter), oldest first; empty until the first insert
+  generations: VecDeque<(f64, BloomFilter)>,
+  generation_count: usize,
+  generation_ms: f64,
+  items_per_generation: usize,
+  // False positive rate of each generation, so the union stays under the target
+  generation_rate: f64,
+}
+
+#[wasm_bindgen]
+impl ExpiringBloomFilter {
+  /// Creates a new expiring Bloom filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of distinct items inserted within one TTL
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  /// * `ttl_ms` - How long an insert is remembered, in milliseconds
+  /// * `generations` - The number of generations (at least 2), defaulting to 4; more
+  ///   generations expire items closer to the TTL at the cost of slower lookups
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    expected_items: usize,
+    false_positive_rate: f64,
+    ttl_ms: f64,
+    generations: Option<usize>,
+  ) -> Result<ExpiringBloomFilter, SketchError> {
+    let generation_count = generations.unwrap_or(4);
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+      return Err(SketchError::InvalidParameter(
+        "false_positive_rate must be between 0 and 1".to_string(),
+      ));
+    }
+    if !(ttl_ms > 0.0 && ttl_ms.is_finite()) {
+      return Err(SketchError::InvalidParameter("ttl_ms must be positive".to_string()));
+    }
+    if generation_count < 2 {
+      return Err(SketchError::InvalidParameter("generations must be at least 2".to_string()));
+    }
+    let live_spans = generation_count - 1;
+    Ok(ExpiringBloomFilter {
+      generations: VecDeque::with_capacity(generation_count),
+      generation_count,
+      generation_ms: ttl_ms / live_spans as f64,
+      items_per_generation: expected_items.div_ceil(live_spans).max(1),
+      generation_rate: false_positive_rate / generation_count as f64,
+    })
+  }
+
+  /// Inserts an item seen at the given time, starting new generations as time passes.
+  /// Timestamps are expected to be non-decreasing; an earlier timestamp is treated as the
+  /// latest one seen.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  /// * `timestamp_ms` - When the item was seen; defaults to the current time
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str, timestamp_ms: Option<f64>) {
+    self.advance(timestamp_ms.unwrap_or_else(runtime::now_ms));
+    let (_, newest) = self.generations.back_mut().expect("advance starts a generation");
+    newest.insert_hash(hash_str(item));
+  }
+
+  /// Checks if an item was probably inserted within the TTL before the given time.
+  /// Returns false if it definitely was not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  /// * `now_ms` - The time to check at; defaults to the current time
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str, now_ms: Option<f64>) -> bool {
+    let now_ms = now_ms.unwrap_or_else(runtime::now_ms);
+    let hash = hash_str(item);
+    let lifetime = self.generation_ms * self.generation_count as f64;
+    self
+      .generations
+      .iter()
+      .any(|(start, filter)| start + lifetime > now_ms && filter.contains_hash(hash))
+  }
+
+  /// Returns the number of generations currently held.
+  #[wasm_bindgen(getter)]
+  pub fn live_generations(&self) -> usize {
+    self.generations.len()
+  }
+
+  /// Returns the number of bytes used by the bit arrays of all generations.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.generations.iter().map(|(_, filter)| filter.memory_bytes()).sum()
+  }
+
+  /// Forgets every item.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.generations.clear();
+  }
+}
+
+impl ExpiringBloomFilter {
+  // Starts the generations due by `now_ms`, keeping their starts aligned to the first one
+  fn advance(&mut self, now_ms: f64) {
+    let Some(&(newest_start, _)) = self.generations.back() else {
+      self.start_generation(now_ms);
+      return;
+    };
+    let due = ((now_ms - newest_start) / self.generation_ms).floor();
+    if due < 1.0 {
+      return;
+    }
+    if due >= self.generation_count as f64 {
+      // Every held generation has expired
+      self.generations.clear();
+      self.start_generation(newest_start + due * self.generation_ms);
+      return;
+    }
+    for step in 1..=due as usize {
+      self.start_generation(newest_start + step as f64 * self.generation_ms);
+    }
+  }
+
+  fn start_generation(&mut self, start_ms: f64) {
+    if self.generations.len() == self.generation_count {
+      self.generations.pop_front();
+    }
+    let filter = BloomFilter::new(self.items_per_generation, self.generation_rate);
+    self.generations.push_back((start_ms, filter));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MINUTE: f64 = 60_000.0;
+  const START: f64 = 1_700_000_000_000.0;
+
+  #[test]
+  fn test_items_expire_after_ttl() {
+    let mut filter = ExpiringBloomFilter::new(1000, 0.01, 60.0 * MINUTE, None).unwrap();
+    filter.insert("request_1", Some(START));
+    assert!(filter.contains("request_1", Some(START)));
+    assert!(filter.contains("request_1", Some(START + 59.0 * MINUTE)));
+    assert!(!filter.contains("request_2", Some(START)));
+
+    // Forgotten at most one generation (20 minutes) after the TTL
+    assert!(!filter.contains("request_1", Some(START + 80.0 * MINUTE)));
+
+    // Re-inserting refreshes the TTL
+    filter.insert("request_1", Some(START + 50.0 * MINUTE));
+    filter.insert("request_3", Some(START + 100.0 * MINUTE));
+    assert!(filter.contains("request_1", Some(START + 100.0 * MINUTE)));
+    assert_eq!(filter.live_generations(), 4);
+  }
+
+  #[test]
+  fn test_long_gap_drops_everything() {
+    let mut filter = ExpiringBloomFilter::new(100, 0.01, MINUTE, Some(2)).unwrap();
+    filter.insert("old", Some(START));
+    filter.insert("new", Some(START + 10.0 * MINUTE));
+    assert_eq!(filter.live_generations(), 1);
+    assert!(!filter.contains("old", Some(START + 10.0 * MINUTE)));
+    assert!(filter.contains("new", Some(START + 10.5 * MINUTE)));
+
+    assert!(ExpiringBloomFilter::new(100, 0.01, MINUTE, Some(1)).is_err());
+    assert!(ExpiringBloomFilter::new(100, 0.01, 0.0, None).is_err());
+  }
+}
@@ -0,0 +1,118 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::count_min_sketch::CountMinSketch;
+use crate::hash::hash_str;
+
+/// A CountMinSketch whose increments are gated by a BloomFilter, checked inside wasm so each
+/// increment crosses the JS boundary once.
+///
+/// In exclude mode, keys the filter reports as present (e.g. known bots) are not counted; in
+/// include mode only those keys are counted (an allowlist). Because the filter may report false
+/// positives, exclude mode can drop a few legitimate keys and include mode can admit a few
+/// unlisted ones, at the filter's false-positive rate.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct FilteredCounter {
+  filter: BloomFilter,
+  counts: CountMinSketch,
+  exclude: bool,
+}
+
+#[wasm_bindgen]
+impl FilteredCounter {
+  /// Creates a new filtered counter. The filter is moved into the counter; further keys can be
+  /// added to it with `insert_filter_key`.
+  ///
+  /// # Arguments
+  ///
+  /// * `filter` - The filter that gates increments
+  /// * `exclude` - True to skip keys in the filter, false to count only keys in the filter
+  /// * `width` - The number of counters in each row of the sketch
+  /// * `depth` - The number of rows in the sketch
+  #[wasm_bindgen(constructor)]
+  pub fn new(filter: BloomFilter, exclude: bool, width: usize, depth: usize) -> FilteredCounter {
+    FilteredCounter { filter, counts: CountMinSketch::new(width, depth), exclude }
+  }
+
+  /// Increments a key's count if it passes the filter.
+  /// Returns true if the increment was applied.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to increment
+  #[wasm_bindgen]
+  pub fn increment(&mut self, item: &str) -> bool {
+    if self.filter.contains(item) == self.exclude {
+      return false;
+    }
+    self.counts.increment_hash(hash_str(item));
+    true
+  }
+
+  /// Returns the estimated count of a key. Keys that never passed the filter estimate 0,
+  /// apart from ordinary CountMinSketch overestimation.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn estimate(&self, item: &str) -> u32 {
+    self.counts.estimate_hash(hash_str(item))
+  }
+
+  /// Adds a key to the gating filter, e.g. a newly detected bot.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The key to add to the filter
+  #[wasm_bindgen]
+  pub fn insert_filter_key(&mut self, item: &str) {
+    self.filter.insert(item);
+  }
+
+  /// Returns the number of increments that passed the filter.
+  #[wasm_bindgen]
+  pub fn total_count(&self) -> f64 {
+    self.counts.total_count()
+  }
+
+  /// Clears the counts, keeping the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.counts.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_exclude_mode() {
+    let mut bots = BloomFilter::new(100, 0.01);
+    bots.insert("crawler");
+    let mut counter = FilteredCounter::new(bots, true, 100, 4);
+
+    assert!(counter.increment("visitor"));
+    assert!(!counter.increment("crawler"));
+    assert_eq!(counter.estimate("visitor"), 1);
+    assert_eq!(counter.estimate("crawler"), 0);
+
+    counter.insert_filter_key("visitor");
+    assert!(!counter.increment("visitor"));
+    assert_eq!(counter.total_count(), 1.0);
+  }
+
+  #[test]
+  fn test_include_mode() {
+    let mut allowlist = BloomFilter::new(100, 0.01);
+    allowlist.insert("/checkout");
+    let mut counter = FilteredCounter::new(allowlist, false, 100, 4);
+
+    assert!(counter.increment("/checkout"));
+    assert!(!counter.increment("/random"));
+    assert_eq!(counter.estimate("/checkout"), 1);
+    assert_eq!(counter.estimate("/random"), 0);
+  }
+}
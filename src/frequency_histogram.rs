@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+/// A log-bucketed histogram of per-key frequencies with bounded relative error, in the style
+/// of DDSketch: a frequency `f` falls into bucket `ceil(log_gamma(f))`, and every value in a
+/// bucket is within `relative_accuracy` of the bucket's representative value.
+#[derive(Debug, Clone)]
+pub(crate) struct FrequencyHistogram {
+  ln_gamma: f64,
+  // Number of keys per bucket index, for frequencies of at least 1
+  buckets: BTreeMap<i32, u64>,
+  keys: u64,
+}
+
+impl FrequencyHistogram {
+  /// Creates an empty histogram; `relative_accuracy` must be in (0, 1).
+  pub(crate) fn new(relative_accuracy: f64) -> FrequencyHistogram {
+    let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+    FrequencyHistogram { ln_gamma: gamma.ln(), buckets: BTreeMap::new(), keys: 0 }
+  }
+
+  /// Moves one key from frequency `from` to frequency `to`; a frequency of 0 means untracked.
+  pub(crate) fn record_change(&mut self, from: u32, to: u32) {
+    if from > 0 {
+      let index = self.index(from);
+      if let Some(count) = self.buckets.get_mut(&index) {
+        *count -= 1;
+        if *count == 0 {
+          self.buckets.remove(&index);
+        }
+        self.keys -= 1;
+      }
+    }
+    if to > 0 {
+      *self.buckets.entry(self.index(to)).or_insert(0) += 1;
+      self.keys += 1;
+    }
+  }
+
+  /// Returns the approximate frequency at quantile `q` in [0, 1], or 0 if no key is tracked.
+  pub(crate) fn quantile(&self, q: f64) -> f64 {
+    if self.keys == 0 {
+      return 0.0;
+    }
+    let rank = (q * (self.keys - 1) as f64).round() as u64;
+    let mut seen = 0;
+    for (&index, &count) in &self.buckets {
+      seen += count;
+      if seen > rank {
+        return self.value(index);
+      }
+    }
+    unreachable!("bucket counts sum to the key count")
+  }
+
+  pub(crate) fn key_count(&self) -> u64 {
+    self.keys
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.buckets.clear();
+    self.keys = 0;
+  }
+
+  fn index(&self, frequency: u32) -> i32 {
+    (f64::from(frequency).ln() / self.ln_gamma).ceil() as i32
+  }
+
+  // The value minimizing the relative error over the bucket (gamma^(i-1), gamma^i]
+  fn value(&self, index: i32) -> f64 {
+    let upper = (index as f64 * self.ln_gamma).exp();
+    let gamma = self.ln_gamma.exp();
+    2.0 * upper / (1.0 + gamma)
+  }
+}
@@ -0,0 +1,329 @@
+//! Entry points for fuzzing decoders and merge paths with cargo-fuzz.
+//!
+//! Each function accepts arbitrary bytes and panics only when an invariant is violated,
+//! so a fuzz target is a one-liner:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| sketch_wasm::fuzz::fuzz_ops(data));
+//! ```
+
+use std::collections::HashMap;
+
+use crate::bloom::{BloomFilter, BloomFilterJson, MAX_HEADER_LEN};
+use crate::bloom_cascade::BloomCascade;
+use crate::bloom_view::Layout;
+use crate::column::for_each_value;
+use crate::count_min_sketch::CountMinSketch;
+use crate::hyperloglog::HyperLogLog;
+use crate::redis_bloom::{RedisBloomFilter, CHAIN_HEADER_LEN, LINK_HEADER_LEN};
+use crate::replay::{ReplayLog, ReplayTarget};
+use crate::stream_loader::BloomFilterLoader;
+use crate::sync::SyncChannel;
+
+const TARGETS: [ReplayTarget; 3] =
+  [ReplayTarget::BloomFilter, ReplayTarget::CountMinSketch, ReplayTarget::HyperLogLog];
+// Items probed in decoded filters
+const KEYS: [&str; 4] = ["", "key0", "key1", "hello"];
+// Sub-filter bytes a RedisBloom header may declare before it is skipped
+const MAX_REDIS_BYTES: u64 = 1 << 20;
+
+/// Feeds `data` to every binary decoder. Decoders must reject malformed input with an error
+/// rather than panicking, and anything they accept must re-encode to the same bytes.
+pub fn fuzz_roundtrip(data: &[u8]) {
+  for target in TARGETS {
+    if let Ok(ops) = ReplayLog::parse(data, target) {
+      let mut log = ReplayLog::new(target);
+      for op in ops {
+        log.record(op);
+      }
+      assert_eq!(log.into_bytes(), data, "replay log did not round-trip");
+    }
+  }
+
+  let mut filter = BloomFilter::new(64, 0.01);
+  let _ = filter.replay(data);
+  if let Ok(decoded) = CountMinSketch::from_bytes_compressed(data) {
+    let reencoded = CountMinSketch::from_bytes_compressed(&decoded.to_bytes_compressed()).unwrap();
+    assert_eq!(reencoded.cells(), decoded.cells(), "CountMinSketch did not round-trip");
+  }
+  if let Ok(decoded) = CountMinSketch::from_bytes(data) {
+    let reencoded = CountMinSketch::from_bytes(&decoded.to_bytes()).unwrap();
+    assert_eq!(reencoded.cells(), decoded.cells(), "CountMinSketch did not round-trip");
+  }
+  if let Ok(decoded) = BloomFilter::from_bytes(data) {
+    let bytes = decoded.to_bytes();
+    assert_eq!(
+      BloomFilter::from_bytes(&bytes).unwrap().to_bytes(),
+      bytes,
+      "BloomFilter did not round-trip"
+    );
+    // BloomFilterView reads the same buffer in place
+    let layout = Layout::parse(&data[..data.len().min(MAX_HEADER_LEN)], data.len())
+      .expect("BloomFilterView rejected a filter that from_bytes accepts");
+    for key in KEYS {
+      assert_eq!(
+        layout.contains(key, |index| data[index]),
+        decoded.contains(key),
+        "BloomFilterView disagrees with BloomFilter"
+      );
+    }
+  } else if let Ok(layout) = Layout::parse(&data[..data.len().min(MAX_HEADER_LEN)], data.len()) {
+    for key in KEYS {
+      layout.contains(key, |index| data[index]);
+    }
+  }
+  if let Ok(decoded) = BloomCascade::from_bytes(data) {
+    let bytes = decoded.to_bytes();
+    assert_eq!(
+      BloomCascade::from_bytes(&bytes).unwrap().to_bytes(),
+      bytes,
+      "BloomCascade did not round-trip"
+    );
+  }
+
+  let mut sketch = CountMinSketch::new(64, 4);
+  let _ = sketch.replay(data);
+  if let Ok(mut hll) = HyperLogLog::new(Some(8)) {
+    let _ = hll.replay(data);
+  }
+
+  for column in 0..2 {
+    let _ = for_each_value(data, "csv", Some(column), |_| {});
+    let _ = for_each_value(data, "arrow", Some(column), |_| {});
+  }
+
+  let _ = SyncChannel::new().apply_bloom(&mut filter, data);
+  let _ = SyncChannel::new().apply_count_min_sketch(&mut sketch, data);
+}
+
+/// Parses `data` as a `BloomFilter.toJSON` document. Anything the decoder accepts must
+/// re-encode to a document that decodes to the same filter.
+pub fn fuzz_json(data: &[u8]) {
+  let Ok(repr) = serde_json::from_slice::<BloomFilterJson>(data) else {
+    return;
+  };
+  if let Ok(decoded) = BloomFilter::from_json_repr(repr) {
+    let reencoded = BloomFilter::from_json_repr(decoded.to_json_repr()).unwrap();
+    assert_eq!(reencoded.to_bytes(), decoded.to_bytes(), "BloomFilter JSON did not round-trip");
+  }
+}
+
+/// Streams `data` into a `BloomFilterLoader` in uneven chunks. The loader must accept exactly
+/// the buffers `BloomFilter::from_bytes` accepts, and its partial answers must never contradict
+/// the complete filter.
+pub fn fuzz_stream(data: &[u8]) {
+  // A header may declare up to 256 MiB of bits that never arrive, which the loader allocates
+  // up front; skip those, as from_bytes rejects them anyway
+  if BloomFilter::read_params(&mut &data[..]).is_ok_and(|params| params.bit_count / 8 > data.len())
+  {
+    return;
+  }
+  let mut loader = BloomFilterLoader::new();
+  let mut answers = Vec::new();
+  let mut pushed = Ok(());
+  for chunk in data.chunks(7) {
+    pushed = loader.push(chunk);
+    if pushed.is_err() {
+      break;
+    }
+    answers.extend(KEYS.iter().filter_map(|&key| Some((key, loader.contains(key)?))));
+  }
+
+  match (pushed.and_then(|_| loader.finish()), BloomFilter::from_bytes(data)) {
+    (Ok(streamed), Ok(decoded)) => {
+      assert_eq!(
+        streamed.to_bytes(),
+        decoded.to_bytes(),
+        "BloomFilterLoader decoded a different filter"
+      );
+      for (key, answer) in answers {
+        assert_eq!(
+          answer,
+          decoded.contains(key),
+          "BloomFilterLoader answered before its bits arrived"
+        );
+      }
+    }
+    (Err(_), Err(_)) => {}
+    (streamed, decoded) => panic!(
+      "BloomFilterLoader and from_bytes disagree: {:?} vs {:?}",
+      streamed.map(|_| ()),
+      decoded.map(|_| ())
+    ),
+  }
+}
+
+/// Splits `data` into `BF.SCANDUMP` chunks: a little-endian u16 header length and the header,
+/// then chunks of a little-endian u32 iterator, a length byte and the payload. A loaded filter
+/// must dump back the header it was created from.
+pub fn fuzz_redis_bloom(data: &[u8]) {
+  let Some((len, rest)) = data.split_first_chunk::<2>() else {
+    return;
+  };
+  let (header, mut input) = rest.split_at(usize::from(u16::from_le_bytes(*len)).min(rest.len()));
+  // Valid headers may declare filters of any size, so skip the large ones to keep allocations
+  // small; sub-filter records start with their byte length
+  let declared = header
+    .get(CHAIN_HEADER_LEN..)
+    .unwrap_or_default()
+    .chunks(LINK_HEADER_LEN)
+    .filter_map(|link| link.first_chunk::<8>())
+    .map(|bytes| u64::from_le_bytes(*bytes))
+    .fold(0, u64::saturating_add);
+  if declared > MAX_REDIS_BYTES {
+    return;
+  }
+  let Ok(mut filter) = RedisBloomFilter::from_header(header) else {
+    return;
+  };
+
+  while let [a, b, c, d, len, rest @ ..] = input {
+    let (payload, rest) = rest.split_at(usize::from(*len).min(rest.len()));
+    let _ = filter.load_chunk(f64::from(u32::from_le_bytes([*a, *b, *c, *d])), payload);
+    input = rest;
+  }
+  let mut dumped = filter.scandump(0.0).unwrap();
+  assert_eq!(dumped.data(), header, "RedisBloomFilter header did not round-trip");
+  for key in KEYS {
+    if filter.insert(key) {
+      assert!(filter.contains(key), "RedisBloomFilter lost an inserted key");
+    }
+  }
+  while dumped.iterator() != 0.0 {
+    dumped = filter.scandump(dumped.iterator()).unwrap();
+  }
+}
+
+/// Interprets `data` as a program of inserts, merges and resizes applied to pairs of sketches,
+/// checking after every step that no sketch under-reports what was inserted.
+pub fn fuzz_ops(data: &[u8]) {
+  let mut filter = BloomFilter::new(256, 0.01);
+  let mut local = CountMinSketch::new(64, 4);
+  let global = CountMinSketch::new(64, 4);
+  let mut hll_a = HyperLogLog::new(Some(10)).unwrap();
+  let mut hll_b = HyperLogLog::new(Some(10)).unwrap();
+  let mut union = HyperLogLog::new(Some(10)).unwrap();
+
+  let mut inserted = Vec::new();
+  let mut local_counts: HashMap<String, u32> = HashMap::new();
+  let mut mirrored = false;
+
+  for chunk in data.chunks(2) {
+    let key = format!("key{}", chunk.get(1).copied().unwrap_or(0));
+    match chunk[0] % 6 {
+      0 => {
+        filter.insert(&key);
+        inserted.push(key);
+      }
+      1 => {
+        local.increment(&key);
+        *local_counts.entry(key).or_default() += 1;
+      }
+      2 => {
+        hll_a.add(&key);
+        union.add(&key);
+      }
+      3 => {
+        hll_b.add(&key);
+        union.add(&key);
+      }
+      4 => {
+        if !mirrored && local.width() == global.width() {
+          mirrored = local.mirror_into(&global).is_ok();
+        }
+      }
+      _ => {
+        if !mirrored && local.width() > 8 {
+          local.halve_width().unwrap();
+        }
+      }
+    }
+  }
+
+  for key in &inserted {
+    assert!(filter.contains(key), "Bloom filter lost an inserted key");
+  }
+  for (key, &count) in &local_counts {
+    assert!(local.estimate(key) >= count, "CountMinSketch underestimated a key");
+  }
+
+  hll_a.merge(&hll_b).unwrap();
+  assert_eq!(hll_a.count(), union.count(), "HyperLogLog merge differs from direct insertion");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Prefixes a RedisBloom header with its length and appends one chunk holding its bits
+  fn redis_input(header: &[u8], bits: &[u8]) -> Vec<u8> {
+    let mut out = (header.len() as u16).to_le_bytes().to_vec();
+    out.extend_from_slice(header);
+    out.extend_from_slice(&(bits.len() as u32 + 1).to_le_bytes());
+    out.push(bits.len() as u8);
+    out.extend_from_slice(bits);
+    out
+  }
+
+  // Valid encodings for every entry point, which the smoke test then mutates
+  fn seeds() -> Vec<Vec<u8>> {
+    let mut filter = BloomFilter::with_seed(40, 0.01, 7);
+    for i in 0..20 {
+      filter.insert(&format!("key{}", i));
+    }
+    let cascade = BloomCascade::new(vec!["key0".to_string()], vec!["key1".to_string()], None);
+    let mut redis_header = Vec::new();
+    redis_header.extend_from_slice(&0u64.to_le_bytes());
+    redis_header.extend_from_slice(&1u32.to_le_bytes());
+    redis_header.extend_from_slice(&0u32.to_le_bytes());
+    redis_header.extend_from_slice(&2u32.to_le_bytes());
+    for field in [8u64, 64, 0, 0.01f64.to_bits(), 10f64.to_bits()] {
+      redis_header.extend_from_slice(&field.to_le_bytes());
+    }
+    redis_header.extend_from_slice(&3u32.to_le_bytes());
+    redis_header.extend_from_slice(&6u64.to_le_bytes());
+    redis_header.push(6);
+
+    vec![
+      filter.to_bytes(),
+      cascade.unwrap().to_bytes(),
+      serde_json::to_vec(&filter.to_json_repr()).unwrap(),
+      redis_input(&redis_header, &[0x5a; 8]),
+    ]
+  }
+
+  #[test]
+  fn test_entry_points_accept_arbitrary_input() {
+    let inputs: [&[u8]; 4] =
+      [&[], &[b'R', 1, 2, 1, 0], &[0, 1, 1, 2, 4, 0, 5, 5, 3, 9], &[255; 64]];
+    for input in inputs {
+      fuzz_roundtrip(input);
+      fuzz_ops(input);
+      fuzz_json(input);
+      fuzz_stream(input);
+      fuzz_redis_bloom(input);
+    }
+  }
+
+  // A short deterministic run of every entry point over truncated and bit-flipped seeds, so CI
+  // catches decoder panics without a fuzzing toolchain
+  #[test]
+  fn test_smoke_mutated_seeds() {
+    for seed in seeds() {
+      let mut inputs: Vec<Vec<u8>> = (0..=seed.len()).map(|len| seed[..len].to_vec()).collect();
+      for index in 0..seed.len() {
+        for bit in [0, 3, 7] {
+          let mut flipped = seed.clone();
+          flipped[index] ^= 1 << bit;
+          inputs.push(flipped);
+        }
+      }
+      for input in inputs {
+        fuzz_roundtrip(&input);
+        fuzz_json(&input);
+        fuzz_stream(&input);
+        fuzz_redis_bloom(&input);
+      }
+    }
+  }
+}
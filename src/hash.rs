@@ -0,0 +1,94 @@
+const PRIME64_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME64_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME64_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME64_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME64_5: u64 = 0x27d4_eb2f_1656_67c5;
+
+/// Hashes a string key to 64 bits: xxHash64 of its UTF-8 bytes with seed 0. The algorithm is
+/// fixed so other implementations can reproduce every position derived from it.
+pub(crate) fn hash_str(item: &str) -> u64 {
+  xxhash64(item.as_bytes(), 0)
+}
+
+/// Computes xxHash64 as specified at <https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md>.
+pub(crate) fn xxhash64(input: &[u8], seed: u64) -> u64 {
+  let read_u64 = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().expect("8-byte lane"));
+  let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().expect("4-byte lane"));
+
+  let mut rest = input;
+  let mut acc = if input.len() >= 32 {
+    let mut lanes = [
+      seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+      seed.wrapping_add(PRIME64_2),
+      seed,
+      seed.wrapping_sub(PRIME64_1),
+    ];
+    while rest.len() >= 32 {
+      for (i, lane) in lanes.iter_mut().enumerate() {
+        *lane = xxh64_round(*lane, read_u64(&rest[i * 8..i * 8 + 8]));
+      }
+      rest = &rest[32..];
+    }
+    let mut acc = lanes[0]
+      .rotate_left(1)
+      .wrapping_add(lanes[1].rotate_left(7))
+      .wrapping_add(lanes[2].rotate_left(12))
+      .wrapping_add(lanes[3].rotate_left(18));
+    for lane in lanes {
+      acc = (acc ^ xxh64_round(0, lane)).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+    }
+    acc
+  } else {
+    seed.wrapping_add(PRIME64_5)
+  };
+  acc = acc.wrapping_add(input.len() as u64);
+
+  while rest.len() >= 8 {
+    acc ^= xxh64_round(0, read_u64(&rest[..8]));
+    acc = acc.rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+    rest = &rest[8..];
+  }
+  if rest.len() >= 4 {
+    acc ^= u64::from(read_u32(&rest[..4])).wrapping_mul(PRIME64_1);
+    acc = acc.rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+    rest = &rest[4..];
+  }
+  for &byte in rest {
+    acc ^= u64::from(byte).wrapping_mul(PRIME64_5);
+    acc = acc.rotate_left(11).wrapping_mul(PRIME64_1);
+  }
+
+  acc ^= acc >> 33;
+  acc = acc.wrapping_mul(PRIME64_2);
+  acc ^= acc >> 29;
+  acc = acc.wrapping_mul(PRIME64_3);
+  acc ^ (acc >> 32)
+}
+
+fn xxh64_round(acc: u64, lane: u64) -> u64 {
+  acc.wrapping_add(lane.wrapping_mul(PRIME64_2)).rotate_left(31).wrapping_mul(PRIME64_1)
+}
+
+/// Derives an independent, well-mixed 64-bit value from a hash and a seed
+/// using the SplitMix64 finalizer.
+pub(crate) fn mix64(hash: u64, seed: u64) -> u64 {
+  let mut z = hash ^ seed.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_xxhash64_known_answers() {
+    // Reference outputs of the xxHash C implementation
+    assert_eq!(xxhash64(b"", 0), 0xef46_db37_51d8_e999);
+    assert_eq!(xxhash64(b"a", 0), 0xd24e_c4f1_a98c_6e5b);
+    assert_eq!(xxhash64(b"abc", 0), 0x44bc_2cf5_ad77_0999);
+    assert_eq!(xxhash64(b"Nobody inspects the spammish repetition", 0), 0xfbce_a83c_8a37_8bf1);
+    assert_eq!(hash_str("abc"), xxhash64(b"abc", 0));
+  }
+}
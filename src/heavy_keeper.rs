@@ -1,9 +1,19 @@
 use std::cmp::Reverse;
-use std::collections::hash_map::DefaultHasher;
-use std::collections::{BinaryHeap, HashMap};
-use std::hash::{Hash, Hasher};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use regex_lite::Regex;
 use wasm_bindgen::prelude::*;
 
+use crate::column::for_each_value;
+use crate::count_min_sketch::CountMinSketch;
+use crate::entries::{write_csv, Entries};
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+use crate::memory::try_zeroed;
+use crate::runtime;
+
 /// Represents an item and its count in the Heavy Keeper data structure.
 #[wasm_bindgen]
 #[derive(Debug)]
@@ -38,6 +48,148 @@ impl TopKItem {
   }
 }
 
+/// A counter whose occupant decayed to zero and was replaced, logged by
+/// `HeavyKeeper::enable_eviction_log`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Eviction {
+  evicted: String,
+  replacement: String,
+  row: usize,
+  sequence: f64,
+}
+
+#[wasm_bindgen]
+impl Eviction {
+  /// Returns the item that lost the counter.
+  #[wasm_bindgen(getter)]
+  pub fn evicted(&self) -> String {
+    self.evicted.clone()
+  }
+
+  /// Returns the item that took over the counter.
+  #[wasm_bindgen(getter)]
+  pub fn replacement(&self) -> String {
+    self.replacement.clone()
+  }
+
+  /// Returns the row of the replaced counter.
+  #[wasm_bindgen(getter)]
+  pub fn row(&self) -> usize {
+    self.row
+  }
+
+  /// Returns the number of adds made to the sketch up to and including the evicting one.
+  #[wasm_bindgen(getter)]
+  pub fn sequence(&self) -> f64 {
+    self.sequence
+  }
+}
+
+/// How persistently an item has held a top-k position across recorded snapshots.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TopKStability {
+  streak: u32,
+  mean_rank: f64,
+  // Sum of squared rank deviations over the streak (Welford's method)
+  rank_m2: f64,
+}
+
+#[wasm_bindgen]
+impl TopKStability {
+  /// Returns the number of consecutive snapshots, up to the latest, the item was in the top-k.
+  #[wasm_bindgen(getter)]
+  pub fn streak(&self) -> u32 {
+    self.streak
+  }
+
+  /// Returns the item's average rank over the streak, where 0 is the top entry.
+  #[wasm_bindgen(getter)]
+  pub fn mean_rank(&self) -> f64 {
+    self.mean_rank
+  }
+
+  /// Returns the variance of the item's rank over the streak; 0 means it never moved.
+  #[wasm_bindgen(getter)]
+  pub fn rank_variance(&self) -> f64 {
+    if self.streak == 0 {
+      0.0
+    } else {
+      self.rank_m2 / self.streak as f64
+    }
+  }
+}
+
+impl TopKStability {
+  fn observe(&mut self, rank: usize) {
+    self.streak += 1;
+    let delta = rank as f64 - self.mean_rank;
+    self.mean_rank += delta / self.streak as f64;
+    self.rank_m2 += delta * (rank as f64 - self.mean_rank);
+  }
+}
+
+/// Parameter recommendations for a HeavyKeeper, derived from the stream it has seen.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct TuningReport {
+  collision_rate: f64,
+  eviction_rate: f64,
+  top_k_churn: f64,
+  width: usize,
+  depth: usize,
+  decay: f64,
+}
+
+#[wasm_bindgen]
+impl TuningReport {
+  /// Returns the fraction of counter updates that landed on a counter owned by another item.
+  #[wasm_bindgen(getter)]
+  pub fn collision_rate(&self) -> f64 {
+    self.collision_rate
+  }
+
+  /// Returns the fraction of counter updates that evicted the counter's owner.
+  #[wasm_bindgen(getter)]
+  pub fn eviction_rate(&self) -> f64 {
+    self.eviction_rate
+  }
+
+  /// Returns the fraction of adds that displaced an item from the top-k.
+  #[wasm_bindgen(getter)]
+  pub fn top_k_churn(&self) -> f64 {
+    self.top_k_churn
+  }
+
+  /// Returns the recommended number of counters per row.
+  #[wasm_bindgen(getter)]
+  pub fn recommended_width(&self) -> usize {
+    self.width
+  }
+
+  /// Returns the recommended number of rows.
+  #[wasm_bindgen(getter)]
+  pub fn recommended_depth(&self) -> usize {
+    self.depth
+  }
+
+  /// Returns the recommended decay factor.
+  #[wasm_bindgen(getter)]
+  pub fn recommended_decay(&self) -> f64 {
+    self.decay
+  }
+}
+
+// Stream statistics gathered for tuning reports
+#[derive(Debug, Clone, Default)]
+struct StreamStats {
+  adds: u64,
+  collisions: u64,
+  evictions: u64,
+  top_k_displacements: u64,
+}
+
 // Internal struct for min-heap operations
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct HeapItem {
@@ -58,10 +210,17 @@ impl Ord for HeapItem {
   }
 }
 
+// A uniform sample of the payloads added with one item
+#[derive(Debug, Clone, Default)]
+struct Reservoir {
+  seen: u64,
+  samples: Vec<String>,
+}
+
 /// A probabilistic data structure for finding the top-k most frequent items in a data stream.
 /// It uses a small amount of memory while providing approximate frequency estimates.
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HeavyKeeper {
   width: usize,
   depth: usize,
@@ -73,6 +232,21 @@ pub struct HeavyKeeper {
   top_k_heap: BinaryHeap<Reverse<HeapItem>>,
   // Track all seen items for accurate counting
   all_counts: HashMap<String, u32>,
+  // Source of randomness for probabilistic decay
+  rng: ChaCha8Rng,
+  // Backing counts for items that lost their counters, present when enabled
+  residual: Option<CountMinSketch>,
+  // Payloads kept per item holding a counter; zero capacity disables sampling
+  exemplar_capacity: usize,
+  exemplars: HashMap<String, Reservoir>,
+  // Rank history of the items in the latest recorded snapshot
+  stability: HashMap<String, TopKStability>,
+  stats: StreamStats,
+  // Most recent evictions, oldest first; zero capacity disables logging
+  eviction_capacity: usize,
+  evictions: VecDeque<Eviction>,
+  // Hashes a stored item; wrappers whose items encode several values may combine their hashes
+  key_hash: fn(&str) -> u64,
 }
 
 #[wasm_bindgen]
@@ -87,35 +261,60 @@ impl HeavyKeeper {
   /// * `decay` - The decay factor for count reduction (between 0 and 1)
   #[wasm_bindgen(constructor)]
   pub fn new(width: usize, depth: usize, k: usize, decay: f64) -> Self {
-    let mut counters = Vec::with_capacity(depth);
-    let mut hash_seeds = Vec::with_capacity(depth);
+    HeavyKeeper::with_counters(width, vec![vec![(String::new(), 0); width]; depth], k, decay)
+  }
 
-    for i in 0..depth {
-      let mut row = Vec::with_capacity(width);
-      for _ in 0..width {
-        row.push((String::new(), 0));
-      }
-      counters.push(row);
-      hash_seeds.push(i as u64);
+  /// Creates a new Heavy Keeper like the constructor, but fails with an out-of-memory error
+  /// instead of aborting if the counters cannot be allocated.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  /// * `k` - The number of top items to track
+  /// * `decay` - The decay factor for count reduction (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn try_new(width: usize, depth: usize, k: usize, decay: f64) -> Result<Self, SketchError> {
+    let mut counters = try_zeroed::<Vec<(String, u32)>>(depth)?;
+    for row in &mut counters {
+      *row = try_zeroed(width)?;
     }
+    Ok(HeavyKeeper::with_counters(width, counters, k, decay))
+  }
 
+  // Creates an empty instance around a counter matrix of `width` unoccupied counters per row
+  fn with_counters(width: usize, counters: Vec<Vec<(String, u32)>>, k: usize, decay: f64) -> Self {
+    let depth = counters.len();
     HeavyKeeper {
       width,
       depth,
       k,
       decay,
       counters,
-      hash_seeds,
+      hash_seeds: (0..depth as u64).collect(),
       top_k_heap: BinaryHeap::new(),
       all_counts: HashMap::new(),
+      rng: runtime::new_rng(),
+      residual: None,
+      exemplar_capacity: 0,
+      exemplars: HashMap::new(),
+      stability: HashMap::new(),
+      stats: StreamStats::default(),
+      key_hash: hash_str,
+      eviction_capacity: 0,
+      evictions: VecDeque::new(),
     }
   }
 
+  /// Returns an independent deep copy of the sketch, e.g. to snapshot it while the original
+  /// keeps changing and compare the two later.
+  #[wasm_bindgen(js_name = clone)]
+  pub fn deep_clone(&self) -> HeavyKeeper {
+    self.clone()
+  }
+
   fn hash(&self, item: &str, seed: u64) -> usize {
-    let mut hasher = DefaultHasher::new();
-    item.hash(&mut hasher);
-    seed.hash(&mut hasher);
-    (hasher.finish() as usize) % self.width
+    (mix64((self.key_hash)(item), seed) % self.width as u64) as usize
   }
 
   fn update_top_k(&mut self, item: &str, count: u32) {
@@ -144,87 +343,380 @@ impl HeavyKeeper {
       } else if let Some(Reverse(min_item)) = self.top_k_heap.peek() {
         if count > min_item.count {
           self.top_k_heap.pop(); // Remove minimum
+          self.stats.top_k_displacements += 1;
           self.top_k_heap.push(Reverse(HeapItem { item: item.to_string(), count }));
         }
       }
     }
   }
 
-  /// Adds an item to the Heavy Keeper.
+  /// Backs the sketch with a CountMinSketch that counts every added item, so `query` still
+  /// returns an approximate count for items that were never in, or were evicted from, the
+  /// top-k counters. Only items added after this call are counted.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row of the backing sketch
+  /// * `depth` - The number of rows in the backing sketch
+  #[wasm_bindgen]
+  pub fn enable_residual_counts(&mut self, width: usize, depth: usize) {
+    self.residual = Some(CountMinSketch::new(width, depth));
+  }
+
+  /// Drops the backing CountMinSketch; `query` returns 0 for untracked items again.
+  #[wasm_bindgen]
+  pub fn disable_residual_counts(&mut self) {
+    self.residual = None;
+  }
+
+  /// Keeps a uniform random sample of up to `per_item` payloads for every item that holds a
+  /// counter, so leaderboard entries can show representative raw events. Payloads are supplied
+  /// with `add_with_exemplar` and dropped once their item loses all its counters.
+  /// Passing 0 disables sampling and discards any kept payloads.
+  ///
+  /// # Arguments
+  ///
+  /// * `per_item` - The maximum number of payloads kept per item
+  #[wasm_bindgen]
+  pub fn enable_exemplars(&mut self, per_item: usize) {
+    self.exemplar_capacity = per_item;
+    if per_item == 0 {
+      self.exemplars.clear();
+    }
+    for reservoir in self.exemplars.values_mut() {
+      reservoir.samples.truncate(per_item);
+    }
+  }
+
+  /// Adds an item along with an example payload (e.g. the serialized raw event) that may be
+  /// kept as one of the item's exemplars.
   ///
   /// # Arguments
   ///
   /// * `item` - The item to add
+  /// * `payload` - The raw event the item was derived from
   #[wasm_bindgen]
-  pub fn add(&mut self, item: &str) {
-    for i in 0..self.depth {
-      let pos = self.hash(item, self.hash_seeds[i]);
-      let counter = &mut self.counters[i][pos];
+  pub fn add_with_exemplar(&mut self, item: &str, payload: String) {
+    self.add_weighted(item, 1);
+    if self.exemplar_capacity == 0 || self.counter_estimate(item) == 0 {
+      return;
+    }
 
-      if counter.0.is_empty() {
-        counter.0 = item.to_string();
-        counter.1 = 1;
-      } else if counter.0 == item {
-        counter.1 += 1;
-      } else {
-        // Decay the counter with probability decay
-        if js_sys::Math::random() < self.decay {
-          counter.1 = counter.1.saturating_sub(1);
-          if counter.1 == 0 {
-            counter.0 = item.to_string();
-            counter.1 = 1;
-          }
-        }
+    // Reservoir sampling keeps every payload equally likely to be retained
+    let reservoir = self.exemplars.entry(item.to_string()).or_default();
+    reservoir.seen += 1;
+    if reservoir.samples.len() < self.exemplar_capacity {
+      reservoir.samples.push(payload);
+    } else {
+      let slot = self.rng.gen_range(0..reservoir.seen);
+      if let Some(sample) = reservoir.samples.get_mut(slot as usize) {
+        *sample = payload;
       }
     }
+  }
 
-    // Update top-k with current estimated count
-    let estimated_count = self.query(item);
-    if estimated_count > 0 {
-      self.update_top_k(item, estimated_count);
+  /// Returns the payloads kept for an item, or an empty list if none were kept.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to look up
+  #[wasm_bindgen]
+  pub fn exemplars(&self, item: &str) -> Vec<String> {
+    self.exemplars.get(item).map(|reservoir| reservoir.samples.clone()).unwrap_or_default()
+  }
+
+  /// Returns an item's kept payloads as entries, each weighted by the number of payloads it
+  /// stands for in the uniform sample.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to look up
+  #[wasm_bindgen]
+  pub fn exemplar_entries(&self, item: &str) -> Entries {
+    let Some(reservoir) = self.exemplars.get(item) else {
+      return Entries::default();
+    };
+    let weight = reservoir.seen as f64 / reservoir.samples.len() as f64;
+    reservoir.samples.iter().map(|sample| (sample.clone(), weight)).collect()
+  }
+
+  /// Starts logging every counter replacement, to audit how aggressively the sketch churns
+  /// under its decay factor. Only the most recent `capacity` evictions are kept; passing 0
+  /// stops logging and discards the log.
+  ///
+  /// # Arguments
+  ///
+  /// * `capacity` - The maximum number of evictions kept between drains
+  #[wasm_bindgen]
+  pub fn enable_eviction_log(&mut self, capacity: usize) {
+    self.eviction_capacity = capacity;
+    while self.evictions.len() > capacity {
+      self.evictions.pop_front();
     }
   }
 
+  /// Returns the logged evictions, oldest first, and empties the log.
+  #[wasm_bindgen]
+  pub fn drain_evictions(&mut self) -> Vec<Eviction> {
+    self.evictions.drain(..).collect()
+  }
+
+  /// Adds an item to the Heavy Keeper.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to add
+  #[wasm_bindgen]
+  pub fn add(&mut self, item: &str) {
+    self.add_weighted(item, 1);
+  }
+
+  /// Adds every non-empty value of one column of a CSV, newline-delimited or Arrow IPC buffer,
+  /// parsing it entirely in wasm so no JS string array is built. Nothing is added if any row is
+  /// malformed.
+  /// Returns the number of values added.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The column data as UTF-8 text or an Arrow IPC stream or file (a `Uint8Array`)
+  /// * `format` - `"csv"` (with a header row), `"lines"` (one value per line) or `"arrow"` (a
+  ///   `Utf8` column, optionally dictionary-encoded; nulls are skipped)
+  /// * `column` - The zero-based column to read; defaults to 0
+  #[wasm_bindgen]
+  pub fn ingest_column(
+    &mut self,
+    buffer: &[u8],
+    format: &str,
+    column: Option<usize>,
+  ) -> Result<usize, SketchError> {
+    for_each_value(buffer, format, column, |value| self.add(value))
+  }
+
+  /// Adds an item with a weight, so items are ranked by accumulated weight (e.g. total bytes or
+  /// total latency) rather than by occurrence count. A colliding counter decays by the full
+  /// weight, and the new item takes over the counter with whatever weight remains.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to add
+  /// * `weight` - The weight contributed by this occurrence
+  #[wasm_bindgen]
+  pub fn add_weighted(&mut self, item: &str, weight: u32) {
+    if weight == 0 {
+      return;
+    }
+    if let Some(residual) = &mut self.residual {
+      residual.increment_hash_by((self.key_hash)(item), weight);
+    }
+
+    let positions = self.positions(item);
+    self.add_at(item, weight, &positions);
+  }
+
   /// Returns the estimated frequency of an item.
+  /// Items without a counter fall back to the backing sketch if residual counts are enabled.
   ///
   /// # Arguments
   ///
   /// * `item` - The item to query
   #[wasm_bindgen]
   pub fn query(&self, item: &str) -> u32 {
-    let mut min_count = u32::MAX;
+    match (self.counter_estimate(item), &self.residual) {
+      (0, Some(residual)) => residual.estimate_hash((self.key_hash)(item)),
+      (count, _) => count,
+    }
+  }
 
-    for i in 0..self.depth {
-      let pos = self.hash(item, self.hash_seeds[i]);
-      let counter = &self.counters[i][pos];
+  /// Seeds this sketch's counters with scaled-down counts from a previous epoch's sketch,
+  /// so leaderboards after a window rotation don't flap while counts rebuild.
+  /// Counters that scale down to zero are left empty. Exemplars are replaced by the previous
+  /// sketch's samples for the items carried over, if sampling is enabled here.
+  ///
+  /// # Arguments
+  ///
+  /// * `previous` - The sketch from the previous epoch; must have the same width and depth
+  /// * `scale` - The factor applied to each carried-over count (between 0 and 1)
+  #[wasm_bindgen]
+  pub fn prime_from(&mut self, previous: &HeavyKeeper, scale: f64) -> Result<(), SketchError> {
+    if self.width != previous.width || self.depth != previous.depth {
+      return Err(SketchError::Incompatible(
+        "cannot prime a HeavyKeeper from one with different dimensions".to_string(),
+      ));
+    }
+    if !(0.0..=1.0).contains(&scale) {
+      return Err(SketchError::InvalidParameter("scale must be between 0 and 1".to_string()));
+    }
 
-      if counter.0 == item {
-        min_count = min_count.min(counter.1);
+    for (row, previous_row) in self.counters.iter_mut().zip(&previous.counters) {
+      for (counter, (item, count)) in row.iter_mut().zip(previous_row) {
+        let scaled = (*count as f64 * scale).floor() as u32;
+        *counter = if scaled == 0 { (String::new(), 0) } else { (item.clone(), scaled) };
       }
     }
 
-    if min_count == u32::MAX {
-      0
-    } else {
-      min_count
+    self.top_k_heap.clear();
+    self.all_counts.clear();
+    self.exemplars.clear();
+    let mut primed: Vec<String> = self
+      .counters
+      .iter()
+      .flatten()
+      .filter(|(item, _)| !item.is_empty())
+      .map(|(item, _)| item.clone())
+      .collect();
+    primed.sort_unstable();
+    primed.dedup();
+    for item in primed {
+      let count = self.counter_estimate(&item);
+      if count > 0 {
+        self.update_top_k(&item, count);
+      }
+      // Every primed item holds a counter, so it keeps the previous epoch's exemplars
+      if self.exemplar_capacity == 0 {
+        continue;
+      }
+      if let Some(reservoir) = previous.exemplars.get(&item) {
+        let mut reservoir = reservoir.clone();
+        reservoir.samples.truncate(self.exemplar_capacity);
+        self.exemplars.insert(item, reservoir);
+      }
+    }
+    Ok(())
+  }
+
+  /// Records the current top-k ranking as a snapshot, e.g. on every leaderboard refresh.
+  /// Items in the top-k extend their streak; items that dropped out lose their history.
+  #[wasm_bindgen]
+  pub fn record_snapshot(&mut self) {
+    let mut previous = std::mem::take(&mut self.stability);
+    for (rank, entry) in self.top_k().into_iter().enumerate() {
+      let mut stability = previous.remove(&entry.item).unwrap_or_default();
+      stability.observe(rank);
+      self.stability.insert(entry.item, stability);
+    }
+  }
+
+  /// Returns how stable an item's top-k position has been across recorded snapshots, so
+  /// persistent heavy hitters can be told apart from transient bursts. Returns `None` if the
+  /// item was not in the top-k at the latest snapshot.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to look up
+  #[wasm_bindgen]
+  pub fn stability(&self, item: &str) -> Option<TopKStability> {
+    self.stability.get(item).copied()
+  }
+
+  /// Recommends width, depth and decay adjustments from the statistics of the stream seen so
+  /// far. Frequent collisions call for more width, frequent evictions for another row, and a
+  /// churning top-k for gentler decay; a sketch with almost no collisions can be made narrower.
+  /// Recommendations equal the current parameters until enough items have been added.
+  #[wasm_bindgen]
+  pub fn tuning_report(&self) -> TuningReport {
+    let updates = (self.stats.adds * self.depth as u64).max(1) as f64;
+    let collision_rate = self.stats.collisions as f64 / updates;
+    let eviction_rate = self.stats.evictions as f64 / updates;
+    let top_k_churn = self.stats.top_k_displacements as f64 / self.stats.adds.max(1) as f64;
+
+    let mut report = TuningReport {
+      collision_rate,
+      eviction_rate,
+      top_k_churn,
+      width: self.width,
+      depth: self.depth,
+      decay: self.decay,
+    };
+    // Rates over fewer adds than counters say little about the workload
+    if self.stats.adds < (self.width * self.depth) as u64 {
+      return report;
+    }
+
+    if collision_rate > 0.5 {
+      report.width = self.width * 2;
+    } else if collision_rate < 0.05 && self.width / 2 >= self.k {
+      report.width = self.width / 2;
+    }
+    if eviction_rate > 0.1 && self.depth < 8 {
+      report.depth = self.depth + 1;
     }
+    if top_k_churn > 0.01 {
+      report.decay = (self.decay * 0.9).max(0.5);
+    } else if top_k_churn < 0.001 && eviction_rate < 0.01 {
+      report.decay = (self.decay * 1.05).min(0.99);
+    }
+    report
   }
 
   /// Returns the top-k most frequent items.
   #[wasm_bindgen]
   pub fn top_k(&self) -> Vec<TopKItem> {
-    // For accuracy, we still need to aggregate all counts to handle hash collisions
-    let mut counts = HashMap::with_capacity(self.width);
+    self.select_top(self.summed_counts())
+  }
 
-    // Aggregate counts across all hash functions
-    for row in &self.counters {
-      for (item, count) in row {
-        if !item.is_empty() {
-          *counts.entry(item.clone()).or_insert(0) += count;
-        }
-      }
-    }
+  /// Returns the top-k most frequent tracked items that start with `prefix`, e.g. the top
+  /// search queries starting with "how to".
+  ///
+  /// # Arguments
+  ///
+  /// * `prefix` - The prefix items must start with
+  #[wasm_bindgen]
+  pub fn top_k_matching(&self, prefix: &str) -> Vec<TopKItem> {
+    self.select_top(self.summed_counts().into_iter().filter(|(item, _)| item.starts_with(prefix)))
+  }
+
+  /// Returns the top-k most frequent tracked items matching a regular expression. The pattern
+  /// matches anywhere in an item unless anchored with `^` and `$`.
+  ///
+  /// # Arguments
+  ///
+  /// * `pattern` - The regular expression items must match
+  #[wasm_bindgen]
+  pub fn top_k_filtered(&self, pattern: &str) -> Result<Vec<TopKItem>, SketchError> {
+    let regex =
+      Regex::new(pattern).map_err(|err| SketchError::InvalidParameter(err.to_string()))?;
+    Ok(self.select_top(self.summed_counts().into_iter().filter(|(item, _)| regex.is_match(item))))
+  }
+
+  /// Returns every item currently holding a counter with its count, not just the top k.
+  /// Counts match `query`, so they can be lower than the `top_k` count of the same item, which
+  /// sums its counters across rows.
+  #[wasm_bindgen]
+  pub fn entries(&self) -> Entries {
+    self.tracked_counts().into_iter().map(|(item, count)| (item, f64::from(count))).collect()
+  }
 
+  /// Formats the top-k items as CSV with an `item,count` header row, largest first. Building
+  /// the whole report in wasm avoids a getter call per row.
+  ///
+  /// # Arguments
+  ///
+  /// * `delimiter` - The field separator, e.g. `"\t"` for TSV; defaults to a comma
+  #[wasm_bindgen]
+  pub fn top_k_csv(&self, delimiter: Option<char>) -> Result<String, SketchError> {
+    let top = self.top_k();
+    write_csv(
+      ["item", "count"],
+      top.iter().map(|entry| (entry.item.as_str(), f64::from(entry.count))),
+      delimiter,
+    )
+  }
+
+  /// Formats every tracked item as CSV with an `item,weight` header row, as `entries` would
+  /// return them.
+  ///
+  /// # Arguments
+  ///
+  /// * `delimiter` - The field separator, e.g. `"\t"` for TSV; defaults to a comma
+  #[wasm_bindgen]
+  pub fn entries_csv(&self, delimiter: Option<char>) -> Result<String, SketchError> {
+    self.entries().to_csv(delimiter)
+  }
+}
+
+impl HeavyKeeper {
+  // Picks the k largest counts, largest first
+  fn select_top(&self, counts: impl IntoIterator<Item = (String, u32)>) -> Vec<TopKItem> {
     // Use min-heap for efficient top-k selection
     let mut heap = BinaryHeap::new();
 
@@ -246,10 +738,172 @@ impl HeavyKeeper {
       .collect();
 
     // Sort in descending order by count
-    items.sort_unstable_by(|a, b| b.count.cmp(&a.count));
+    items.sort_unstable_by_key(|item| Reverse(item.count));
 
     items
   }
+
+  // Sums each tracked item's counts across rows, which also covers items split by collisions
+  fn summed_counts(&self) -> HashMap<String, u32> {
+    let mut counts = HashMap::with_capacity(self.width);
+    for row in &self.counters {
+      for (item, count) in row {
+        if !item.is_empty() {
+          *counts.entry(item.clone()).or_insert(0) += count;
+        }
+      }
+    }
+    counts
+  }
+
+  // Reports each tracked item once with its `query` count, however many rows it holds
+  fn tracked_counts(&self) -> HashMap<String, u32> {
+    let mut counts = HashMap::with_capacity(self.width);
+    for row in &self.counters {
+      for (item, _) in row {
+        if !item.is_empty() && !counts.contains_key(item) {
+          counts.insert(item.clone(), self.counter_estimate(item));
+        }
+      }
+    }
+    counts
+  }
+
+  /// Replaces how stored items are hashed. Must be called before anything is added.
+  pub(crate) fn with_key_hash(mut self, key_hash: fn(&str) -> u64) -> Self {
+    self.key_hash = key_hash;
+    self
+  }
+
+  /// Returns the counter column of an item in each row.
+  pub(crate) fn positions(&self, item: &str) -> Vec<usize> {
+    self.hash_seeds.iter().map(|&seed| self.hash(item, seed)).collect()
+  }
+
+  /// Adds an item whose counter columns were computed with `positions`, letting sketches of the
+  /// same width and depth share one hashing pass. Does not update residual counts.
+  pub(crate) fn add_at(&mut self, item: &str, weight: u32, positions: &[usize]) {
+    if weight == 0 {
+      return;
+    }
+
+    self.stats.adds += 1;
+    let mut evicted_items = Vec::new();
+    for (row_index, (row, &pos)) in self.counters.iter_mut().zip(positions).enumerate() {
+      let counter = &mut row[pos];
+
+      if counter.0.is_empty() {
+        counter.0 = item.to_string();
+        counter.1 = weight;
+      } else if counter.0 == item {
+        counter.1 = counter.1.saturating_add(weight);
+      } else {
+        self.stats.collisions += 1;
+        // Decay the counter with probability decay
+        if self.rng.gen::<f64>() < self.decay {
+          if counter.1 > weight {
+            counter.1 -= weight;
+          } else {
+            let remaining = weight - counter.1;
+            let evicted = std::mem::replace(&mut counter.0, item.to_string());
+            counter.1 = remaining.max(1);
+            self.stats.evictions += 1;
+            if self.eviction_capacity > 0 {
+              if self.evictions.len() == self.eviction_capacity {
+                self.evictions.pop_front();
+              }
+              self.evictions.push_back(Eviction {
+                evicted: evicted.clone(),
+                replacement: item.to_string(),
+                row: row_index,
+                sequence: self.stats.adds as f64,
+              });
+            }
+            evicted_items.push(evicted);
+          }
+        }
+      }
+    }
+
+    // Exemplars are only kept while their item holds a counter
+    for evicted in evicted_items {
+      if self.counter_estimate(&evicted) == 0 {
+        self.exemplars.remove(&evicted);
+      }
+    }
+
+    // Update top-k with current estimated count
+    let estimated_count = self.counter_estimate_at(item, positions);
+    if estimated_count > 0 {
+      self.update_top_k(item, estimated_count);
+    }
+  }
+
+  // Estimates an item's count from the top-k counters alone
+  fn counter_estimate(&self, item: &str) -> u32 {
+    self.counter_estimate_at(item, &self.positions(item))
+  }
+
+  fn counter_estimate_at(&self, item: &str, positions: &[usize]) -> u32 {
+    let mut min_count = u32::MAX;
+
+    for (row, &pos) in self.counters.iter().zip(positions) {
+      let counter = &row[pos];
+
+      if counter.0 == item {
+        min_count = min_count.min(counter.1);
+      }
+    }
+
+    if min_count == u32::MAX {
+      0
+    } else {
+      min_count
+    }
+  }
+
+  /// Lowers the number of tracked top items, dropping the smallest ones along with the counts
+  /// kept for items outside the top k, and releases the freed capacity.
+  pub(crate) fn shrink_k(&mut self, k: usize) {
+    while self.top_k_heap.len() > k {
+      self.top_k_heap.pop();
+    }
+    self.top_k_heap.shrink_to_fit();
+    let ranked: HashSet<&str> =
+      self.top_k_heap.iter().map(|Reverse(entry)| entry.item.as_str()).collect();
+    self.all_counts.retain(|item, _| ranked.contains(item.as_str()));
+    self.all_counts.shrink_to_fit();
+    self.k = k;
+  }
+
+  pub(crate) fn k(&self) -> usize {
+    self.k
+  }
+
+  pub(crate) fn memory_bytes(&self) -> usize {
+    let counters: usize = self
+      .counters
+      .iter()
+      .flatten()
+      .map(|(item, _)| item.capacity() + size_of::<(String, u32)>())
+      .sum();
+    let tracked: usize =
+      self.all_counts.keys().map(|item| item.capacity() + size_of::<(String, u32)>()).sum();
+    let heap: usize = self
+      .top_k_heap
+      .iter()
+      .map(|Reverse(entry)| entry.item.capacity() + size_of::<HeapItem>())
+      .sum();
+    let residual = self.residual.as_ref().map_or(0, CountMinSketch::memory_bytes);
+    let exemplars: usize = self
+      .exemplars
+      .iter()
+      .map(|(item, reservoir)| {
+        item.capacity() + reservoir.samples.iter().map(String::capacity).sum::<usize>()
+      })
+      .sum();
+    counters + tracked + heap + residual + exemplars
+  }
 }
 
 #[cfg(test)]
@@ -277,6 +931,32 @@ mod tests {
     assert!(top[0].count >= 90); // Should have high count
   }
 
+  #[test]
+  fn test_try_new() {
+    let mut hk = HeavyKeeper::try_new(1000, 5, 3, 0.9).unwrap();
+    hk.add("item");
+    assert_eq!(hk.query("item"), 1);
+    assert!(matches!(
+      HeavyKeeper::try_new(usize::MAX, 2, 3, 0.9),
+      Err(SketchError::OutOfMemory(_))
+    ));
+  }
+
+  #[test]
+  fn test_clone_is_independent() {
+    let mut hk = HeavyKeeper::new(1000, 5, 3, 0.9);
+    for _ in 0..50 {
+      hk.add("before");
+    }
+    let snapshot = hk.deep_clone();
+    for _ in 0..50 {
+      hk.add("after");
+    }
+    assert_eq!(snapshot.query("before"), hk.query("before"));
+    assert_eq!(snapshot.query("after"), 0);
+    assert!(hk.query("after") > 0);
+  }
+
   #[test]
   fn test_query_functionality() {
     let mut hk = HeavyKeeper::new(1000, 5, 3, 0.9);
@@ -288,7 +968,7 @@ mod tests {
 
     // Query should return approximate count
     let count = hk.query("test_item");
-    assert!(count >= 40 && count <= 60); // Allow some error margin
+    assert!((40..=60).contains(&count)); // Allow some error margin
 
     // Query non-existent item should return 0
     assert_eq!(hk.query("nonexistent"), 0);
@@ -359,4 +1039,241 @@ mod tests {
     // The most frequent items should be at the top
     assert!(top[0].item == "item0" || top[0].count >= 15);
   }
+
+  #[test]
+  fn test_weighted_ranking() {
+    let mut hk = HeavyKeeper::new(1000, 5, 3, 0.9);
+
+    // Frequent but cheap versus rare but expensive
+    for _ in 0..100 {
+      hk.add_weighted("/health", 1);
+    }
+    for _ in 0..10 {
+      hk.add_weighted("/report", 500);
+    }
+
+    let top = hk.top_k();
+    assert_eq!(top[0].item, "/report");
+    assert!(hk.query("/report") >= 5000);
+    assert!(hk.query("/health") <= 100);
+  }
+
+  #[test]
+  fn test_residual_counts() {
+    let mut hk = HeavyKeeper::new(1, 1, 1, 1.0);
+    hk.enable_residual_counts(1000, 4);
+    for _ in 0..100 {
+      hk.add("leader");
+    }
+    hk.add_weighted("evictor", 200);
+    hk.add_weighted("leader", 5);
+
+    // "leader" lost the only counter, but its count survives in the backing sketch
+    assert_eq!(hk.top_k()[0].item, "evictor");
+    assert!(hk.query("leader") >= 105);
+    assert_eq!(hk.query("never_added"), 0);
+
+    hk.disable_residual_counts();
+    assert_eq!(hk.query("leader"), 0);
+  }
+
+  #[test]
+  fn test_csv_export() {
+    let mut hk = HeavyKeeper::new(100, 3, 1, 0.9);
+    for _ in 0..5 {
+      hk.add("frequent");
+    }
+    hk.add("rare");
+
+    let count = hk.top_k()[0].count();
+    assert_eq!(hk.top_k_csv(None).unwrap(), format!("item,count\r\nfrequent,{}\r\n", count));
+    let tsv = hk.entries_csv(Some('\t')).unwrap();
+    assert!(tsv.starts_with("item\tweight\r\nfrequent\t"));
+    assert_eq!(tsv.lines().count(), 3);
+  }
+
+  #[test]
+  fn test_entries() {
+    let mut hk = HeavyKeeper::new(100, 3, 1, 0.9);
+    for _ in 0..5 {
+      hk.add("frequent");
+    }
+    hk.add("rare");
+
+    let entries = hk.entries();
+    assert_eq!(entries.items(), ["frequent", "rare"]);
+    // top_k keeps summing an item's counters across rows, unlike entries
+    let summed: u32 = hk
+      .counters
+      .iter()
+      .flatten()
+      .filter(|(item, _)| item == "frequent")
+      .map(|(_, count)| count)
+      .sum();
+    assert_eq!(hk.top_k()[0].count(), summed);
+    assert!(summed > hk.query("frequent"));
+    assert_eq!(entries.weights(), [f64::from(hk.query("frequent")), f64::from(hk.query("rare"))]);
+
+    hk.enable_exemplars(2);
+    for i in 0..4 {
+      hk.add_with_exemplar("frequent", format!("event {}", i));
+    }
+    let samples = hk.exemplar_entries("frequent");
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples.weights(), [2.0, 2.0]);
+    assert!(hk.exemplar_entries("rare").is_empty());
+  }
+
+  #[test]
+  fn test_filtered_top_k() {
+    let mut hk = HeavyKeeper::new(100, 3, 2, 0.9);
+    for (item, count) in [("how to cook", 5), ("what is rust", 9), ("how to fly", 3), ("how to", 1)]
+    {
+      for _ in 0..count {
+        hk.add(item);
+      }
+    }
+
+    let matching: Vec<String> = hk.top_k_matching("how to ").iter().map(|i| i.item()).collect();
+    assert_eq!(matching, ["how to cook", "how to fly"]);
+    let filtered = hk.top_k_filtered("^(what|how to) [a-z ]+$").unwrap();
+    assert_eq!(filtered[0].item(), "what is rust");
+    assert_eq!(filtered.len(), 2);
+    assert!(hk.top_k_matching("why").is_empty());
+    assert!(hk.top_k_filtered("(").is_err());
+  }
+
+  #[test]
+  fn test_eviction_log() {
+    let mut hk = HeavyKeeper::new(1, 2, 2, 1.0);
+    hk.add("first");
+    hk.add("second");
+    assert!(hk.drain_evictions().is_empty());
+
+    hk.enable_eviction_log(3);
+    hk.add("third");
+    let evictions = hk.drain_evictions();
+    assert_eq!(evictions.len(), 2);
+    assert_eq!(evictions[0].evicted(), "second");
+    assert_eq!(evictions[0].replacement(), "third");
+    assert_eq!(evictions[1].row(), 1);
+    assert_eq!(evictions[0].sequence(), 3.0);
+    assert!(hk.drain_evictions().is_empty());
+
+    for item in ["a", "b", "c"] {
+      hk.add(item);
+    }
+    assert_eq!(hk.drain_evictions().len(), 3);
+    hk.enable_eviction_log(0);
+    hk.add("d");
+    assert!(hk.drain_evictions().is_empty());
+  }
+
+  #[test]
+  fn test_exemplars() {
+    let mut hk = HeavyKeeper::new(1, 1, 1, 1.0);
+    hk.add_with_exemplar("ignored", "before enabling".to_string());
+    assert!(hk.exemplars("ignored").is_empty());
+
+    hk.enable_exemplars(3);
+    for i in 0..50 {
+      hk.add_with_exemplar("leader", format!("event {}", i));
+    }
+    let kept = hk.exemplars("leader");
+    assert_eq!(kept.len(), 3);
+    assert!(kept.iter().all(|payload| payload.starts_with("event ")));
+
+    // Losing the only counter drops the item's exemplars
+    hk.add_weighted("evictor", 100);
+    assert!(hk.exemplars("leader").is_empty());
+  }
+
+  #[test]
+  fn test_stability() {
+    let mut hk = HeavyKeeper::new(1000, 5, 2, 0.9);
+    for _ in 0..3 {
+      for _ in 0..100 {
+        hk.add("steady");
+      }
+      hk.record_snapshot();
+    }
+    for _ in 0..1000 {
+      hk.add("burst");
+    }
+    hk.record_snapshot();
+
+    let steady = hk.stability("steady").unwrap();
+    assert_eq!(steady.streak(), 4);
+    assert_eq!(steady.mean_rank(), 0.25);
+    assert!((steady.rank_variance() - 0.1875).abs() < 1e-9);
+
+    let burst = hk.stability("burst").unwrap();
+    assert_eq!(burst.streak(), 1);
+    assert_eq!(burst.rank_variance(), 0.0);
+    assert!(hk.stability("absent").is_none());
+  }
+
+  #[test]
+  fn test_tuning_report() {
+    // Far too narrow for the number of distinct items
+    let mut narrow = HeavyKeeper::new(8, 2, 4, 0.9);
+    for i in 0..2000 {
+      narrow.add(&format!("item_{}", i % 500));
+    }
+    let report = narrow.tuning_report();
+    assert!(report.collision_rate() > 0.5);
+    assert_eq!(report.recommended_width(), 16);
+    assert_eq!(report.recommended_depth(), 3);
+
+    // Too few adds to judge
+    let fresh = HeavyKeeper::new(1000, 5, 3, 0.9);
+    assert_eq!(fresh.tuning_report().recommended_width(), 1000);
+  }
+
+  #[test]
+  fn test_prime_from() {
+    let mut previous = HeavyKeeper::new(1000, 5, 3, 0.9);
+    for _ in 0..100 {
+      previous.add("leader");
+    }
+    for _ in 0..3 {
+      previous.add("noise");
+    }
+
+    let mut next = HeavyKeeper::new(1000, 5, 3, 0.9);
+    next.prime_from(&previous, 0.5).unwrap();
+    assert_eq!(next.query("leader"), previous.query("leader") / 2);
+    assert_eq!(next.query("noise"), 1);
+    assert_eq!(next.top_k()[0].item, "leader");
+
+    next.prime_from(&previous, 0.1).unwrap();
+    assert_eq!(next.query("noise"), 0);
+
+    assert!(next.prime_from(&HeavyKeeper::new(10, 5, 3, 0.9), 0.5).is_err());
+    assert!(next.prime_from(&previous, 1.5).is_err());
+  }
+
+  #[test]
+  fn test_prime_from_rebuilds_exemplars() {
+    let mut previous = HeavyKeeper::new(1000, 5, 3, 0.9);
+    previous.enable_exemplars(4);
+    for i in 0..10 {
+      previous.add_with_exemplar("leader", format!("event {}", i));
+    }
+    previous.add_with_exemplar("noise", "rare".to_string());
+
+    let mut next = HeavyKeeper::new(1000, 5, 3, 0.9);
+    next.enable_exemplars(2);
+    next.add_with_exemplar("stale", "old epoch".to_string());
+    next.prime_from(&previous, 0.5).unwrap();
+    assert!(next.exemplars("stale").is_empty());
+    assert!(next.exemplars("noise").is_empty());
+    let kept = next.exemplars("leader");
+    assert_eq!(kept.len(), 2);
+    assert!(kept.iter().all(|payload| previous.exemplars("leader").contains(payload)));
+
+    let mut unsampled = HeavyKeeper::new(1000, 5, 3, 0.9);
+    unsampled.prime_from(&previous, 0.5).unwrap();
+    assert!(unsampled.exemplars("leader").is_empty());
+  }
 }
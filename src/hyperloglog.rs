@@ -1,14 +1,21 @@
 use wasm_bindgen::prelude::*;
 
+use crate::column::for_each_value;
+use crate::error::SketchError;
+use crate::memory::try_zeroed;
+use crate::replay::{Journal, ReplayLog, ReplayOp, ReplayTarget};
+
 /// A probabilistic data structure for counting the number of distinct elements in a set.
 /// It uses a small amount of memory while providing an estimate of the cardinality.
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HyperLogLog {
   registers: Vec<u8>,
   m: usize,
   p: u8,
   alpha: f64,
+  // Recording and write-ahead log, either of which may be active
+  journal: Journal,
 }
 
 #[wasm_bindgen]
@@ -28,25 +35,51 @@ impl HyperLogLog {
 
     let m = 1usize << p; // 2^p
     let registers = vec![0u8; m];
+    let alpha = Self::alpha(m);
+
+    Ok(HyperLogLog { registers, m, p, alpha, journal: Journal::new(ReplayTarget::HyperLogLog) })
+  }
+
+  /// Creates a new HyperLogLog like the constructor, but fails with an out-of-memory error
+  /// instead of aborting if the registers cannot be allocated.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The precision parameter (between 4 and 16)
+  #[wasm_bindgen]
+  pub fn try_new(precision: Option<u8>) -> Result<HyperLogLog, SketchError> {
+    let p = precision.unwrap_or(14);
+    if !(4..=16).contains(&p) {
+      return Err(SketchError::InvalidParameter("precision must be between 4 and 16".to_string()));
+    }
+    let m = 1usize << p;
+    let registers = try_zeroed(m)?;
+    Ok(HyperLogLog {
+      registers,
+      m,
+      p,
+      alpha: Self::alpha(m),
+      journal: Journal::new(ReplayTarget::HyperLogLog),
+    })
+  }
 
-    // Calculate alpha constant based on m
-    let alpha = match m {
+  // Calculate alpha constant based on m
+  fn alpha(m: usize) -> f64 {
+    match m {
       16 => 0.673,
       32 => 0.697,
       64 => 0.709,
       _ => 0.7213 / (1.0 + 1.079 / (m as f64)),
-    };
-
-    Ok(HyperLogLog { registers, m, p, alpha })
+    }
   }
 
-  fn hash(&self, value: &str) -> u32 {
+  pub(crate) fn hash(value: &[u8]) -> u32 {
     // MurmurHash3 32-bit implementation
     let mut h1 = 0xdeadbeef_u32;
     let c1 = 0xcc9e2d51_u32;
     let c2 = 0x1b873593_u32;
 
-    for byte in value.bytes() {
+    for &byte in value {
       let mut k1 = byte as u32;
       k1 = k1.wrapping_mul(c1);
       k1 = k1.rotate_left(15);
@@ -74,14 +107,69 @@ impl HyperLogLog {
   /// * `value` - The item to add
   #[wasm_bindgen]
   pub fn add(&mut self, value: &str) {
-    let hash = self.hash(value);
-    let index = (hash & ((self.m - 1) as u32)) as usize; // Get first p bits
-    let w = hash >> self.p; // Get remaining bits
+    self.add_hash(Self::hash(value.as_bytes()));
+  }
 
-    let leading_zeros =
-      if w == 0 { 32 - self.p + 1 } else { (w << self.p).leading_zeros() as u8 + 1 };
+  /// Adds every number in a `Uint32Array`, hashing each natively instead of converting it to a
+  /// string. Numeric keys are hashed from their binary form, so `add_u32_array([42])` and
+  /// `add('42')` count as different items.
+  ///
+  /// # Arguments
+  ///
+  /// * `values` - The numeric items to add
+  #[wasm_bindgen]
+  pub fn add_u32_array(&mut self, values: &[u32]) {
+    for value in values {
+      self.add_hash(Self::hash(&value.to_le_bytes()));
+    }
+  }
+
+  /// Adds every number in a `Float64Array`, hashing each natively instead of converting it to a
+  /// string. `0` and `-0` count as the same item, as do all NaN values. Numeric keys are hashed
+  /// from their binary form, so they never collide with string items.
+  ///
+  /// # Arguments
+  ///
+  /// * `values` - The numeric items to add
+  #[wasm_bindgen]
+  pub fn add_f64_array(&mut self, values: &[f64]) {
+    for &value in values {
+      let canonical = if value == 0.0 {
+        0.0
+      } else if value.is_nan() {
+        f64::NAN
+      } else {
+        value
+      };
+      self.add_hash(Self::hash(&canonical.to_bits().to_le_bytes()));
+    }
+  }
 
-    self.registers[index] = self.registers[index].max(leading_zeros);
+  fn add_hash(&mut self, hash: u32) {
+    let (index, leading_zeros) = Self::slot(hash, self.p);
+    self.raise_register(index, leading_zeros);
+    self.journal.record(ReplayOp::Update(u64::from(hash)));
+  }
+
+  /// Adds every non-empty value of one column of a CSV, newline-delimited or Arrow IPC buffer,
+  /// parsing it entirely in wasm so no JS string array is built. Nothing is added if any row is
+  /// malformed.
+  /// Returns the number of values added.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The column data as UTF-8 text or an Arrow IPC stream or file (a `Uint8Array`)
+  /// * `format` - `"csv"` (with a header row), `"lines"` (one value per line) or `"arrow"` (a
+  ///   `Utf8` column, optionally dictionary-encoded; nulls are skipped)
+  /// * `column` - The zero-based column to read; defaults to 0
+  #[wasm_bindgen]
+  pub fn ingest_column(
+    &mut self,
+    buffer: &[u8],
+    format: &str,
+    column: Option<usize>,
+  ) -> Result<usize, SketchError> {
+    for_each_value(buffer, format, column, |value| self.add(value))
   }
 
   /// Returns the estimated number of distinct items in the set.
@@ -126,17 +214,374 @@ impl HyperLogLog {
     if self.m != other.m {
       return Err(JsValue::from_str("Cannot merge HyperLogLog instances with different precision"));
     }
+    self.journal.check_unlogged("merge counters")?;
 
+    // Clamp incoming values so a corrupted sketch cannot poison this one
+    let max_rank = self.max_rank();
     for i in 0..self.m {
-      self.registers[i] = self.registers[i].max(other.registers[i]);
+      self.registers[i] = self.registers[i].max(other.registers[i].min(max_rank));
     }
 
     Ok(())
   }
 
+  /// Estimates the number of distinct items in this set but not in `other` (|A \ B|),
+  /// computed as |A ∪ B| - |B|. This is a best-effort estimate: its absolute error is on the
+  /// order of the union's error, so it is unreliable when the difference is small relative to
+  /// the union. Both instances must have the same precision.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - The HyperLogLog instance for set B
+  #[wasm_bindgen]
+  pub fn estimate_difference(&self, other: &HyperLogLog) -> Result<f64, SketchError> {
+    if self.m != other.m {
+      return Err(SketchError::Incompatible(
+        "cannot compare HyperLogLog instances with different precision".to_string(),
+      ));
+    }
+
+    let union_registers =
+      self.registers.iter().zip(&other.registers).map(|(&a, &b)| a.max(b)).collect();
+    let union = HyperLogLog {
+      registers: union_registers,
+      journal: Journal::new(ReplayTarget::HyperLogLog),
+      ..*self
+    };
+    Ok((union.count() - other.count()).max(0.0))
+  }
+
+  /// Returns the number of registers holding values that are impossible for the configured
+  /// precision, which indicates corrupted or adversarial input. A healthy counter returns 0.
+  #[wasm_bindgen]
+  pub fn validate(&self) -> usize {
+    let max_rank = self.max_rank();
+    self.registers.iter().filter(|&&rank| rank > max_rank).count()
+  }
+
+  /// Clamps impossible register values to the largest possible value for the configured
+  /// precision, returning the number of registers repaired.
+  #[wasm_bindgen]
+  pub fn repair(&mut self) -> usize {
+    let max_rank = self.max_rank();
+    let mut repaired = 0;
+    for rank in self.registers.iter_mut().filter(|rank| **rank > max_rank) {
+      *rank = max_rank;
+      repaired += 1;
+    }
+    repaired
+  }
+
+  // The largest rank a 32-bit hash can produce once p bits are used for the index
+  fn max_rank(&self) -> u8 {
+    32 - self.p + 1
+  }
+
+  /// Returns an independent deep copy of the HyperLogLog, e.g. to snapshot it while the original
+  /// keeps changing and compare the two later.
+  #[wasm_bindgen(js_name = clone)]
+  pub fn deep_clone(&self) -> HyperLogLog {
+    self.clone()
+  }
+
   /// Clears all counters in the HyperLogLog instance.
   #[wasm_bindgen]
   pub fn clear(&mut self) {
     self.registers.fill(0);
+    self.journal.record(ReplayOp::Clear);
+  }
+
+  /// Starts recording every add and clear applied to the counter.
+  /// Any previous recording is discarded.
+  #[wasm_bindgen]
+  pub fn start_recording(&mut self) {
+    self.journal.start_recording();
+  }
+
+  /// Stops recording and returns the recorded operations as a compact buffer of hashed keys.
+  /// Returns an empty buffer if the counter was not recording.
+  #[wasm_bindgen]
+  pub fn stop_recording(&mut self) -> Vec<u8> {
+    self.journal.stop_recording()
+  }
+
+  /// Starts keeping the most recent `capacity` operations in a write-ahead log, so updates made
+  /// since the last persisted snapshot can be recovered with `replay` after a crash. Older
+  /// operations are dropped, which `wal_overflowed` reports. Any previous log is discarded.
+  ///
+  /// While recording or keeping a write-ahead log, mutations that cannot be replayed, such as
+  /// merges and resizes, fail instead of silently diverging from the log.
+  ///
+  /// # Arguments
+  ///
+  /// * `capacity` - The maximum number of operations kept
+  #[wasm_bindgen]
+  pub fn enable_wal(&mut self, capacity: usize) {
+    self.journal.enable_wal(capacity);
+  }
+
+  /// Stops keeping a write-ahead log.
+  #[wasm_bindgen]
+  pub fn disable_wal(&mut self) {
+    self.journal.disable_wal();
+  }
+
+  /// Returns the write-ahead log in the same format as `stop_recording`, ready for `replay`.
+  /// Returns an empty buffer if the counter has no write-ahead log.
+  #[wasm_bindgen]
+  pub fn wal_bytes(&self) -> Vec<u8> {
+    self.journal.wal_bytes()
+  }
+
+  /// Returns true if the write-ahead log has dropped operations since it was last truncated,
+  /// so replaying it onto the last snapshot would lose updates; persist a new snapshot instead.
+  #[wasm_bindgen]
+  pub fn wal_overflowed(&self) -> bool {
+    self.journal.wal_overflowed()
+  }
+
+  /// Empties the write-ahead log; call after persisting a snapshot of the counter.
+  #[wasm_bindgen]
+  pub fn truncate_wal(&mut self) {
+    self.journal.truncate_wal();
+  }
+
+  /// Re-applies a buffer produced by `stop_recording` to this counter.
+  /// The buffer is validated fully before any operation is applied.
+  ///
+  /// # Arguments
+  ///
+  /// * `buffer` - The recorded operations
+  #[wasm_bindgen]
+  pub fn replay(&mut self, buffer: &[u8]) -> Result<(), SketchError> {
+    let ops = ReplayLog::parse(buffer, ReplayTarget::HyperLogLog)?;
+    if ops.iter().any(|op| matches!(op, ReplayOp::Update(hash) if *hash > u64::from(u32::MAX))) {
+      return Err(SketchError::Malformed("HyperLogLog hashes are 32 bits wide".to_string()));
+    }
+    for op in ops {
+      match op {
+        // Weighted updates only parse for CountMinSketch logs
+        ReplayOp::Update(hash) | ReplayOp::UpdateBy(hash, _) => self.add_hash(hash as u32),
+        ReplayOp::Clear => self.clear(),
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns the precision parameter.
+  #[wasm_bindgen]
+  pub fn precision(&self) -> u8 {
+    self.p
+  }
+
+  /// Shrinks the register array by `factor` for bandwidth-limited sync, lowering the precision
+  /// by `log2(factor)`. A server can merge the result after downsampling its own copy the same way.
+  ///
+  /// # Arguments
+  ///
+  /// * `factor` - The shrink factor; a power of two of at least 2 that keeps the precision at 4
+  ///   or more
+  #[wasm_bindgen]
+  pub fn downsample(&mut self, factor: u32) -> Result<(), SketchError> {
+    let shift = factor.trailing_zeros();
+    if factor < 2 || !factor.is_power_of_two() || shift + 4 > u32::from(self.p) {
+      return Err(SketchError::InvalidParameter(format!(
+        "factor must be a power of two that decreases the precision from {} to 4 or more",
+        self.p
+      )));
+    }
+    self.reduce_precision(self.p - shift as u8)
+  }
+
+  /// Lowers the precision in place, shrinking memory by a factor of 2^(precision - new_precision).
+  /// The result is identical to having added every item at the lower precision.
+  ///
+  /// # Arguments
+  ///
+  /// * `new_precision` - The target precision (between 4 and the current precision)
+  #[wasm_bindgen]
+  pub fn reduce_precision(&mut self, new_precision: u8) -> Result<(), SketchError> {
+    self.journal.check_unlogged("reduce the precision")?;
+    if new_precision < 4 || new_precision > self.p {
+      return Err(SketchError::InvalidParameter(
+        "new precision must be between 4 and the current precision".to_string(),
+      ));
+    }
+
+    let new_m = 1usize << new_precision;
+    let dropped_bits = u32::from(self.p - new_precision);
+    let saturated = 32 - self.p + 1;
+    let mut registers = vec![0u8; new_m];
+
+    for (index, &rank) in self.registers.iter().enumerate() {
+      if rank == 0 {
+        continue;
+      }
+      // The dropped index bits sit just below the rank bits, so a register that saw only
+      // zeros keeps counting leading zeros into them
+      let rank = if rank < saturated {
+        rank
+      } else {
+        let high = (index >> new_precision) as u32;
+        if high == 0 {
+          32 - new_precision + 1
+        } else {
+          saturated - 1 + (high.leading_zeros() - (32 - dropped_bits)) as u8 + 1
+        }
+      };
+      let new_index = index & (new_m - 1);
+      registers[new_index] = registers[new_index].max(rank);
+    }
+
+    self.registers = registers;
+    self.m = new_m;
+    self.p = new_precision;
+    self.alpha = Self::alpha(new_m);
+    Ok(())
+  }
+}
+
+impl HyperLogLog {
+  /// Splits a hash into its register index and rank for precision `p`.
+  pub(crate) fn slot(hash: u32, p: u8) -> (usize, u8) {
+    let index = (hash & ((1u32 << p) - 1)) as usize; // Get first p bits
+    let w = hash >> p; // Get remaining bits
+
+    let leading_zeros = if w == 0 { 32 - p + 1 } else { (w << p).leading_zeros() as u8 + 1 };
+    (index, leading_zeros)
+  }
+
+  /// Raises a register to at least `rank`.
+  pub(crate) fn raise_register(&mut self, index: usize, rank: u8) {
+    self.registers[index] = self.registers[index].max(rank);
+  }
+
+  pub(crate) fn memory_bytes(&self) -> usize {
+    self.registers.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_count() {
+    let mut hll = HyperLogLog::new(Some(12)).unwrap();
+    for i in 0..10000 {
+      hll.add(&format!("item{}", i));
+    }
+    let count = hll.count();
+    assert!((9000.0..=11000.0).contains(&count));
+  }
+
+  #[test]
+  fn test_try_new() {
+    let hll = HyperLogLog::try_new(Some(12)).unwrap();
+    assert_eq!(hll.registers, HyperLogLog::new(Some(12)).unwrap().registers);
+    assert!(matches!(HyperLogLog::try_new(Some(17)), Err(SketchError::InvalidParameter(_))));
+  }
+
+  #[test]
+  fn test_reduce_precision_matches_direct_insertion() {
+    let mut high = HyperLogLog::new(Some(12)).unwrap();
+    let mut low = HyperLogLog::new(Some(8)).unwrap();
+    for i in 0..5000 {
+      let item = format!("item{}", i);
+      high.add(&item);
+      low.add(&item);
+    }
+
+    high.reduce_precision(8).unwrap();
+    assert_eq!(high.precision(), 8);
+    assert_eq!(high.registers, low.registers);
+    assert_eq!(high.count(), low.count());
+    assert!(high.reduce_precision(10).is_err());
+  }
+
+  #[test]
+  fn test_validate_and_repair() {
+    let mut hll = HyperLogLog::new(Some(14)).unwrap();
+    for i in 0..1000 {
+      hll.add(&format!("item{}", i));
+    }
+    assert_eq!(hll.validate(), 0);
+
+    let mut corrupted = HyperLogLog::new(Some(14)).unwrap();
+    corrupted.registers[0] = 200;
+    corrupted.registers[1] = 19;
+    assert_eq!(corrupted.validate(), 1);
+
+    hll.merge(&corrupted).unwrap();
+    assert_eq!(hll.validate(), 0);
+    assert_eq!(hll.registers[0], 19);
+
+    assert_eq!(corrupted.repair(), 1);
+    assert_eq!(corrupted.validate(), 0);
+    assert!(corrupted.count().is_finite());
+  }
+
+  #[test]
+  fn test_estimate_difference() {
+    let mut last_week = HyperLogLog::new(Some(14)).unwrap();
+    let mut this_week = HyperLogLog::new(Some(14)).unwrap();
+    for i in 0..10000 {
+      last_week.add(&format!("user{}", i));
+    }
+    for i in 7000..15000 {
+      this_week.add(&format!("user{}", i));
+    }
+
+    // 7000 users were active last week but not this week
+    let churned = last_week.estimate_difference(&this_week).unwrap();
+    assert!((6000.0..=8000.0).contains(&churned), "churned = {}", churned);
+    assert!(last_week.estimate_difference(&HyperLogLog::new(Some(10)).unwrap()).is_err());
+  }
+
+  #[test]
+  fn test_add_numeric_arrays() {
+    let mut hll = HyperLogLog::new(Some(12)).unwrap();
+    let ids: Vec<u32> = (0..10000).collect();
+    hll.add_u32_array(&ids);
+    hll.add_u32_array(&ids[..5000]);
+    assert!((9000.0..=11000.0).contains(&hll.count()));
+
+    let mut floats = HyperLogLog::new(Some(12)).unwrap();
+    floats.add_f64_array(&[0.0, -0.0, 1.5, f64::NAN, -f64::NAN]);
+    assert_eq!(floats.count(), 3.0);
+  }
+
+  #[test]
+  fn test_downsample() {
+    let mut hll = HyperLogLog::new(Some(12)).unwrap();
+    let mut coarse = HyperLogLog::new(Some(10)).unwrap();
+    for i in 0..5000 {
+      hll.add(&format!("item_{}", i));
+      coarse.add(&format!("item_{}", i));
+    }
+    hll.downsample(4).unwrap();
+    assert_eq!(hll.precision(), 10);
+    assert_eq!(hll.count(), coarse.count());
+
+    assert!(hll.downsample(3).is_err());
+    assert!(hll.downsample(1).is_err());
+    let err = hll.downsample(1 << 7).unwrap_err();
+    assert!(err.to_string().contains("decreases the precision"), "{}", err);
+    hll.downsample(1 << 6).unwrap();
+    assert_eq!(hll.precision(), 4);
+  }
+
+  #[test]
+  fn test_record_and_replay() {
+    let mut hll = HyperLogLog::new(Some(10)).unwrap();
+    hll.start_recording();
+    for i in 0..1000 {
+      hll.add(&format!("item{}", i));
+    }
+    let log = hll.stop_recording();
+
+    let mut replayed = HyperLogLog::new(Some(10)).unwrap();
+    replayed.replay(&log).unwrap();
+    assert_eq!(replayed.registers, hll.registers);
   }
 }
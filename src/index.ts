@@ -1,7 +1,63 @@
 import * as wasm from '../pkg/sketch_wasm';
 
 export const BloomFilter = wasm.BloomFilter;
+export const ShardedBloomFilter = wasm.ShardedBloomFilter;
+export const ScalableBloomFilter = wasm.ScalableBloomFilter;
+export const PartitionedBloomFilter = wasm.PartitionedBloomFilter;
+export const StableBloomFilter = wasm.StableBloomFilter;
+export const ExpiringBloomFilter = wasm.ExpiringBloomFilter;
+export const BloomFilterLoader = wasm.BloomFilterLoader;
+export const BloomFilterView = wasm.BloomFilterView;
+export const BloomCascade = wasm.BloomCascade;
+export const CountingBloomFilter = wasm.CountingBloomFilter;
+export const SpectralBloomFilter = wasm.SpectralBloomFilter;
+export const DeletableBloomFilter = wasm.DeletableBloomFilter;
+export const CuckooFilter = wasm.CuckooFilter;
+export const XorFilter = wasm.XorFilter;
+export const RedisBloomFilter = wasm.RedisBloomFilter;
 export const HyperLogLog = wasm.HyperLogLog;
+export const SlidingHyperLogLog = wasm.SlidingHyperLogLog;
+export const CountingHyperLogLog = wasm.CountingHyperLogLog;
+export const AdaptiveHyperLogLog = wasm.AdaptiveHyperLogLog;
+export const CardinalityRecorder = wasm.CardinalityRecorder;
+export const ActivityHeatmap = wasm.ActivityHeatmap;
 export const CountMinSketch = wasm.CountMinSketch;
+export const CountMinSketch64 = wasm.CountMinSketch64;
+export const CountMinSketchF64 = wasm.CountMinSketchF64;
+export const FilteredCounter = wasm.FilteredCounter;
+export const TimestampedCountMinSketch = wasm.TimestampedCountMinSketch;
+export const RangeCountSketch = wasm.RangeCountSketch;
 export const HeavyKeeper = wasm.HeavyKeeper;
+export const TieredHeavyKeeper = wasm.TieredHeavyKeeper;
+export const PairHeavyKeeper = wasm.PairHeavyKeeper;
+export const WeightedBloomFilter = wasm.WeightedBloomFilter;
+export const WeightedMinHash = wasm.WeightedMinHash;
+export const WeightedCardinality = wasm.WeightedCardinality;
+export const SyncChannel = wasm.SyncChannel;
+export const MemoryPressurePolicy = wasm.MemoryPressurePolicy;
+export const SkewEstimator = wasm.SkewEstimator;
+export const Entries = wasm.Entries;
 export const init = wasm.init;
+export const hash_test_vectors = wasm.hash_test_vectors;
+export const enable_test_mode = wasm.enable_test_mode;
+export const disable_test_mode = wasm.disable_test_mode;
+export const set_clock_ms = wasm.set_clock_ms;
+export const set_clock = wasm.set_clock;
+export const reserve_memory = wasm.reserve_memory;
+export const convert = {
+  bloom_from_entries: wasm.bloom_from_entries,
+  count_min_from_entries: wasm.count_min_from_entries,
+  hyperloglog_from_entries: wasm.hyperloglog_from_entries,
+};
+
+export {
+  initSketchWasm,
+  missingFeatures,
+  SketchWasmMemoryError,
+  SketchWasmUnsupportedError,
+} from './init';
+export type { InitOptions, SketchWasm } from './init';
+export { processInSlices, processLinesInSlices } from './batch';
+export type { BatchOptions } from './batch';
+export { fromResponseStream } from './stream';
+export type { StreamedSketch, StreamedSketchKind } from './stream';
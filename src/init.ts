@@ -0,0 +1,210 @@
+import type * as Wasm from '../pkg/sketch_wasm';
+
+/** Every sketch constructor and utility, loaded and ready to use. */
+export interface SketchWasm {
+  BloomFilter: typeof Wasm.BloomFilter;
+  ShardedBloomFilter: typeof Wasm.ShardedBloomFilter;
+  ScalableBloomFilter: typeof Wasm.ScalableBloomFilter;
+  PartitionedBloomFilter: typeof Wasm.PartitionedBloomFilter;
+  StableBloomFilter: typeof Wasm.StableBloomFilter;
+  ExpiringBloomFilter: typeof Wasm.ExpiringBloomFilter;
+  BloomFilterLoader: typeof Wasm.BloomFilterLoader;
+  BloomFilterView: typeof Wasm.BloomFilterView;
+  BloomCascade: typeof Wasm.BloomCascade;
+  WeightedBloomFilter: typeof Wasm.WeightedBloomFilter;
+  CountingBloomFilter: typeof Wasm.CountingBloomFilter;
+  SpectralBloomFilter: typeof Wasm.SpectralBloomFilter;
+  DeletableBloomFilter: typeof Wasm.DeletableBloomFilter;
+  CuckooFilter: typeof Wasm.CuckooFilter;
+  XorFilter: typeof Wasm.XorFilter;
+  RedisBloomFilter: typeof Wasm.RedisBloomFilter;
+  HyperLogLog: typeof Wasm.HyperLogLog;
+  SlidingHyperLogLog: typeof Wasm.SlidingHyperLogLog;
+  CountingHyperLogLog: typeof Wasm.CountingHyperLogLog;
+  AdaptiveHyperLogLog: typeof Wasm.AdaptiveHyperLogLog;
+  CardinalityRecorder: typeof Wasm.CardinalityRecorder;
+  ActivityHeatmap: typeof Wasm.ActivityHeatmap;
+  CountMinSketch: typeof Wasm.CountMinSketch;
+  CountMinSketch64: typeof Wasm.CountMinSketch64;
+  CountMinSketchF64: typeof Wasm.CountMinSketchF64;
+  FilteredCounter: typeof Wasm.FilteredCounter;
+  TimestampedCountMinSketch: typeof Wasm.TimestampedCountMinSketch;
+  RangeCountSketch: typeof Wasm.RangeCountSketch;
+  HeavyKeeper: typeof Wasm.HeavyKeeper;
+  TieredHeavyKeeper: typeof Wasm.TieredHeavyKeeper;
+  PairHeavyKeeper: typeof Wasm.PairHeavyKeeper;
+  WeightedMinHash: typeof Wasm.WeightedMinHash;
+  WeightedCardinality: typeof Wasm.WeightedCardinality;
+  SyncChannel: typeof Wasm.SyncChannel;
+  MemoryPressurePolicy: typeof Wasm.MemoryPressurePolicy;
+  SkewEstimator: typeof Wasm.SkewEstimator;
+  /** Deterministic randomness and time for integration tests. */
+  testing: {
+    enable_test_mode: typeof Wasm.enable_test_mode;
+    disable_test_mode: typeof Wasm.disable_test_mode;
+    set_clock_ms: typeof Wasm.set_clock_ms;
+    set_clock: typeof Wasm.set_clock;
+  };
+  /** Builds one summary type from the `entries()` export of another. */
+  convert: {
+    bloom_from_entries: typeof Wasm.bloom_from_entries;
+    count_min_from_entries: typeof Wasm.count_min_from_entries;
+    hyperloglog_from_entries: typeof Wasm.hyperloglog_from_entries;
+  };
+  /** Hash outputs for verifying other implementations against this one. */
+  conformance: {
+    hash_test_vectors: typeof Wasm.hash_test_vectors;
+  };
+  /** Up-front memory reservation for large sketches. */
+  memory: {
+    reserve_memory: typeof Wasm.reserve_memory;
+  };
+}
+
+/** Options for `initSketchWasm`, applied on every call. */
+export interface InitOptions {
+  /**
+   * Bytes of memory to reserve right after loading, e.g. the total `memory_bytes` of the
+   * sketches the page will create, so they do not grow memory during user interaction.
+   */
+  reserveBytes?: number;
+}
+
+/** Thrown by `initSketchWasm` when the environment cannot run the module. */
+export class SketchWasmUnsupportedError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = 'SketchWasmUnsupportedError';
+  }
+}
+
+/** Thrown by `initSketchWasm` when the requested memory reservation is refused. */
+export class SketchWasmMemoryError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = 'SketchWasmMemoryError';
+  }
+}
+
+let loading: Promise<SketchWasm> | undefined;
+
+/**
+ * Returns the features the module needs that the current environment lacks.
+ * 64-bit seeds and hashes cross the boundary as BigInt, so BigInt support is required.
+ */
+export function missingFeatures(): string[] {
+  const missing: string[] = [];
+  if (typeof WebAssembly !== 'object' || typeof WebAssembly.instantiate !== 'function') {
+    missing.push('WebAssembly');
+  }
+  if (typeof BigInt !== 'function' || typeof BigUint64Array !== 'function') {
+    missing.push('BigInt');
+  }
+  return missing;
+}
+
+/**
+ * Loads the WebAssembly module and returns a facade exposing every sketch.
+ * The module is loaded once; later calls return the same facade. Rejects with a
+ * `SketchWasmUnsupportedError` if the environment lacks a required feature or the
+ * module fails to load, so callers can fall back gracefully. A `reserveBytes` option is
+ * honoured by every call, including those made after the module has loaded.
+ */
+export function initSketchWasm(options: InitOptions = {}): Promise<SketchWasm> {
+  if (!loading) {
+    loading = load();
+    // Allow a retry after a transient load failure
+    loading.catch(() => {
+      loading = undefined;
+    });
+  }
+  const { reserveBytes } = options;
+  if (!reserveBytes) {
+    return loading;
+  }
+  return loading.then((sketches) => {
+    reserve(sketches, reserveBytes);
+    return sketches;
+  });
+}
+
+/** Grows memory for `bytes` more, rejecting with a `SketchWasmMemoryError` if refused. */
+function reserve(sketches: SketchWasm, bytes: number): void {
+  try {
+    sketches.memory.reserve_memory(bytes);
+  } catch (error) {
+    throw new SketchWasmMemoryError(String(error));
+  }
+}
+
+async function load(): Promise<SketchWasm> {
+  const missing = missingFeatures();
+  if (missing.length > 0) {
+    throw new SketchWasmUnsupportedError(
+      `sketch-wasm requires unsupported features: ${missing.join(', ')}`
+    );
+  }
+
+  let wasm: typeof Wasm;
+  try {
+    wasm = await import('../pkg/sketch_wasm');
+  } catch (error) {
+    throw new SketchWasmUnsupportedError(`failed to load sketch-wasm: ${String(error)}`);
+  }
+
+  return {
+    BloomFilter: wasm.BloomFilter,
+    ShardedBloomFilter: wasm.ShardedBloomFilter,
+    ScalableBloomFilter: wasm.ScalableBloomFilter,
+    PartitionedBloomFilter: wasm.PartitionedBloomFilter,
+    StableBloomFilter: wasm.StableBloomFilter,
+    ExpiringBloomFilter: wasm.ExpiringBloomFilter,
+    BloomFilterLoader: wasm.BloomFilterLoader,
+    BloomFilterView: wasm.BloomFilterView,
+    BloomCascade: wasm.BloomCascade,
+    WeightedBloomFilter: wasm.WeightedBloomFilter,
+    CountingBloomFilter: wasm.CountingBloomFilter,
+    SpectralBloomFilter: wasm.SpectralBloomFilter,
+    DeletableBloomFilter: wasm.DeletableBloomFilter,
+    CuckooFilter: wasm.CuckooFilter,
+    XorFilter: wasm.XorFilter,
+    RedisBloomFilter: wasm.RedisBloomFilter,
+    HyperLogLog: wasm.HyperLogLog,
+    SlidingHyperLogLog: wasm.SlidingHyperLogLog,
+    CountingHyperLogLog: wasm.CountingHyperLogLog,
+    AdaptiveHyperLogLog: wasm.AdaptiveHyperLogLog,
+    CardinalityRecorder: wasm.CardinalityRecorder,
+    ActivityHeatmap: wasm.ActivityHeatmap,
+    CountMinSketch: wasm.CountMinSketch,
+    CountMinSketch64: wasm.CountMinSketch64,
+    CountMinSketchF64: wasm.CountMinSketchF64,
+    FilteredCounter: wasm.FilteredCounter,
+    TimestampedCountMinSketch: wasm.TimestampedCountMinSketch,
+    RangeCountSketch: wasm.RangeCountSketch,
+    HeavyKeeper: wasm.HeavyKeeper,
+    TieredHeavyKeeper: wasm.TieredHeavyKeeper,
+    PairHeavyKeeper: wasm.PairHeavyKeeper,
+    WeightedMinHash: wasm.WeightedMinHash,
+    WeightedCardinality: wasm.WeightedCardinality,
+    SyncChannel: wasm.SyncChannel,
+    MemoryPressurePolicy: wasm.MemoryPressurePolicy,
+    SkewEstimator: wasm.SkewEstimator,
+    testing: {
+      enable_test_mode: wasm.enable_test_mode,
+      disable_test_mode: wasm.disable_test_mode,
+      set_clock_ms: wasm.set_clock_ms,
+      set_clock: wasm.set_clock,
+    },
+    convert: {
+      bloom_from_entries: wasm.bloom_from_entries,
+      count_min_from_entries: wasm.count_min_from_entries,
+      hyperloglog_from_entries: wasm.hyperloglog_from_entries,
+    },
+    conformance: {
+      hash_test_vectors: wasm.hash_test_vectors,
+    },
+    memory: {
+      reserve_memory: wasm.reserve_memory,
+    },
+  };
+}
@@ -7,16 +7,101 @@
 
 use wasm_bindgen::prelude::*;
 
+mod activity_heatmap;
+mod adaptive_hyperloglog;
+mod arrow;
 mod bloom;
+mod bloom_cascade;
+mod bloom_view;
+mod cardinality_recorder;
+mod codec;
+mod column;
+mod conformance;
+mod convert;
 mod count_min_sketch;
+mod count_min_sketch64;
+mod count_min_sketch_f64;
+mod counter_matrix;
+mod counting_bloom;
+mod counting_hyperloglog;
+mod cuckoo;
+mod deletable_bloom;
+mod entries;
+mod error;
+mod expiring_bloom;
+mod filtered_counter;
+mod frequency_histogram;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+mod hash;
 mod heavy_keeper;
 mod hyperloglog;
+mod memory;
+mod memory_pressure;
+mod pair_heavy_keeper;
+mod partitioned_bloom;
+mod range_count;
+mod redis_bloom;
+mod replay;
+mod runtime;
+mod scalable_bloom;
+mod sharded_bloom;
+mod skew_estimator;
+mod sliding_hyperloglog;
+mod spectral_bloom;
+mod stable_bloom;
+mod stream_loader;
+mod sync;
+mod tiered_heavy_keeper;
+mod timestamped_count_min;
+mod weighted_bloom;
+mod weighted_cardinality;
+mod weighted_minhash;
+mod xor_filter;
 // mod approx_top_k;
 
-pub use bloom::BloomFilter;
-pub use count_min_sketch::CountMinSketch;
-pub use heavy_keeper::HeavyKeeper;
+pub use activity_heatmap::ActivityHeatmap;
+pub use adaptive_hyperloglog::AdaptiveHyperLogLog;
+pub use bloom::{BloomFilter, ProbeTrace};
+pub use bloom_cascade::BloomCascade;
+pub use bloom_view::BloomFilterView;
+pub use cardinality_recorder::CardinalityRecorder;
+pub use conformance::{hash_test_vectors, HashTestVector};
+pub use convert::{bloom_from_entries, count_min_from_entries, hyperloglog_from_entries};
+pub use count_min_sketch::{CountMinSketch, ThresholdAlert};
+pub use count_min_sketch64::CountMinSketch64;
+pub use count_min_sketch_f64::CountMinSketchF64;
+pub use counting_bloom::CountingBloomFilter;
+pub use counting_hyperloglog::CountingHyperLogLog;
+pub use cuckoo::CuckooFilter;
+pub use deletable_bloom::DeletableBloomFilter;
+pub use entries::Entries;
+pub use error::SketchError;
+pub use expiring_bloom::ExpiringBloomFilter;
+pub use filtered_counter::FilteredCounter;
+pub use heavy_keeper::{Eviction, HeavyKeeper, TopKStability, TuningReport};
 pub use hyperloglog::HyperLogLog;
+pub use memory::reserve_memory;
+pub use memory_pressure::{DegradationReport, MemoryPressurePolicy};
+pub use pair_heavy_keeper::{PairHeavyKeeper, PairTopKItem};
+pub use partitioned_bloom::PartitionedBloomFilter;
+pub use range_count::RangeCountSketch;
+pub use redis_bloom::{RedisBloomFilter, ScanDumpChunk};
+pub use runtime::{disable_test_mode, enable_test_mode, set_clock, set_clock_ms};
+pub use scalable_bloom::ScalableBloomFilter;
+pub use sharded_bloom::ShardedBloomFilter;
+pub use skew_estimator::SkewEstimator;
+pub use sliding_hyperloglog::SlidingHyperLogLog;
+pub use spectral_bloom::SpectralBloomFilter;
+pub use stable_bloom::StableBloomFilter;
+pub use stream_loader::BloomFilterLoader;
+pub use sync::SyncChannel;
+pub use tiered_heavy_keeper::TieredHeavyKeeper;
+pub use timestamped_count_min::TimestampedCountMinSketch;
+pub use weighted_bloom::WeightedBloomFilter;
+pub use weighted_cardinality::WeightedCardinality;
+pub use weighted_minhash::WeightedMinHash;
+pub use xor_filter::XorFilter;
 
 #[wasm_bindgen]
 extern "C" {
@@ -33,6 +118,7 @@ pub fn init() {
 
 #[cfg(test)]
 mod tests {
+  #[allow(unused_imports)]
   use super::*;
 
   #[test]
@@ -0,0 +1,48 @@
+//! Up-front reservation of linear memory, so large sketches created mid-session do not stall
+//! on `memory.grow` or fail unpredictably.
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+
+/// Grows the module's memory so that at least `bytes` more can be allocated without growing
+/// it again. The block is allocated and freed at once; the allocator keeps the freed pages for
+/// later sketches. Call it at startup, e.g. with the total `memory_bytes` of the sketches the
+/// page will create. Fails with an out-of-memory error if the host refuses to grow memory.
+///
+/// # Arguments
+///
+/// * `bytes` - The number of bytes to reserve
+#[wasm_bindgen]
+pub fn reserve_memory(bytes: usize) -> Result<(), SketchError> {
+  let mut block: Vec<u8> = Vec::new();
+  block.try_reserve_exact(bytes).map_err(|err| out_of_memory(bytes, err))?;
+  Ok(())
+}
+
+/// Allocates a zeroed vector, returning an error instead of aborting if memory cannot grow.
+pub(crate) fn try_zeroed<T: Clone + Default>(len: usize) -> Result<Vec<T>, SketchError> {
+  let mut values = Vec::new();
+  values
+    .try_reserve_exact(len)
+    .map_err(|err| out_of_memory(len.saturating_mul(std::mem::size_of::<T>()), err))?;
+  values.resize(len, T::default());
+  Ok(values)
+}
+
+fn out_of_memory(bytes: usize, err: std::collections::TryReserveError) -> SketchError {
+  SketchError::OutOfMemory(format!("could not allocate {} bytes: {}", bytes, err))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reserve_memory() {
+    reserve_memory(1 << 20).unwrap();
+    assert!(matches!(reserve_memory(usize::MAX), Err(SketchError::OutOfMemory(_))));
+    assert_eq!(try_zeroed::<u64>(4).unwrap(), vec![0; 4]);
+    assert!(try_zeroed::<u64>(usize::MAX / 4).is_err());
+  }
+}
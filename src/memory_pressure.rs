@@ -0,0 +1,189 @@
+use wasm_bindgen::prelude::*;
+
+use crate::count_min_sketch::CountMinSketch;
+use crate::heavy_keeper::HeavyKeeper;
+use crate::hyperloglog::HyperLogLog;
+
+/// Describes what a memory-pressure response did to a single sketch.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct DegradationReport {
+  structure: String,
+  action: String,
+  bytes_before: usize,
+  bytes_after: usize,
+}
+
+#[wasm_bindgen]
+impl DegradationReport {
+  /// Returns the kind of sketch that was inspected.
+  #[wasm_bindgen(getter)]
+  pub fn structure(&self) -> String {
+    self.structure.clone()
+  }
+
+  /// Returns a description of the degradation applied, or "none" if the sketch was already at
+  /// its policy floor.
+  #[wasm_bindgen(getter)]
+  pub fn action(&self) -> String {
+    self.action.clone()
+  }
+
+  /// Returns whether the sketch was degraded.
+  #[wasm_bindgen(getter)]
+  pub fn degraded(&self) -> bool {
+    self.action != "none"
+  }
+
+  /// Returns the approximate memory used by the sketch before the response, in bytes.
+  #[wasm_bindgen(getter)]
+  pub fn bytes_before(&self) -> usize {
+    self.bytes_before
+  }
+
+  /// Returns the approximate memory used by the sketch after the response, in bytes.
+  #[wasm_bindgen(getter)]
+  pub fn bytes_after(&self) -> usize {
+    self.bytes_after
+  }
+}
+
+/// A policy describing how far sketches may be downgraded when the host reports memory pressure.
+/// Each call degrades a sketch by one step (one precision bit, half the width, half of k) and
+/// never below the configured floors, so long-running pages lose accuracy instead of crashing.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryPressurePolicy {
+  min_hll_precision: u8,
+  min_cms_width: usize,
+  min_heavy_keeper_k: usize,
+}
+
+#[wasm_bindgen]
+impl MemoryPressurePolicy {
+  /// Creates a new memory-pressure policy.
+  ///
+  /// # Arguments
+  ///
+  /// * `min_hll_precision` - The lowest precision a HyperLogLog may be folded down to
+  /// * `min_cms_width` - The narrowest width a CountMinSketch may be halved down to
+  /// * `min_heavy_keeper_k` - The smallest k a HeavyKeeper may be shrunk down to
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    min_hll_precision: u8,
+    min_cms_width: usize,
+    min_heavy_keeper_k: usize,
+  ) -> MemoryPressurePolicy {
+    MemoryPressurePolicy {
+      min_hll_precision: min_hll_precision.max(4),
+      min_cms_width: min_cms_width.max(1),
+      min_heavy_keeper_k: min_heavy_keeper_k.max(1),
+    }
+  }
+
+  /// Folds a HyperLogLog down by one precision bit, halving its register memory.
+  ///
+  /// # Arguments
+  ///
+  /// * `hll` - The HyperLogLog to degrade
+  #[wasm_bindgen]
+  pub fn relieve_hyperloglog(&self, hll: &mut HyperLogLog) -> DegradationReport {
+    let bytes_before = hll.memory_bytes();
+    let precision = hll.precision();
+    let mut action = "none".to_string();
+
+    if precision > self.min_hll_precision && hll.reduce_precision(precision - 1).is_ok() {
+      action = format!("reduced precision from {} to {}", precision, precision - 1);
+    }
+
+    DegradationReport {
+      structure: "HyperLogLog".to_string(),
+      action,
+      bytes_before,
+      bytes_after: hll.memory_bytes(),
+    }
+  }
+
+  /// Halves the width of a CountMinSketch, folding counters together.
+  /// Sketches linked by mirrors are left unchanged.
+  ///
+  /// # Arguments
+  ///
+  /// * `sketch` - The CountMinSketch to degrade
+  #[wasm_bindgen]
+  pub fn relieve_count_min_sketch(&self, sketch: &mut CountMinSketch) -> DegradationReport {
+    let bytes_before = sketch.memory_bytes();
+    let width = sketch.width();
+    let mut action = "none".to_string();
+
+    if width / 2 >= self.min_cms_width && sketch.halve_width().is_ok() {
+      action = format!("halved width from {} to {}", width, width / 2);
+    }
+
+    DegradationReport {
+      structure: "CountMinSketch".to_string(),
+      action,
+      bytes_before,
+      bytes_after: sketch.memory_bytes(),
+    }
+  }
+
+  /// Halves the number of top items a HeavyKeeper tracks and frees the counts it keeps for
+  /// items outside the new top k. The counter array keeps its size, so the saving grows with
+  /// the number of distinct items seen; `bytes_after` reports what remains.
+  ///
+  /// # Arguments
+  ///
+  /// * `hk` - The HeavyKeeper to degrade
+  #[wasm_bindgen]
+  pub fn relieve_heavy_keeper(&self, hk: &mut HeavyKeeper) -> DegradationReport {
+    let bytes_before = hk.memory_bytes();
+    let k = hk.k();
+    let new_k = (k / 2).max(self.min_heavy_keeper_k);
+    let mut action = "none".to_string();
+
+    if new_k < k {
+      hk.shrink_k(new_k);
+      action = format!("shrank k from {} to {}", k, new_k);
+    }
+
+    DegradationReport {
+      structure: "HeavyKeeper".to_string(),
+      action,
+      bytes_before,
+      bytes_after: hk.memory_bytes(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_relieve_respects_floors() {
+    let policy = MemoryPressurePolicy::new(10, 256, 4);
+
+    let mut hll = HyperLogLog::new(Some(11)).unwrap();
+    let report = policy.relieve_hyperloglog(&mut hll);
+    assert!(report.degraded());
+    assert_eq!(report.bytes_before(), 2048);
+    assert_eq!(report.bytes_after(), 1024);
+    assert!(!policy.relieve_hyperloglog(&mut hll).degraded());
+
+    let mut cms = CountMinSketch::new(512, 4);
+    assert!(policy.relieve_count_min_sketch(&mut cms).degraded());
+    assert_eq!(cms.width(), 256);
+    assert!(!policy.relieve_count_min_sketch(&mut cms).degraded());
+
+    let mut hk = HeavyKeeper::new(100, 3, 10, 0.9);
+    for i in 0..1000 {
+      hk.add(&format!("item_{}", i));
+    }
+    let report = policy.relieve_heavy_keeper(&mut hk);
+    assert_eq!(report.action(), "shrank k from 10 to 5");
+    assert!(report.bytes_after() < report.bytes_before());
+    policy.relieve_heavy_keeper(&mut hk);
+    assert!(!policy.relieve_heavy_keeper(&mut hk).degraded());
+  }
+}
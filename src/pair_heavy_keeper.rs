@@ -0,0 +1,167 @@
+use wasm_bindgen::prelude::*;
+
+use crate::hash::{hash_str, mix64};
+use crate::heavy_keeper::HeavyKeeper;
+
+/// Represents a pair of items and its co-occurrence count.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct PairTopKItem {
+  first: String,
+  second: String,
+  count: u32,
+}
+
+#[wasm_bindgen]
+impl PairTopKItem {
+  /// Returns the first item of the pair.
+  #[wasm_bindgen(getter)]
+  pub fn first(&self) -> String {
+    self.first.clone()
+  }
+
+  /// Returns the second item of the pair.
+  #[wasm_bindgen(getter)]
+  pub fn second(&self) -> String {
+    self.second.clone()
+  }
+
+  /// Returns the pair's count.
+  #[wasm_bindgen(getter)]
+  pub fn count(&self) -> u32 {
+    self.count
+  }
+}
+
+/// A Heavy Keeper over ordered pairs, for finding the most frequent co-occurrences
+/// (e.g. query→click) in a stream of `(a, b)` events.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct PairHeavyKeeper {
+  inner: HeavyKeeper,
+}
+
+#[wasm_bindgen]
+impl PairHeavyKeeper {
+  /// Creates a new pair Heavy Keeper with the specified parameters.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  /// * `k` - The number of top pairs to track
+  /// * `decay` - The decay factor for count reduction (between 0 and 1)
+  #[wasm_bindgen(constructor)]
+  pub fn new(width: usize, depth: usize, k: usize, decay: f64) -> Self {
+    PairHeavyKeeper {
+      inner: HeavyKeeper::new(width, depth, k, decay).with_key_hash(Self::key_hash),
+    }
+  }
+
+  /// Adds one occurrence of the pair `(first, second)`.
+  ///
+  /// # Arguments
+  ///
+  /// * `first` - The first item of the pair
+  /// * `second` - The second item of the pair
+  #[wasm_bindgen]
+  pub fn add(&mut self, first: &str, second: &str) {
+    self.inner.add(&Self::compose(first, second));
+  }
+
+  /// Returns the estimated frequency of the pair `(first, second)`.
+  ///
+  /// # Arguments
+  ///
+  /// * `first` - The first item of the pair
+  /// * `second` - The second item of the pair
+  #[wasm_bindgen]
+  pub fn query(&self, first: &str, second: &str) -> u32 {
+    self.inner.query(&Self::compose(first, second))
+  }
+
+  /// Returns the top-k most frequent pairs.
+  #[wasm_bindgen]
+  pub fn top_k(&self) -> Vec<PairTopKItem> {
+    self
+      .inner
+      .top_k()
+      .into_iter()
+      .filter_map(|entry| {
+        let (first, second) = Self::decompose(&entry.item())?;
+        Some(PairTopKItem { first, second, count: entry.count() })
+      })
+      .collect()
+  }
+
+  // Length-prefixing the first item keeps ("a:", "b") and ("a", ":b") distinct
+  fn compose(first: &str, second: &str) -> String {
+    format!("{}:{}{}", first.len(), first, second)
+  }
+
+  fn decompose(key: &str) -> Option<(String, String)> {
+    let (first, second) = Self::split(key)?;
+    Some((first.to_string(), second.to_string()))
+  }
+
+  fn split(key: &str) -> Option<(&str, &str)> {
+    let (len, rest) = key.split_once(':')?;
+    let len: usize = len.parse().ok()?;
+    if !rest.is_char_boundary(len) {
+      return None;
+    }
+    Some(rest.split_at(len))
+  }
+
+  // Combines the items' own hashes in order, so a pair's counters follow from hash_str of each
+  // item rather than from the composed key
+  fn key_hash(key: &str) -> u64 {
+    match Self::split(key) {
+      Some((first, second)) => Self::pair_hash(first, second),
+      None => hash_str(key),
+    }
+  }
+
+  fn pair_hash(first: &str, second: &str) -> u64 {
+    mix64(hash_str(second), hash_str(first))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_compose_roundtrip() {
+    for (first, second) in [("a", "b"), ("a:", "b"), ("", ":"), ("你好", "🌍")] {
+      let key = PairHeavyKeeper::compose(first, second);
+      assert_eq!(PairHeavyKeeper::decompose(&key), Some((first.to_string(), second.to_string())));
+    }
+  }
+
+  #[test]
+  fn test_pair_hash_is_ordered() {
+    let key = PairHeavyKeeper::compose("query", "click");
+    assert_eq!(PairHeavyKeeper::key_hash(&key), PairHeavyKeeper::pair_hash("query", "click"));
+    assert_ne!(PairHeavyKeeper::pair_hash("a", "b"), PairHeavyKeeper::pair_hash("b", "a"));
+    assert_ne!(PairHeavyKeeper::pair_hash("a", "a"), PairHeavyKeeper::pair_hash("b", "b"));
+  }
+
+  #[test]
+  fn test_top_pairs() {
+    let mut hk = PairHeavyKeeper::new(1000, 5, 3, 0.9);
+    for _ in 0..100 {
+      hk.add("how to", "docs");
+    }
+    for _ in 0..20 {
+      hk.add("how to", "blog");
+    }
+
+    let top = hk.top_k();
+    assert_eq!(top[0].first(), "how to");
+    assert_eq!(top[0].second(), "docs");
+    assert!(top[0].count() >= 90);
+    assert!(hk.query("how to", "blog") > 0);
+    assert_eq!(hk.query("docs", "how to"), 0);
+  }
+}
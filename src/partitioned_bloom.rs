@@ -0,0 +1,171 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+
+/// A Bloom filter whose bit array is split into one equal partition per hash function, so
+/// each probe sets exactly one bit in its own partition.
+///
+/// Partitions fill independently, which makes the false positive rate simply the product of
+/// their fill ratios, and lets each hash function's region be exported and analyzed on its
+/// own. Capacity and false positive rate match a `BloomFilter` of the same parameters.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct PartitionedBloomFilter {
+  // One bit array per probe, packed into 64-bit words least significant bit first
+  partitions: Vec<Vec<u64>>,
+  // Number of bits in each partition
+  partition_len: usize,
+}
+
+#[wasm_bindgen]
+impl PartitionedBloomFilter {
+  /// Creates a new partitioned Bloom filter with the specified expected number of items and
+  /// false positive rate.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items to be inserted
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  #[wasm_bindgen(constructor)]
+  pub fn new(expected_items: usize, false_positive_rate: f64) -> PartitionedBloomFilter {
+    let (size, hash_count) = BloomFilter::geometry(expected_items, false_positive_rate);
+    let hash_count = hash_count.max(1);
+    let partition_len = size.div_ceil(hash_count).max(1);
+    PartitionedBloomFilter {
+      partitions: vec![vec![0; partition_len.div_ceil(64)]; hash_count],
+      partition_len,
+    }
+  }
+
+  /// Inserts an item into the filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    let hash = hash_str(item);
+    for (i, partition) in self.partitions.iter_mut().enumerate() {
+      let bit = BloomFilter::probe_index(hash, i, self.partition_len);
+      partition[bit / 64] |= 1 << (bit % 64);
+    }
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    let hash = hash_str(item);
+    self.partitions.iter().enumerate().all(|(i, partition)| {
+      let bit = BloomFilter::probe_index(hash, i, self.partition_len);
+      partition[bit / 64] & (1 << (bit % 64)) != 0
+    })
+  }
+
+  /// Returns the fraction of bits set in one partition.
+  ///
+  /// # Arguments
+  ///
+  /// * `index` - The partition, which is also the index of the probe that writes to it
+  #[wasm_bindgen]
+  pub fn partition_fill(&self, index: usize) -> Result<f64, SketchError> {
+    let partition = self.partition(index)?;
+    let set: u32 = partition.iter().map(|word| word.count_ones()).sum();
+    Ok(f64::from(set) / self.partition_len as f64)
+  }
+
+  /// Returns one partition's bits as bytes, least significant bit first, for analysis.
+  /// Trailing bits of the last byte past the partition length are zero.
+  ///
+  /// # Arguments
+  ///
+  /// * `index` - The partition, which is also the index of the probe that writes to it
+  #[wasm_bindgen]
+  pub fn partition_bytes(&self, index: usize) -> Result<Vec<u8>, SketchError> {
+    let partition = self.partition(index)?;
+    let mut bytes: Vec<u8> = partition.iter().flat_map(|word| word.to_le_bytes()).collect();
+    bytes.truncate(self.partition_len.div_ceil(8));
+    Ok(bytes)
+  }
+
+  /// Returns the current false positive rate: the product of the partition fill ratios.
+  #[wasm_bindgen]
+  pub fn current_false_positive_rate(&self) -> f64 {
+    (0..self.partitions.len())
+      .map(|index| self.partition_fill(index).expect("index is in range"))
+      .product()
+  }
+
+  /// Returns the number of partitions, equal to the number of probes per item.
+  #[wasm_bindgen(getter)]
+  pub fn partition_count(&self) -> usize {
+    self.partitions.len()
+  }
+
+  /// Returns the number of bits in each partition.
+  #[wasm_bindgen(getter)]
+  pub fn partition_len(&self) -> usize {
+    self.partition_len
+  }
+
+  /// Clears all bits in the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    for partition in &mut self.partitions {
+      partition.fill(0);
+    }
+  }
+}
+
+impl PartitionedBloomFilter {
+  fn partition(&self, index: usize) -> Result<&[u64], SketchError> {
+    self.partitions.get(index).map(Vec::as_slice).ok_or_else(|| {
+      SketchError::InvalidParameter(format!(
+        "partition {} out of range for {} partitions",
+        index,
+        self.partitions.len()
+      ))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_partitioned_filter() {
+    let mut filter = PartitionedBloomFilter::new(1000, 0.01);
+    for i in 0..1000 {
+      filter.insert(&format!("item_{}", i));
+    }
+    for i in 0..1000 {
+      assert!(filter.contains(&format!("item_{}", i)));
+    }
+    let false_positives = (0..10_000).filter(|i| filter.contains(&format!("other_{}", i))).count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+    assert!((filter.current_false_positive_rate() - 0.01).abs() < 0.01);
+  }
+
+  #[test]
+  fn test_partition_export() {
+    let mut filter = PartitionedBloomFilter::new(100, 0.01);
+    filter.insert("alpha");
+    for index in 0..filter.partition_count() {
+      let bytes = filter.partition_bytes(index).unwrap();
+      assert_eq!(bytes.len(), filter.partition_len().div_ceil(8));
+      assert_eq!(bytes.iter().map(|byte| byte.count_ones()).sum::<u32>(), 1);
+      assert_eq!(filter.partition_fill(index).unwrap(), 1.0 / filter.partition_len() as f64);
+    }
+    assert!(filter.partition_bytes(filter.partition_count()).is_err());
+
+    filter.clear();
+    assert!(!filter.contains("alpha"));
+  }
+}
@@ -0,0 +1,202 @@
+use wasm_bindgen::prelude::*;
+
+use crate::count_min_sketch::CountMinSketch;
+use crate::error::SketchError;
+use crate::hash::mix64;
+
+// Keys cross the boundary as JS numbers, which are exact integers up to 2^53
+const MAX_BITS: u32 = 53;
+
+/// Counts integer keys, such as timestamps or ports, and estimates how many fall in a range.
+/// Level `l` is a CountMinSketch over `key >> l`, so any range splits into at most two dyadic
+/// intervals per level and a query reads `O(bits)` counters instead of one per key.
+///
+/// Each interval may be overestimated by the usual CountMinSketch error, so a range estimate
+/// never undercounts but its error grows with the number of levels it touches.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct RangeCountSketch {
+  bits: u32,
+  // One sketch per level, finest first; the level covering the whole key space is the total
+  levels: Vec<CountMinSketch>,
+  total: u64,
+}
+
+#[wasm_bindgen]
+impl RangeCountSketch {
+  /// Creates a sketch for keys from 0 to `2^bits - 1`.
+  ///
+  /// # Arguments
+  ///
+  /// * `bits` - The size of the key space in bits (1 to 53), e.g. 16 for ports or 32 for
+  ///   timestamps in seconds
+  /// * `width` - The number of counters in each row of every level
+  /// * `depth` - The number of rows in every level
+  #[wasm_bindgen(constructor)]
+  pub fn new(bits: u32, width: usize, depth: usize) -> Result<RangeCountSketch, SketchError> {
+    if !(1..=MAX_BITS).contains(&bits) {
+      return Err(SketchError::InvalidParameter(format!(
+        "bits must be between 1 and {}",
+        MAX_BITS
+      )));
+    }
+    if width == 0 || depth == 0 {
+      return Err(SketchError::InvalidParameter("width and depth must be positive".to_string()));
+    }
+    let levels = (0..bits).map(|_| CountMinSketch::new(width, depth)).collect();
+    Ok(RangeCountSketch { bits, levels, total: 0 })
+  }
+
+  /// Increments the count of a key.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - An integer from 0 to `2^bits - 1`
+  #[wasm_bindgen]
+  pub fn increment(&mut self, key: f64) -> Result<(), SketchError> {
+    let key = self.check_key(key)?;
+    for (level, sketch) in self.levels.iter_mut().enumerate() {
+      sketch.increment_hash(Self::node_hash(level, key >> level));
+    }
+    self.total += 1;
+    Ok(())
+  }
+
+  /// Returns the estimated count of a single key.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - An integer from 0 to `2^bits - 1`
+  #[wasm_bindgen]
+  pub fn estimate(&self, key: f64) -> Result<u32, SketchError> {
+    let key = self.check_key(key)?;
+    Ok(self.levels[0].estimate_hash(Self::node_hash(0, key)))
+  }
+
+  /// Returns the estimated number of increments with keys from `lo` to `hi`, inclusive.
+  /// Returns 0 if `lo` is greater than `hi`.
+  ///
+  /// # Arguments
+  ///
+  /// * `lo` - The first key of the range
+  /// * `hi` - The last key of the range
+  #[wasm_bindgen]
+  pub fn estimate_range(&self, lo: f64, hi: f64) -> Result<f64, SketchError> {
+    let (mut lo, mut hi) = (self.check_key(lo)?, self.check_key(hi)?);
+    let mut sum = 0;
+    let mut level = 0;
+    // Peel off the unaligned ends at each level, then move up to the parent intervals
+    while lo <= hi {
+      if level == self.levels.len() {
+        sum += self.total;
+        break;
+      }
+      if lo & 1 == 1 {
+        sum += u64::from(self.node_estimate(level, lo));
+        lo += 1;
+      }
+      if hi & 1 == 0 {
+        sum += u64::from(self.node_estimate(level, hi));
+        if hi == 0 {
+          break;
+        }
+        hi -= 1;
+      }
+      lo >>= 1;
+      hi >>= 1;
+      level += 1;
+    }
+    Ok(sum.min(self.total) as f64)
+  }
+
+  /// Returns the number of increments since the last clear.
+  #[wasm_bindgen]
+  pub fn total_count(&self) -> f64 {
+    self.total as f64
+  }
+
+  /// Returns the size of the key space in bits.
+  #[wasm_bindgen(getter)]
+  pub fn bits(&self) -> u32 {
+    self.bits
+  }
+
+  /// Clears all counts.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    for sketch in &mut self.levels {
+      sketch.clear();
+    }
+    self.total = 0;
+  }
+
+  /// Returns the total size of the counters of every level in bytes.
+  #[wasm_bindgen]
+  pub fn memory_bytes(&self) -> usize {
+    self.levels.iter().map(CountMinSketch::memory_bytes).sum()
+  }
+}
+
+impl RangeCountSketch {
+  fn check_key(&self, key: f64) -> Result<u64, SketchError> {
+    if !(key >= 0.0 && key.fract() == 0.0 && key < (1u64 << self.bits) as f64) {
+      return Err(SketchError::InvalidParameter(format!(
+        "key must be an integer from 0 to 2^{} - 1",
+        self.bits
+      )));
+    }
+    Ok(key as u64)
+  }
+
+  // Levels are separate sketches, so the level only decorrelates their hashes
+  fn node_hash(level: usize, node: u64) -> u64 {
+    mix64(node, level as u64)
+  }
+
+  fn node_estimate(&self, level: usize, node: u64) -> u32 {
+    self.levels[level].estimate_hash(Self::node_hash(level, node))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_range_matches_exact_counts() {
+    let mut sketch = RangeCountSketch::new(10, 4096, 5).unwrap();
+    let mut exact = vec![0u64; 1024];
+    for i in 0..5000u64 {
+      let key = mix64(i, 7) % 1024;
+      sketch.increment(key as f64).unwrap();
+      exact[key as usize] += 1;
+    }
+
+    for (lo, hi) in [(0, 1023), (0, 0), (1023, 1023), (1, 1022), (100, 355), (512, 767), (7, 8)] {
+      let truth: u64 = exact[lo..=hi].iter().sum();
+      let estimate = sketch.estimate_range(lo as f64, hi as f64).unwrap();
+      assert!(estimate >= truth as f64, "[{}, {}]: {} < {}", lo, hi, estimate, truth);
+      assert!(estimate <= truth as f64 + 20.0, "[{}, {}]: {} vs {}", lo, hi, estimate, truth);
+    }
+    assert_eq!(sketch.estimate_range(0.0, 1023.0).unwrap(), 5000.0);
+    assert_eq!(sketch.estimate_range(5.0, 4.0).unwrap(), 0.0);
+    assert!(sketch.estimate(exact.iter().position(|&c| c > 0).unwrap() as f64).unwrap() > 0);
+  }
+
+  #[test]
+  fn test_rejects_bad_keys() {
+    assert!(RangeCountSketch::new(0, 100, 4).is_err());
+    assert!(RangeCountSketch::new(54, 100, 4).is_err());
+
+    let mut sketch = RangeCountSketch::new(16, 100, 4).unwrap();
+    assert!(sketch.increment(65536.0).is_err());
+    assert!(sketch.increment(-1.0).is_err());
+    assert!(sketch.increment(1.5).is_err());
+    sketch.increment(443.0).unwrap();
+    assert_eq!(sketch.estimate_range(0.0, 65535.0).unwrap(), 1.0);
+
+    sketch.clear();
+    assert_eq!(sketch.total_count(), 0.0);
+    assert_eq!(sketch.memory_bytes(), 16 * 100 * 4 * 4);
+  }
+}
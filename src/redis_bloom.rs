@@ -0,0 +1,594 @@
+//! Bloom filters in RedisBloom's `BF.SCANDUMP` / `BF.LOADCHUNK` chunk format, so a filter
+//! maintained server-side can be queried in the browser and a locally updated copy sent back.
+//!
+//! The first chunk (iterator 1) is the chain header, little-endian and packed:
+//!
+//! | field      | type | meaning                                |
+//! |------------|------|----------------------------------------|
+//! | `size`     | u64  | items added across all sub-filters     |
+//! | `nfilters` | u32  | number of sub-filters                  |
+//! | `options`  | u32  | RedisBloom option flags                |
+//! | `growth`   | u32  | capacity growth factor when scaling    |
+//!
+//! followed by one 53-byte record per sub-filter: `bytes` u64, `bits` u64, `size` u64,
+//! `error` f64, `bpe` f64, `hashes` u32, `entries` u64 and `n2` u8. The remaining chunks are
+//! the sub-filters' bit arrays, concatenated in order; a chunk returned with iterator `i`
+//! holds `data` starting at byte `i - data.len() - 1` of that concatenation.
+//!
+//! Items are hashed as RedisBloom does: MurmurHash64A over the UTF-8 bytes when the
+//! `FORCE64` option is set, 32-bit MurmurHash2 otherwise, probed by double hashing.
+
+use wasm_bindgen::prelude::*;
+
+use crate::codec::read_u64;
+use crate::error::SketchError;
+use crate::memory::try_zeroed;
+
+// RedisBloom's BLOOM_OPT_FORCE64 flag
+const OPT_FORCE64: u32 = 4;
+pub(crate) const CHAIN_HEADER_LEN: usize = 20;
+pub(crate) const LINK_HEADER_LEN: usize = 53;
+// RedisBloom derives the probe count from the error rate, and even the smallest positive f64
+// error needs fewer than 1100 probes
+const MAX_HASHES: u32 = 2048;
+// RedisBloom's MAX_SCANDUMP_SIZE
+const MAX_CHUNK_LEN: usize = 10 * 1024 * 1024;
+
+/// One chunk of a `BF.SCANDUMP` export: pass `iterator` and `data` to `BF.LOADCHUNK`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ScanDumpChunk {
+  iterator: f64,
+  data: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ScanDumpChunk {
+  /// Returns the iterator to send with this chunk, or 0 once the dump is complete.
+  #[wasm_bindgen(getter)]
+  pub fn iterator(&self) -> f64 {
+    self.iterator
+  }
+
+  /// Returns the chunk payload (empty once the dump is complete).
+  #[wasm_bindgen(getter)]
+  pub fn data(&self) -> Vec<u8> {
+    self.data.clone()
+  }
+}
+
+#[derive(Debug)]
+struct Link {
+  hashes: u32,
+  n2: u8,
+  // Capacity the sub-filter was sized for
+  entries: u64,
+  error: f64,
+  bpe: f64,
+  bits: u64,
+  // Items added to this sub-filter
+  size: u64,
+  bytes: Vec<u8>,
+}
+
+/// A scalable bloom filter imported from RedisBloom with `BF.SCANDUMP`.
+///
+/// Create it from the header chunk, feed it the remaining chunks with `load_chunk`, then
+/// query it locally. Items inserted locally go into the newest sub-filter, which does not
+/// scale further, and can be exported back with `scandump`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct RedisBloomFilter {
+  links: Vec<Link>,
+  size: u64,
+  options: u32,
+  growth: u32,
+  // Sorted, disjoint byte ranges of the bit arrays received by load_chunk
+  loaded: Vec<(usize, usize)>,
+}
+
+#[wasm_bindgen]
+impl RedisBloomFilter {
+  /// Creates an empty filter from the header chunk, the one `BF.SCANDUMP` returns with
+  /// iterator 1.
+  ///
+  /// # Arguments
+  ///
+  /// * `header` - The header chunk's data
+  #[wasm_bindgen]
+  pub fn from_header(header: &[u8]) -> Result<RedisBloomFilter, SketchError> {
+    let mut input = header;
+    let size = read_u64(&mut input)?;
+    let nfilters = read_u32(&mut input)?;
+    let options = read_u32(&mut input)?;
+    let growth = read_u32(&mut input)?;
+    if nfilters == 0 {
+      return Err(SketchError::Malformed("header declares no sub-filters".to_string()));
+    }
+    if input.len() != nfilters as usize * LINK_HEADER_LEN {
+      return Err(SketchError::Malformed(format!(
+        "header has {} bytes of sub-filter records, expected {}",
+        input.len(),
+        nfilters as usize * LINK_HEADER_LEN
+      )));
+    }
+
+    let mut links = Vec::with_capacity(nfilters as usize);
+    for _ in 0..nfilters {
+      let byte_len = read_u64(&mut input)?;
+      let bits = read_u64(&mut input)?;
+      let link_size = read_u64(&mut input)?;
+      let error = f64::from_bits(read_u64(&mut input)?);
+      let bpe = f64::from_bits(read_u64(&mut input)?);
+      let hashes = read_u32(&mut input)?;
+      let entries = read_u64(&mut input)?;
+      let n2 = input[0];
+      input = &input[1..];
+
+      if bits == 0 || hashes == 0 {
+        return Err(SketchError::Malformed("sub-filter has no bits or hashes".to_string()));
+      }
+      if hashes > MAX_HASHES {
+        return Err(SketchError::Malformed(format!("sub-filter has {} hashes", hashes)));
+      }
+      if byte_len.checked_mul(8).is_none_or(|capacity| capacity < bits) {
+        return Err(SketchError::Malformed(format!(
+          "sub-filter of {} bits does not fit in {} bytes",
+          bits, byte_len
+        )));
+      }
+      if n2 > 63 || (n2 > 0 && bits != 1 << n2) {
+        return Err(SketchError::Malformed(format!(
+          "sub-filter of {} bits does not match n2 = {}",
+          bits, n2
+        )));
+      }
+      let byte_len = usize::try_from(byte_len)
+        .map_err(|_| SketchError::OutOfMemory(format!("sub-filter of {} bytes", byte_len)))?;
+      links.push(Link {
+        hashes,
+        n2,
+        entries,
+        error,
+        bpe,
+        bits,
+        size: link_size,
+        bytes: try_zeroed(byte_len)?,
+      });
+    }
+
+    Ok(RedisBloomFilter { links, size, options, growth, loaded: Vec::new() })
+  }
+
+  /// Loads one data chunk, exactly as passed to `BF.LOADCHUNK`.
+  ///
+  /// # Arguments
+  ///
+  /// * `iterator` - The iterator `BF.SCANDUMP` returned with the chunk
+  /// * `data` - The chunk's data
+  #[wasm_bindgen]
+  pub fn load_chunk(&mut self, iterator: f64, data: &[u8]) -> Result<(), SketchError> {
+    let iterator = parse_iterator(iterator)?;
+    if data.is_empty() || iterator < data.len() + 1 {
+      return Err(SketchError::Malformed(format!(
+        "chunk of {} bytes cannot end at iterator {}",
+        data.len(),
+        iterator
+      )));
+    }
+    let start = iterator - data.len() - 1;
+    if start + data.len() > self.total_bytes() {
+      return Err(SketchError::Malformed(format!(
+        "chunk at iterator {} runs past the filter's {} bytes",
+        iterator,
+        self.total_bytes()
+      )));
+    }
+
+    self.mark_loaded(start, start + data.len());
+    let (mut data, mut offset) = (data, start);
+    for link in &mut self.links {
+      if data.is_empty() {
+        break;
+      }
+      if offset >= link.bytes.len() {
+        offset -= link.bytes.len();
+        continue;
+      }
+      let len = data.len().min(link.bytes.len() - offset);
+      link.bytes[offset..offset + len].copy_from_slice(&data[..len]);
+      data = &data[len..];
+      offset = 0;
+    }
+    Ok(())
+  }
+
+  /// Returns true once every byte of every sub-filter's bits has been loaded. Chunks sent
+  /// twice or overlapping count once.
+  #[wasm_bindgen]
+  pub fn is_loaded(&self) -> bool {
+    self.loaded.first() == Some(&(0, self.total_bytes()))
+  }
+
+  /// Returns the chunk following `iterator`, exactly as `BF.SCANDUMP` would: start at 0
+  /// and pass each returned iterator back in until it is 0.
+  ///
+  /// # Arguments
+  ///
+  /// * `iterator` - 0 for the first chunk, then the iterator of the previous chunk
+  #[wasm_bindgen]
+  pub fn scandump(&self, iterator: f64) -> Result<ScanDumpChunk, SketchError> {
+    let iterator = parse_iterator(iterator)?;
+    if iterator == 0 {
+      return Ok(ScanDumpChunk { iterator: 1.0, data: self.header() });
+    }
+
+    let mut offset = iterator - 1;
+    for link in &self.links {
+      if offset < link.bytes.len() {
+        let len = (link.bytes.len() - offset).min(MAX_CHUNK_LEN);
+        return Ok(ScanDumpChunk {
+          iterator: (iterator + len) as f64,
+          data: link.bytes[offset..offset + len].to_vec(),
+        });
+      }
+      offset -= link.bytes.len();
+    }
+    Ok(ScanDumpChunk { iterator: 0.0, data: Vec::new() })
+  }
+
+  /// Checks if an item might be in the set, like `BF.EXISTS`.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    let (a, b) = self.hash(item.as_bytes());
+    self.links.iter().rev().any(|link| {
+      (0..link.hashes).all(|i| {
+        let bit = self.position(link, a, b, i);
+        link.bytes[(bit >> 3) as usize] & (1 << (bit & 7)) != 0
+      })
+    })
+  }
+
+  /// Adds an item to the newest sub-filter, like `BF.ADD`. Returns false if the item was
+  /// probably already present, in which case nothing changes.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to add
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) -> bool {
+    if self.contains(item) {
+      return false;
+    }
+    let (a, b) = self.hash(item.as_bytes());
+    let last = self.links.len() - 1;
+    for i in 0..self.links[last].hashes {
+      let bit = self.position(&self.links[last], a, b, i);
+      self.links[last].bytes[(bit >> 3) as usize] |= 1 << (bit & 7);
+    }
+    self.links[last].size += 1;
+    self.size += 1;
+    true
+  }
+
+  /// Returns the number of items added, as reported by `BF.INFO`.
+  #[wasm_bindgen(getter)]
+  pub fn len(&self) -> f64 {
+    self.size as f64
+  }
+
+  /// Returns true if no items have been added.
+  #[wasm_bindgen]
+  pub fn is_empty(&self) -> bool {
+    self.size == 0
+  }
+
+  /// Returns the number of sub-filters in the chain.
+  #[wasm_bindgen(getter)]
+  pub fn filter_count(&self) -> usize {
+    self.links.len()
+  }
+
+  /// Returns the number of bytes used by the bit arrays.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.total_bytes()
+  }
+}
+
+impl RedisBloomFilter {
+  fn total_bytes(&self) -> usize {
+    self.links.iter().map(|link| link.bytes.len()).sum()
+  }
+
+  // Records that bytes `start..end` have been received, merging touching ranges
+  fn mark_loaded(&mut self, start: usize, end: usize) {
+    let mut merged = (start, end);
+    self.loaded.retain(|&(lo, hi)| {
+      if hi < merged.0 || lo > merged.1 {
+        return true;
+      }
+      merged = (merged.0.min(lo), merged.1.max(hi));
+      false
+    });
+    let at = self.loaded.partition_point(|&(lo, _)| lo < merged.0);
+    self.loaded.insert(at, merged);
+  }
+
+  fn header(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(CHAIN_HEADER_LEN + self.links.len() * LINK_HEADER_LEN);
+    out.extend_from_slice(&self.size.to_le_bytes());
+    out.extend_from_slice(&(self.links.len() as u32).to_le_bytes());
+    out.extend_from_slice(&self.options.to_le_bytes());
+    out.extend_from_slice(&self.growth.to_le_bytes());
+    for link in &self.links {
+      out.extend_from_slice(&(link.bytes.len() as u64).to_le_bytes());
+      out.extend_from_slice(&link.bits.to_le_bytes());
+      out.extend_from_slice(&link.size.to_le_bytes());
+      out.extend_from_slice(&link.error.to_le_bytes());
+      out.extend_from_slice(&link.bpe.to_le_bytes());
+      out.extend_from_slice(&link.hashes.to_le_bytes());
+      out.extend_from_slice(&link.entries.to_le_bytes());
+      out.push(link.n2);
+    }
+    out
+  }
+
+  fn force64(&self) -> bool {
+    self.options & OPT_FORCE64 != 0
+  }
+
+  fn hash(&self, key: &[u8]) -> (u64, u64) {
+    if self.force64() {
+      let a = murmur_hash64a(key, 0xc6a4_a793_5bd1_e995);
+      (a, murmur_hash64a(key, a))
+    } else {
+      let a = murmur_hash2(key, 0x9747_b28c);
+      (u64::from(a), u64::from(murmur_hash2(key, a)))
+    }
+  }
+
+  // Mirrors RedisBloom's choice between 32-bit, 64-bit and non-power-of-two probing
+  fn position(&self, link: &Link, a: u64, b: u64, i: u32) -> u64 {
+    if link.n2 == 0 {
+      a.wrapping_add(u64::from(i).wrapping_mul(b)) % link.bits
+    } else if self.force64() || link.n2 > 31 {
+      a.wrapping_add(u64::from(i).wrapping_mul(b)) & (link.bits - 1)
+    } else {
+      let x = (a as u32).wrapping_add(i.wrapping_mul(b as u32));
+      u64::from(x & ((1u32 << link.n2) - 1))
+    }
+  }
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, SketchError> {
+  let Some((bytes, rest)) = input.split_first_chunk::<4>() else {
+    return Err(SketchError::Malformed("truncated u32".to_string()));
+  };
+  *input = rest;
+  Ok(u32::from_le_bytes(*bytes))
+}
+
+fn parse_iterator(iterator: f64) -> Result<usize, SketchError> {
+  if !(iterator >= 0.0 && iterator.fract() == 0.0 && iterator <= usize::MAX as f64) {
+    return Err(SketchError::InvalidParameter(format!(
+      "iterator must be a non-negative integer, got {}",
+      iterator
+    )));
+  }
+  Ok(iterator as usize)
+}
+
+/// Austin Appleby's MurmurHash64A.
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+  const M: u64 = 0xc6a4_a793_5bd1_e995;
+  const R: u32 = 47;
+  let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+  let mut blocks = key.chunks_exact(8);
+  for block in &mut blocks {
+    let mut k = u64::from_le_bytes(block.try_into().unwrap());
+    k = k.wrapping_mul(M);
+    k ^= k >> R;
+    k = k.wrapping_mul(M);
+    h ^= k;
+    h = h.wrapping_mul(M);
+  }
+  let tail = blocks.remainder();
+  if !tail.is_empty() {
+    for (i, &byte) in tail.iter().enumerate() {
+      h ^= u64::from(byte) << (8 * i);
+    }
+    h = h.wrapping_mul(M);
+  }
+  h ^= h >> R;
+  h = h.wrapping_mul(M);
+  h ^ (h >> R)
+}
+
+/// Austin Appleby's 32-bit MurmurHash2.
+fn murmur_hash2(key: &[u8], seed: u32) -> u32 {
+  const M: u32 = 0x5bd1_e995;
+  let mut h = seed ^ key.len() as u32;
+  let mut blocks = key.chunks_exact(4);
+  for block in &mut blocks {
+    let mut k = u32::from_le_bytes(block.try_into().unwrap());
+    k = k.wrapping_mul(M);
+    k ^= k >> 24;
+    k = k.wrapping_mul(M);
+    h = h.wrapping_mul(M);
+    h ^= k;
+  }
+  let tail = blocks.remainder();
+  if !tail.is_empty() {
+    for (i, &byte) in tail.iter().enumerate() {
+      h ^= u32::from(byte) << (8 * i);
+    }
+    h = h.wrapping_mul(M);
+  }
+  h ^= h >> 13;
+  h = h.wrapping_mul(M);
+  h ^ (h >> 15)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OPT_NOROUND: u32 = 1;
+
+  // Builds a header for one empty sub-filter sized as BF.RESERVE would size it
+  fn header(capacity: u64, error: f64, options: u32) -> Vec<u8> {
+    let bpe = -error.ln() / std::f64::consts::LN_2.powi(2);
+    let hashes = (std::f64::consts::LN_2 * bpe).ceil() as u32;
+    let (bits, n2) = if options & OPT_NOROUND != 0 {
+      ((capacity as f64 * bpe) as u64, 0u8)
+    } else {
+      let n2 = (capacity as f64 * bpe).log2().ceil() as u8;
+      (1u64 << n2, n2)
+    };
+    let mut out = Vec::new();
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&options.to_le_bytes());
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&bits.div_ceil(8).to_le_bytes());
+    out.extend_from_slice(&bits.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&error.to_le_bytes());
+    out.extend_from_slice(&bpe.to_le_bytes());
+    out.extend_from_slice(&hashes.to_le_bytes());
+    out.extend_from_slice(&capacity.to_le_bytes());
+    out.push(n2);
+    out
+  }
+
+  fn dump(filter: &RedisBloomFilter) -> Vec<ScanDumpChunk> {
+    let mut chunks = Vec::new();
+    let mut iterator = 0.0;
+    loop {
+      let chunk = filter.scandump(iterator).unwrap();
+      if chunk.iterator == 0.0 {
+        return chunks;
+      }
+      iterator = chunk.iterator;
+      chunks.push(chunk);
+    }
+  }
+
+  // SMHasher's verification: hash keys 0..i with seed 256 - i, then hash the concatenated
+  // results with seed 0 and read the first four bytes
+  fn verification(hash: impl Fn(&[u8], u32) -> Vec<u8>) -> u32 {
+    let key: Vec<u8> = (0..=255).collect();
+    let hashes: Vec<u8> = (0..256).flat_map(|i| hash(&key[..i], 256 - i as u32)).collect();
+    u32::from_le_bytes(hash(&hashes, 0)[..4].try_into().unwrap())
+  }
+
+  // BF.RESERVE bf 0.01 10 followed by BF.ADD bf hello, as BF.SCANDUMP returns it
+  const FIXTURE_HEADER: [u8; 73] = [
+    // size 1, one sub-filter, FORCE64 | NOROUND, growth 2
+    1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0, 2, 0, 0, 0,
+    // 14 bytes, 110 bits, size 1
+    14, 0, 0, 0, 0, 0, 0, 0, 110, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0,
+    // error 0.005 (tightened from 0.01), bpe -ln(0.005) / ln(2)^2
+    0x7b, 0x14, 0xae, 0x47, 0xe1, 0x7a, 0x74, 0x3f, 0xe9, 0x86, 0x2f, 0xb2, 0x35, 0x0e, 0x26, 0x40,
+    // 8 hashes, 10 entries, n2 0
+    8, 0, 0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0,
+  ];
+  const FIXTURE_ITERATOR: f64 = 15.0;
+  const FIXTURE_BITS: [u8; 14] = [8, 0, 8, 0, 0, 32, 0, 32, 128, 0, 128, 0, 128, 0];
+
+  #[test]
+  fn test_murmur_known_answers() {
+    assert_eq!(
+      verification(|key, seed| murmur_hash2(key, seed).to_le_bytes().to_vec()),
+      0x27864c1e
+    );
+    assert_eq!(
+      verification(|key, seed| murmur_hash64a(key, u64::from(seed)).to_le_bytes().to_vec()),
+      0x1f0d3804
+    );
+    assert_eq!(murmur_hash64a(b"hello", 0xc6a4_a793_5bd1_e995), 0x5ba5_b8a5_9803_e699);
+    assert_eq!(murmur_hash2(b"hello", 0x9747_b28c), 0x7f1d_dbbd);
+  }
+
+  #[test]
+  fn test_scandump_fixture() {
+    let mut filter = RedisBloomFilter::from_header(&FIXTURE_HEADER).unwrap();
+    filter.load_chunk(FIXTURE_ITERATOR, &FIXTURE_BITS).unwrap();
+    assert!(filter.is_loaded());
+    assert_eq!(filter.len(), 1.0);
+    assert!(filter.contains("hello"));
+    assert!(!filter.contains("world"));
+    assert!(!filter.insert("hello"));
+
+    let chunks = dump(&filter);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].data, FIXTURE_HEADER);
+    assert_eq!(chunks[1].iterator, FIXTURE_ITERATOR);
+    assert_eq!(chunks[1].data, FIXTURE_BITS);
+  }
+
+  #[test]
+  fn test_repeated_chunks_do_not_complete_the_load() {
+    let mut filter = RedisBloomFilter::from_header(&FIXTURE_HEADER).unwrap();
+    filter.load_chunk(8.0, &FIXTURE_BITS[..7]).unwrap();
+    filter.load_chunk(8.0, &FIXTURE_BITS[..7]).unwrap();
+    filter.load_chunk(6.0, &FIXTURE_BITS[2..5]).unwrap();
+    assert!(!filter.is_loaded());
+    filter.load_chunk(FIXTURE_ITERATOR, &FIXTURE_BITS[7..]).unwrap();
+    assert!(filter.is_loaded());
+    assert!(filter.contains("hello"));
+  }
+
+  #[test]
+  fn test_scandump_round_trip() {
+    for options in [OPT_NOROUND | OPT_FORCE64, OPT_FORCE64, OPT_NOROUND, 0] {
+      let mut filter = RedisBloomFilter::from_header(&header(1000, 0.01, options)).unwrap();
+      assert!(!filter.is_loaded());
+      filter
+        .load_chunk(filter.memory_bytes() as f64 + 1.0, &vec![0; filter.memory_bytes()])
+        .unwrap();
+      assert!(filter.is_loaded());
+      // A false positive while filling makes BF.ADD report the item as present
+      let added = (0..1000).filter(|i| filter.insert(&format!("user_{}", i))).count();
+      assert!(added > 980, "{} added", added);
+      assert!(!filter.insert("user_0"));
+
+      let chunks = dump(&filter);
+      assert_eq!(chunks[0].iterator, 1.0);
+      let mut copy = RedisBloomFilter::from_header(&chunks[0].data).unwrap();
+      for chunk in &chunks[1..] {
+        copy.load_chunk(chunk.iterator, &chunk.data).unwrap();
+      }
+      assert!(copy.is_loaded());
+      assert_eq!(copy.len(), added as f64);
+      assert_eq!(copy.header(), filter.header());
+      for i in 0..1000 {
+        assert!(copy.contains(&format!("user_{}", i)));
+      }
+      let false_positives = (0..10_000).filter(|i| copy.contains(&format!("other_{}", i))).count();
+      assert!(false_positives < 200, "{} false positives", false_positives);
+    }
+  }
+
+  #[test]
+  fn test_rejects_malformed_chunks() {
+    assert!(RedisBloomFilter::from_header(&[0; 10]).is_err());
+    let mut bad = header(100, 0.01, 0);
+    bad[CHAIN_HEADER_LEN + 8] ^= 1;
+    assert!(RedisBloomFilter::from_header(&bad).is_err());
+    let mut bad = header(100, 0.01, 0);
+    bad[CHAIN_HEADER_LEN + 43] = 0x80;
+    assert!(RedisBloomFilter::from_header(&bad).is_err());
+
+    let mut filter = RedisBloomFilter::from_header(&header(100, 0.01, OPT_NOROUND)).unwrap();
+    let len = filter.memory_bytes();
+    assert!(filter.load_chunk(len as f64 + 2.0, &vec![0; len]).is_err());
+    assert!(filter.load_chunk(1.0, &[]).is_err());
+    assert!(filter.scandump(-1.0).is_err());
+  }
+}
@@ -0,0 +1,325 @@
+use std::collections::VecDeque;
+
+use crate::error::SketchError;
+
+const MAGIC: u8 = b'R';
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 3;
+
+/// The sketch type a replay log was recorded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReplayTarget {
+  BloomFilter = 1,
+  CountMinSketch = 2,
+  HyperLogLog = 3,
+}
+
+/// A single recorded operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReplayOp {
+  /// An insert/increment/add of a key, identified by its hash
+  Update(u64),
+  /// A CountMinSketch increment of a key by a count, logged once rather than once per unit
+  UpdateBy(u64, u32),
+  /// A clear of the whole sketch
+  Clear,
+}
+
+const OP_UPDATE: u8 = 1;
+const OP_CLEAR: u8 = 2;
+const OP_UPDATE_BY: u8 = 3;
+
+/// A compact, append-only log of operations applied to a sketch.
+///
+/// Layout: `'R'`, version byte, target byte, then one record per operation:
+/// an op byte, followed by the 8-byte little-endian key hash for updates, and for weighted
+/// updates also the 4-byte little-endian count.
+#[derive(Debug, Clone)]
+pub(crate) struct ReplayLog {
+  bytes: Vec<u8>,
+}
+
+impl ReplayLog {
+  pub(crate) fn new(target: ReplayTarget) -> ReplayLog {
+    ReplayLog { bytes: vec![MAGIC, VERSION, target as u8] }
+  }
+
+  pub(crate) fn record(&mut self, op: ReplayOp) {
+    match op {
+      ReplayOp::Update(hash) => {
+        self.bytes.push(OP_UPDATE);
+        self.bytes.extend_from_slice(&hash.to_le_bytes());
+      }
+      ReplayOp::UpdateBy(hash, count) => {
+        self.bytes.push(OP_UPDATE_BY);
+        self.bytes.extend_from_slice(&hash.to_le_bytes());
+        self.bytes.extend_from_slice(&count.to_le_bytes());
+      }
+      ReplayOp::Clear => self.bytes.push(OP_CLEAR),
+    }
+  }
+
+  pub(crate) fn into_bytes(self) -> Vec<u8> {
+    self.bytes
+  }
+
+  /// Parses a replay buffer recorded from `target`, validating it fully before returning.
+  pub(crate) fn parse(buffer: &[u8], target: ReplayTarget) -> Result<Vec<ReplayOp>, SketchError> {
+    if buffer.len() < HEADER_LEN || buffer[0] != MAGIC {
+      return Err(SketchError::Malformed("not a replay log".to_string()));
+    }
+    if buffer[1] != VERSION {
+      return Err(SketchError::Malformed(format!("unsupported replay log version {}", buffer[1])));
+    }
+    if buffer[2] != target as u8 {
+      return Err(SketchError::Incompatible(
+        "replay log was recorded from a different sketch type".to_string(),
+      ));
+    }
+
+    let mut ops = Vec::new();
+    let mut rest = &buffer[HEADER_LEN..];
+    while let Some((&op, tail)) = rest.split_first() {
+      match op {
+        OP_UPDATE => {
+          let Some((hash, tail)) = tail.split_first_chunk::<8>() else {
+            return Err(SketchError::Malformed("truncated replay record".to_string()));
+          };
+          ops.push(ReplayOp::Update(u64::from_le_bytes(*hash)));
+          rest = tail;
+        }
+        OP_UPDATE_BY if target == ReplayTarget::CountMinSketch => {
+          let Some((hash, tail)) = tail.split_first_chunk::<8>() else {
+            return Err(SketchError::Malformed("truncated replay record".to_string()));
+          };
+          let Some((count, tail)) = tail.split_first_chunk::<4>() else {
+            return Err(SketchError::Malformed("truncated replay record".to_string()));
+          };
+          ops.push(ReplayOp::UpdateBy(u64::from_le_bytes(*hash), u32::from_le_bytes(*count)));
+          rest = tail;
+        }
+        OP_CLEAR => {
+          ops.push(ReplayOp::Clear);
+          rest = tail;
+        }
+        _ => return Err(SketchError::Malformed(format!("unknown replay op {}", op))),
+      }
+    }
+    Ok(ops)
+  }
+}
+
+/// A bounded write-ahead log that keeps only the most recent operations, so a host can replay
+/// the updates made since its last snapshot after a crash. Serializes to the replay log layout.
+#[derive(Debug, Clone)]
+pub(crate) struct WriteAheadLog {
+  target: ReplayTarget,
+  capacity: usize,
+  ops: VecDeque<ReplayOp>,
+  // Whether operations have been dropped since the last truncate or clear
+  overflowed: bool,
+}
+
+impl WriteAheadLog {
+  pub(crate) fn new(target: ReplayTarget, capacity: usize) -> WriteAheadLog {
+    WriteAheadLog { target, capacity, ops: VecDeque::new(), overflowed: false }
+  }
+
+  /// Appends an operation, dropping the oldest one and flagging the overflow once the log is
+  /// full.
+  pub(crate) fn record(&mut self, op: ReplayOp) {
+    // Nothing before a clear affects the sketch, so it need not be replayed
+    if op == ReplayOp::Clear {
+      self.ops.clear();
+      self.overflowed = false;
+    }
+    if self.ops.len() == self.capacity {
+      self.overflowed = true;
+      if self.capacity == 0 {
+        return;
+      }
+      self.ops.pop_front();
+    }
+    self.ops.push_back(op);
+  }
+
+  pub(crate) fn truncate(&mut self) {
+    self.ops.clear();
+    self.overflowed = false;
+  }
+
+  pub(crate) fn overflowed(&self) -> bool {
+    self.overflowed
+  }
+
+  pub(crate) fn to_bytes(&self) -> Vec<u8> {
+    let mut log = ReplayLog::new(self.target);
+    for &op in &self.ops {
+      log.record(op);
+    }
+    log.into_bytes()
+  }
+}
+
+/// The recording and write-ahead log of one sketch, either of which may be active. Sketches
+/// pass every insert, increment and clear to `record`, and call `check_unlogged` before any
+/// other mutation, which a replay could not reproduce.
+#[derive(Debug, Clone)]
+pub(crate) struct Journal {
+  target: ReplayTarget,
+  recording: Option<ReplayLog>,
+  wal: Option<WriteAheadLog>,
+}
+
+impl Journal {
+  pub(crate) fn new(target: ReplayTarget) -> Journal {
+    Journal { target, recording: None, wal: None }
+  }
+
+  /// Starts a recording, discarding any previous one.
+  pub(crate) fn start_recording(&mut self) {
+    self.recording = Some(ReplayLog::new(self.target));
+  }
+
+  /// Ends the recording and returns it, or an empty buffer if none was active.
+  pub(crate) fn stop_recording(&mut self) -> Vec<u8> {
+    self.recording.take().map(ReplayLog::into_bytes).unwrap_or_default()
+  }
+
+  /// Starts a write-ahead log of the last `capacity` operations, discarding any previous one.
+  pub(crate) fn enable_wal(&mut self, capacity: usize) {
+    self.wal = Some(WriteAheadLog::new(self.target, capacity));
+  }
+
+  pub(crate) fn disable_wal(&mut self) {
+    self.wal = None;
+  }
+
+  /// Returns the write-ahead log as a replay buffer, or an empty buffer if none is active.
+  pub(crate) fn wal_bytes(&self) -> Vec<u8> {
+    self.wal.as_ref().map(WriteAheadLog::to_bytes).unwrap_or_default()
+  }
+
+  pub(crate) fn truncate_wal(&mut self) {
+    if let Some(wal) = &mut self.wal {
+      wal.truncate();
+    }
+  }
+
+  /// Returns true if the write-ahead log has dropped operations since it was last truncated.
+  pub(crate) fn wal_overflowed(&self) -> bool {
+    self.wal.as_ref().is_some_and(WriteAheadLog::overflowed)
+  }
+
+  /// Appends an operation to the recording and the write-ahead log, whichever are active.
+  pub(crate) fn record(&mut self, op: ReplayOp) {
+    if let Some(log) = &mut self.recording {
+      log.record(op);
+    }
+    if let Some(wal) = &mut self.wal {
+      wal.record(op);
+    }
+  }
+
+  /// Fails if a recording or write-ahead log is active, since replaying it would silently miss
+  /// the mutation described by `operation`.
+  pub(crate) fn check_unlogged(&self, operation: &str) -> Result<(), SketchError> {
+    if self.recording.is_some() || self.wal.is_some() {
+      return Err(SketchError::Incompatible(format!(
+        "cannot {} while recording or keeping a write-ahead log",
+        operation
+      )));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let mut log = ReplayLog::new(ReplayTarget::HyperLogLog);
+    log.record(ReplayOp::Update(42));
+    log.record(ReplayOp::Clear);
+    log.record(ReplayOp::Update(u64::MAX));
+    let bytes = log.into_bytes();
+
+    let ops = ReplayLog::parse(&bytes, ReplayTarget::HyperLogLog).unwrap();
+    assert_eq!(ops, vec![ReplayOp::Update(42), ReplayOp::Clear, ReplayOp::Update(u64::MAX)]);
+
+    assert!(ReplayLog::parse(&bytes, ReplayTarget::BloomFilter).is_err());
+    assert!(ReplayLog::parse(&bytes[..bytes.len() - 1], ReplayTarget::HyperLogLog).is_err());
+  }
+
+  #[test]
+  fn test_weighted_updates() {
+    let mut log = ReplayLog::new(ReplayTarget::CountMinSketch);
+    log.record(ReplayOp::UpdateBy(7, u32::MAX));
+    let bytes = log.into_bytes();
+    assert_eq!(bytes.len(), HEADER_LEN + 13);
+    let ops = ReplayLog::parse(&bytes, ReplayTarget::CountMinSketch).unwrap();
+    assert_eq!(ops, vec![ReplayOp::UpdateBy(7, u32::MAX)]);
+    assert!(ReplayLog::parse(&bytes[..bytes.len() - 1], ReplayTarget::CountMinSketch).is_err());
+
+    // Only CountMinSketch has weighted updates
+    let mut hll = bytes.clone();
+    hll[2] = ReplayTarget::HyperLogLog as u8;
+    assert!(ReplayLog::parse(&hll, ReplayTarget::HyperLogLog).is_err());
+  }
+
+  #[test]
+  fn test_write_ahead_log_keeps_tail() {
+    let mut wal = WriteAheadLog::new(ReplayTarget::CountMinSketch, 2);
+    for hash in 1..=3 {
+      wal.record(ReplayOp::Update(hash));
+    }
+    let ops = ReplayLog::parse(&wal.to_bytes(), ReplayTarget::CountMinSketch).unwrap();
+    assert_eq!(ops, vec![ReplayOp::Update(2), ReplayOp::Update(3)]);
+
+    wal.record(ReplayOp::Clear);
+    wal.record(ReplayOp::Update(4));
+    let ops = ReplayLog::parse(&wal.to_bytes(), ReplayTarget::CountMinSketch).unwrap();
+    assert_eq!(ops, vec![ReplayOp::Clear, ReplayOp::Update(4)]);
+
+    wal.truncate();
+    assert!(ReplayLog::parse(&wal.to_bytes(), ReplayTarget::CountMinSketch).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_write_ahead_log_flags_overflow() {
+    let mut wal = WriteAheadLog::new(ReplayTarget::CountMinSketch, 2);
+    wal.record(ReplayOp::Update(1));
+    wal.record(ReplayOp::Update(2));
+    assert!(!wal.overflowed());
+    wal.record(ReplayOp::Update(3));
+    assert!(wal.overflowed());
+
+    // A clear makes everything before it irrelevant
+    wal.record(ReplayOp::Clear);
+    assert!(!wal.overflowed());
+    wal.record(ReplayOp::Update(4));
+    wal.record(ReplayOp::Update(5));
+    assert!(wal.overflowed());
+    wal.truncate();
+    assert!(!wal.overflowed());
+
+    let mut empty = WriteAheadLog::new(ReplayTarget::BloomFilter, 0);
+    empty.record(ReplayOp::Update(1));
+    assert!(empty.overflowed());
+  }
+
+  #[test]
+  fn test_journal_rejects_unlogged_mutations() {
+    let mut journal = Journal::new(ReplayTarget::BloomFilter);
+    assert!(journal.check_unlogged("merge").is_ok());
+    journal.start_recording();
+    assert!(journal.check_unlogged("merge").is_err());
+    assert!(!journal.stop_recording().is_empty());
+    journal.enable_wal(4);
+    assert!(journal.check_unlogged("merge").is_err());
+    journal.disable_wal();
+    assert!(journal.check_unlogged("merge").is_ok());
+  }
+}
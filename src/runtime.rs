@@ -0,0 +1,114 @@
+//! Sources of randomness and time shared by every sketch, with a deterministic test mode.
+//!
+//! By default sketches draw entropy from the platform and read `Date.now()`. In test mode,
+//! each sketch created afterwards gets an RNG derived from a fixed seed and the order of
+//! creation, and the clock returns a fixed or host-provided time, so an app's integration tests
+//! produce the same sketches on every run.
+
+use std::cell::{Cell, RefCell};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use wasm_bindgen::prelude::*;
+
+use crate::hash::mix64;
+
+#[derive(Debug)]
+enum Clock {
+  System,
+  Fixed(f64),
+  Callback(js_sys::Function),
+}
+
+thread_local! {
+  // Seed and number of RNGs handed out, present in test mode
+  static TEST_SEED: Cell<Option<(u64, u64)>> = const { Cell::new(None) };
+  static CLOCK: RefCell<Clock> = const { RefCell::new(Clock::System) };
+}
+
+/// Makes randomness deterministic: every sketch created from now on seeds its RNG from `seed`
+/// and its creation order. Sketches created before the call keep their RNGs.
+///
+/// # Arguments
+///
+/// * `seed` - The seed for all subsequently created RNGs
+#[wasm_bindgen]
+pub fn enable_test_mode(seed: u64) {
+  TEST_SEED.with(|state| state.set(Some((seed, 0))));
+}
+
+/// Restores platform randomness and the system clock.
+#[wasm_bindgen]
+pub fn disable_test_mode() {
+  TEST_SEED.with(|state| state.set(None));
+  CLOCK.with(|clock| *clock.borrow_mut() = Clock::System);
+}
+
+/// Freezes the clock used by time-based sketches at the given time.
+///
+/// # Arguments
+///
+/// * `now_ms` - The time to report, in milliseconds since the epoch
+#[wasm_bindgen]
+pub fn set_clock_ms(now_ms: f64) {
+  CLOCK.with(|clock| *clock.borrow_mut() = Clock::Fixed(now_ms));
+}
+
+/// Drives the clock used by time-based sketches from a callback, e.g. a fake timer.
+///
+/// # Arguments
+///
+/// * `callback` - A function returning the current time in milliseconds since the epoch
+#[wasm_bindgen]
+pub fn set_clock(callback: js_sys::Function) {
+  CLOCK.with(|clock| *clock.borrow_mut() = Clock::Callback(callback));
+}
+
+/// Returns a new RNG, seeded deterministically in test mode.
+pub(crate) fn new_rng() -> ChaCha8Rng {
+  match TEST_SEED.with(|state| {
+    let current = state.get();
+    if let Some((seed, created)) = current {
+      state.set(Some((seed, created + 1)));
+    }
+    current
+  }) {
+    Some((seed, created)) => ChaCha8Rng::seed_from_u64(mix64(seed, created)),
+    None => ChaCha8Rng::from_entropy(),
+  }
+}
+
+/// Returns the current time in milliseconds since the epoch.
+pub(crate) fn now_ms() -> f64 {
+  CLOCK.with(|clock| match &*clock.borrow() {
+    Clock::System => js_sys::Date::now(),
+    Clock::Fixed(now) => *now,
+    Clock::Callback(callback) => {
+      callback.call0(&JsValue::NULL).ok().and_then(|value| value.as_f64()).unwrap_or(f64::NAN)
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::Rng;
+
+  #[test]
+  fn test_seeded_rngs_repeat() {
+    enable_test_mode(42);
+    let first: Vec<u64> = (0..2).map(|_| new_rng().gen()).collect();
+    enable_test_mode(42);
+    let second: Vec<u64> = (0..2).map(|_| new_rng().gen()).collect();
+    assert_eq!(first, second);
+    assert_ne!(first[0], first[1]);
+    disable_test_mode();
+  }
+
+  #[test]
+  fn test_fixed_clock() {
+    set_clock_ms(1_000.0);
+    assert_eq!(now_ms(), 1_000.0);
+    disable_test_mode();
+  }
+}
@@ -0,0 +1,174 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+
+/// A Bloom filter for streams of unknown size, after Almeida et al.'s scalable Bloom filters.
+///
+/// Items go into the newest of a chain of slices. When a slice reaches its capacity a new one
+/// is added, `growth` times larger and with its false positive rate multiplied by
+/// `tightening`. The slice rates form a geometric series, so the overall false positive rate
+/// stays below the target however many slices are added.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ScalableBloomFilter {
+  slices: Vec<BloomFilter>,
+  // Capacity and false positive rate of the newest slice
+  capacity: usize,
+  slice_rate: f64,
+  // Items inserted into the newest slice
+  slice_items: usize,
+  growth: usize,
+  tightening: f64,
+  items: usize,
+}
+
+#[wasm_bindgen]
+impl ScalableBloomFilter {
+  /// Creates a new scalable Bloom filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `initial_capacity` - The number of items the first slice holds
+  /// * `false_positive_rate` - The overall false positive rate to stay under (between 0 and 1)
+  /// * `growth` - How many times larger each slice is than the last; defaults to 2
+  /// * `tightening` - The factor applied to each slice's false positive rate (between 0 and
+  ///   1); defaults to 0.5
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    initial_capacity: usize,
+    false_positive_rate: f64,
+    growth: Option<usize>,
+    tightening: Option<f64>,
+  ) -> Result<ScalableBloomFilter, SketchError> {
+    let growth = growth.unwrap_or(2);
+    let tightening = tightening.unwrap_or(0.5);
+    if initial_capacity == 0 {
+      return Err(SketchError::InvalidParameter("initial_capacity must be positive".to_string()));
+    }
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+      return Err(SketchError::InvalidParameter(
+        "false_positive_rate must be between 0 and 1".to_string(),
+      ));
+    }
+    if growth == 0 {
+      return Err(SketchError::InvalidParameter("growth must be positive".to_string()));
+    }
+    if !(tightening > 0.0 && tightening < 1.0) {
+      return Err(SketchError::InvalidParameter("tightening must be between 0 and 1".to_string()));
+    }
+
+    // The first slice's share of the total, so the series sums to the target rate
+    let slice_rate = false_positive_rate * (1.0 - tightening);
+    Ok(ScalableBloomFilter {
+      slices: vec![BloomFilter::new(initial_capacity, slice_rate)],
+      capacity: initial_capacity,
+      slice_rate,
+      slice_items: 0,
+      growth,
+      tightening,
+      items: 0,
+    })
+  }
+
+  /// Inserts an item, adding a slice first if the newest one is full. Items that already test
+  /// positive are skipped, so they do not use up capacity.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    let hash = hash_str(item);
+    if self.contains_hash(hash) {
+      return;
+    }
+    if self.slice_items >= self.capacity {
+      self.capacity = self.capacity.saturating_mul(self.growth);
+      self.slice_rate *= self.tightening;
+      self.slices.push(BloomFilter::new(self.capacity, self.slice_rate));
+      self.slice_items = 0;
+    }
+    self.slices.last_mut().expect("there is always a slice").insert_hash(hash);
+    self.slice_items += 1;
+    self.items += 1;
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.contains_hash(hash_str(item))
+  }
+
+  /// Returns the number of items inserted, not counting those skipped as already present.
+  #[wasm_bindgen(getter)]
+  pub fn len(&self) -> usize {
+    self.items
+  }
+
+  /// Returns true if no items have been inserted.
+  #[wasm_bindgen]
+  pub fn is_empty(&self) -> bool {
+    self.items == 0
+  }
+
+  /// Returns the number of slices in the chain.
+  #[wasm_bindgen(getter)]
+  pub fn slice_count(&self) -> usize {
+    self.slices.len()
+  }
+
+  /// Returns the number of bytes used by the bit arrays of all slices.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.slices.iter().map(BloomFilter::memory_bytes).sum()
+  }
+}
+
+impl ScalableBloomFilter {
+  fn contains_hash(&self, hash: u64) -> bool {
+    self.slices.iter().any(|slice| slice.contains_hash(hash))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_grows_past_initial_capacity() {
+    let mut filter = ScalableBloomFilter::new(100, 0.01, None, None).unwrap();
+    assert!(filter.is_empty());
+    for i in 0..5000 {
+      filter.insert(&format!("item_{}", i));
+    }
+    assert!(filter.slice_count() > 5);
+    for i in 0..5000 {
+      assert!(filter.contains(&format!("item_{}", i)));
+    }
+
+    let false_positives = (0..10_000).filter(|i| filter.contains(&format!("other_{}", i))).count();
+    assert!(false_positives < 150, "{} false positives", false_positives);
+  }
+
+  #[test]
+  fn test_duplicates_do_not_use_capacity() {
+    let mut filter = ScalableBloomFilter::new(10, 0.01, Some(4), Some(0.8)).unwrap();
+    for _ in 0..100 {
+      filter.insert("same");
+    }
+    assert_eq!(filter.len(), 1);
+    assert_eq!(filter.slice_count(), 1);
+
+    assert!(ScalableBloomFilter::new(0, 0.01, None, None).is_err());
+    assert!(ScalableBloomFilter::new(10, 1.0, None, None).is_err());
+    assert!(ScalableBloomFilter::new(10, 0.01, Some(0), None).is_err());
+    assert!(ScalableBloomFilter::new(10, 0.01, None, Some(1.0)).is_err());
+  }
+}
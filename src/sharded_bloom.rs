@@ -0,0 +1,224 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+
+// Seed separating shard routing from the probe positions inside a shard
+const ROUTING_SEED: u64 = 0x0053_4841_5244;
+
+/// A Bloom filter split into independently serialized shards, with every key routed to one
+/// shard by its hash. Enormous filters can then be stored shard by shard (e.g. in IndexedDB),
+/// loaded lazily, and only the hot shards kept resident.
+///
+/// Queries and inserts against a shard that is not loaded fail; use `shard_for` to find which
+/// shard to load first.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ShardedBloomFilter {
+  shards: Vec<Option<BloomFilter>>,
+  expected_per_shard: usize,
+  false_positive_rate: f64,
+}
+
+#[wasm_bindgen]
+impl ShardedBloomFilter {
+  /// Creates a sharded filter with every shard resident and empty.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items across all shards
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  /// * `shard_count` - The number of shards
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    expected_items: usize,
+    false_positive_rate: f64,
+    shard_count: usize,
+  ) -> Result<ShardedBloomFilter, SketchError> {
+    let mut filter = Self::unloaded(expected_items, false_positive_rate, shard_count)?;
+    filter.shards = filter.shards.iter().map(|_| Some(filter.empty_shard())).collect();
+    Ok(filter)
+  }
+
+  /// Creates a sharded filter with no shards resident, to be filled with `load_shard`.
+  /// The parameters must match those the shards were created with.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items across all shards
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  /// * `shard_count` - The number of shards
+  #[wasm_bindgen]
+  pub fn unloaded(
+    expected_items: usize,
+    false_positive_rate: f64,
+    shard_count: usize,
+  ) -> Result<ShardedBloomFilter, SketchError> {
+    if shard_count == 0 {
+      return Err(SketchError::InvalidParameter("shard_count must be positive".to_string()));
+    }
+    Ok(ShardedBloomFilter {
+      shards: (0..shard_count).map(|_| None).collect(),
+      expected_per_shard: expected_items.div_ceil(shard_count).max(1),
+      false_positive_rate,
+    })
+  }
+
+  /// Returns the index of the shard an item is routed to.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to route
+  #[wasm_bindgen]
+  pub fn shard_for(&self, item: &str) -> usize {
+    (mix64(hash_str(item), ROUTING_SEED) % self.shards.len() as u64) as usize
+  }
+
+  /// Inserts an item into its shard, which must be loaded.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) -> Result<(), SketchError> {
+    let shard = self.shard_for(item);
+    self.loaded_mut(shard)?.insert(item);
+    Ok(())
+  }
+
+  /// Checks if an item might be in the set; its shard must be loaded.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> Result<bool, SketchError> {
+    let shard = self.shard_for(item);
+    match &self.shards[shard] {
+      Some(filter) => Ok(filter.contains(item)),
+      None => Err(Self::not_loaded(shard)),
+    }
+  }
+
+  /// Returns whether a shard is resident.
+  ///
+  /// # Arguments
+  ///
+  /// * `shard` - The shard index
+  #[wasm_bindgen]
+  pub fn is_loaded(&self, shard: usize) -> bool {
+    matches!(self.shards.get(shard), Some(Some(_)))
+  }
+
+  /// Serializes one loaded shard for storage.
+  ///
+  /// # Arguments
+  ///
+  /// * `shard` - The shard index
+  #[wasm_bindgen]
+  pub fn export_shard(&self, shard: usize) -> Result<Vec<u8>, SketchError> {
+    self.check_index(shard)?;
+    match &self.shards[shard] {
+      Some(filter) => Ok(filter.to_bytes()),
+      None => Err(Self::not_loaded(shard)),
+    }
+  }
+
+  /// Makes a shard resident from a buffer produced by `export_shard`, replacing any
+  /// resident copy.
+  ///
+  /// # Arguments
+  ///
+  /// * `shard` - The shard index
+  /// * `bytes` - The serialized shard
+  #[wasm_bindgen]
+  pub fn load_shard(&mut self, shard: usize, bytes: &[u8]) -> Result<(), SketchError> {
+    self.check_index(shard)?;
+    let filter = BloomFilter::from_bytes(bytes)?;
+    let expected = self.empty_shard();
+    if filter.bit_count() != expected.bit_count() || filter.probe_count() != expected.probe_count()
+    {
+      return Err(SketchError::Incompatible(
+        "shard was created with different filter parameters".to_string(),
+      ));
+    }
+    self.shards[shard] = Some(filter);
+    Ok(())
+  }
+
+  /// Drops a shard from memory; export it first if it has unsaved inserts.
+  ///
+  /// # Arguments
+  ///
+  /// * `shard` - The shard index
+  #[wasm_bindgen]
+  pub fn unload_shard(&mut self, shard: usize) -> Result<(), SketchError> {
+    self.check_index(shard)?;
+    self.shards[shard] = None;
+    Ok(())
+  }
+
+  /// Returns the number of shards.
+  #[wasm_bindgen(getter)]
+  pub fn shard_count(&self) -> usize {
+    self.shards.len()
+  }
+}
+
+impl ShardedBloomFilter {
+  fn empty_shard(&self) -> BloomFilter {
+    BloomFilter::new(self.expected_per_shard, self.false_positive_rate)
+  }
+
+  fn check_index(&self, shard: usize) -> Result<(), SketchError> {
+    if shard >= self.shards.len() {
+      return Err(SketchError::InvalidParameter(format!("shard {} does not exist", shard)));
+    }
+    Ok(())
+  }
+
+  fn loaded_mut(&mut self, shard: usize) -> Result<&mut BloomFilter, SketchError> {
+    self.check_index(shard)?;
+    self.shards[shard].as_mut().ok_or_else(|| Self::not_loaded(shard))
+  }
+
+  fn not_loaded(shard: usize) -> SketchError {
+    SketchError::InvalidParameter(format!("shard {} is not loaded", shard))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_lazy_loading() {
+    let mut filter = ShardedBloomFilter::new(1000, 0.01, 4).unwrap();
+    for i in 0..1000 {
+      filter.insert(&format!("item_{}", i)).unwrap();
+    }
+    let stored: Vec<Vec<u8>> = (0..4).map(|shard| filter.export_shard(shard).unwrap()).collect();
+
+    let mut lazy = ShardedBloomFilter::unloaded(1000, 0.01, 4).unwrap();
+    let shard = lazy.shard_for("item_42");
+    assert!(lazy.contains("item_42").is_err());
+    lazy.load_shard(shard, &stored[shard]).unwrap();
+    assert!(lazy.contains("item_42").unwrap());
+    assert!((0..4).filter(|&index| lazy.is_loaded(index)).eq([shard]));
+
+    lazy.unload_shard(shard).unwrap();
+    assert!(lazy.insert("item_42").is_err());
+  }
+
+  #[test]
+  fn test_rejects_mismatched_shards() {
+    let mut filter = ShardedBloomFilter::new(1000, 0.01, 4).unwrap();
+    let other = BloomFilter::new(10, 0.01).to_bytes();
+    assert!(filter.load_shard(0, &other).is_err());
+    let shard = filter.export_shard(0).unwrap();
+    assert!(filter.load_shard(4, &shard).is_err());
+    assert!(ShardedBloomFilter::new(1000, 0.01, 0).is_err());
+  }
+}
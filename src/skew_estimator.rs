@@ -0,0 +1,181 @@
+use wasm_bindgen::prelude::*;
+
+use crate::heavy_keeper::HeavyKeeper;
+use crate::hyperloglog::HyperLogLog;
+
+// Items tracked for the rank-frequency fit, and the sketch dimensions tracking them
+const TRACKED_RANKS: usize = 32;
+const KEEPER_WIDTH: usize = 512;
+const KEEPER_DEPTH: usize = 3;
+const KEEPER_DECAY: f64 = 0.9;
+const DISTINCT_PRECISION: u8 = 12;
+// The distinct count is sampled each time the stream length doubles from here
+const FIRST_SAMPLE: u64 = 16;
+
+/// Estimates the shape of a stream online, as input for choosing sketch parameters: its Zipf
+/// skew, from the rank-frequency curve of its heaviest items, and how its distinct count grows
+/// with its length.
+///
+/// Heavy items are tracked with a small HeavyKeeper and distinct items with a HyperLogLog, so
+/// the estimator uses a few tens of kilobytes whatever the stream size.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct SkewEstimator {
+  keeper: HeavyKeeper,
+  distinct: HyperLogLog,
+  items: u64,
+  // (stream length, distinct estimate) sampled at doubling lengths
+  trajectory: Vec<(f64, f64)>,
+}
+
+#[wasm_bindgen]
+impl SkewEstimator {
+  /// Creates an estimator that has seen no items.
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> SkewEstimator {
+    SkewEstimator {
+      keeper: HeavyKeeper::new(KEEPER_WIDTH, KEEPER_DEPTH, TRACKED_RANKS, KEEPER_DECAY),
+      distinct: HyperLogLog::new(Some(DISTINCT_PRECISION)).expect("precision is in range"),
+      items: 0,
+      trajectory: Vec::new(),
+    }
+  }
+
+  /// Observes one item of the stream.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to observe
+  #[wasm_bindgen]
+  pub fn add(&mut self, item: &str) {
+    self.keeper.add(item);
+    self.distinct.add(item);
+    self.items += 1;
+    if self.items >= FIRST_SAMPLE && self.items.is_power_of_two() {
+      self.trajectory.push((self.items as f64, self.distinct.count()));
+    }
+  }
+
+  /// Returns the estimated Zipf exponent `s`, where the item of rank `r` has frequency
+  /// proportional to `r^-s`: near 0 for uniform streams, around 1 for typical web traffic and
+  /// higher for streams dominated by a few items. Returns 0 until two distinct heavy items
+  /// have been seen.
+  #[wasm_bindgen]
+  pub fn zipf_exponent(&self) -> f64 {
+    let points: Vec<(f64, f64)> = self
+      .keeper
+      .top_k()
+      .iter()
+      .map(|entry| self.keeper.query(&entry.item()))
+      .filter(|&count| count > 0)
+      .enumerate()
+      .map(|(rank, count)| (((rank + 1) as f64).ln(), f64::from(count).ln()))
+      .collect();
+    fit_line(&points).map_or(0.0, |(_, slope)| (-slope).max(0.0))
+  }
+
+  /// Returns the estimated number of distinct items seen so far.
+  #[wasm_bindgen]
+  pub fn distinct_count(&self) -> f64 {
+    self.distinct.count()
+  }
+
+  /// Returns the distinct-count trajectory as `[length, distinct, length, distinct, ...]`,
+  /// sampled each time the stream length doubles, starting at 16 items.
+  #[wasm_bindgen]
+  pub fn distinct_trajectory(&self) -> Vec<f64> {
+    self.trajectory.iter().flat_map(|&(items, distinct)| [items, distinct]).collect()
+  }
+
+  /// Projects the distinct count at a future stream length by fitting Heaps' law,
+  /// `distinct = K * length^beta`, to the trajectory; e.g. to size a Bloom filter for the
+  /// end of the day. Returns the current estimate until two samples exist.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - The stream length to project to
+  #[wasm_bindgen]
+  pub fn projected_distinct(&self, items: f64) -> f64 {
+    let points: Vec<(f64, f64)> = self
+      .trajectory
+      .iter()
+      .filter(|&&(_, distinct)| distinct > 0.0)
+      .map(|&(items, distinct)| (items.ln(), distinct.ln()))
+      .collect();
+    match fit_line(&points) {
+      // A stream cannot have more distinct items than items
+      Some((intercept, slope)) => (intercept + slope * items.ln()).exp().min(items),
+      None => self.distinct_count(),
+    }
+  }
+
+  /// Returns the number of items observed.
+  #[wasm_bindgen(getter)]
+  pub fn items(&self) -> f64 {
+    self.items as f64
+  }
+}
+
+impl Default for SkewEstimator {
+  fn default() -> Self {
+    SkewEstimator::new()
+  }
+}
+
+// Least-squares fit of y = intercept + slope * x, or None for fewer than two distinct x values
+fn fit_line(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+  let n = points.len() as f64;
+  let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+  let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+  let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+  if points.len() < 2 || sxx == 0.0 {
+    return None;
+  }
+  let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+  let slope = sxy / sxx;
+  Some((mean_y - slope * mean_x, slope))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_zipf_exponent() {
+    let mut estimator = SkewEstimator::new();
+    assert_eq!(estimator.zipf_exponent(), 0.0);
+
+    // Rank r appears 1000 / r times, an exponent of 1
+    for rank in 1..=50 {
+      for _ in 0..1000 / rank {
+        estimator.add(&format!("item_{}", rank));
+      }
+    }
+    let s = estimator.zipf_exponent();
+    assert!((s - 1.0).abs() < 0.2, "exponent {}", s);
+
+    let mut uniform = SkewEstimator::new();
+    for _ in 0..50 {
+      for item in 0..40 {
+        uniform.add(&format!("item_{}", item));
+      }
+    }
+    assert!(uniform.zipf_exponent() < 0.2);
+  }
+
+  #[test]
+  fn test_distinct_trajectory_and_projection() {
+    let mut estimator = SkewEstimator::new();
+    // Every item is new, so distinct grows linearly
+    for i in 0..4096 {
+      estimator.add(&format!("item_{}", i));
+    }
+    let trajectory = estimator.distinct_trajectory();
+    assert_eq!(trajectory.len(), 2 * 9);
+    assert_eq!(trajectory[0], 16.0);
+    assert_eq!(estimator.items(), 4096.0);
+
+    let projected = estimator.projected_distinct(16_384.0);
+    assert!((projected - 16_384.0).abs() / 16_384.0 < 0.15, "projected {}", projected);
+  }
+}
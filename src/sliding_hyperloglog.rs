@@ -0,0 +1,152 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hyperloglog::HyperLogLog;
+use crate::runtime;
+
+/// A HyperLogLog that answers distinct counts over any trailing time window, e.g. unique
+/// visitors in the last 5 minutes or the last hour, from a single sketch.
+///
+/// Each register keeps a short list of (timestamp, rank) pairs instead of a single rank: a pair
+/// is dropped once a later item reaches the same or a higher rank, or once it falls out of the
+/// maximum window. `count_last` rebuilds the registers from the pairs inside the window.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct SlidingHyperLogLog {
+  p: u8,
+  max_window_ms: f64,
+  // Per register, pairs with increasing timestamps and strictly decreasing ranks
+  registers: Vec<Vec<(f64, u8)>>,
+}
+
+#[wasm_bindgen]
+impl SlidingHyperLogLog {
+  /// Creates a new sliding HyperLogLog.
+  ///
+  /// # Arguments
+  ///
+  /// * `precision` - The precision parameter (between 4 and 16), as for `HyperLogLog`
+  /// * `max_window_ms` - The longest window that will be queried; older items are forgotten
+  #[wasm_bindgen(constructor)]
+  pub fn new(precision: Option<u8>, max_window_ms: f64) -> Result<SlidingHyperLogLog, SketchError> {
+    let p = precision.unwrap_or(14);
+    if !(4..=16).contains(&p) {
+      return Err(SketchError::InvalidParameter("precision must be between 4 and 16".to_string()));
+    }
+    if max_window_ms.is_nan() || max_window_ms <= 0.0 {
+      return Err(SketchError::InvalidParameter("max_window_ms must be positive".to_string()));
+    }
+    Ok(SlidingHyperLogLog { p, max_window_ms, registers: vec![Vec::new(); 1 << p] })
+  }
+
+  /// Adds an item seen at the given time. Timestamps are expected to be non-decreasing;
+  /// an earlier timestamp is treated as the latest one seen.
+  ///
+  /// # Arguments
+  ///
+  /// * `value` - The item to add
+  /// * `timestamp_ms` - When the item was seen, finite; defaults to the current time
+  #[wasm_bindgen]
+  pub fn add(&mut self, value: &str, timestamp_ms: Option<f64>) -> Result<(), SketchError> {
+    let timestamp_ms = check_finite(timestamp_ms.unwrap_or_else(runtime::now_ms), "timestamp_ms")?;
+    let (index, rank) = HyperLogLog::slot(HyperLogLog::hash(value.as_bytes()), self.p);
+    let cutoff = timestamp_ms - self.max_window_ms;
+    let pairs = &mut self.registers[index];
+
+    // Older pairs with a rank no higher than the new one can never be the window maximum again
+    pairs.retain(|&(time, existing)| existing > rank && time >= cutoff);
+    let timestamp_ms = pairs.last().map_or(timestamp_ms, |&(time, _)| time.max(timestamp_ms));
+    pairs.push((timestamp_ms, rank));
+    Ok(())
+  }
+
+  /// Returns the estimated number of distinct items seen in the trailing window.
+  ///
+  /// # Arguments
+  ///
+  /// * `window_ms` - The window length, non-negative; clamped to the maximum window
+  /// * `now_ms` - The end of the window, finite; defaults to the current time
+  #[wasm_bindgen]
+  pub fn count_last(&self, window_ms: f64, now_ms: Option<f64>) -> Result<f64, SketchError> {
+    if window_ms.is_nan() || window_ms < 0.0 {
+      return Err(SketchError::InvalidParameter("window_ms must be non-negative".to_string()));
+    }
+    let now_ms = check_finite(now_ms.unwrap_or_else(runtime::now_ms), "now_ms")?;
+    let start = now_ms - window_ms.min(self.max_window_ms);
+    let mut snapshot = HyperLogLog::new(Some(self.p)).expect("precision was validated");
+    for (index, pairs) in self.registers.iter().enumerate() {
+      // Ranks decrease with time, so the first pair inside the window has the highest rank
+      if let Some(&(_, rank)) = pairs.iter().find(|&&(time, _)| time >= start && time <= now_ms) {
+        snapshot.raise_register(index, rank);
+      }
+    }
+    Ok(snapshot.count())
+  }
+
+  /// Removes all items.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.registers.iter_mut().for_each(Vec::clear);
+  }
+}
+
+// NaN or infinite times would compare false against every pair and corrupt the register lists
+fn check_finite(time_ms: f64, name: &str) -> Result<f64, SketchError> {
+  if !time_ms.is_finite() {
+    return Err(SketchError::InvalidParameter(format!("{} must be finite", name)));
+  }
+  Ok(time_ms)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_count_last() {
+    let mut hll = SlidingHyperLogLog::new(Some(12), 60_000.0).unwrap();
+    for i in 0..1000 {
+      hll.add(&format!("early_{}", i), Some(1_000.0)).unwrap();
+    }
+    for i in 0..2000 {
+      hll.add(&format!("late_{}", i), Some(31_000.0)).unwrap();
+    }
+
+    let last_10s = hll.count_last(10_000.0, Some(35_000.0)).unwrap();
+    let last_60s = hll.count_last(60_000.0, Some(35_000.0)).unwrap();
+    assert!((1800.0..=2200.0).contains(&last_10s));
+    assert!((2700.0..=3300.0).contains(&last_60s));
+
+    // The early items fall out of the maximum window
+    hll.add("latest", Some(70_000.0)).unwrap();
+    assert!((1800.0..=2200.0).contains(&hll.count_last(60_000.0, Some(70_000.0)).unwrap()));
+    assert_eq!(hll.count_last(1_000.0, Some(200_000.0)).unwrap(), 0.0);
+  }
+
+  #[test]
+  fn test_defaults_to_runtime_clock() {
+    let mut hll = SlidingHyperLogLog::new(Some(12), 60_000.0).unwrap();
+    runtime::set_clock_ms(5_000.0);
+    hll.add("a", None).unwrap();
+    assert_eq!(hll.count_last(1_000.0, None).unwrap(), 1.0);
+    runtime::set_clock_ms(10_000.0);
+    assert_eq!(hll.count_last(1_000.0, None).unwrap(), 0.0);
+    runtime::disable_test_mode();
+  }
+
+  #[test]
+  fn test_rejects_bad_parameters() {
+    assert!(SlidingHyperLogLog::new(Some(3), 1_000.0).is_err());
+    assert!(SlidingHyperLogLog::new(None, 0.0).is_err());
+
+    let mut hll = SlidingHyperLogLog::new(Some(12), 60_000.0).unwrap();
+    hll.add("a", Some(1_000.0)).unwrap();
+    for time in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+      assert!(hll.add("b", Some(time)).is_err());
+      assert!(hll.count_last(1_000.0, Some(time)).is_err());
+    }
+    assert!(hll.count_last(f64::NAN, Some(1_000.0)).is_err());
+    assert!(hll.count_last(-1.0, Some(1_000.0)).is_err());
+    assert_eq!(hll.count_last(1_000.0, Some(1_500.0)).unwrap(), 1.0);
+  }
+}
@@ -0,0 +1,132 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::hash::hash_str;
+
+/// A spectral Bloom filter (Cohen and Matias, 2003): a Bloom filter with a 16-bit counter per
+/// slot that answers how many times an item was inserted, not just whether it was.
+///
+/// Inserts use minimal increase, raising only the item's smallest counters, so `count` never
+/// underestimates and overestimates only when every slot of the item is shared with heavier
+/// items. That makes it more accurate than a Count-Min Sketch of the same memory for sets up
+/// to the expected size, but items cannot be removed. Counters saturate at 65535.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct SpectralBloomFilter {
+  counters: Vec<u16>,
+  hash_count: usize,
+}
+
+#[wasm_bindgen]
+impl SpectralBloomFilter {
+  /// Creates a new spectral Bloom filter with the specified expected number of distinct items
+  /// and false positive rate, which bounds how often `count` of an absent item is non-zero.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of distinct items
+  /// * `false_positive_rate` - The desired false positive rate (between 0 and 1)
+  #[wasm_bindgen(constructor)]
+  pub fn new(expected_items: usize, false_positive_rate: f64) -> SpectralBloomFilter {
+    let (len, hash_count) = BloomFilter::geometry(expected_items, false_positive_rate);
+    SpectralBloomFilter { counters: vec![0; len], hash_count }
+  }
+
+  /// Inserts one occurrence of an item.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    self.insert_by(item, 1);
+  }
+
+  /// Inserts `count` occurrences of an item at once.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  /// * `count` - The number of occurrences
+  #[wasm_bindgen]
+  pub fn insert_by(&mut self, item: &str, count: u16) {
+    let slots = self.slots(hash_str(item));
+    let target = self.min_count(&slots).saturating_add(count);
+    for slot in slots {
+      self.counters[slot] = self.counters[slot].max(target);
+    }
+  }
+
+  /// Returns the estimated number of times an item was inserted: never less than the true
+  /// count, and 0 if the item is definitely not in the set.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to count
+  #[wasm_bindgen]
+  pub fn count(&self, item: &str) -> u16 {
+    self.min_count(&self.slots(hash_str(item)))
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.count(item) > 0
+  }
+
+  /// Returns the memory used by the counters, in bytes.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.counters.len() * 2
+  }
+
+  /// Clears all counters in the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.counters.fill(0);
+  }
+}
+
+impl SpectralBloomFilter {
+  fn slots(&self, hash: u64) -> Vec<usize> {
+    (0..self.hash_count).map(|i| BloomFilter::probe_index(hash, i, self.counters.len())).collect()
+  }
+
+  fn min_count(&self, slots: &[usize]) -> u16 {
+    slots.iter().map(|&slot| self.counters[slot]).min().unwrap_or(0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_counts_never_underestimate() {
+    let mut filter = SpectralBloomFilter::new(1000, 0.01);
+    for i in 0..1000u16 {
+      filter.insert_by(&format!("item_{}", i), i % 10 + 1);
+    }
+    filter.insert("item_0");
+
+    let mut exact = 0;
+    for i in 0..1000u16 {
+      let count = filter.count(&format!("item_{}", i));
+      let truth = i % 10 + 1 + u16::from(i == 0);
+      assert!(count >= truth);
+      exact += usize::from(count == truth);
+    }
+    assert!(exact > 980, "{} exact counts", exact);
+
+    filter.insert_by("heavy", u16::MAX);
+    filter.insert("heavy");
+    assert_eq!(filter.count("heavy"), u16::MAX);
+    filter.clear();
+    assert_eq!(filter.count("item_1"), 0);
+  }
+}
@@ -0,0 +1,199 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+use crate::runtime;
+
+/// A Stable Bloom filter (Deng and Rafiei, 2006) for deduplicating unbounded streams, e.g.
+/// "have I seen this URL recently?".
+///
+/// Each cell is a small counter. An insert first decrements a run of cells starting at a
+/// random position, then sets the item's cells to the maximum count, so items that stop
+/// arriving fade out instead of saturating the filter. The fraction of zero cells converges to
+/// a constant, which bounds the false positive rate at the configured target. The price is
+/// false negatives for items that have not recurred recently; a larger maximum count makes
+/// items survive longer, lowering false negatives at the cost of memory.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct StableBloomFilter {
+  cells: Vec<u8>,
+  max_count: u8,
+  hash_count: usize,
+  // Number of cells decremented per insert
+  decrements: usize,
+  rng: ChaCha8Rng,
+}
+
+#[wasm_bindgen]
+impl StableBloomFilter {
+  /// Creates a new Stable Bloom filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `cells` - The number of cells (one byte each)
+  /// * `false_positive_rate` - The false positive rate the filter stabilizes at (between 0
+  ///   and 1)
+  /// * `max_count` - The value an insert sets cells to (between 1 and 255), defaulting to 1;
+  ///   higher values keep items longer
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    cells: usize,
+    false_positive_rate: f64,
+    max_count: Option<u8>,
+  ) -> Result<StableBloomFilter, SketchError> {
+    let max_count = max_count.unwrap_or(1);
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+      return Err(SketchError::InvalidParameter(
+        "false_positive_rate must be between 0 and 1".to_string(),
+      ));
+    }
+    if max_count == 0 {
+      return Err(SketchError::InvalidParameter("max_count must be positive".to_string()));
+    }
+    // One probe per halving of the false positive rate, as for a regular filter
+    let hash_count = (1.0 / false_positive_rate).log2().ceil().max(1.0) as usize;
+    if cells <= hash_count {
+      return Err(SketchError::InvalidParameter(format!(
+        "cells must exceed the {} probes per item",
+        hash_count
+      )));
+    }
+
+    // Solves the stable zero fraction (1 / (1 + 1 / (P * (1/k - 1/m))))^max for P, given
+    // that the false positive rate is (1 - zero fraction)^k
+    let zeros = 1.0 - false_positive_rate.powf(1.0 / hash_count as f64);
+    let per_cell = 1.0 / hash_count as f64 - 1.0 / cells as f64;
+    let denominator = (zeros.powf(-1.0 / f64::from(max_count)) - 1.0) * per_cell;
+    let decrements = ((1.0 / denominator) as usize).clamp(1, cells);
+
+    Ok(StableBloomFilter {
+      cells: vec![0; cells],
+      max_count,
+      hash_count,
+      decrements,
+      rng: runtime::new_rng(),
+    })
+  }
+
+  /// Inserts an item, first decrementing cells so that older items fade out.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    self.decrement();
+    for slot in self.slots(hash_str(item)) {
+      self.cells[slot] = self.max_count;
+    }
+  }
+
+  /// Checks if an item was probably inserted recently.
+  /// Returns false if it was not, or if it has faded out since; true if it probably was.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.slots(hash_str(item)).into_iter().all(|slot| self.cells[slot] > 0)
+  }
+
+  /// Checks whether an item was seen recently and then inserts it, the usual deduplication
+  /// step. Returns the result of the check.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check and insert
+  #[wasm_bindgen]
+  pub fn check_and_insert(&mut self, item: &str) -> bool {
+    let seen = self.contains(item);
+    self.insert(item);
+    seen
+  }
+
+  /// Returns the number of cells decremented per insert.
+  #[wasm_bindgen(getter)]
+  pub fn decrements_per_insert(&self) -> usize {
+    self.decrements
+  }
+
+  /// Returns the number of probes per item.
+  #[wasm_bindgen(getter)]
+  pub fn hash_count(&self) -> usize {
+    self.hash_count
+  }
+
+  /// Returns the current false positive rate, from the fraction of non-zero cells.
+  #[wasm_bindgen]
+  pub fn current_false_positive_rate(&self) -> f64 {
+    let set = self.cells.iter().filter(|&&cell| cell > 0).count();
+    (set as f64 / self.cells.len() as f64).powi(self.hash_count as i32)
+  }
+
+  /// Clears all cells in the filter.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.cells.fill(0);
+  }
+}
+
+impl StableBloomFilter {
+  fn slots(&self, hash: u64) -> Vec<usize> {
+    (0..self.hash_count).map(|i| BloomFilter::probe_index(hash, i, self.cells.len())).collect()
+  }
+
+  // Decrements a run of cells from a random start, wrapping around; cheaper than picking each
+  // cell at random and equivalent for the stable-state analysis
+  fn decrement(&mut self) {
+    let len = self.cells.len();
+    let start = self.rng.gen_range(0..len);
+    for offset in 0..self.decrements {
+      let cell = &mut self.cells[(start + offset) % len];
+      *cell = cell.saturating_sub(1);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_recent_items_are_found() {
+    let mut filter = StableBloomFilter::new(10_000, 0.01, Some(3)).unwrap();
+    assert!(!filter.check_and_insert("url_1"));
+    assert!(filter.check_and_insert("url_1"));
+    for i in 0..10 {
+      filter.insert(&format!("url_{}", i));
+    }
+    assert!(filter.contains("url_9"));
+  }
+
+  #[test]
+  fn test_false_positive_rate_stabilizes() {
+    let mut filter = StableBloomFilter::new(10_000, 0.01, None).unwrap();
+    for i in 0..200_000 {
+      filter.insert(&format!("url_{}", i));
+    }
+    // A regular filter of this size would be saturated by now
+    let rate = filter.current_false_positive_rate();
+    assert!(rate < 0.03, "rate {}", rate);
+    let false_positives = (0..10_000).filter(|i| filter.contains(&format!("new_{}", i))).count();
+    assert!(false_positives < 300, "{} false positives", false_positives);
+
+    // Items inserted long ago have faded out
+    let survivors = (0..1000).filter(|i| filter.contains(&format!("url_{}", i))).count();
+    assert!(survivors < 100, "{} survivors", survivors);
+  }
+
+  #[test]
+  fn test_rejects_bad_parameters() {
+    assert!(StableBloomFilter::new(10_000, 0.0, None).is_err());
+    assert!(StableBloomFilter::new(10_000, 0.01, Some(0)).is_err());
+    assert!(StableBloomFilter::new(3, 0.01, None).is_err());
+  }
+}
@@ -0,0 +1,57 @@
+import * as wasm from '../pkg/sketch_wasm';
+
+/** Sketch kinds that can be decoded while they download. */
+export type StreamedSketchKind = 'bloom';
+
+/** A sketch being decoded from a stream. */
+export interface StreamedSketch {
+  /**
+   * Answers queries against the bytes received so far; `contains` returns `undefined` while
+   * the answer depends on bits that have not arrived. Consumed once `done` resolves. If `done`
+   * rejects, the loader stays usable with what it received and the caller should `free` it.
+   */
+  loader: wasm.BloomFilterLoader;
+  /** Resolves with the fully loaded sketch, or rejects if the stream is malformed or ends early. */
+  done: Promise<wasm.BloomFilter>;
+}
+
+/**
+ * Decodes a serialized sketch chunk by chunk as it downloads, e.g. from
+ * `(await fetch(url)).body`. The header is validated on the first chunks, so a wrong or
+ * corrupt payload fails before the rest is downloaded, and the loader can be queried
+ * before the download completes.
+ */
+export function fromResponseStream(
+  kind: StreamedSketchKind,
+  stream: ReadableStream<Uint8Array>
+): StreamedSketch {
+  if (kind !== 'bloom') {
+    throw new Error(`Invalid parameter: cannot stream sketch kind ${String(kind)}`);
+  }
+  const loader = new wasm.BloomFilterLoader();
+  return { loader, done: pump(loader, stream) };
+}
+
+async function pump(
+  loader: wasm.BloomFilterLoader,
+  stream: ReadableStream<Uint8Array>
+): Promise<wasm.BloomFilter> {
+  const reader = stream.getReader();
+  try {
+    for (;;) {
+      const { done, value } = await reader.read();
+      if (done) {
+        break;
+      }
+      loader.push(value);
+    }
+  } catch (error) {
+    await reader.cancel(error).catch(() => undefined);
+    throw error;
+  }
+  // finish() consumes the loader even when it fails, so check first to leave it to the caller
+  if (!loader.complete) {
+    throw new Error('Malformed input: BloomFilter stream ended early');
+  }
+  return loader.finish();
+}
@@ -0,0 +1,192 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::{BloomFilter, MAX_HEADER_LEN};
+use crate::codec::read_u64;
+use crate::error::SketchError;
+
+/// Incrementally decodes a serialized Bloom filter as its bytes arrive, e.g. from a `fetch`
+/// response body, so a large server-built filter can answer queries before it has fully
+/// downloaded. The header is validated as soon as it is complete and the bit array is filled
+/// one 64-bit word at a time.
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct BloomFilterLoader {
+  // Bytes received but not yet decoded: a partial header or a partial word
+  pending: Vec<u8>,
+  // The filter being filled, present once the header has been decoded
+  filter: Option<BloomFilter>,
+  // Number of 64-bit words stored so far
+  words_loaded: usize,
+}
+
+#[wasm_bindgen]
+impl BloomFilterLoader {
+  /// Creates a loader waiting for the first chunk of a serialized filter.
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> BloomFilterLoader {
+    BloomFilterLoader::default()
+  }
+
+  /// Decodes the next chunk of the stream. Fails as soon as the bytes received so far cannot
+  /// be a valid filter, after which the loader should be discarded.
+  ///
+  /// # Arguments
+  ///
+  /// * `chunk` - The next bytes of the stream, of any length
+  #[wasm_bindgen]
+  pub fn push(&mut self, chunk: &[u8]) -> Result<(), SketchError> {
+    self.pending.extend_from_slice(chunk);
+    if self.filter.is_none() && !self.read_header()? {
+      return Ok(());
+    }
+    let Some(filter) = &mut self.filter else {
+      return Ok(());
+    };
+
+    let word_count = filter.bit_count().div_ceil(64);
+    let mut input = self.pending.as_slice();
+    while input.len() >= 8 {
+      if self.words_loaded == word_count {
+        return Err(SketchError::Malformed("BloomFilter stream has trailing bytes".to_string()));
+      }
+      filter.load_word(self.words_loaded, read_u64(&mut input)?)?;
+      self.words_loaded += 1;
+    }
+    let consumed = self.pending.len() - input.len();
+    self.pending.drain(..consumed);
+    Ok(())
+  }
+
+  /// Checks an item against the part of the filter loaded so far. Returns false if the item
+  /// is definitely not in the set, true if it probably is, and `undefined` if the answer
+  /// depends on bits that have not arrived yet.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> Option<bool> {
+    let filter = self.filter.as_ref()?;
+    let loaded_bits = self.words_loaded * 64;
+    let mut unknown = false;
+    for position in filter.probe_positions(filter.key_hash(item)) {
+      if position >= loaded_bits {
+        unknown = true;
+      } else if !filter.check_bit(position) {
+        return Some(false);
+      }
+    }
+    if unknown {
+      None
+    } else {
+      Some(true)
+    }
+  }
+
+  /// Returns the fraction of the bit array received so far, from 0 to 1.
+  #[wasm_bindgen]
+  pub fn progress(&self) -> f64 {
+    match &self.filter {
+      Some(filter) => self.words_loaded as f64 / filter.bit_count().div_ceil(64) as f64,
+      None => 0.0,
+    }
+  }
+
+  /// Returns true once the header has been decoded and the filter can answer queries.
+  #[wasm_bindgen(getter)]
+  pub fn header_ready(&self) -> bool {
+    self.filter.is_some()
+  }
+
+  /// Returns true once every byte of the filter has been received.
+  #[wasm_bindgen(getter)]
+  pub fn complete(&self) -> bool {
+    self.progress() == 1.0 && self.pending.is_empty()
+  }
+
+  /// Returns the fully loaded filter, consuming the loader. Fails if the stream ended early.
+  #[wasm_bindgen]
+  pub fn finish(self) -> Result<BloomFilter, SketchError> {
+    if !self.complete() {
+      return Err(SketchError::Malformed("BloomFilter stream ended early".to_string()));
+    }
+    Ok(self.filter.expect("a complete loader has a filter"))
+  }
+}
+
+impl BloomFilterLoader {
+  // Decodes the header once enough bytes have arrived, returning whether it is ready
+  fn read_header(&mut self) -> Result<bool, SketchError> {
+    BloomFilter::check_header_prefix(&self.pending)?;
+    let mut input = self.pending.as_slice();
+    match BloomFilter::read_header(&mut input) {
+      Ok(filter) => {
+        let consumed = self.pending.len() - input.len();
+        self.pending.drain(..consumed);
+        self.filter = Some(filter);
+        Ok(true)
+      }
+      // A varint may still be incomplete
+      Err(_) if self.pending.len() < MAX_HEADER_LEN => Ok(false),
+      Err(err) => Err(err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn serialized() -> Vec<u8> {
+    let mut filter = BloomFilter::new(500, 0.01);
+    for i in 0..500 {
+      filter.insert(&format!("item_{}", i));
+    }
+    filter.to_bytes()
+  }
+
+  #[test]
+  fn test_chunked_load_matches_whole_buffer() {
+    let bytes = serialized();
+    for chunk_size in [1, 3, 8, 100, bytes.len()] {
+      let mut loader = BloomFilterLoader::new();
+      for chunk in bytes.chunks(chunk_size) {
+        assert!(!loader.complete());
+        loader.push(chunk).unwrap();
+      }
+      assert!(loader.complete());
+      let filter = loader.finish().unwrap();
+      assert_eq!(filter.to_bytes(), bytes);
+      assert!(filter.contains("item_42"));
+    }
+  }
+
+  #[test]
+  fn test_partial_queries() {
+    let bytes = serialized();
+    let mut loader = BloomFilterLoader::new();
+    assert_eq!(loader.contains("item_1"), None);
+
+    loader.push(&bytes[..bytes.len() / 2]).unwrap();
+    assert!(loader.header_ready());
+    assert!(loader.progress() > 0.3 && loader.progress() < 0.7);
+    for i in 0..500 {
+      assert_ne!(loader.contains(&format!("item_{}", i)), Some(false));
+    }
+    assert!(loader.finish().is_err());
+  }
+
+  #[test]
+  fn test_rejects_bad_streams_early() {
+    let bytes = serialized();
+    assert!(BloomFilterLoader::new().push(b"SWX").is_err());
+
+    let mut wrong_version = bytes[..5].to_vec();
+    wrong_version[4] = 9;
+    assert!(BloomFilterLoader::new().push(&wrong_version).is_err());
+
+    let mut trailing = bytes.clone();
+    trailing.extend_from_slice(&[0; 8]);
+    assert!(BloomFilterLoader::new().push(&trailing).is_err());
+  }
+}
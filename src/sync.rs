@@ -0,0 +1,333 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::codec::{read_u64, read_varint, write_varint};
+use crate::count_min_sketch::CountMinSketch;
+use crate::error::SketchError;
+
+const MAGIC: u8 = b'S';
+const VERSION: u8 = 2;
+const KIND_BLOOM: u8 = 1;
+const KIND_COUNT_MIN: u8 = 2;
+
+/// Keeps a local sketch converged with copies in other tabs or workers by exchanging compact
+/// deltas (e.g. over a `BroadcastChannel`) instead of full serializations.
+///
+/// Each tab owns one channel per shared sketch. `emit_*` returns the changes made locally since
+/// the previous emit (changed Bloom words, or CMS cell increments), and `apply_*` merges a delta
+/// received from a peer without echoing it back on the next emit. A tab joining late can be
+/// brought up to date with the first emit of a fresh channel, which carries the full state.
+///
+/// Every delta starts with the sketch's shape (Bloom size, probe range, probe count and seed, or
+/// CMS width, depth and hash seeds), and applying it to a sketch of another shape fails.
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct SyncChannel {
+  kind: Option<u8>,
+  // Shape of the bound sketch, as written in every delta header
+  shape: Vec<u64>,
+  // State already known to peers: Bloom words or CMS counters
+  synced: Vec<u64>,
+  // CMS total count already known to peers
+  synced_total: u64,
+}
+
+#[wasm_bindgen]
+impl SyncChannel {
+  /// Creates a new synchronization channel.
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> SyncChannel {
+    SyncChannel::default()
+  }
+
+  /// Returns the Bloom words that changed since the last sync as a delta buffer.
+  ///
+  /// # Arguments
+  ///
+  /// * `filter` - The local filter
+  #[wasm_bindgen]
+  pub fn emit_bloom(&mut self, filter: &BloomFilter) -> Result<Vec<u8>, SketchError> {
+    let words = filter.words();
+    let shape = filter.shape();
+    self.bind(KIND_BLOOM, &shape, words.len())?;
+
+    let changed: Vec<(usize, u64)> = words
+      .iter()
+      .enumerate()
+      .filter(|(index, &word)| word & !self.synced[*index] != 0)
+      .map(|(index, &word)| (index, word))
+      .collect();
+
+    let mut out = Self::header(KIND_BLOOM, &shape);
+    write_varint(&mut out, changed.len() as u64);
+    let mut previous = 0;
+    for (index, word) in changed {
+      write_varint(&mut out, (index - previous) as u64);
+      out.extend_from_slice(&word.to_le_bytes());
+      self.synced[index] = word;
+      previous = index;
+    }
+    Ok(out)
+  }
+
+  /// Merges a Bloom delta received from a peer into the local filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `filter` - The local filter
+  /// * `delta` - A buffer produced by `emit_bloom` on a peer's channel
+  #[wasm_bindgen]
+  pub fn apply_bloom(&mut self, filter: &mut BloomFilter, delta: &[u8]) -> Result<(), SketchError> {
+    let len = filter.words().len();
+    let shape = filter.shape();
+    filter.check_unlogged("apply a sync delta")?;
+    self.bind(KIND_BLOOM, &shape, len)?;
+    let mut input = Self::check_header(delta, KIND_BLOOM, &shape)?;
+
+    // Decode fully before touching the filter so a bad delta leaves it unchanged
+    let entries = Self::read_entries(&mut input, len, read_u64)?;
+    for (index, word) in entries {
+      filter.or_word(index, word);
+      self.synced[index] |= word;
+    }
+    Ok(())
+  }
+
+  /// Returns the CountMinSketch counter and total count increments made since the last sync as
+  /// a delta buffer. The total is sent on its own, since conservative-update sketches do not
+  /// add every increment to each row.
+  /// If the local sketch was cleared, the cleared counts are forgotten rather than sent.
+  ///
+  /// # Arguments
+  ///
+  /// * `sketch` - The local sketch
+  #[wasm_bindgen]
+  pub fn emit_count_min_sketch(&mut self, sketch: &CountMinSketch) -> Result<Vec<u8>, SketchError> {
+    let cells = sketch.cells();
+    let shape = Self::count_min_shape(sketch);
+    self.bind(KIND_COUNT_MIN, &shape, cells.len())?;
+
+    let mut changed = Vec::new();
+    for (index, &count) in cells.iter().enumerate() {
+      let count = u64::from(count);
+      if count > self.synced[index] {
+        changed.push((index, count - self.synced[index]));
+      }
+      self.synced[index] = count;
+    }
+
+    let total = sketch.total();
+    let total_increment = total.saturating_sub(self.synced_total);
+    self.synced_total = total;
+
+    let mut out = Self::header(KIND_COUNT_MIN, &shape);
+    write_varint(&mut out, total_increment);
+    write_varint(&mut out, changed.len() as u64);
+    let mut previous = 0;
+    for (index, increment) in changed {
+      write_varint(&mut out, (index - previous) as u64);
+      write_varint(&mut out, increment);
+      previous = index;
+    }
+    Ok(out)
+  }
+
+  /// Merges a CountMinSketch delta received from a peer into the local sketch.
+  ///
+  /// # Arguments
+  ///
+  /// * `sketch` - The local sketch
+  /// * `delta` - A buffer produced by `emit_count_min_sketch` on a peer's channel
+  #[wasm_bindgen]
+  pub fn apply_count_min_sketch(
+    &mut self,
+    sketch: &mut CountMinSketch,
+    delta: &[u8],
+  ) -> Result<(), SketchError> {
+    let len = sketch.cells().len();
+    let shape = Self::count_min_shape(sketch);
+    sketch.check_unlogged("apply a sync delta")?;
+    self.bind(KIND_COUNT_MIN, &shape, len)?;
+    let mut input = Self::check_header(delta, KIND_COUNT_MIN, &shape)?;
+
+    let total_increment = read_varint(&mut input)?;
+    let entries = Self::read_entries(&mut input, len, read_varint)?;
+    for (index, increment) in entries {
+      let increment = u32::try_from(increment).unwrap_or(u32::MAX);
+      sketch.add_to_cell(index, increment);
+      self.synced[index] = self.synced[index].saturating_add(u64::from(increment));
+    }
+    sketch.add_to_total(total_increment);
+    self.synced_total = self.synced_total.saturating_add(total_increment);
+    Ok(())
+  }
+
+  // Ties the channel to one sketch shape on first use
+  fn bind(&mut self, kind: u8, shape: &[u64], len: usize) -> Result<(), SketchError> {
+    match self.kind {
+      None => {
+        self.kind = Some(kind);
+        self.shape = shape.to_vec();
+        self.synced = vec![0; len];
+        Ok(())
+      }
+      Some(bound) if bound == kind && self.shape == shape => Ok(()),
+      Some(_) => Err(SketchError::Incompatible(
+        "sync channel is bound to a sketch of a different type or shape".to_string(),
+      )),
+    }
+  }
+
+  // The parameters `CountMinSketch::check_compatible` compares: width, depth, then each seed
+  fn count_min_shape(sketch: &CountMinSketch) -> Vec<u64> {
+    let mut shape = vec![sketch.width() as u64, sketch.depth() as u64];
+    shape.extend_from_slice(sketch.hash_seeds());
+    shape
+  }
+
+  fn header(kind: u8, shape: &[u64]) -> Vec<u8> {
+    let mut out = vec![MAGIC, VERSION, kind];
+    for &value in shape {
+      write_varint(&mut out, value);
+    }
+    out
+  }
+
+  fn check_header<'a>(delta: &'a [u8], kind: u8, shape: &[u64]) -> Result<&'a [u8], SketchError> {
+    if delta.len() < 3 || delta[0] != MAGIC || delta[1] != VERSION {
+      return Err(SketchError::Malformed("not a sync delta".to_string()));
+    }
+    let incompatible = || {
+      SketchError::Incompatible(
+        "sync delta was emitted for a sketch of a different type or shape".to_string(),
+      )
+    };
+    if delta[2] != kind {
+      return Err(incompatible());
+    }
+    let mut input = &delta[3..];
+    for &expected in shape {
+      if read_varint(&mut input)? != expected {
+        return Err(incompatible());
+      }
+    }
+    Ok(input)
+  }
+
+  fn read_entries(
+    input: &mut &[u8],
+    len: usize,
+    read_value: fn(&mut &[u8]) -> Result<u64, SketchError>,
+  ) -> Result<Vec<(usize, u64)>, SketchError> {
+    let count = read_varint(input)?;
+    let mut entries = Vec::new();
+    let mut index = 0u64;
+    for _ in 0..count {
+      index = index.saturating_add(read_varint(input)?);
+      if index >= len as u64 {
+        return Err(SketchError::Malformed("sync delta index out of range".to_string()));
+      }
+      entries.push((index as usize, read_value(input)?));
+    }
+    if !input.is_empty() {
+      return Err(SketchError::Malformed("trailing bytes after sync delta".to_string()));
+    }
+    Ok(entries)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_bloom_tabs_converge() {
+    let (mut a, mut b) = (BloomFilter::new(1000, 0.01), BloomFilter::new(1000, 0.01));
+    let (mut channel_a, mut channel_b) = (SyncChannel::new(), SyncChannel::new());
+
+    a.insert("from_a");
+    b.insert("from_b");
+    let delta_a = channel_a.emit_bloom(&a).unwrap();
+    let delta_b = channel_b.emit_bloom(&b).unwrap();
+    channel_b.apply_bloom(&mut b, &delta_a).unwrap();
+    channel_a.apply_bloom(&mut a, &delta_b).unwrap();
+
+    assert!(a.contains("from_b") && b.contains("from_a"));
+    assert_eq!(a.words(), b.words());
+
+    // Nothing new locally, so nothing is echoed back
+    let echo = channel_a.emit_bloom(&a).unwrap();
+    let mut empty = SyncChannel::header(KIND_BLOOM, &a.shape());
+    write_varint(&mut empty, 0);
+    assert_eq!(echo, empty);
+  }
+
+  #[test]
+  fn test_count_min_tabs_converge() {
+    let (mut a, mut b) = (CountMinSketch::new(100, 4), CountMinSketch::new(100, 4));
+    let (mut channel_a, mut channel_b) = (SyncChannel::new(), SyncChannel::new());
+
+    a.increment("x");
+    a.increment("x");
+    b.increment("x");
+    let delta_a = channel_a.emit_count_min_sketch(&a).unwrap();
+    let delta_b = channel_b.emit_count_min_sketch(&b).unwrap();
+    channel_b.apply_count_min_sketch(&mut b, &delta_a).unwrap();
+    channel_a.apply_count_min_sketch(&mut a, &delta_b).unwrap();
+
+    assert_eq!(a.estimate("x"), 3);
+    assert_eq!(b.estimate("x"), 3);
+    assert_eq!(a.total_count(), 3.0);
+
+    a.increment("y");
+    let delta = channel_a.emit_count_min_sketch(&a).unwrap();
+    channel_b.apply_count_min_sketch(&mut b, &delta).unwrap();
+    assert_eq!(b.estimate("y"), 1);
+    assert_eq!(b.estimate("x"), 3);
+  }
+
+  #[test]
+  fn test_rejects_mismatched_deltas() {
+    let mut filter = BloomFilter::new(1000, 0.01);
+    let mut sketch = CountMinSketch::new(100, 4);
+    let delta = SyncChannel::new().emit_count_min_sketch(&sketch).unwrap();
+
+    assert!(SyncChannel::new().apply_bloom(&mut filter, &delta).is_err());
+    assert!(SyncChannel::new().apply_count_min_sketch(&mut sketch, &delta[..4]).is_err());
+
+    let mut channel = SyncChannel::new();
+    channel.emit_bloom(&filter).unwrap();
+    assert!(channel.emit_count_min_sketch(&sketch).is_err());
+
+    // Same cell count, different seeds: the counters would not line up
+    let mut bytes = sketch.to_bytes();
+    // The first row seed follows the magic, version, encoding, width, depth and total
+    bytes[9] = 7;
+    let mut reseeded = CountMinSketch::from_bytes(&bytes).unwrap();
+    assert!(matches!(
+      SyncChannel::new().apply_count_min_sketch(&mut reseeded, &delta),
+      Err(SketchError::Incompatible(_))
+    ));
+    let mut seeded = BloomFilter::with_seed(1000, 0.01, 42);
+    let bloom_delta = SyncChannel::new().emit_bloom(&filter).unwrap();
+    assert!(matches!(
+      SyncChannel::new().apply_bloom(&mut seeded, &bloom_delta),
+      Err(SketchError::Incompatible(_))
+    ));
+  }
+
+  #[test]
+  fn test_conservative_totals_converge() {
+    let mut a = CountMinSketch::with_conservative_update(100, 4);
+    let mut b = CountMinSketch::with_conservative_update(100, 4);
+    let (mut channel_a, mut channel_b) = (SyncChannel::new(), SyncChannel::new());
+    for i in 0..200 {
+      a.increment(&format!("item{}", i % 13));
+    }
+
+    let delta = channel_a.emit_count_min_sketch(&a).unwrap();
+    channel_b.apply_count_min_sketch(&mut b, &delta).unwrap();
+    assert_eq!(b.total_count(), 200.0);
+    assert_eq!(b.estimate("item0"), a.estimate("item0"));
+  }
+}
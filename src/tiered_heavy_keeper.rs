@@ -0,0 +1,200 @@
+use wasm_bindgen::prelude::*;
+
+use crate::entries::Entries;
+use crate::error::SketchError;
+use crate::heavy_keeper::{HeavyKeeper, TopKItem};
+use crate::runtime;
+
+// One leaderboard covering the current window of a fixed span
+#[derive(Debug)]
+struct Tier {
+  span_ms: f64,
+  window_start_ms: f64,
+  sketch: HeavyKeeper,
+}
+
+/// Maintains several leaderboards over tumbling windows of different spans (e.g. hour, day and
+/// week) from a single `add` call. All tiers share one set of counter positions, so each event
+/// is hashed once no matter how many tiers there are.
+///
+/// Windows are aligned to multiples of their span since the epoch (so a 24-hour tier rolls over
+/// at midnight UTC), and a tier starts over empty when an add falls in a later window.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct TieredHeavyKeeper {
+  tiers: Vec<Tier>,
+  width: usize,
+  depth: usize,
+  k: usize,
+  decay: f64,
+}
+
+#[wasm_bindgen]
+impl TieredHeavyKeeper {
+  /// Creates a new tiered Heavy Keeper. Every tier uses the same sketch parameters.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  /// * `k` - The number of top items to track per tier
+  /// * `decay` - The decay factor for count reduction (between 0 and 1)
+  /// * `spans_ms` - The window span of each tier, e.g. `[3600000, 86400000, 604800000]`
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    width: usize,
+    depth: usize,
+    k: usize,
+    decay: f64,
+    spans_ms: &[f64],
+  ) -> Result<TieredHeavyKeeper, SketchError> {
+    if spans_ms.is_empty() || spans_ms.iter().any(|span| span.is_nan() || *span <= 0.0) {
+      return Err(SketchError::InvalidParameter(
+        "at least one tier is needed and every span must be positive".to_string(),
+      ));
+    }
+    let tiers = spans_ms
+      .iter()
+      .map(|&span_ms| Tier {
+        span_ms,
+        window_start_ms: f64::NEG_INFINITY,
+        sketch: HeavyKeeper::new(width, depth, k, decay),
+      })
+      .collect();
+    Ok(TieredHeavyKeeper { tiers, width, depth, k, decay })
+  }
+
+  /// Adds an item to every tier, rotating tiers whose window has ended.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to add
+  /// * `timestamp_ms` - When the item was seen; defaults to the current time
+  #[wasm_bindgen]
+  pub fn add(&mut self, item: &str, timestamp_ms: Option<f64>) {
+    let timestamp_ms = timestamp_ms.unwrap_or_else(runtime::now_ms);
+    self.rotate(timestamp_ms);
+
+    let positions = self.tiers[0].sketch.positions(item);
+    for tier in &mut self.tiers {
+      tier.sketch.add_at(item, 1, &positions);
+    }
+  }
+
+  /// Returns the top-k items of a tier's current window.
+  ///
+  /// # Arguments
+  ///
+  /// * `tier` - The tier index, in the order the spans were given
+  #[wasm_bindgen]
+  pub fn top_k(&self, tier: usize) -> Result<Vec<TopKItem>, SketchError> {
+    self.tier(tier).map(|tier| tier.sketch.top_k())
+  }
+
+  /// Returns every item tracked in a tier's current window with its count.
+  ///
+  /// # Arguments
+  ///
+  /// * `tier` - The tier index, in the order the spans were given
+  #[wasm_bindgen]
+  pub fn entries(&self, tier: usize) -> Result<Entries, SketchError> {
+    self.tier(tier).map(|tier| tier.sketch.entries())
+  }
+
+  /// Formats a tier's top-k items as CSV with an `item,count` header row, largest first.
+  ///
+  /// # Arguments
+  ///
+  /// * `tier` - The tier index, in the order the spans were given
+  /// * `delimiter` - The field separator, e.g. `"\t"` for TSV; defaults to a comma
+  #[wasm_bindgen]
+  pub fn top_k_csv(&self, tier: usize, delimiter: Option<char>) -> Result<String, SketchError> {
+    self.tier(tier)?.sketch.top_k_csv(delimiter)
+  }
+
+  /// Formats every item tracked in a tier's current window as CSV with an `item,weight`
+  /// header row.
+  ///
+  /// # Arguments
+  ///
+  /// * `tier` - The tier index, in the order the spans were given
+  /// * `delimiter` - The field separator, e.g. `"\t"` for TSV; defaults to a comma
+  #[wasm_bindgen]
+  pub fn entries_csv(&self, tier: usize, delimiter: Option<char>) -> Result<String, SketchError> {
+    self.tier(tier)?.sketch.entries_csv(delimiter)
+  }
+
+  /// Returns the start of a tier's current window, in milliseconds since the epoch,
+  /// or `-Infinity` before the first add.
+  ///
+  /// # Arguments
+  ///
+  /// * `tier` - The tier index, in the order the spans were given
+  #[wasm_bindgen]
+  pub fn window_start(&self, tier: usize) -> Result<f64, SketchError> {
+    self.tier(tier).map(|tier| tier.window_start_ms)
+  }
+
+  /// Returns the number of tiers.
+  #[wasm_bindgen(getter)]
+  pub fn tier_count(&self) -> usize {
+    self.tiers.len()
+  }
+}
+
+impl TieredHeavyKeeper {
+  fn tier(&self, tier: usize) -> Result<&Tier, SketchError> {
+    self
+      .tiers
+      .get(tier)
+      .ok_or_else(|| SketchError::InvalidParameter(format!("tier {} does not exist", tier)))
+  }
+
+  fn rotate(&mut self, timestamp_ms: f64) {
+    for tier in &mut self.tiers {
+      let window_start_ms = (timestamp_ms / tier.span_ms).floor() * tier.span_ms;
+      if window_start_ms > tier.window_start_ms {
+        if tier.window_start_ms.is_finite() {
+          tier.sketch = HeavyKeeper::new(self.width, self.depth, self.k, self.decay);
+        }
+        tier.window_start_ms = window_start_ms;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HOUR: f64 = 3_600_000.0;
+
+  #[test]
+  fn test_tiers_rotate_independently() {
+    let mut tiered = TieredHeavyKeeper::new(100, 3, 2, 0.9, &[HOUR, 24.0 * HOUR]).unwrap();
+    for _ in 0..10 {
+      tiered.add("morning", Some(HOUR));
+    }
+    for _ in 0..3 {
+      tiered.add("afternoon", Some(5.0 * HOUR));
+    }
+
+    let hourly = tiered.top_k(0).unwrap();
+    assert_eq!(hourly.len(), 1);
+    assert_eq!(hourly[0].item(), "afternoon");
+    assert_eq!(tiered.window_start(0).unwrap(), 5.0 * HOUR);
+
+    let daily = tiered.top_k(1).unwrap();
+    assert_eq!(daily[0].item(), "morning");
+    assert_eq!(daily[1].item(), "afternoon");
+
+    assert!(tiered.top_k(2).is_err());
+    assert_eq!(tiered.entries(1).unwrap().items(), ["morning", "afternoon"]);
+  }
+
+  #[test]
+  fn test_rejects_bad_spans() {
+    assert!(TieredHeavyKeeper::new(100, 3, 2, 0.9, &[]).is_err());
+    assert!(TieredHeavyKeeper::new(100, 3, 2, 0.9, &[HOUR, 0.0]).is_err());
+  }
+}
@@ -0,0 +1,184 @@
+use wasm_bindgen::prelude::*;
+
+use crate::count_min_sketch::CountMinSketch;
+use crate::error::SketchError;
+use crate::hash::hash_str;
+use crate::runtime;
+
+/// A CountMinSketch that also records when each cell was last incremented, so that estimates
+/// can ignore items that have gone quiet in very long-lived sketches.
+///
+/// Incrementing an item refreshes every one of its cells, so if any of its cells is older than
+/// the query window the item cannot have been incremented within it. `estimate_fresh` reports
+/// such items as 0 instead of returning counts built up by ancient collisions. Counts
+/// themselves are not windowed: a fresh item's estimate includes its whole history.
+///
+/// Timestamps are stored as whole seconds since the Unix epoch in 32 bits per cell.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct TimestampedCountMinSketch {
+  counts: CountMinSketch,
+  // Last update of each cell in seconds since the epoch, row by row; 0 if never updated
+  updated_s: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl TimestampedCountMinSketch {
+  /// Creates a new timestamped Count-Min Sketch.
+  ///
+  /// # Arguments
+  ///
+  /// * `width` - The number of counters in each row
+  /// * `depth` - The number of hash functions (rows)
+  #[wasm_bindgen(constructor)]
+  pub fn new(width: usize, depth: usize) -> TimestampedCountMinSketch {
+    TimestampedCountMinSketch {
+      counts: CountMinSketch::new(width, depth),
+      updated_s: vec![0; width * depth],
+    }
+  }
+
+  /// Increments the count for an item seen at the given time.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to increment
+  /// * `timestamp_ms` - When the item was seen; defaults to the current time
+  #[wasm_bindgen]
+  pub fn increment(&mut self, item: &str, timestamp_ms: Option<f64>) {
+    let hash = hash_str(item);
+    let seconds = Self::to_seconds(timestamp_ms.unwrap_or_else(runtime::now_ms));
+    let width = self.counts.width();
+    for (row, column) in self.counts.columns(hash).into_iter().enumerate() {
+      let cell = &mut self.updated_s[row * width + column];
+      *cell = (*cell).max(seconds);
+    }
+    self.counts.increment_hash(hash);
+  }
+
+  /// Returns the estimated frequency of an item over the sketch's whole lifetime.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to query
+  #[wasm_bindgen]
+  pub fn estimate(&self, item: &str) -> u32 {
+    self.counts.estimate(item)
+  }
+
+  /// Returns the estimated frequency of an item, or 0 if any of its cells has not been
+  /// updated within `max_age_ms`, which proves the item itself has not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to query
+  /// * `max_age_ms` - The longest time since the item's last increment to still count it
+  /// * `now_ms` - The time to measure ages from; defaults to the current time
+  #[wasm_bindgen]
+  pub fn estimate_fresh(&self, item: &str, max_age_ms: f64, now_ms: Option<f64>) -> u32 {
+    let hash = hash_str(item);
+    let now_ms = now_ms.unwrap_or_else(runtime::now_ms);
+    // Cells keep whole seconds, so round the cutoff down to avoid dropping fresh updates
+    let cutoff = Self::to_seconds(now_ms - max_age_ms.max(0.0));
+    let width = self.counts.width();
+    let stale = self
+      .counts
+      .columns(hash)
+      .into_iter()
+      .enumerate()
+      .any(|(row, column)| self.updated_s[row * width + column] < cutoff);
+    if stale {
+      0
+    } else {
+      self.counts.estimate_hash(hash)
+    }
+  }
+
+  /// Merges another timestamped sketch into this one, adding counts and keeping the later
+  /// update time of each cell.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - A sketch with the same width and depth
+  #[wasm_bindgen]
+  pub fn merge(&mut self, other: &TimestampedCountMinSketch) -> Result<(), SketchError> {
+    self.counts.merge(&other.counts)?;
+    for (mine, theirs) in self.updated_s.iter_mut().zip(&other.updated_s) {
+      *mine = (*mine).max(*theirs);
+    }
+    Ok(())
+  }
+
+  /// Returns the number of counters in each row.
+  #[wasm_bindgen(getter)]
+  pub fn width(&self) -> usize {
+    self.counts.width()
+  }
+
+  /// Returns the number of rows.
+  #[wasm_bindgen(getter)]
+  pub fn depth(&self) -> usize {
+    self.counts.depth()
+  }
+
+  /// Returns the memory used by the counters and timestamps, in bytes.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.counts.memory_bytes() + self.updated_s.len() * std::mem::size_of::<u32>()
+  }
+
+  /// Clears all counters and timestamps.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.counts.clear();
+    self.updated_s.fill(0);
+  }
+}
+
+impl TimestampedCountMinSketch {
+  // Float-to-int casts saturate, so times before 1970 or after 2106 clamp to the range ends
+  fn to_seconds(timestamp_ms: f64) -> u32 {
+    (timestamp_ms / 1000.0).floor() as u32
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HOUR_MS: f64 = 3_600_000.0;
+  const START: f64 = 1_700_000_000_000.0;
+
+  #[test]
+  fn test_stale_items_estimate_zero() {
+    let mut sketch = TimestampedCountMinSketch::new(100, 4);
+    for _ in 0..5 {
+      sketch.increment("old", Some(START));
+    }
+    sketch.increment("recent", Some(START + 10.0 * HOUR_MS));
+    let now = Some(START + 10.5 * HOUR_MS);
+
+    assert_eq!(sketch.estimate("old"), 5);
+    assert_eq!(sketch.estimate_fresh("old", HOUR_MS, now), 0);
+    assert_eq!(sketch.estimate_fresh("old", 11.0 * HOUR_MS, now), 5);
+    assert_eq!(sketch.estimate_fresh("recent", HOUR_MS, now), 1);
+    assert_eq!(sketch.estimate_fresh("never", HOUR_MS, now), 0);
+  }
+
+  #[test]
+  fn test_merge_keeps_latest_update() {
+    let mut a = TimestampedCountMinSketch::new(100, 4);
+    let mut b = TimestampedCountMinSketch::new(100, 4);
+    a.increment("item", Some(START));
+    b.increment("item", Some(START + HOUR_MS));
+    a.merge(&b).unwrap();
+
+    let now = Some(START + 1.5 * HOUR_MS);
+    assert_eq!(a.estimate_fresh("item", HOUR_MS, now), 2);
+    assert!(a.merge(&TimestampedCountMinSketch::new(50, 4)).is_err());
+
+    a.clear();
+    assert_eq!(a.estimate_fresh("item", 10.0 * HOUR_MS, now), 0);
+    assert_eq!(a.memory_bytes(), 100 * 4 * 8);
+  }
+}
@@ -0,0 +1,112 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::hash::hash_str;
+
+/// A Bloom filter with two tiers of importance.
+/// High-importance keys are inserted and checked with extra hash probes, which lowers their
+/// false-positive rate at the cost of a slightly fuller bit array for everyone else.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WeightedBloomFilter {
+  filter: BloomFilter,
+  extra_hashes: usize,
+}
+
+#[wasm_bindgen]
+impl WeightedBloomFilter {
+  /// Creates a new weighted Bloom filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `expected_items` - The expected number of items to be inserted
+  /// * `false_positive_rate` - The desired false positive rate for regular keys (between 0 and 1)
+  /// * `extra_hashes` - The number of additional probes used for high-importance keys
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    expected_items: usize,
+    false_positive_rate: f64,
+    extra_hashes: usize,
+  ) -> WeightedBloomFilter {
+    WeightedBloomFilter {
+      filter: BloomFilter::new(expected_items, false_positive_rate),
+      extra_hashes,
+    }
+  }
+
+  /// Inserts a regular item.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert(&mut self, item: &str) {
+    self.filter.set_probes(hash_str(item), self.filter.probe_count());
+  }
+
+  /// Inserts a high-importance item using the extra probes.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to insert
+  #[wasm_bindgen]
+  pub fn insert_important(&mut self, item: &str) {
+    self.filter.set_probes(hash_str(item), self.important_probes());
+  }
+
+  /// Checks if an item might be in the set, using the regular probes.
+  /// High-importance items are always found by this check as well.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    self.filter.check_probes(hash_str(item), self.filter.probe_count())
+  }
+
+  /// Checks if an item might be in the high-importance tier, using the extra probes.
+  /// Regular items are not guaranteed to be found by this check.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains_important(&self, item: &str) -> bool {
+    self.filter.check_probes(hash_str(item), self.important_probes())
+  }
+
+  fn important_probes(&self) -> usize {
+    self.filter.probe_count() + self.extra_hashes
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_two_tier_membership() {
+    let mut filter = WeightedBloomFilter::new(1000, 0.05, 4);
+    filter.insert("regular");
+    filter.insert_important("vip");
+
+    assert!(filter.contains("regular"));
+    assert!(filter.contains("vip"));
+    assert!(filter.contains_important("vip"));
+  }
+
+  #[test]
+  fn test_important_keys_have_fewer_false_positives() {
+    let mut filter = WeightedBloomFilter::new(1000, 0.05, 4);
+    for i in 0..1000 {
+      filter.insert(&format!("item_{}", i));
+    }
+
+    let probes = 10000;
+    let regular = (0..probes).filter(|i| filter.contains(&format!("test_{}", i))).count();
+    let important =
+      (0..probes).filter(|i| filter.contains_important(&format!("test_{}", i))).count();
+    assert!(important < regular);
+  }
+}
@@ -0,0 +1,131 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+
+/// Estimates the sum of weights over distinct keys, counting each key once at its weight
+/// (e.g. distinct users weighted by account value), however often it is added.
+///
+/// Every key maps each register's hash to an exponential variable with rate equal to its
+/// weight, and the register keeps the minimum. The minimum over a set of keys is exponential
+/// with rate equal to their total weight, so the registers yield an unbiased estimate of it
+/// with a relative standard error of about `1 / sqrt(registers - 2)`. A key added with
+/// different weights counts at its largest.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct WeightedCardinality {
+  registers: Vec<f64>,
+  seed: u64,
+}
+
+#[wasm_bindgen]
+impl WeightedCardinality {
+  /// Creates a new weighted cardinality sketch.
+  ///
+  /// # Arguments
+  ///
+  /// * `registers` - The number of registers (at least 3); each add costs one hash per register
+  /// * `seed` - The seed for the register hashes; only sketches with the same seed can merge
+  #[wasm_bindgen(constructor)]
+  pub fn new(registers: usize, seed: u64) -> Result<WeightedCardinality, SketchError> {
+    if registers < 3 {
+      return Err(SketchError::InvalidParameter("registers must be at least 3".to_string()));
+    }
+    Ok(WeightedCardinality { registers: vec![f64::INFINITY; registers], seed })
+  }
+
+  /// Adds a key with its weight.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - The key to add
+  /// * `weight` - The key's weight, which must be positive and finite
+  #[wasm_bindgen]
+  pub fn add(&mut self, key: &str, weight: f64) -> Result<(), SketchError> {
+    if !weight.is_finite() || weight <= 0.0 {
+      return Err(SketchError::InvalidParameter("weight must be positive and finite".to_string()));
+    }
+    let hash = hash_str(key);
+    for (index, register) in self.registers.iter_mut().enumerate() {
+      // Uniform in (0, 1], so the logarithm is finite
+      let uniform =
+        ((mix64(hash ^ self.seed, index as u64) >> 11) + 1) as f64 / (1u64 << 53) as f64;
+      *register = register.min(-uniform.ln() / weight);
+    }
+    Ok(())
+  }
+
+  /// Returns the estimated sum of weights over the distinct keys added.
+  #[wasm_bindgen]
+  pub fn estimate(&self) -> f64 {
+    let sum: f64 = self.registers.iter().sum();
+    if sum.is_infinite() {
+      return 0.0;
+    }
+    (self.registers.len() - 1) as f64 / sum
+  }
+
+  /// Merges another sketch into this one, so it estimates the union of both key sets.
+  ///
+  /// # Arguments
+  ///
+  /// * `other` - A sketch with the same number of registers and seed
+  #[wasm_bindgen]
+  pub fn merge(&mut self, other: &WeightedCardinality) -> Result<(), SketchError> {
+    if self.registers.len() != other.registers.len() || self.seed != other.seed {
+      return Err(SketchError::Incompatible(
+        "sketches must have the same register count and seed".to_string(),
+      ));
+    }
+    for (register, &theirs) in self.registers.iter_mut().zip(&other.registers) {
+      *register = register.min(theirs);
+    }
+    Ok(())
+  }
+
+  /// Removes all keys.
+  #[wasm_bindgen]
+  pub fn clear(&mut self) {
+    self.registers.fill(f64::INFINITY);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_estimates_distinct_weight_sum() {
+    let mut sketch = WeightedCardinality::new(256, 7).unwrap();
+    assert_eq!(sketch.estimate(), 0.0);
+    for i in 1..=1000 {
+      sketch.add(&format!("user_{}", i), i as f64).unwrap();
+    }
+    let estimate = sketch.estimate();
+    for i in 1..=1000 {
+      sketch.add(&format!("user_{}", i), i as f64).unwrap();
+    }
+    assert_eq!(sketch.estimate(), estimate);
+    assert!((estimate - 500_500.0).abs() < 0.2 * 500_500.0);
+    assert!(sketch.add("user", 0.0).is_err());
+  }
+
+  #[test]
+  fn test_merge_matches_union() {
+    let mut left = WeightedCardinality::new(64, 1).unwrap();
+    let mut right = WeightedCardinality::new(64, 1).unwrap();
+    let mut union = WeightedCardinality::new(64, 1).unwrap();
+    for i in 0..100 {
+      let key = format!("key_{}", i);
+      if i % 2 == 0 {
+        left.add(&key, 2.0).unwrap();
+      } else {
+        right.add(&key, 2.0).unwrap();
+      }
+      union.add(&key, 2.0).unwrap();
+    }
+    left.merge(&right).unwrap();
+    assert_eq!(left.estimate(), union.estimate());
+    assert!(left.merge(&WeightedCardinality::new(64, 2).unwrap()).is_err());
+  }
+}
@@ -0,0 +1,148 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+
+/// Computes weighted-MinHash signatures with Improved Consistent Weighted Sampling (ICWS), so
+/// similarity can be estimated over weighted sets such as term-frequency vectors.
+///
+/// The fraction of positions where two signatures agree estimates the weighted Jaccard
+/// similarity `sum(min(a, b)) / sum(max(a, b))`. Signatures are only comparable when produced
+/// with the same number of hashes and seed.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct WeightedMinHash {
+  num_hashes: usize,
+  seed: u64,
+}
+
+#[wasm_bindgen]
+impl WeightedMinHash {
+  /// Creates a new weighted-MinHash signer.
+  ///
+  /// # Arguments
+  ///
+  /// * `num_hashes` - The signature length; more hashes give a more accurate similarity
+  /// * `seed` - The seed for the sampling functions
+  #[wasm_bindgen(constructor)]
+  pub fn new(num_hashes: usize, seed: u64) -> Result<WeightedMinHash, SketchError> {
+    if num_hashes == 0 {
+      return Err(SketchError::InvalidParameter("num_hashes must be positive".to_string()));
+    }
+    Ok(WeightedMinHash { num_hashes, seed })
+  }
+
+  /// Returns the signature of a weighted set. Terms with a weight of zero are ignored.
+  ///
+  /// # Arguments
+  ///
+  /// * `terms` - The set's elements, e.g. the distinct words of a document
+  /// * `weights` - The weight of each term, e.g. its frequency (a `Float64Array`)
+  #[wasm_bindgen]
+  pub fn signature(&self, terms: Vec<String>, weights: &[f64]) -> Result<Vec<u64>, SketchError> {
+    if terms.len() != weights.len() {
+      return Err(SketchError::InvalidParameter(
+        "terms and weights must have the same length".to_string(),
+      ));
+    }
+    if weights.iter().any(|weight| !weight.is_finite() || *weight < 0.0) {
+      return Err(SketchError::InvalidParameter(
+        "weights must be finite and non-negative".to_string(),
+      ));
+    }
+
+    let elements: Vec<(u64, f64)> = terms
+      .iter()
+      .zip(weights)
+      .filter(|(_, &weight)| weight > 0.0)
+      .map(|(term, &weight)| (hash_str(term), weight))
+      .collect();
+    if elements.is_empty() {
+      return Err(SketchError::InvalidParameter(
+        "at least one term needs a positive weight".to_string(),
+      ));
+    }
+
+    let signature = (0..self.num_hashes as u64)
+      .map(|k| {
+        let seed = mix64(self.seed, k);
+        let (element, t, _) = elements
+          .iter()
+          .map(|&(element, weight)| {
+            let (r, c, beta) = Self::variates(element, seed);
+            let t = (weight.ln() / r + beta).floor();
+            let y = (r * (t - beta)).exp();
+            (element, t, c / (y * r.exp()))
+          })
+          .min_by(|a, b| a.2.total_cmp(&b.2))
+          .expect("elements is not empty");
+        mix64(element, t as i64 as u64)
+      })
+      .collect();
+    Ok(signature)
+  }
+
+  /// Estimates the weighted Jaccard similarity of two sets from their signatures.
+  ///
+  /// # Arguments
+  ///
+  /// * `a` - The first signature
+  /// * `b` - The second signature, from a signer with the same parameters
+  #[wasm_bindgen]
+  pub fn similarity(a: &[u64], b: &[u64]) -> Result<f64, SketchError> {
+    if a.len() != b.len() || a.is_empty() {
+      return Err(SketchError::Incompatible(
+        "signatures must be non-empty and of the same length".to_string(),
+      ));
+    }
+    let matches = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    Ok(matches as f64 / a.len() as f64)
+  }
+
+  // Draws r, c ~ Gamma(2, 1) and beta ~ Uniform(0, 1), fixed per element and hash function
+  fn variates(element: u64, seed: u64) -> (f64, f64, f64) {
+    let uniform = |stream: u64| {
+      // 53 random bits, offset away from zero so logarithms stay finite
+      ((mix64(element, seed ^ stream) >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    };
+    let r = -(uniform(1) * uniform(2)).ln();
+    let c = -(uniform(3) * uniform(4)).ln();
+    (r, c, uniform(5))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn terms(words: &[&str]) -> Vec<String> {
+    words.iter().map(|word| word.to_string()).collect()
+  }
+
+  #[test]
+  fn test_similarity_tracks_weighted_jaccard() {
+    let signer = WeightedMinHash::new(512, 7).unwrap();
+    let words = terms(&["rust", "wasm", "sketch", "bloom"]);
+    let a = signer.signature(words.clone(), &[4.0, 2.0, 1.0, 1.0]).unwrap();
+    let b = signer.signature(words.clone(), &[4.0, 2.0, 1.0, 1.0]).unwrap();
+    assert_eq!(WeightedMinHash::similarity(&a, &b).unwrap(), 1.0);
+
+    // Weighted Jaccard: (2 + 2 + 1 + 0) / (4 + 2 + 1 + 1) = 0.625
+    let c = signer.signature(words, &[2.0, 2.0, 1.0, 0.0]).unwrap();
+    let estimate = WeightedMinHash::similarity(&a, &c).unwrap();
+    assert!((estimate - 0.625).abs() < 0.08, "estimate {}", estimate);
+
+    let d = signer.signature(terms(&["unrelated"]), &[3.0]).unwrap();
+    assert!(WeightedMinHash::similarity(&a, &d).unwrap() < 0.05);
+  }
+
+  #[test]
+  fn test_rejects_bad_input() {
+    let signer = WeightedMinHash::new(16, 0).unwrap();
+    assert!(signer.signature(terms(&["a"]), &[1.0, 2.0]).is_err());
+    assert!(signer.signature(terms(&["a"]), &[-1.0]).is_err());
+    assert!(signer.signature(terms(&["a"]), &[0.0]).is_err());
+    assert!(WeightedMinHash::similarity(&[1, 2], &[1]).is_err());
+    assert!(WeightedMinHash::new(0, 0).is_err());
+  }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::SketchError;
+use crate::hash::{hash_str, mix64};
+
+// Slots per key, plus a constant so tiny sets still peel
+const SIZE_FACTOR: f64 = 1.23;
+const SIZE_OFFSET: usize = 32;
+// Construction fails with vanishing probability after this many seeds
+const MAX_ATTEMPTS: u64 = 100;
+
+/// A static xor filter (Graf and Lemire, 2020) with 8-bit fingerprints, built once from a
+/// complete key set and then only queried; e.g. a read-only blocklist.
+///
+/// Each key maps to three slots whose fingerprints XOR to the key's fingerprint. It uses about
+/// 9.9 bits per key for a false positive rate of about 0.4%, and a lookup reads exactly three
+/// bytes. Keys cannot be added after construction.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct XorFilter {
+  fingerprints: Vec<u8>,
+  block_len: usize,
+  seed: u64,
+  keys: usize,
+}
+
+#[wasm_bindgen]
+impl XorFilter {
+  /// Builds a filter containing exactly the given items. Duplicates are allowed.
+  ///
+  /// # Arguments
+  ///
+  /// * `items` - Every item the filter should contain (an array of strings)
+  #[wasm_bindgen]
+  pub fn from_items(items: Vec<String>) -> Result<XorFilter, SketchError> {
+    let hashes: HashSet<u64> = items.iter().map(|item| hash_str(item)).collect();
+    Self::build(&hashes.into_iter().collect::<Vec<_>>())
+  }
+
+  /// Checks if an item might be in the set.
+  /// Returns true if the item is probably in the set, false if it is definitely not.
+  ///
+  /// # Arguments
+  ///
+  /// * `item` - The item to check
+  #[wasm_bindgen]
+  pub fn contains(&self, item: &str) -> bool {
+    // An empty filter's slots are all zero, which would match every zero fingerprint
+    if self.keys == 0 {
+      return false;
+    }
+    let hash = mix64(hash_str(item), self.seed);
+    let [a, b, c] = self.slots(hash);
+    fingerprint(hash) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+  }
+
+  /// Returns the number of distinct keys the filter was built from.
+  #[wasm_bindgen(getter)]
+  pub fn len(&self) -> usize {
+    self.keys
+  }
+
+  /// Returns true if the filter was built from no keys.
+  #[wasm_bindgen]
+  pub fn is_empty(&self) -> bool {
+    self.keys == 0
+  }
+
+  /// Returns the number of bytes used by the fingerprint table.
+  #[wasm_bindgen(getter)]
+  pub fn memory_bytes(&self) -> usize {
+    self.fingerprints.len()
+  }
+
+  /// Returns the average memory per key, in bits.
+  #[wasm_bindgen]
+  pub fn bits_per_key(&self) -> f64 {
+    (self.fingerprints.len() * 8) as f64 / self.keys.max(1) as f64
+  }
+}
+
+impl XorFilter {
+  fn build(keys: &[u64]) -> Result<XorFilter, SketchError> {
+    let block_len = ((SIZE_FACTOR * keys.len() as f64) as usize + SIZE_OFFSET).div_ceil(3);
+    let mut filter =
+      XorFilter { fingerprints: vec![0; 3 * block_len], block_len, seed: 0, keys: keys.len() };
+    for attempt in 0..MAX_ATTEMPTS {
+      filter.seed = mix64(attempt, 0);
+      if let Some(order) = filter.peel(keys) {
+        filter.assign(&order);
+        return Ok(filter);
+      }
+    }
+    Err(SketchError::InvalidParameter("could not build an XorFilter from these keys".to_string()))
+  }
+
+  // Finds an order in which every key has a slot no later key touches, as (hash, slot index)
+  // pairs, or None if the key hypergraph has a cycle under this seed
+  fn peel(&self, keys: &[u64]) -> Option<Vec<(u64, usize)>> {
+    let len = self.fingerprints.len();
+    let mut counts = vec![0u32; len];
+    let mut xors = vec![0u64; len];
+    for &key in keys {
+      let hash = mix64(key, self.seed);
+      for slot in self.slots(hash) {
+        counts[slot] += 1;
+        xors[slot] ^= hash;
+      }
+    }
+
+    let mut queue: Vec<usize> = (0..len).filter(|&slot| counts[slot] == 1).collect();
+    let mut order = Vec::with_capacity(keys.len());
+    while let Some(slot) = queue.pop() {
+      if counts[slot] != 1 {
+        continue;
+      }
+      // The only key left in this slot
+      let hash = xors[slot];
+      order.push((hash, slot));
+      for other in self.slots(hash) {
+        counts[other] -= 1;
+        xors[other] ^= hash;
+        if counts[other] == 1 {
+          queue.push(other);
+        }
+      }
+    }
+    (order.len() == keys.len()).then_some(order)
+  }
+
+  // Sets each key's free slot, last peeled first, so its three slots XOR to its fingerprint
+  fn assign(&mut self, order: &[(u64, usize)]) {
+    for &(hash, free) in order.iter().rev() {
+      let [a, b, c] = self.slots(hash);
+      self.fingerprints[free] = 0;
+      self.fingerprints[free] =
+        fingerprint(hash) ^ self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c];
+    }
+  }
+
+  // One slot in each third of the table, from different rotations of the hash
+  fn slots(&self, hash: u64) -> [usize; 3] {
+    let reduce = |i: u32| {
+      let bits = hash.rotate_left(21 * i) as u32;
+      i as usize * self.block_len + ((u64::from(bits) * self.block_len as u64) >> 32) as usize
+    };
+    [reduce(0), reduce(1), reduce(2)]
+  }
+}
+
+fn fingerprint(hash: u64) -> u8 {
+  (hash ^ (hash >> 32)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_contains_every_key() {
+    let items: Vec<String> = (0..10_000).map(|i| format!("blocked_{}", i)).collect();
+    let filter = XorFilter::from_items(items.clone()).unwrap();
+    for item in &items {
+      assert!(filter.contains(item));
+    }
+    assert_eq!(filter.len(), 10_000);
+    assert!(filter.bits_per_key() < 10.0);
+
+    let false_positives = (0..100_000).filter(|i| filter.contains(&format!("other_{}", i))).count();
+    assert!(false_positives < 600, "{} false positives", false_positives);
+  }
+
+  #[test]
+  fn test_small_and_duplicate_sets() {
+    let filter = XorFilter::from_items(vec!["a".to_string(), "a".to_string()]).unwrap();
+    assert!(filter.contains("a"));
+    assert_eq!(filter.len(), 1);
+
+    let empty = XorFilter::from_items(Vec::new()).unwrap();
+    assert!(empty.is_empty());
+    assert!((0..10_000).all(|i| !empty.contains(&format!("item_{}", i))));
+  }
+}
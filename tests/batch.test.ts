@@ -0,0 +1,36 @@
+import { HyperLogLog } from '../pkg';
+import { processInSlices, processLinesInSlices } from '../src/batch';
+
+describe('processInSlices', () => {
+  test('should apply every item and report progress', async () => {
+    const hll = new HyperLogLog(12);
+    const items = Array.from({ length: 10_000 }, (_, i) => `user${i}`);
+    const progress: number[] = [];
+
+    const processed = await processInSlices(items, (item) => hll.add(item), {
+      sliceMs: 0,
+      onProgress: (done) => progress.push(done),
+    });
+
+    expect(processed).toBe(items.length);
+    expect(progress.length).toBeGreaterThan(1);
+    expect(progress[progress.length - 1]).toBe(items.length);
+    expect(Math.abs(hll.count() - items.length) / items.length).toBeLessThan(0.05);
+  });
+
+  test('should stop when aborted', async () => {
+    const controller = new AbortController();
+    controller.abort();
+    const run = processInSlices([1, 2, 3], () => undefined, { signal: controller.signal });
+    await expect(run).rejects.toBeDefined();
+  });
+});
+
+describe('processLinesInSlices', () => {
+  test('should apply every non-empty line', async () => {
+    const lines: string[] = [];
+    const processed = await processLinesInSlices('a\r\nb\n\nc', (line) => lines.push(line));
+    expect(processed).toBe(3);
+    expect(lines).toEqual(['a', 'b', 'c']);
+  });
+});
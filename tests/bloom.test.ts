@@ -73,4 +73,40 @@ describe('BloomFilter WebAssembly Bindings', () => {
     filter.insert(unicodeString);
     expect(filter.contains(unicodeString)).toBe(true);
   });
+
+  test('should pack bits into a compact backing store', () => {
+    const filter = new BloomFilter(10_000_000, 0.01);
+    expect(filter.memory_bytes).toBeLessThan(12 * 1024 * 1024);
+  });
+
+  test('should round-trip through bytes', () => {
+    const filter = new BloomFilter(100, 0.01);
+    filter.insert('persisted');
+    const bytes = filter.to_bytes();
+    expect(bytes).toBeInstanceOf(Uint8Array);
+
+    const restored = BloomFilter.from_bytes(bytes);
+    expect(restored.contains('persisted')).toBe(true);
+    expect(restored.to_bytes()).toEqual(bytes);
+    expect(() => BloomFilter.from_bytes(new Uint8Array([1, 2, 3]))).toThrow();
+  });
+
+  test('should round-trip through JSON', () => {
+    const filter = new BloomFilter(20, 0.01);
+    filter.insert('configured');
+    const json = JSON.parse(JSON.stringify(filter));
+    expect(json.version).toBe(1);
+    expect(typeof json.bits).toBe('string');
+
+    const restored = BloomFilter.fromJSON(json);
+    expect(restored.contains('configured')).toBe(true);
+    expect(restored.toJSON()).toEqual(json);
+    expect(() => BloomFilter.fromJSON({ ...json, version: 2 })).toThrow();
+  });
+
+  test('should insert and check many items in one call', () => {
+    const filter = new BloomFilter(1000, 0.01);
+    filter.insert_many(['a', 'b', 'c']);
+    expect(filter.contains_many(['a', 'missing', 'c'])).toEqual(new Uint8Array([1, 0, 1]));
+  });
 });
@@ -14,6 +14,16 @@ describe('CountMinSketch WebAssembly Bindings', () => {
     expect(cms.estimate('not_present')).toBe(0);
   });
 
+  test('should create conservative-update sketches', () => {
+    expect(new CountMinSketch(1000, 5).conservative).toBe(false);
+    const cms = CountMinSketch.with_conservative_update(1000, 5);
+    expect(cms.conservative).toBe(true);
+    cms.increment('test');
+    cms.increment('test');
+    expect(cms.estimate('test')).toBe(2);
+    expect(CountMinSketch.from_bytes(cms.to_bytes()).conservative).toBe(true);
+  });
+
   test('should handle empty strings', () => {
     const cms = new CountMinSketch(1000, 5);
     cms.increment('');
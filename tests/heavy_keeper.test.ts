@@ -141,4 +141,25 @@ describe('HeavyKeeper', () => {
     expect(highDecayTopK.length).toBeGreaterThan(0);
     expect(lowDecayTopK.length).toBeGreaterThan(0);
   });
+
+  test('should export tracked items as parallel arrays', () => {
+    const sketch = new HeavyKeeper(100, 3, 1, 0.9);
+    sketch.add('frequent');
+    sketch.add('frequent');
+    sketch.add('rare');
+
+    const entries = sketch.entries();
+    expect(entries.items).toEqual(['frequent', 'rare']);
+    expect(entries.weights.length).toBe(entries.len);
+  });
+
+  test('should filter the leaderboard by prefix and pattern', () => {
+    const sketch = new HeavyKeeper(100, 3, 5, 0.9);
+    sketch.add('how to cook');
+    sketch.add('what is rust');
+
+    expect(sketch.top_k_matching('how to').map((entry) => entry.item)).toEqual(['how to cook']);
+    expect(sketch.top_k_filtered('rust$').map((entry) => entry.item)).toEqual(['what is rust']);
+    expect(() => sketch.top_k_filtered('(')).toThrow();
+  });
 });
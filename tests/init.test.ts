@@ -0,0 +1,50 @@
+import {
+  initSketchWasm,
+  missingFeatures,
+  SketchWasmMemoryError,
+  SketchWasmUnsupportedError,
+} from '../src/init';
+
+describe('initSketchWasm', () => {
+  test('should expose every sketch through the facade', async () => {
+    const sketches = await initSketchWasm();
+    const filter = new sketches.BloomFilter(100, 0.01);
+    filter.insert('item');
+    expect(filter.contains('item')).toBe(true);
+    expect(typeof sketches.testing.enable_test_mode).toBe('function');
+  });
+
+  test('should load the module once', async () => {
+    expect(await initSketchWasm()).toBe(await initSketchWasm());
+  });
+
+  test('should report no missing features under Node', () => {
+    expect(missingFeatures()).toEqual([]);
+  });
+
+  test('should reserve memory up front', async () => {
+    const sketches = await initSketchWasm();
+    expect(() => sketches.memory.reserve_memory(1 << 20)).not.toThrow();
+    expect(() => sketches.BloomFilter.try_new(1000, 0.01)).not.toThrow();
+    expect(() => sketches.CountMinSketch.try_new(1000, 5)).not.toThrow();
+    expect(() => sketches.HeavyKeeper.try_new(1000, 5, 10, 0.9)).not.toThrow();
+    expect(() => sketches.HyperLogLog.try_new(12)).not.toThrow();
+    expect(new SketchWasmMemoryError('refused').name).toBe('SketchWasmMemoryError');
+  });
+
+  test('should honour reserveBytes after the module has loaded', async () => {
+    const sketches = await initSketchWasm();
+    expect(await initSketchWasm({ reserveBytes: 1 << 20 })).toBe(sketches);
+    await expect(initSketchWasm({ reserveBytes: 0xffffffff })).rejects.toBeInstanceOf(
+      SketchWasmMemoryError
+    );
+    // A refused reservation leaves the loaded module usable
+    expect(await initSketchWasm()).toBe(sketches);
+  });
+
+  test('should name the error for graceful fallbacks', () => {
+    const error = new SketchWasmUnsupportedError('no WebAssembly');
+    expect(error).toBeInstanceOf(Error);
+    expect(error.name).toBe('SketchWasmUnsupportedError');
+  });
+});
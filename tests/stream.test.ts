@@ -0,0 +1,49 @@
+import { BloomFilter } from '../pkg';
+import { fromResponseStream } from '../src/stream';
+
+function streamOf(bytes: Uint8Array, chunkSize: number): ReadableStream<Uint8Array> {
+  let offset = 0;
+  return new ReadableStream({
+    pull(controller) {
+      if (offset >= bytes.length) {
+        controller.close();
+        return;
+      }
+      controller.enqueue(bytes.slice(offset, offset + chunkSize));
+      offset += chunkSize;
+    },
+  });
+}
+
+describe('fromResponseStream', () => {
+  test('should load a Bloom filter chunk by chunk', async () => {
+    const source = new BloomFilter(1000, 0.01);
+    source.insert('item');
+    const { loader, done } = fromResponseStream('bloom', streamOf(source.to_bytes(), 7));
+    expect(loader.contains('item')).toBeUndefined();
+
+    const filter: BloomFilter = await done;
+    expect(filter.contains('item')).toBe(true);
+  });
+
+  test('should reject a stream that is not a Bloom filter', async () => {
+    const { loader, done } = fromResponseStream(
+      'bloom',
+      streamOf(new Uint8Array([1, 2, 3, 4, 5]), 2)
+    );
+    await expect(done).rejects.toBeDefined();
+    // The caller still owns the loader after a failure
+    expect(loader.header_ready).toBe(false);
+    loader.free();
+  });
+
+  test('should keep the loader usable when the stream ends early', async () => {
+    const source = new BloomFilter(1000, 0.01);
+    source.insert('item');
+    const bytes = source.to_bytes();
+    const { loader, done } = fromResponseStream('bloom', streamOf(bytes.slice(0, 64), 16));
+    await expect(done).rejects.toThrow('ended early');
+    expect(loader.progress()).toBeGreaterThan(0);
+    loader.free();
+  });
+});